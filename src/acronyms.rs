@@ -0,0 +1,123 @@
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::{export, front_matter, glossary};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// The diagnostics source name, for `md-echo-disable acronyms`.
+pub const SOURCE: &str = "acronyms";
+
+fn word_regex() -> &'static Regex {
+    static WORD: OnceLock<Regex> = OnceLock::new();
+    WORD.get_or_init(|| Regex::new(r"\b[A-Z][A-Za-z0-9]*\b").unwrap())
+}
+
+/// The acronym `word` spells, with a plural `s` dropped: at least two
+/// capitals and no other lowercase letters. Roman numerals do not count.
+fn acronym(word: &str) -> Option<&str> {
+    let stem = word.strip_suffix('s').unwrap_or(word);
+    let capitals = stem.chars().filter(char::is_ascii_uppercase).count();
+    let is_acronym = capitals >= 2 && !stem.chars().any(|c| c.is_ascii_lowercase());
+    let is_numeral = stem.chars().all(|c| matches!(c, 'I' | 'V' | 'X'));
+    (is_acronym && !is_numeral).then_some(stem)
+}
+
+/// `markdown` with everything but prose (code, URLs, HTML, front matter
+/// and glossary definitions) blanked out. Offsets and lines stay the same.
+fn prose(markdown: &str) -> String {
+    let hidden = glossary::hide_definitions(markdown);
+    let mut masked: Vec<u8> = hidden
+        .bytes()
+        .map(|byte| if byte == b'\n' { b'\n' } else { b' ' })
+        .collect();
+    let body_start = front_matter::body_start(&hidden);
+    let mut in_code = false;
+    for (event, range) in Parser::new_ext(&hidden, export::markdown_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            Event::Text(_) if !in_code && range.start >= body_start => {
+                masked[range.clone()].copy_from_slice(&hidden.as_bytes()[range]);
+            }
+            _ => {}
+        }
+    }
+    // Only ASCII was blanked, so the text is still UTF-8 where it was kept.
+    String::from_utf8_lossy(&masked).into_owned()
+}
+
+/// Whether the acronym at `start..end` of `text` is defined right there,
+/// as in `Full Name (FN)` or `FN (Full Name)`.
+fn is_definition(text: &str, start: usize, end: usize, acronym: &str) -> bool {
+    let before = text[..start].trim_end_matches(' ');
+    let after = &text[end..];
+    if before.ends_with('(') && after.starts_with(')') {
+        return before[..before.len() - 1]
+            .trim_end()
+            .ends_with(char::is_alphanumeric);
+    }
+    let Some(expansion) = after.trim_start_matches(' ').strip_prefix('(') else {
+        return false;
+    };
+    let expansion = expansion.split(')').next().unwrap_or_default();
+    let first = acronym.chars().next().unwrap_or_default();
+    expansion.split_whitespace().count() >= 2
+        && expansion
+            .trim_start()
+            .chars()
+            .next()
+            .is_some_and(|c| c.eq_ignore_ascii_case(&first))
+}
+
+/// Warnings for acronyms that are used before they are defined or never
+/// defined at all, at their first use. Glossary `terms` and `ignore`d
+/// acronyms count as defined.
+pub fn check(
+    markdown: &str,
+    file: Option<PathBuf>,
+    terms: &BTreeMap<String, String>,
+    ignore: &[String],
+) -> Vec<Diagnostic> {
+    let text = prose(markdown);
+    // First use and first definition of each acronym, as lines.
+    let mut uses: Vec<(String, usize)> = Vec::new();
+    let mut defined: BTreeMap<String, usize> = BTreeMap::new();
+    let mut seen = HashSet::new();
+    let mut line = 0;
+    let mut counted = 0;
+    for found in word_regex().find_iter(&text) {
+        let Some(name) = acronym(found.as_str()) else {
+            continue;
+        };
+        line += text[counted..found.start()].matches('\n').count();
+        counted = found.start();
+        if is_definition(&text, found.start(), found.end(), name) {
+            defined.entry(name.to_string()).or_insert(line);
+        }
+        if seen.insert(name.to_string()) {
+            uses.push((name.to_string(), line));
+        }
+    }
+
+    uses.into_iter()
+        .filter(|(name, _)| !terms.contains_key(name) && !ignore.contains(name))
+        .filter_map(|(name, line)| {
+            let message = match defined.get(&name) {
+                Some(&at) if at > line => {
+                    format!("{} is used before it is defined on line {}", name, at + 1)
+                }
+                Some(_) => return None,
+                None => format!("{} is never defined", name),
+            };
+            Some(Diagnostic {
+                source: SOURCE.to_string(),
+                severity: Severity::Warning,
+                file: file.clone(),
+                line: Some(line),
+                message,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,211 @@
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::{export, front_matter};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+/// The diagnostics source name, for `md-echo-disable alt-text`.
+pub const SOURCE: &str = "alt-text";
+
+/// Stands for the image in the captioning command.
+pub const IMAGE_PLACEHOLDER: &str = "{image}";
+
+fn img_tag_regex() -> &'static Regex {
+    static IMG: OnceLock<Regex> = OnceLock::new();
+    IMG.get_or_init(|| Regex::new(r#"(?i)<img\b[^>]*>"#).unwrap())
+}
+
+fn src_regex() -> &'static Regex {
+    static SRC: OnceLock<Regex> = OnceLock::new();
+    SRC.get_or_init(|| Regex::new(r#"(?i)\bsrc\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap())
+}
+
+fn alt_regex() -> &'static Regex {
+    static ALT: OnceLock<Regex> = OnceLock::new();
+    ALT.get_or_init(|| Regex::new(r#"(?i)\balt\s*=\s*(?:"\s*[^"\s]|'\s*[^'\s]|[^\s>"'])"#).unwrap())
+}
+
+fn empty_alt_regex() -> &'static Regex {
+    static EMPTY: OnceLock<Regex> = OnceLock::new();
+    EMPTY.get_or_init(|| Regex::new(r#"(?i)\s+alt\s*=\s*(?:""|'')"#).unwrap())
+}
+
+/// An image without alt text.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Image {
+    /// Byte offset the alt text goes at: inside `![]` or after `<img`.
+    pub insert_at: usize,
+    /// Written as an HTML `<img>` tag rather than `![]()`.
+    pub html: bool,
+    /// Zero-based line of the image.
+    pub line: usize,
+    pub src: String,
+}
+
+/// The images in `markdown` that have no alt text, in order.
+pub fn missing(markdown: &str) -> Vec<Image> {
+    let body_start = front_matter::body_start(markdown);
+    let line_of = |offset: usize| markdown[..offset].matches('\n').count();
+    let mut images = Vec::new();
+    // The image being read and whether text was seen inside its brackets.
+    let mut open: Option<(Image, bool)> = None;
+    let parser = Parser::new_ext(&markdown[body_start..], export::markdown_options());
+    for (event, range) in parser.into_offset_iter() {
+        let start = body_start + range.start;
+        match event {
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                let image = Image {
+                    insert_at: start + 2,
+                    html: false,
+                    line: line_of(start),
+                    src: dest_url.to_string(),
+                };
+                open = Some((image, false));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((image, false)) = open.take() {
+                    images.push(image);
+                }
+            }
+            Event::Text(text) | Event::Code(text) if !text.trim().is_empty() => {
+                if let Some((_, described)) = &mut open {
+                    *described = true;
+                }
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                for tag in img_tag_regex().find_iter(&html) {
+                    if alt_regex().is_match(tag.as_str()) {
+                        continue;
+                    }
+                    let src = src_regex()
+                        .captures(tag.as_str())
+                        .and_then(|caps| caps.iter().skip(1).flatten().next())
+                        .map_or(String::new(), |src| src.as_str().to_string());
+                    images.push(Image {
+                        insert_at: start + tag.start() + "<img".len(),
+                        html: true,
+                        line: line_of(start + tag.start()),
+                        src,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    images
+}
+
+/// A warning for each image in `markdown` without alt text.
+pub fn check(markdown: &str, file: Option<PathBuf>) -> Vec<Diagnostic> {
+    missing(markdown)
+        .into_iter()
+        .map(|image| Diagnostic {
+            source: SOURCE.to_string(),
+            severity: Severity::Warning,
+            file: file.clone(),
+            line: Some(image.line),
+            message: format!("Image '{}' has no alt text", image.src),
+        })
+        .collect()
+}
+
+/// `markdown` with `alt` given to `image`, if the image is still where it
+/// was found.
+pub fn insert(markdown: &str, image: &Image, alt: &str) -> Option<String> {
+    if !missing(markdown).contains(image) {
+        return None;
+    }
+    let alt = alt.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut text = markdown.to_string();
+    if image.html {
+        let tag_end = text[image.insert_at..].find('>')? + image.insert_at;
+        // An empty `alt=""` is replaced rather than added to.
+        if let Some(found) = empty_alt_regex().find(&text[image.insert_at..tag_end]) {
+            let range = image.insert_at + found.start()..image.insert_at + found.end();
+            text.replace_range(range, "");
+        }
+        let alt = alt.replace('&', "&amp;").replace('"', "&quot;");
+        text.insert_str(image.insert_at, &format!(" alt=\"{}\"", alt));
+    } else {
+        let alt = alt.replace('[', "\\[").replace(']', "\\]");
+        text.insert_str(image.insert_at, &alt);
+    }
+    Some(text)
+}
+
+/// Where the captioning command can read the image: remote URLs as they
+/// are, local paths relative to `dir`.
+pub fn locate(src: &str, dir: &Path) -> String {
+    if src.contains("://") {
+        return src.to_string();
+    }
+    let path = src.split(['?', '#']).next().unwrap_or(src);
+    dir.join(path).display().to_string()
+}
+
+/// A caption being written for an image by the configured command on a
+/// background thread.
+pub struct Caption {
+    pub image: Image,
+    pending: Arc<Mutex<Option<Result<String, String>>>>,
+    pub result: Option<Result<String, String>>,
+}
+
+impl Caption {
+    pub fn start(command: &[String], image: Image, location: &str, ctx: egui::Context) -> Self {
+        let pending = Arc::new(Mutex::new(None));
+        {
+            let pending = Arc::clone(&pending);
+            let command: Vec<String> = command
+                .iter()
+                .map(|arg| arg.replace(IMAGE_PLACEHOLDER, location))
+                .collect();
+            thread::spawn(move || {
+                let result = run(&command);
+                if let Ok(mut slot) = pending.lock() {
+                    *slot = Some(result);
+                }
+                ctx.request_repaint();
+            });
+        }
+        Self {
+            image,
+            pending,
+            result: None,
+        }
+    }
+
+    /// Picks up the caption once the command has finished.
+    pub fn poll(&mut self) {
+        if self.result.is_none() {
+            self.result = self.pending.lock().ok().and_then(|mut slot| slot.take());
+        }
+    }
+}
+
+fn run(command: &[String]) -> Result<String, String> {
+    let Some((program, args)) = command.split_first() else {
+        return Err("The captioning command is empty.".to_string());
+    };
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|err| format!("Failed to run '{}': {}", program, err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "'{}' failed ({}): {}",
+            program,
+            output.status,
+            stderr.trim()
+        ));
+    }
+    let caption = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if caption.is_empty() {
+        return Err(format!("'{}' returned no text.", program));
+    }
+    Ok(caption)
+}
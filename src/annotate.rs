@@ -0,0 +1,62 @@
+use similar::{DiffTag, TextDiff};
+
+/// Marks the differences between `old` and `new` with CriticMarkup: whole
+/// lines are diffed first, then replaced lines are paired up and compared
+/// word by word.
+pub fn annotate(old: &str, new: &str) -> String {
+    let lines = TextDiff::from_lines(old, new);
+    let mut out = String::with_capacity(new.len());
+    for op in lines.ops() {
+        let old_lines = &lines.old_slices()[op.old_range()];
+        let new_lines = &lines.new_slices()[op.new_range()];
+        match op.tag() {
+            DiffTag::Equal => out.push_str(&new_lines.concat()),
+            DiffTag::Insert => mark(&mut out, "{++", "++}", &new_lines.concat()),
+            DiffTag::Delete => mark(&mut out, "{--", "--}", &old_lines.concat()),
+            DiffTag::Replace => {
+                let paired = old_lines.len().min(new_lines.len());
+                for (old_line, new_line) in old_lines.iter().zip(new_lines.iter()) {
+                    annotate_words(&mut out, old_line, new_line);
+                }
+                mark(&mut out, "{--", "--}", &old_lines[paired..].concat());
+                mark(&mut out, "{++", "++}", &new_lines[paired..].concat());
+            }
+        }
+    }
+    out
+}
+
+fn annotate_words(out: &mut String, old: &str, new: &str) {
+    let words = TextDiff::from_words(old, new);
+    for op in words.ops() {
+        let old_text = words.old_slices()[op.old_range()].concat();
+        let new_text = words.new_slices()[op.new_range()].concat();
+        match op.tag() {
+            DiffTag::Equal => out.push_str(&new_text),
+            DiffTag::Insert => mark(out, "{++", "++}", &new_text),
+            DiffTag::Delete => mark(out, "{--", "--}", &old_text),
+            DiffTag::Replace if !old_text.contains('\n') && !new_text.contains('\n') => {
+                out.push_str(&format!("{{~~{}~>{}~~}}", old_text, new_text));
+            }
+            DiffTag::Replace => {
+                mark(out, "{--", "--}", &old_text);
+                mark(out, "{++", "++}", &new_text);
+            }
+        }
+    }
+}
+
+/// Wraps `text` in markers line by line so no markup spans a line break.
+fn mark(out: &mut String, open: &str, close: &str, text: &str) {
+    for segment in text.split_inclusive('\n') {
+        let body = segment.trim_end_matches(['\n', '\r']);
+        if body.trim().is_empty() {
+            out.push_str(segment);
+            continue;
+        }
+        out.push_str(open);
+        out.push_str(body);
+        out.push_str(close);
+        out.push_str(&segment[body.len()..]);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::dates;
+use crate::export;
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Expands `{name}` (the document's file stem) and `{timestamp}` (UTC,
+/// `YYYYMMDD-HHMMSS`) in an `[assets]` pattern.
+pub fn expand(pattern: &str, document: &Path) -> String {
+    let name = document
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "image".to_string());
+    pattern
+        .replace("{name}", &name)
+        .replace("{timestamp}", &dates::timestamp(SystemTime::now()))
+}
+
+/// `path`, or `path` with `-2`, `-3`, ... appended to its stem if that
+/// file already exists.
+pub fn unique_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .expect("unbounded range")
+}
+
+/// Where a new asset for `document` goes: `folder` and `file_name`
+/// patterns expanded, relative to the document's folder.
+pub fn target_path(document: &Path, folder: &str, file_name: &str) -> PathBuf {
+    let dir = document.parent().unwrap_or(Path::new("."));
+    unique_path(
+        dir.join(expand(folder, document))
+            .join(expand(file_name, document)),
+    )
+}
+
+/// Markdown image reference to `image` from `document`, with `/`
+/// separators and the destination in angle brackets if it has spaces.
+pub fn image_link(document: &Path, image: &Path) -> String {
+    let dir = document.parent().unwrap_or(Path::new("."));
+    let relative = export::relative_path(dir, image)
+        .to_string_lossy()
+        .replace('\\', "/");
+    if relative.contains(' ') {
+        format!("![](<{}>)", relative)
+    } else {
+        format!("![]({})", relative)
+    }
+}
+
+/// Extensions of files that are inserted as images when dropped.
+const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "gif", "webp", "svg", "bmp", "avif"];
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+}
+
+/// Copies `source` into the asset folder of `document`, keeping its file
+/// name, and returns the copy's path.
+pub fn import_file(document: &Path, folder: &str, source: &Path) -> Result<PathBuf, String> {
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("{}: not a file", source.display()))?;
+    // Braces in the dropped file's name are not placeholders.
+    let file_name = file_name.replace('{', "(").replace('}', ")");
+    let target = target_path(document, folder, &file_name);
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+    fs::copy(source, &target).map_err(|err| format!("{}: {}", source.display(), err))?;
+    Ok(target)
+}
+
+/// Writes the image on the system clipboard to `path` as PNG, creating
+/// the folder if needed. Returns `Ok(false)` when the clipboard holds no
+/// image.
+pub fn save_clipboard_image(path: &Path) -> Result<bool, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|err| err.to_string())?;
+    let image = match clipboard.get_image() {
+        Ok(image) => image,
+        Err(arboard::Error::ContentNotAvailable) => return Ok(false),
+        Err(err) => return Err(err.to_string()),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+    let file = fs::File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.bytes))
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(true)
+}
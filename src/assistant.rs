@@ -0,0 +1,241 @@
+use crate::diff_view::DiffView;
+use directories::ProjectDirs;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Backend names for `[assistant] backend`.
+pub const OPENAI: &str = "openai";
+pub const LLAMA_CPP: &str = "llama.cpp";
+
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What to ask the assistant to do with the selection.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Task {
+    Rewrite,
+    Summarize,
+    Continue,
+}
+
+impl Task {
+    pub const ALL: [Task; 3] = [Task::Rewrite, Task::Summarize, Task::Continue];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Task::Rewrite => "Rewrite",
+            Task::Summarize => "Summarize",
+            Task::Continue => "Continue",
+        }
+    }
+
+    fn instruction(self) -> &'static str {
+        match self {
+            Task::Rewrite => {
+                "Rewrite the Markdown text below to read more clearly. Keep its meaning, \
+                 language and Markdown formatting."
+            }
+            Task::Summarize => {
+                "Summarize the Markdown text below in a few sentences, in the same language."
+            }
+            Task::Continue => {
+                "Continue the Markdown text below with one or two paragraphs in the same \
+                 style and language. Reply with the new text only, without repeating it."
+            }
+        }
+    }
+
+    /// The text that would take the place of `source`.
+    fn proposal(self, source: &str, reply: &str) -> String {
+        match self {
+            Task::Rewrite | Task::Summarize => reply.to_string(),
+            Task::Continue => format!("{}\n\n{}", source.trim_end(), reply),
+        }
+    }
+}
+
+/// Where to send requests, from `[assistant]` in the config.
+#[derive(Clone)]
+pub struct Backend {
+    pub kind: String,
+    /// Base URL: `https://api.openai.com/v1` or the llama.cpp server.
+    pub endpoint: String,
+    pub model: String,
+    pub api_key: Option<String>,
+    pub max_tokens: u32,
+}
+
+impl Backend {
+    /// The reply to `prompt`, following the `system` instruction.
+    pub fn complete(&self, system: &str, prompt: &str) -> Result<String, String> {
+        let endpoint = self.endpoint.trim_end_matches('/');
+        let reply = match self.kind.as_str() {
+            OPENAI => {
+                let body = json!({
+                    "model": self.model,
+                    "max_tokens": self.max_tokens,
+                    "messages": [
+                        { "role": "system", "content": system },
+                        { "role": "user", "content": prompt },
+                    ],
+                });
+                let response = self.post(&format!("{}/chat/completions", endpoint), &body)?;
+                response["choices"][0]["message"]["content"]
+                    .as_str()
+                    .map(str::to_string)
+            }
+            LLAMA_CPP => {
+                let body = json!({
+                    "prompt": format!("{}\n\n{}\n\n", system, prompt),
+                    "n_predict": self.max_tokens,
+                });
+                let response = self.post(&format!("{}/completion", endpoint), &body)?;
+                response["content"].as_str().map(str::to_string)
+            }
+            other => return Err(format!("Unknown assistant backend '{}'", other)),
+        };
+        let reply = reply.ok_or("The assistant's reply had no text.")?;
+        let reply = reply.trim();
+        if reply.is_empty() {
+            return Err("The assistant returned no text.".to_string());
+        }
+        Ok(reply.to_string())
+    }
+
+    fn post(&self, url: &str, body: &Value) -> Result<Value, String> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("md-echo/", env!("CARGO_PKG_VERSION")))
+            .build();
+        let mut request = agent.post(url).set("Content-Type", "application/json");
+        if let Some(key) = &self.api_key {
+            request = request.set("Authorization", &format!("Bearer {}", key));
+        }
+        let response = match request.send_string(&body.to_string()) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(code, response)) => {
+                let detail = response.into_string().unwrap_or_default();
+                let detail = serde_json::from_str::<Value>(&detail)
+                    .ok()
+                    .and_then(|value| value["error"]["message"].as_str().map(str::to_string))
+                    .unwrap_or(detail);
+                return Err(format!("{} answered {}: {}", url, code, detail.trim()));
+            }
+            Err(err) => return Err(format!("Could not reach {}: {}", url, err)),
+        };
+        let text = response
+            .into_string()
+            .map_err(|err| format!("Could not read the reply from {}: {}", url, err))?;
+        serde_json::from_str(&text).map_err(|err| format!("Unexpected reply from {}: {}", url, err))
+    }
+}
+
+/// The file holding the API key, next to (but not in) `config.toml`.
+fn key_file() -> Option<PathBuf> {
+    ProjectDirs::from("com", "fibnas", "md-echo")
+        .map(|dirs| dirs.config_dir().join("assistant.key"))
+}
+
+/// The API key from the environment variable `env_var`, or else from the
+/// key file.
+pub fn load_key(env_var: &str) -> Option<String> {
+    let key = std::env::var(env_var)
+        .ok()
+        .filter(|_| !env_var.is_empty())
+        .or_else(|| fs::read_to_string(key_file()?).ok())?;
+    let key = key.trim();
+    (!key.is_empty()).then(|| key.to_string())
+}
+
+/// Writes `key` to the key file, readable only by the user.
+pub fn store_key(key: &str) -> Result<(), String> {
+    let path = key_file().ok_or("No configuration directory")?;
+    let error = |err: std::io::Error| format!("{}: {}", path.display(), err);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    // Only the user may read the key, from the moment the file exists.
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path).map_err(error)?;
+    #[cfg(unix)]
+    {
+        // `mode` only applies when the file is created.
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))
+            .map_err(error)?;
+    }
+    file.write_all(key.trim().as_bytes()).map_err(error)
+}
+
+/// A selection sent to the assistant on a background thread.
+pub struct Request {
+    pub task: Task,
+    /// Char range of the selection in the document.
+    pub range: Range<usize>,
+    pub source: String,
+    pending: Arc<Mutex<Option<Result<String, String>>>>,
+    /// The text to put in place of the selection, or why there is none.
+    pub result: Option<Result<String, String>>,
+    /// The selection against the proposal.
+    pub diff: Option<DiffView>,
+}
+
+impl Request {
+    pub fn start(
+        backend: Backend,
+        task: Task,
+        instruction: &str,
+        range: Range<usize>,
+        source: String,
+        ctx: egui::Context,
+    ) -> Self {
+        let pending = Arc::new(Mutex::new(None));
+        {
+            let pending = Arc::clone(&pending);
+            let mut system = task.instruction().to_string();
+            if !instruction.trim().is_empty() {
+                system.push_str(&format!(" {}", instruction.trim()));
+            }
+            let text = source.clone();
+            thread::spawn(move || {
+                let result = backend
+                    .complete(&system, &text)
+                    .map(|reply| task.proposal(&text, &reply));
+                if let Ok(mut slot) = pending.lock() {
+                    *slot = Some(result);
+                }
+                ctx.request_repaint();
+            });
+        }
+        Self {
+            task,
+            range,
+            source,
+            pending,
+            result: None,
+            diff: None,
+        }
+    }
+
+    /// Picks up the reply once it has arrived.
+    pub fn poll(&mut self) {
+        if self.result.is_some() {
+            return;
+        }
+        self.result = self.pending.lock().ok().and_then(|mut slot| slot.take());
+        if let Some(Ok(proposal)) = &self.result {
+            self.diff = Some(DiffView::new(&self.source, proposal));
+        }
+    }
+}
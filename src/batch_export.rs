@@ -0,0 +1,200 @@
+use crate::book;
+use crate::export::{self, ExportFormat, HtmlOptions};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Where each exported file goes.
+#[derive(Clone)]
+pub enum Destination {
+    /// `[export] output_dir`, expanded per document and resolved against
+    /// the working directory.
+    Pattern { root: PathBuf, dir: String },
+    /// One folder for every file.
+    Folder(PathBuf),
+    /// `output`, with the subfolders the file has below `source`.
+    Mirror { source: PathBuf, output: PathBuf },
+}
+
+/// Everything the export of one file needs besides the file itself.
+#[derive(Clone)]
+pub struct Settings {
+    pub format: ExportFormat,
+    pub filename_pattern: String,
+    pub destination: Destination,
+    pub pdf_command: Option<Vec<String>>,
+    pub html: HtmlOptions,
+}
+
+/// The outcome for one source file: the written file, or why it failed.
+pub struct Outcome {
+    pub source: PathBuf,
+    pub result: Result<PathBuf, String>,
+}
+
+#[derive(Default)]
+struct Progress {
+    current: Option<PathBuf>,
+    outcomes: Vec<Outcome>,
+    done: bool,
+}
+
+/// Exports a list of files one after another on a background thread.
+/// Dropping the job cancels the files not started yet.
+pub struct BatchExportJob {
+    pub total: usize,
+    progress: Arc<Mutex<Progress>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl BatchExportJob {
+    pub fn start(files: Vec<PathBuf>, settings: Settings, ctx: egui::Context) -> Self {
+        let total = files.len();
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let progress = Arc::clone(&progress);
+            let cancel = Arc::clone(&cancel);
+            thread::spawn(move || {
+                // Output path -> source, so two sources never overwrite
+                // each other's export.
+                let mut written: HashMap<PathBuf, PathBuf> = HashMap::new();
+                for source in files {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if let Ok(mut progress) = progress.lock() {
+                        progress.current = Some(source.clone());
+                    }
+                    ctx.request_repaint();
+
+                    let result = export_file(&source, &settings).and_then(|output| {
+                        match written.insert(output.clone(), source.clone()) {
+                            Some(first) => Err(format!(
+                                "{} was already written for {}",
+                                output.display(),
+                                first.display()
+                            )),
+                            None => Ok(output),
+                        }
+                    });
+                    let Ok(mut progress) = progress.lock() else {
+                        break;
+                    };
+                    progress.outcomes.push(Outcome { source, result });
+                }
+                if let Ok(mut progress) = progress.lock() {
+                    progress.current = None;
+                    progress.done = true;
+                }
+                ctx.request_repaint();
+            });
+        }
+
+        Self {
+            total,
+            progress,
+            cancel,
+        }
+    }
+
+    /// Runs `f` on the outcomes so far and the file being exported.
+    pub fn with_progress<R>(&self, f: impl FnOnce(&[Outcome], Option<&Path>) -> R) -> R {
+        match self.progress.lock() {
+            Ok(progress) => f(&progress.outcomes, progress.current.as_deref()),
+            Err(_) => f(&[], None),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.progress
+            .lock()
+            .map(|progress| progress.done)
+            .unwrap_or(true)
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for BatchExportJob {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Exports `source` the way Export → Document does and returns the
+/// written path. Existing exports of the same file are replaced.
+pub fn export_file(source: &Path, settings: &Settings) -> Result<PathBuf, String> {
+    let markdown = fs::read_to_string(source).map_err(|err| format!("Failed to read: {}", err))?;
+    let stem = source.file_stem().map_or("Untitled".to_string(), |stem| {
+        stem.to_string_lossy().into_owned()
+    });
+    let file_name = export::output_name(
+        &settings.filename_pattern,
+        &markdown,
+        &stem,
+        settings.format,
+    );
+    let dir = match &settings.destination {
+        Destination::Pattern { root, dir } => {
+            root.join(export::expand_pattern(dir, &markdown, &stem))
+        }
+        Destination::Folder(dir) => dir.clone(),
+        Destination::Mirror {
+            source: root,
+            output,
+        } => {
+            let relative = source
+                .parent()
+                .and_then(|dir| dir.strip_prefix(root).ok())
+                .unwrap_or(Path::new(""));
+            output.join(relative)
+        }
+    };
+    let output = dir.join(file_name);
+    let output_dir = output.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(output_dir)
+        .map_err(|err| format!("Failed to create {}: {}", output_dir.display(), err))?;
+
+    let base_dir = source.parent().unwrap_or(Path::new("."));
+    let markdown = export::rebase_links(&markdown, base_dir, output_dir);
+    export::write_document(
+        &markdown,
+        &stem,
+        settings.format,
+        &output,
+        settings.pdf_command.as_deref(),
+        &settings.html,
+    )?;
+    Ok(output)
+}
+
+/// Markdown files listed in a manifest: the chapters of a `SUMMARY.md`
+/// style markdown file, or else one path per line (blank lines and lines
+/// starting with `#` are skipped). Paths are relative to the manifest.
+pub fn manifest_files(manifest: &Path) -> Result<Vec<PathBuf>, String> {
+    let text =
+        fs::read_to_string(manifest).map_err(|err| format!("{}: {}", manifest.display(), err))?;
+    let dir = manifest.parent().unwrap_or(Path::new("."));
+    let files: Vec<PathBuf> = if export::is_markdown(manifest) {
+        book::parse_summary(&text, dir)
+            .into_iter()
+            .filter_map(|chapter| chapter.path)
+            .collect()
+    } else {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| export::normalize(&dir.join(line)))
+            .collect()
+    };
+    if files.is_empty() {
+        return Err(format!("{} lists no files.", manifest.display()));
+    }
+    Ok(files)
+}
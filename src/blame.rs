@@ -0,0 +1,190 @@
+use similar::{DiffTag, TextDiff};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Default)]
+pub struct BlameLine {
+    pub commit: String,
+    pub author: String,
+    /// Unix timestamp of the authored change.
+    pub time: i64,
+    pub summary: String,
+}
+
+impl BlameLine {
+    pub fn is_committed(&self) -> bool {
+        !self.commit.chars().all(|c| c == '0')
+    }
+
+    /// `"Alice, 3 weeks ago"`
+    pub fn label(&self) -> String {
+        if !self.is_committed() {
+            return "Not committed yet".to_string();
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs() as i64)
+            .unwrap_or(self.time);
+        format!("{}, {}", self.author, age((now - self.time).max(0)))
+    }
+}
+
+fn age(seconds: i64) -> String {
+    const UNITS: [(i64, &str); 6] = [
+        (365 * 24 * 3600, "year"),
+        (30 * 24 * 3600, "month"),
+        (7 * 24 * 3600, "week"),
+        (24 * 3600, "day"),
+        (3600, "hour"),
+        (60, "minute"),
+    ];
+    for (size, unit) in UNITS {
+        let count = seconds / size;
+        if count > 0 {
+            let plural = if count == 1 { "" } else { "s" };
+            return format!("{} {}{} ago", count, unit, plural);
+        }
+    }
+    "just now".to_string()
+}
+
+struct BlameData {
+    /// The file contents `git blame` ran against.
+    text: String,
+    lines: Vec<BlameLine>,
+}
+
+/// `git blame` for one file, computed on a background thread and mapped onto
+/// the (possibly edited) buffer.
+pub struct Blame {
+    pub path: PathBuf,
+    pending: Arc<Mutex<Option<Result<BlameData, String>>>>,
+    data: Option<BlameData>,
+    pub error: Option<String>,
+    mapping_hash: u64,
+    mapping: Vec<Option<usize>>,
+}
+
+impl Blame {
+    pub fn start(path: &Path, ctx: egui::Context) -> Self {
+        let result = Arc::new(Mutex::new(None));
+        {
+            let result = Arc::clone(&result);
+            let path = path.to_path_buf();
+            thread::spawn(move || {
+                let blame = run_blame(&path);
+                if let Ok(mut slot) = result.lock() {
+                    *slot = Some(blame);
+                }
+                ctx.request_repaint();
+            });
+        }
+        Self {
+            path: path.to_path_buf(),
+            pending: result,
+            data: None,
+            error: None,
+            mapping_hash: 0,
+            mapping: Vec::new(),
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.data.is_none() && self.error.is_none()
+    }
+
+    /// Picks up the finished blame and maps it onto `buffer`.
+    pub fn update(&mut self, buffer: &str) {
+        if self.is_loading() {
+            match self.pending.lock().ok().and_then(|mut slot| slot.take()) {
+                Some(Ok(data)) => self.data = Some(data),
+                Some(Err(err)) => self.error = Some(err),
+                None => return,
+            }
+        }
+        let Some(data) = &self.data else {
+            return;
+        };
+
+        let mut hasher = DefaultHasher::new();
+        buffer.hash(&mut hasher);
+        let hash = hasher.finish();
+        if hash != self.mapping_hash {
+            self.mapping_hash = hash;
+            self.mapping = map_lines(&data.text, buffer);
+        }
+    }
+
+    /// Blame info for a buffer line; lines added since the file was blamed
+    /// have none.
+    pub fn line(&self, buffer_line: usize) -> Option<&BlameLine> {
+        let index = (*self.mapping.get(buffer_line)?)?;
+        self.data.as_ref()?.lines.get(index)
+    }
+}
+
+/// For each line of `new`, the matching line of `old` if it is unchanged.
+fn map_lines(old: &str, new: &str) -> Vec<Option<usize>> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut mapping = Vec::new();
+    for op in diff.ops() {
+        match op.tag() {
+            DiffTag::Equal => mapping.extend(op.old_range().map(Some)),
+            DiffTag::Insert | DiffTag::Replace => {
+                mapping.extend(std::iter::repeat_n(None, op.new_range().len()))
+            }
+            DiffTag::Delete => {}
+        }
+    }
+    mapping
+}
+
+fn run_blame(path: &Path) -> Result<BlameData, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let name = path.file_name().unwrap_or_default();
+    let output = Command::new("git")
+        .args(["blame", "--line-porcelain", "--"])
+        .arg(name)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| format!("Failed to run git: {}", err))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(BlameData {
+        text,
+        lines: parse_porcelain(&String::from_utf8_lossy(&output.stdout)),
+    })
+}
+
+fn parse_porcelain(output: &str) -> Vec<BlameLine> {
+    let mut lines = Vec::new();
+    let mut current = BlameLine::default();
+    let mut expect_header = true;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            lines.push(std::mem::take(&mut current));
+            expect_header = true;
+            continue;
+        }
+        if expect_header {
+            current.commit = line.split(' ').next().unwrap_or_default().to_string();
+            expect_header = false;
+        } else if let Some(author) = line.strip_prefix("author ") {
+            current.author = author.to_string();
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            current.time = time.parse().unwrap_or_default();
+        } else if let Some(summary) = line.strip_prefix("summary ") {
+            current.summary = summary.to_string();
+        }
+    }
+    lines
+}
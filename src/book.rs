@@ -0,0 +1,163 @@
+use crate::export;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A manuscript described by an mdBook-style `SUMMARY.md`, optionally located
+/// through a `book.toml` next to it.
+pub struct Book {
+    pub title: Option<String>,
+    pub root: PathBuf,
+    pub src_dir: PathBuf,
+    pub build_dir: PathBuf,
+    /// True when the manifest comes from an mdBook project (`book.toml`).
+    pub is_mdbook: bool,
+    pub chapters: Vec<Chapter>,
+}
+
+pub struct Chapter {
+    pub title: String,
+    pub path: Option<PathBuf>,
+    pub depth: usize,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BookToml {
+    book: BookSection,
+    build: BuildSection,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BookSection {
+    title: Option<String>,
+    src: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BuildSection {
+    #[serde(rename = "build-dir")]
+    build_dir: Option<String>,
+}
+
+impl Book {
+    pub fn detect(root: &Path) -> Option<Book> {
+        let book_toml = fs::read_to_string(root.join("book.toml")).ok();
+        let is_mdbook = book_toml.is_some();
+        let parsed: BookToml = book_toml
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+        let src_dir = match (is_mdbook, parsed.book.src) {
+            (true, src) => root.join(src.unwrap_or_else(|| "src".to_string())),
+            (false, _) => root.to_path_buf(),
+        };
+        let build_dir = root.join(parsed.build.build_dir.as_deref().unwrap_or("book"));
+
+        let summary = fs::read_to_string(src_dir.join("SUMMARY.md")).ok()?;
+        let chapters = parse_summary(&summary, &src_dir);
+        Some(Book {
+            title: parsed.book.title,
+            root: root.to_path_buf(),
+            src_dir,
+            build_dir,
+            is_mdbook,
+            chapters,
+        })
+    }
+
+    pub fn chapter_paths(&self) -> Vec<PathBuf> {
+        self.chapters
+            .iter()
+            .filter_map(|chapter| chapter.path.clone())
+            .collect()
+    }
+
+    pub fn chapter_index(&self, file: &Path) -> Option<usize> {
+        let file = export::normalize(file);
+        self.chapters
+            .iter()
+            .position(|chapter| chapter.path.as_deref() == Some(file.as_path()))
+    }
+
+    /// Path of the rendered HTML page for `file`, relative to the book output.
+    /// mdBook renders `README.md` chapters as `index.html`.
+    pub fn rendered_page(&self, file: &Path) -> Option<String> {
+        let relative = export::normalize(file)
+            .strip_prefix(export::normalize(&self.src_dir))
+            .ok()?
+            .to_path_buf();
+        let mut page = relative.with_extension("html");
+        if relative
+            .file_stem()
+            .map(|stem| stem.eq_ignore_ascii_case("readme"))
+            .unwrap_or(false)
+        {
+            page.set_file_name("index.html");
+        }
+        Some(page.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// Returns the next (or previous) chapter with a file, relative to `file`.
+    pub fn neighbour(&self, file: &Path, forward: bool) -> Option<PathBuf> {
+        let current = self.chapter_index(file)?;
+        let candidates: Box<dyn Iterator<Item = &Chapter>> = if forward {
+            Box::new(self.chapters[current + 1..].iter())
+        } else {
+            Box::new(self.chapters[..current].iter().rev())
+        };
+        candidates.filter_map(|chapter| chapter.path.clone()).next()
+    }
+}
+
+pub fn parse_summary(summary: &str, src_dir: &Path) -> Vec<Chapter> {
+    let mut chapters = Vec::new();
+    for line in summary.lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if let Some(part) = trimmed.strip_prefix("# ") {
+            if !chapters.is_empty() {
+                chapters.push(Chapter {
+                    title: part.trim().to_string(),
+                    path: None,
+                    depth: 0,
+                });
+            }
+            continue;
+        }
+
+        let is_item = trimmed.starts_with("- ") || trimmed.starts_with("* ");
+        let Some(caps) = export::link_regex().captures(trimmed) else {
+            if is_item {
+                if let Some(draft) = trimmed.get(2..).and_then(parse_draft) {
+                    chapters.push(Chapter {
+                        title: draft,
+                        path: None,
+                        depth: indent / 2 + 1,
+                    });
+                }
+            }
+            continue;
+        };
+
+        let title = caps[1].trim_start_matches('[').trim_end_matches(']');
+        let target = caps[2].split('#').next().unwrap_or_default();
+        let path = (!target.is_empty() && !export::is_external(target))
+            .then(|| export::normalize(&src_dir.join(target)));
+        chapters.push(Chapter {
+            title: title.to_string(),
+            path,
+            depth: if is_item { indent / 2 + 1 } else { 0 },
+        });
+    }
+    chapters
+}
+
+/// Draft chapters are written as `- [Title]()` with an empty target.
+fn parse_draft(item: &str) -> Option<String> {
+    let inner = item.trim().strip_prefix('[')?;
+    let (title, rest) = inner.split_once(']')?;
+    rest.trim().starts_with("()").then(|| title.to_string())
+}
@@ -0,0 +1,424 @@
+use crate::export;
+use crate::zip::{self, ZipWriter};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use walkdir::WalkDir;
+
+/// Where files from outside the document's folder go in the archive.
+const OUTSIDE_DIR: &str = "assets";
+
+fn html_attribute_regex() -> &'static Regex {
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    ATTRIBUTE.get_or_init(|| {
+        Regex::new(r#"(?i)(\b(?:src|href|poster)\s*=\s*)(?:"([^"]*)"|'([^']*)')"#).unwrap()
+    })
+}
+
+/// A markdown link or image, with its target either in angle brackets,
+/// where it may contain spaces, or bare.
+fn target_link_regex() -> &'static Regex {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    LINK.get_or_init(|| {
+        Regex::new(r"(!?\[[^\]]*\])\(\s*(?:<([^>\n]*)>|([^)\s>]+))((?:\s+[^)]*)?)\)").unwrap()
+    })
+}
+
+fn reference_regex() -> &'static Regex {
+    static REFERENCE: OnceLock<Regex> = OnceLock::new();
+    REFERENCE.get_or_init(|| Regex::new(r"(?m)^( {0,3}\[[^\]]+\]:[ \t]*)<?([^\s>]+)>?").unwrap())
+}
+
+/// A document ready to be packed with the local files it links to.
+pub struct Bundle {
+    /// The document's text with links pointing into the archive.
+    pub markdown: String,
+    /// Each file and its path in the archive.
+    pub files: Vec<(PathBuf, String)>,
+    /// Local links that point at no file, left as they are.
+    pub missing: Vec<String>,
+}
+
+/// Gathers the files `markdown`, a document in `dir`, links to: images,
+/// attachments and media, from markdown links and reference definitions
+/// and from `src`, `href` and `poster` attributes. Files under `dir` keep
+/// their relative path, others go in `assets/`. Links to other markdown
+/// documents are left alone, since their own files would not come along.
+pub fn collect(markdown: &str, dir: &Path) -> Bundle {
+    let mut collector = Collector {
+        dir: export::normalize(dir),
+        names: HashMap::new(),
+        taken: HashSet::new(),
+        files: Vec::new(),
+        missing: Vec::new(),
+    };
+    let text = rewrite_targets(markdown, |target| collector.rewrite(target));
+    Bundle {
+        markdown: text,
+        files: collector.files,
+        missing: collector.missing,
+    }
+}
+
+/// `markdown` with the targets of its links, reference definitions and
+/// `src`, `href` and `poster` attributes replaced where `rewrite` gives a
+/// new one.
+pub fn rewrite_targets(markdown: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -> String {
+    let text = target_link_regex()
+        .replace_all(markdown, |caps: &regex::Captures| {
+            let (target, angled) = match (caps.get(2), caps.get(3)) {
+                (Some(target), _) => (target.as_str(), true),
+                (None, Some(target)) => (target.as_str(), false),
+                (None, None) => return caps[0].to_string(),
+            };
+            match rewrite(target) {
+                Some(target) if angled => format!("{}(<{}>{})", &caps[1], target, &caps[4]),
+                Some(target) => format!("{}({}{})", &caps[1], target, &caps[4]),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned();
+    let text = reference_regex()
+        .replace_all(&text, |caps: &regex::Captures| match rewrite(&caps[2]) {
+            Some(target) => format!("{}{}", &caps[1], target),
+            None => caps[0].to_string(),
+        })
+        .into_owned();
+    html_attribute_regex()
+        .replace_all(&text, |caps: &regex::Captures| {
+            let (value, quote) = match (caps.get(2), caps.get(3)) {
+                (Some(value), _) => (value.as_str(), '"'),
+                (None, Some(value)) => (value.as_str(), '\''),
+                (None, None) => return caps[0].to_string(),
+            };
+            match rewrite(value) {
+                Some(target) => format!("{}{}{}{}", &caps[1], quote, target, quote),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+struct Collector {
+    dir: PathBuf,
+    /// Archive path of each file already added.
+    names: HashMap<PathBuf, String>,
+    taken: HashSet<String>,
+    files: Vec<(PathBuf, String)>,
+    missing: Vec<String>,
+}
+
+impl Collector {
+    /// The archive path `target` is rewritten to, if it is a local file.
+    fn rewrite(&mut self, target: &str) -> Option<String> {
+        if export::is_external(target) || target.starts_with("data:") {
+            return None;
+        }
+        let (path_part, suffix) = match target.find(['?', '#']) {
+            Some(at) => target.split_at(at),
+            None => (target, ""),
+        };
+        let decoded = path_part.replace("%20", " ");
+        let path = export::normalize(&self.dir.join(&decoded));
+        if export::is_markdown(&path) || path.is_dir() {
+            return None;
+        }
+        if !path.is_file() {
+            if !self.missing.iter().any(|missing| missing == path_part) {
+                self.missing.push(path_part.to_string());
+            }
+            return None;
+        }
+        let name = match self.names.get(&path) {
+            Some(name) => name.clone(),
+            None => {
+                let name = self.archive_name(&path);
+                self.names.insert(path.clone(), name.clone());
+                self.taken.insert(name.clone());
+                self.files.push((path, name.clone()));
+                name
+            }
+        };
+        let link = if name.contains(' ') && !target.contains(' ') {
+            name.replace(' ', "%20")
+        } else {
+            name
+        };
+        Some(format!("{}{}", link, suffix))
+    }
+
+    fn archive_name(&self, path: &Path) -> String {
+        if let Ok(relative) = path.strip_prefix(&self.dir) {
+            let name = relative.to_string_lossy().replace('\\', "/");
+            if !self.taken.contains(&name) {
+                return name;
+            }
+        }
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".to_string());
+        let (stem, extension) = match file_name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (file_name.as_str(), String::new()),
+        };
+        (1..)
+            .map(|n| match n {
+                1 => format!("{}/{}", OUTSIDE_DIR, file_name),
+                n => format!("{}/{}-{}{}", OUTSIDE_DIR, stem, n, extension),
+            })
+            .find(|name| !self.taken.contains(name))
+            .expect("unbounded range")
+    }
+}
+
+/// Writes `bundle` to the zip file `output`, with the document as
+/// `document_name` at the top.
+pub fn write(bundle: &Bundle, document_name: &str, output: &Path) -> Result<String, String> {
+    let write_error =
+        |err: std::io::Error| format!("Failed to write {}: {}", output.display(), err);
+    let file = fs::File::create(output).map_err(write_error)?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    zip.add(document_name, bundle.markdown.as_bytes())
+        .map_err(write_error)?;
+    for (path, name) in &bundle.files {
+        let data =
+            fs::read(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        zip.add(name, &data).map_err(write_error)?;
+    }
+    zip.finish().map_err(write_error)?;
+
+    let mut message = format!(
+        "Wrote {} with {} and {} linked file{}.",
+        output.display(),
+        document_name,
+        bundle.files.len(),
+        if bundle.files.len() == 1 { "" } else { "s" }
+    );
+    if !bundle.missing.is_empty() {
+        message.push_str("\n\nThese links point at files that do not exist and were left out:\n");
+        message.push_str(&bundle.missing.join("\n"));
+    }
+    Ok(message)
+}
+
+/// Files zip tools and file managers leave behind.
+fn is_junk(name: &str) -> bool {
+    name.starts_with("__MACOSX/")
+        || name
+            .rsplit('/')
+            .next()
+            .is_some_and(|file| matches!(file, ".DS_Store" | "Thumbs.db" | "desktop.ini"))
+}
+
+/// `%XX` escapes in a link target decoded.
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut at = 0;
+    while at < bytes.len() {
+        let hex = text
+            .get(at + 1..at + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[at], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                at += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                at += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// `target` resolved against the folder `dir` of a `/`-separated path
+/// inside an import, or None when it leaves the import.
+fn resolve(dir: &str, target: &str) -> Option<String> {
+    let mut parts: Vec<&str> = dir.split('/').filter(|part| !part.is_empty()).collect();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn parent_of(name: &str) -> &str {
+    name.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// The files of the zip archive or folder at `source`, by `/`-separated
+/// path. A folder's files keep the folder's name in front; hidden files
+/// and folders are left out.
+pub fn read_source(source: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let read_error = |path: &Path, err: std::io::Error| format!("{}: {}", path.display(), err);
+    if !source.is_dir() {
+        let data = fs::read(source).map_err(|err| read_error(source, err))?;
+        return zip::read_archive(&data);
+    }
+    let base = source.parent().unwrap_or(source);
+    let mut files = Vec::new();
+    let walker = WalkDir::new(source)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        });
+    for entry in walker {
+        let entry = entry.map_err(|err| err.to_string())?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let Ok(relative) = path.strip_prefix(base) else {
+            continue;
+        };
+        let name = relative.to_string_lossy().replace('\\', "/");
+        let data = fs::read(path).map_err(|err| read_error(path, err))?;
+        files.push((name, data));
+    }
+    Ok(files)
+}
+
+/// What an import did.
+pub struct Imported {
+    /// The markdown documents written, in order.
+    pub documents: Vec<PathBuf>,
+    pub assets: usize,
+    /// Files that were already in the workspace with the same content.
+    pub reused: usize,
+    /// Files written under another name because the name was taken.
+    pub renamed: Vec<(String, PathBuf)>,
+}
+
+/// Writes `files` (from [`read_source`]) into `root`, keeping their paths.
+/// A name that is taken gets a number, except that an asset identical to
+/// the existing file is not copied again, and links between the imported
+/// files are fixed to match.
+pub fn import(files: Vec<(String, Vec<u8>)>, root: &Path) -> Result<Imported, String> {
+    import_renamed(files, root, str::to_string)
+}
+
+/// [`import`], writing each file at `rename` of its path instead.
+pub fn import_renamed(
+    files: Vec<(String, Vec<u8>)>,
+    root: &Path,
+    rename: impl Fn(&str) -> String,
+) -> Result<Imported, String> {
+    let mut planned: HashSet<String> = HashSet::new();
+    // Destination of each imported file, by its path in the import.
+    let mut destinations: HashMap<String, String> = HashMap::new();
+    let mut entries = Vec::new();
+    let mut imported = Imported {
+        documents: Vec::new(),
+        assets: 0,
+        reused: 0,
+        renamed: Vec::new(),
+    };
+    for (name, data) in files {
+        if is_junk(&name) {
+            continue;
+        }
+        let outside = || format!("'{}' would be written outside the workspace.", name);
+        let Some(wanted) = resolve("", &rename(&name)).filter(|name| !name.is_empty()) else {
+            return Err(outside());
+        };
+        let Some(name) = resolve("", &name) else {
+            return Err(outside());
+        };
+        let markdown = export::is_markdown(Path::new(&name));
+        let existing = root.join(&wanted);
+        let destination = if !existing.exists() && !planned.contains(&wanted) {
+            wanted
+        } else if !markdown && fs::read(&existing).is_ok_and(|current| current == data) {
+            imported.reused += 1;
+            destinations.insert(name, existing_name(&existing, root));
+            continue;
+        } else {
+            let (stem, extension) = match wanted.rsplit_once('.') {
+                Some((stem, extension)) if !stem.ends_with('/') && !stem.is_empty() => {
+                    (stem.to_string(), format!(".{}", extension))
+                }
+                _ => (wanted.clone(), String::new()),
+            };
+            let renamed = (2..)
+                .map(|n| format!("{}-{}{}", stem, n, extension))
+                .find(|candidate| !root.join(candidate).exists() && !planned.contains(candidate))
+                .expect("unbounded range");
+            imported.renamed.push((wanted, root.join(&renamed)));
+            renamed
+        };
+        planned.insert(destination.clone());
+        destinations.insert(name.clone(), destination.clone());
+        entries.push((name, destination, data));
+    }
+
+    for (name, destination, data) in &entries {
+        let path = root.join(destination);
+        let markdown = export::is_markdown(Path::new(name));
+        let data = match std::str::from_utf8(data) {
+            Ok(text) if markdown => {
+                fix_links(text, name, destination, &destinations, root).into_bytes()
+            }
+            _ => data.clone(),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+        }
+        fs::write(&path, data).map_err(|err| format!("{}: {}", path.display(), err))?;
+        if markdown {
+            imported.documents.push(path);
+        } else {
+            imported.assets += 1;
+        }
+    }
+    Ok(imported)
+}
+
+fn existing_name(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// The links of the document `name`, imported as `destination`, pointed at
+/// where the files they lead to were imported, where that moved them.
+fn fix_links(
+    text: &str,
+    name: &str,
+    destination: &str,
+    destinations: &HashMap<String, String>,
+    root: &Path,
+) -> String {
+    rewrite_targets(text, |target| {
+        if export::is_external(target) || target.starts_with("data:") {
+            return None;
+        }
+        let (path_part, suffix) = match target.find(['?', '#']) {
+            Some(at) => target.split_at(at),
+            None => (target, ""),
+        };
+        let linked = resolve(parent_of(name), &percent_decode(path_part))?;
+        let moved_to = destinations.get(&linked)?;
+        if moved_to == &linked && destination == name {
+            return None;
+        }
+        let from = root.join(parent_of(destination));
+        let relative = export::relative_path(&from, &root.join(moved_to))
+            .to_string_lossy()
+            .replace('\\', "/")
+            .replace(' ', "%20");
+        Some(format!("{}{}", relative, suffix))
+    })
+}
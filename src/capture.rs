@@ -0,0 +1,127 @@
+use crate::dates;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How often the clipboard is read while capturing.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches the clipboard on a background thread and collects every text
+/// copied while it runs. Whatever is on the clipboard when capturing
+/// starts is not collected. Dropping the capture stops it.
+pub struct ClipboardCapture {
+    copied: Arc<Mutex<Vec<String>>>,
+    error: Arc<Mutex<Option<String>>>,
+    stop: Arc<AtomicBool>,
+    /// Entries written so far.
+    pub count: usize,
+}
+
+impl ClipboardCapture {
+    pub fn start(ctx: egui::Context) -> Self {
+        let copied = Arc::new(Mutex::new(Vec::new()));
+        let error = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let copied = Arc::clone(&copied);
+            let error = Arc::clone(&error);
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut clipboard = match arboard::Clipboard::new() {
+                    Ok(clipboard) => clipboard,
+                    Err(err) => {
+                        if let Ok(mut error) = error.lock() {
+                            *error = Some(format!("Clipboard unavailable: {}", err));
+                        }
+                        ctx.request_repaint();
+                        return;
+                    }
+                };
+                let mut last = clipboard.get_text().ok();
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(POLL_INTERVAL);
+                    let Ok(text) = clipboard.get_text() else {
+                        continue;
+                    };
+                    if last.as_ref() == Some(&text) {
+                        continue;
+                    }
+                    last = Some(text.clone());
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    if let Ok(mut copied) = copied.lock() {
+                        copied.push(text);
+                    }
+                    ctx.request_repaint();
+                }
+            });
+        }
+
+        Self {
+            copied,
+            error,
+            stop,
+            count: 0,
+        }
+    }
+
+    /// Texts copied since the last call.
+    pub fn take(&mut self) -> Vec<String> {
+        self.copied
+            .lock()
+            .map(|mut copied| std::mem::take(&mut *copied))
+            .unwrap_or_default()
+    }
+
+    /// Why the clipboard could not be watched, if it could not.
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().ok().and_then(|error| error.clone())
+    }
+}
+
+impl Drop for ClipboardCapture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A capture document entry for `text`, headed by the current UTC date and
+/// time. A copied URL becomes a link; other text is quoted.
+pub fn entry(text: &str) -> String {
+    let text = text.trim();
+    let heading = format!("### {} {} UTC", dates::today(), dates::time_of_day());
+    let is_url = (text.starts_with("http://") || text.starts_with("https://"))
+        && !text.contains(char::is_whitespace);
+    if is_url {
+        return format!("{}\n\n<{}>\n", heading, text);
+    }
+    let quoted: Vec<String> = text
+        .lines()
+        .map(|line| format!("> {}", line).trim_end().to_string())
+        .collect();
+    format!("{}\n\n{}\n", heading, quoted.join("\n"))
+}
+
+/// Appends `entry` to `document`, separated from what is there by a blank
+/// line.
+pub fn append_entry(document: &mut String, entry: &str) {
+    let trimmed = document.trim_end_matches('\n').len();
+    document.truncate(trimmed);
+    if !document.is_empty() {
+        document.push_str("\n\n");
+    }
+    document.push_str(entry);
+}
+
+/// Appends `entries` to the capture file at `path`, creating it if needed.
+pub fn append_to_file(path: &Path, entries: &[String]) -> Result<(), String> {
+    let mut document = fs::read_to_string(path).unwrap_or_default();
+    for entry in entries {
+        append_entry(&mut document, entry);
+    }
+    fs::write(path, document).map_err(|err| format!("{}: {}", path.display(), err))
+}
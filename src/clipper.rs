@@ -0,0 +1,179 @@
+use crate::dates;
+use crate::html_markdown;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Method, Response, Server};
+use url::Url;
+
+/// Larger request bodies are refused.
+const MAX_BODY_BYTES: u64 = 16 * 1024 * 1024;
+
+/// A page sent by the bookmarklet, converted to markdown.
+pub struct Clip {
+    pub url: String,
+    pub title: String,
+    pub markdown: String,
+}
+
+/// A local HTTP endpoint, on 127.0.0.1 only, that accepts pages POSTed by
+/// the web clipper bookmarklet. Requests must carry the token the
+/// bookmarklet was made with, so other web pages cannot file clips.
+/// Dropping the clipper stops the server.
+pub struct Clipper {
+    server: Arc<Server>,
+    clips: Arc<Mutex<Vec<Clip>>>,
+    pub port: u16,
+    /// Clips filed so far.
+    pub count: usize,
+}
+
+impl Clipper {
+    pub fn start(port: u16, token: &str, ctx: egui::Context) -> Result<Self, String> {
+        let server = Server::http(("127.0.0.1", port))
+            .map_err(|err| format!("Web clipper could not listen on port {}: {}", port, err))?;
+        let server = Arc::new(server);
+        let clips = Arc::new(Mutex::new(Vec::new()));
+        {
+            let server = Arc::clone(&server);
+            let clips = Arc::clone(&clips);
+            let token = token.to_string();
+            thread::spawn(move || {
+                for mut request in server.incoming_requests() {
+                    let (status, message) = match request.method() {
+                        Method::Post => {
+                            let mut body = String::new();
+                            let read = request
+                                .as_reader()
+                                .take(MAX_BODY_BYTES)
+                                .read_to_string(&mut body);
+                            match read
+                                .map_err(|err| err.to_string())
+                                .and_then(|_| clip(&body, &token))
+                            {
+                                Ok(clip) => {
+                                    if let Ok(mut clips) = clips.lock() {
+                                        clips.push(clip);
+                                    }
+                                    ctx.request_repaint();
+                                    (200, "Clipped".to_string())
+                                }
+                                Err(err) => (400, err),
+                            }
+                        }
+                        _ => (200, "md-echo web clipper".to_string()),
+                    };
+                    let response = Response::from_string(message)
+                        .with_status_code(status)
+                        .with_header(
+                            Header::from_bytes(&b"Access-Control-Allow-Origin"[..], &b"*"[..])
+                                .expect("valid header"),
+                        );
+                    let _ = request.respond(response);
+                }
+            });
+        }
+        Ok(Self {
+            server,
+            clips,
+            port,
+            count: 0,
+        })
+    }
+
+    /// Clips received since the last call.
+    pub fn take(&mut self) -> Vec<Clip> {
+        self.clips
+            .lock()
+            .map(|mut clips| std::mem::take(&mut *clips))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Clipper {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+/// Parses a clip from a form-encoded body with `token`, `url`, `title`,
+/// and `html` (the selection, or the page) fields.
+fn clip(body: &str, token: &str) -> Result<Clip, String> {
+    let fields: Vec<(String, String)> = url::form_urlencoded::parse(body.as_bytes())
+        .into_owned()
+        .collect();
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map_or("", |(_, value)| value.as_str())
+    };
+    if token.is_empty() || field("token") != token {
+        return Err("Wrong token; copy the bookmarklet from md-echo again.".to_string());
+    }
+    let url = field("url").to_string();
+    let base = Url::parse(&url).ok();
+    let html = field("html");
+    let markdown = if html.trim().is_empty() {
+        String::new()
+    } else {
+        html_markdown::convert(html, base.as_ref())
+    };
+    let title = field("title").trim();
+    let title = if title.is_empty() {
+        url.as_str()
+    } else {
+        title
+    };
+    Ok(Clip {
+        title: title.to_string(),
+        url,
+        markdown,
+    })
+}
+
+/// An inbox entry for `clip`: a linked heading, when it was clipped, and
+/// the page content.
+pub fn entry(clip: &Clip) -> String {
+    let title = clip.title.replace(['[', ']'], "");
+    let heading = if clip.url.is_empty() {
+        format!("## {}", title)
+    } else {
+        format!("## [{}](<{}>)", title, clip.url)
+    };
+    let mut entry = format!(
+        "{}\n\n*Clipped {} {} UTC*\n",
+        heading,
+        dates::today(),
+        dates::time_of_day()
+    );
+    if !clip.markdown.trim().is_empty() {
+        entry.push('\n');
+        entry.push_str(clip.markdown.trim_end());
+        entry.push('\n');
+    }
+    entry
+}
+
+/// A random token for a new bookmarklet.
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// A `javascript:` bookmarklet that sends the selection (or the article,
+/// or the whole page) to the clipper on `port`.
+pub fn bookmarklet(port: u16, token: &str) -> String {
+    format!(
+        "javascript:(()=>{{const s=getSelection();let h='';\
+if(s.rangeCount&&!s.isCollapsed){{const d=document.createElement('div');\
+for(let i=0;i<s.rangeCount;i++)d.appendChild(s.getRangeAt(i).cloneContents());h=d.innerHTML;}}\
+else{{h=(document.querySelector('article')||document.querySelector('main')||document.body).innerHTML;}}\
+fetch('http://127.0.0.1:{}/clip',{{method:'POST',body:new URLSearchParams({{token:'{}',url:location.href,title:document.title,html:h}})}})\
+.then(r=>r.text()).then(t=>alert('md-echo: '+t)).catch(()=>alert('md-echo is not listening'));}})()",
+        port, token
+    )
+}
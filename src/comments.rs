@@ -0,0 +1,86 @@
+use crate::export;
+use std::ops::Range;
+
+/// An HTML comment in the source, used as a lightweight review note.
+pub struct HtmlComment {
+    pub range: Range<usize>,
+    /// Zero-based line the comment starts on.
+    pub line: usize,
+    pub text: String,
+}
+
+impl HtmlComment {
+    /// Comments that carry md-echo directives rather than notes.
+    pub fn is_directive(&self) -> bool {
+        self.text.starts_with("md-echo-")
+    }
+}
+
+/// Finds every `<!-- ... -->` comment outside fenced code blocks.
+pub fn find_comments(text: &str) -> Vec<HtmlComment> {
+    let mut comments = Vec::new();
+    let mut in_fence = false;
+    let mut offset = 0;
+    let mut open: Option<(usize, usize)> = None;
+
+    for (line_index, line) in text.split_inclusive('\n').enumerate() {
+        if open.is_none() && export::is_fence(line) {
+            in_fence = !in_fence;
+        }
+        if !in_fence {
+            let mut cursor = 0;
+            loop {
+                match open {
+                    None => match line[cursor..].find("<!--") {
+                        Some(start) => {
+                            open = Some((offset + cursor + start, line_index));
+                            cursor += start + 4;
+                        }
+                        None => break,
+                    },
+                    Some((start, start_line)) => match line[cursor..].find("-->") {
+                        Some(end) => {
+                            let end = offset + cursor + end + 3;
+                            comments.push(HtmlComment {
+                                range: start..end,
+                                line: start_line,
+                                text: text[start + 4..end - 3].trim().to_string(),
+                            });
+                            open = None;
+                            cursor = end - offset;
+                        }
+                        None => break,
+                    },
+                }
+            }
+        }
+        offset += line.len();
+    }
+    comments
+}
+
+/// Removes HTML comments, or turns them into visible italic notes when
+/// `show` is set.
+pub fn render_comments(markdown: &str, show: bool) -> String {
+    let comments = find_comments(markdown);
+    if comments.is_empty() {
+        return markdown.to_string();
+    }
+
+    let mut out = String::with_capacity(markdown.len());
+    let mut last = 0;
+    for comment in comments {
+        out.push_str(&markdown[last..comment.range.start]);
+        if show && !comment.is_directive() && !comment.text.is_empty() {
+            let note = comment
+                .text
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            out.push_str(&format!("*💬 {}*", note.replace('*', "\\*")));
+        }
+        last = comment.range.end;
+    }
+    out.push_str(&markdown[last..]);
+    out
+}
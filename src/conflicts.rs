@@ -0,0 +1,123 @@
+use std::ops::Range;
+
+/// A `<<<<<<<` ... `>>>>>>>` block left behind by a merge.
+pub struct Conflict {
+    /// Whole block including the marker lines.
+    pub range: Range<usize>,
+    /// Zero-based line of the `<<<<<<<` marker.
+    pub line: usize,
+    pub ours: Range<usize>,
+    /// Common ancestor section of diff3-style conflicts (`|||||||`).
+    pub base: Option<Range<usize>>,
+    pub theirs: Range<usize>,
+    pub ours_label: String,
+    pub theirs_label: String,
+}
+
+#[derive(Clone, Copy)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    Both,
+}
+
+impl Conflict {
+    /// Text that replaces the whole block for `resolution`.
+    pub fn resolve(&self, text: &str, resolution: Resolution) -> String {
+        match resolution {
+            Resolution::Ours => text[self.ours.clone()].to_string(),
+            Resolution::Theirs => text[self.theirs.clone()].to_string(),
+            Resolution::Both => {
+                let mut both = text[self.ours.clone()].to_string();
+                if !both.is_empty() && !both.ends_with('\n') {
+                    both.push('\n');
+                }
+                both.push_str(&text[self.theirs.clone()]);
+                both
+            }
+        }
+    }
+
+    /// Byte ranges of the four marker lines, for highlighting.
+    pub fn marker_ranges(&self) -> Vec<Range<usize>> {
+        let mut markers = Vec::with_capacity(4);
+        markers.push(self.range.start..self.ours.start);
+        match &self.base {
+            Some(base) => {
+                markers.push(self.ours.end..base.start);
+                markers.push(base.end..self.theirs.start);
+            }
+            None => markers.push(self.ours.end..self.theirs.start),
+        }
+        markers.push(self.theirs.end..self.range.end);
+        markers
+    }
+}
+
+fn marker<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(prefix)?;
+    (rest.is_empty() || rest.starts_with([' ', '\t', '\r', '\n'])).then(|| rest.trim())
+}
+
+/// Finds complete conflict blocks; unterminated ones are ignored.
+pub fn find_conflicts(text: &str) -> Vec<Conflict> {
+    if !text.contains("<<<<<<<") {
+        return Vec::new();
+    }
+
+    struct Open {
+        start: usize,
+        line: usize,
+        ours_label: String,
+        ours_start: usize,
+        ours_end: Option<usize>,
+        base_start: Option<usize>,
+        base_end: Option<usize>,
+        theirs_start: Option<usize>,
+    }
+
+    let mut conflicts = Vec::new();
+    let mut open: Option<Open> = None;
+    let mut offset = 0;
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let next = offset + line.len();
+        if let Some(label) = marker(line, "<<<<<<<") {
+            open = Some(Open {
+                start: offset,
+                line: index,
+                ours_label: label.to_string(),
+                ours_start: next,
+                ours_end: None,
+                base_start: None,
+                base_end: None,
+                theirs_start: None,
+            });
+        } else if let Some(current) = open.as_mut() {
+            if current.theirs_start.is_none() && marker(line, "|||||||").is_some() {
+                current.ours_end.get_or_insert(offset);
+                current.base_start = Some(next);
+            } else if current.theirs_start.is_none() && line.trim_end() == "=======" {
+                current.ours_end.get_or_insert(offset);
+                if current.base_start.is_some() {
+                    current.base_end = Some(offset);
+                }
+                current.theirs_start = Some(next);
+            } else if let (Some(label), Some(theirs_start)) =
+                (marker(line, ">>>>>>>"), current.theirs_start)
+            {
+                let current = open.take().expect("conflict is open");
+                conflicts.push(Conflict {
+                    range: current.start..next,
+                    line: current.line,
+                    ours: current.ours_start..current.ours_end.unwrap_or(current.ours_start),
+                    base: current.base_start.zip(current.base_end).map(|(a, b)| a..b),
+                    theirs: theirs_start..offset,
+                    ours_label: current.ours_label,
+                    theirs_label: label.to_string(),
+                });
+            }
+        }
+        offset = next;
+    }
+    conflicts
+}
@@ -0,0 +1,529 @@
+use crate::export;
+use pulldown_cmark::{Event, LinkType, Parser, Tag, TagEnd};
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Words left lowercase inside a title-case heading.
+const SMALL_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "from", "in", "into", "nor", "of", "off",
+    "on", "or", "over", "per", "so", "the", "to", "up", "via", "vs", "with", "yet",
+];
+
+/// A style choice that should be made the same way across the workspace.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    HeadingCase,
+    ListMarker,
+    Emphasis,
+    Strong,
+    LinkStyle,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 5] = [
+        Rule::HeadingCase,
+        Rule::ListMarker,
+        Rule::Emphasis,
+        Rule::Strong,
+        Rule::LinkStyle,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Rule::HeadingCase => "Heading capitalization",
+            Rule::ListMarker => "Bullet list markers",
+            Rule::Emphasis => "Emphasis style",
+            Rule::Strong => "Strong emphasis style",
+            Rule::LinkStyle => "Link style",
+        }
+    }
+
+    /// The styles the rule tells apart; findings refer to them by index.
+    pub fn styles(self) -> &'static [&'static str] {
+        match self {
+            Rule::HeadingCase => &["Title Case", "Sentence case"],
+            Rule::ListMarker => &["-", "*", "+"],
+            Rule::Emphasis => &["*text*", "_text_"],
+            Rule::Strong => &["**text**", "__text__"],
+            Rule::LinkStyle => &["[text](url)", "[text][ref]"],
+        }
+    }
+}
+
+/// A place where a rule's style is used.
+pub struct Finding {
+    pub path: PathBuf,
+    /// Zero-based line.
+    pub line: usize,
+    pub style: usize,
+    /// The line, trimmed, for the report.
+    pub excerpt: String,
+}
+
+/// What the workspace does for one rule.
+pub struct Report {
+    pub rule: Rule,
+    /// Uses of each style.
+    pub counts: Vec<usize>,
+    pub findings: Vec<Finding>,
+    /// The style to fix the others to; the most common one at first.
+    pub target: usize,
+}
+
+impl Report {
+    /// Whether more than one style is in use.
+    pub fn is_mixed(&self) -> bool {
+        self.counts.iter().filter(|&&count| count > 0).count() > 1
+    }
+
+    /// The findings that do not use the target style.
+    pub fn deviations(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.style != self.target)
+    }
+}
+
+/// Checks every rule across `documents` (path and text).
+pub fn check(documents: &[(PathBuf, String)]) -> Vec<Report> {
+    Rule::ALL
+        .into_iter()
+        .map(|rule| {
+            let mut counts = vec![0; rule.styles().len()];
+            let mut findings = Vec::new();
+            for (path, text) in documents {
+                for usage in usages(text, rule) {
+                    counts[usage.style] += 1;
+                    let line_start = text[..usage.start].rfind('\n').map_or(0, |index| index + 1);
+                    let line_end = text[usage.start..]
+                        .find('\n')
+                        .map_or(text.len(), |index| usage.start + index);
+                    findings.push(Finding {
+                        path: path.clone(),
+                        line: text[..usage.start].matches('\n').count(),
+                        style: usage.style,
+                        excerpt: text[line_start..line_end].trim().to_string(),
+                    });
+                }
+            }
+            // Ties go to the first style listed.
+            let target = (0..counts.len())
+                .rev()
+                .max_by_key(|&style| counts[style])
+                .unwrap_or(0);
+            Report {
+                rule,
+                counts,
+                findings,
+                target,
+            }
+        })
+        .collect()
+}
+
+/// `text` with every use of `rule` switched to style `target`.
+pub fn fix(text: &str, rule: Rule, target: usize) -> String {
+    let mut edits: Vec<(Range<usize>, String)> = usages(text, rule)
+        .into_iter()
+        .filter(|usage| usage.style != target)
+        .flat_map(|usage| usage.edits(text, target))
+        .collect();
+    if rule == Rule::LinkStyle {
+        edits.extend(link_definition_edits(text, target));
+    }
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    let mut fixed = text.to_string();
+    for (range, replacement) in edits {
+        fixed.replace_range(range, &replacement);
+    }
+    fixed
+}
+
+/// One use of a style, with what it takes to change it.
+struct Usage {
+    style: usize,
+    start: usize,
+    kind: UsageKind,
+}
+
+enum UsageKind {
+    /// The ranges of the heading's text, without inline code or link
+    /// destinations.
+    Heading(Vec<Range<usize>>),
+    /// A delimiter character at each offset, one of `symbols` by style.
+    Delimiters {
+        offsets: Vec<usize>,
+        symbols: &'static [char],
+    },
+    Link(LinkUsage),
+}
+
+struct LinkUsage {
+    range: Range<usize>,
+    /// The link text, brackets excluded.
+    text: Range<usize>,
+    destination: String,
+    title: String,
+}
+
+impl Usage {
+    fn edits(&self, text: &str, target: usize) -> Vec<(Range<usize>, String)> {
+        match &self.kind {
+            UsageKind::Heading(ranges) => {
+                let mut first = true;
+                ranges
+                    .iter()
+                    .map(|range| {
+                        let recased = recase(&text[range.clone()], target == 0, &mut first);
+                        (range.clone(), recased)
+                    })
+                    .collect()
+            }
+            UsageKind::Delimiters { offsets, symbols } => offsets
+                .iter()
+                .map(|&offset| (offset..offset + 1, symbols[target].to_string()))
+                .collect(),
+            UsageKind::Link(link) => {
+                let label = &text[link.text.clone()];
+                let replacement = if target == 0 {
+                    format!("[{}]({})", label, inline_destination(link))
+                } else {
+                    format!("[{}][{}]", label, reference_label(text, link))
+                };
+                vec![(link.range.clone(), replacement)]
+            }
+        }
+    }
+}
+
+fn usages(text: &str, rule: Rule) -> Vec<Usage> {
+    let mut usages = Vec::new();
+    let mut heading: Option<(usize, Vec<Range<usize>>)> = None;
+    // The link being read, and the span of its text so far.
+    let mut link: Option<(LinkType, LinkUsage, Option<Range<usize>>)> = None;
+    let mut list_is_bullet = Vec::new();
+    let mut in_link = false;
+    for (event, range) in Parser::new_ext(text, export::markdown_options()).into_offset_iter() {
+        if let Some((_, _, span)) = &mut link {
+            if !matches!(event, Event::End(TagEnd::Link)) {
+                let span = span.get_or_insert(range.clone());
+                span.start = span.start.min(range.start);
+                span.end = span.end.max(range.end);
+            }
+        }
+        match event {
+            Event::Start(Tag::Heading { .. }) => heading = Some((range.start, Vec::new())),
+            Event::End(TagEnd::Heading(_)) => {
+                let Some((start, ranges)) = heading.take() else {
+                    continue;
+                };
+                let words = ranges.iter().map(|range| &text[range.clone()]);
+                if rule == Rule::HeadingCase {
+                    if let Some(style) = heading_case(words) {
+                        usages.push(Usage {
+                            style,
+                            start,
+                            kind: UsageKind::Heading(ranges),
+                        });
+                    }
+                }
+            }
+            Event::Text(_) if !in_link => {
+                if let Some((_, ranges)) = &mut heading {
+                    ranges.push(range);
+                }
+            }
+            Event::Start(Tag::List(first)) => list_is_bullet.push(first.is_none()),
+            Event::End(TagEnd::List(_)) => {
+                list_is_bullet.pop();
+            }
+            Event::Start(Tag::Item) if rule == Rule::ListMarker => {
+                let style = match text.as_bytes()[range.start] {
+                    b'-' => 0,
+                    b'*' => 1,
+                    b'+' => 2,
+                    _ => continue,
+                };
+                if list_is_bullet.last() == Some(&true) {
+                    usages.push(Usage {
+                        style,
+                        start: range.start,
+                        kind: UsageKind::Delimiters {
+                            offsets: vec![range.start],
+                            symbols: &['-', '*', '+'],
+                        },
+                    });
+                }
+            }
+            Event::Start(Tag::Emphasis) if rule == Rule::Emphasis => {
+                if let Some(style) = emphasis_style(text, &range, 1) {
+                    usages.push(Usage {
+                        style,
+                        start: range.start,
+                        kind: UsageKind::Delimiters {
+                            offsets: vec![range.start, range.end - 1],
+                            symbols: &['*', '_'],
+                        },
+                    });
+                }
+            }
+            Event::Start(Tag::Strong) if rule == Rule::Strong => {
+                if let Some(style) = emphasis_style(text, &range, 2) {
+                    let end = range.end;
+                    usages.push(Usage {
+                        style,
+                        start: range.start,
+                        kind: UsageKind::Delimiters {
+                            offsets: vec![range.start, range.start + 1, end - 2, end - 1],
+                            symbols: &['*', '_'],
+                        },
+                    });
+                }
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                ..
+            }) => {
+                in_link = true;
+                let usage = LinkUsage {
+                    range,
+                    text: 0..0,
+                    destination: dest_url.to_string(),
+                    title: title.to_string(),
+                };
+                link = Some((link_type, usage, None));
+            }
+            Event::End(TagEnd::Link) => {
+                in_link = false;
+                let Some((link_type, usage, text_range)) = link.take() else {
+                    continue;
+                };
+                let style = match link_type {
+                    LinkType::Inline => 0,
+                    LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut => 1,
+                    _ => continue,
+                };
+                // Links without text are left alone; there is nothing to
+                // put between the brackets.
+                if let (Rule::LinkStyle, Some(text_range)) = (rule, text_range) {
+                    usages.push(Usage {
+                        style,
+                        start: usage.range.start,
+                        kind: UsageKind::Link(LinkUsage {
+                            text: text_range,
+                            ..usage
+                        }),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    usages
+}
+
+fn emphasis_style(text: &str, range: &Range<usize>, width: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let delimiter = bytes[range.start];
+    let same = |offset: usize| bytes[offset] == delimiter;
+    let closed = (0..width).all(|index| same(range.start + index) && same(range.end - 1 - index));
+    match delimiter {
+        b'*' if closed => Some(0),
+        b'_' if closed => Some(1),
+        _ => None,
+    }
+}
+
+/// The words of a heading that say whether it is in title or sentence
+/// case: all but the first, leaving out small words, acronyms, and words
+/// that do not start with a letter.
+fn telling_words<'a>(words: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    words
+        .flat_map(str::split_whitespace)
+        .skip(1)
+        .filter(|word| {
+            let mut chars = word.chars();
+            let starts_with_letter = chars.next().is_some_and(char::is_alphabetic);
+            let rest_lowercase = chars.all(|c| !c.is_uppercase());
+            starts_with_letter
+                && rest_lowercase
+                && !SMALL_WORDS.contains(&word.to_lowercase().as_str())
+        })
+        .collect()
+}
+
+/// 0 for title case, 1 for sentence case, or `None` when the heading has
+/// no words that tell. A single lowercase word means sentence case, so
+/// capitalized proper nouns do not make a sentence-case heading look
+/// like title case.
+fn heading_case<'a>(words: impl Iterator<Item = &'a str>) -> Option<usize> {
+    let words = telling_words(words);
+    if words.is_empty() {
+        return None;
+    }
+    let lowercase = words
+        .iter()
+        .any(|word| word.starts_with(|c: char| c.is_lowercase()));
+    Some(if lowercase { 1 } else { 0 })
+}
+
+/// `text` with its words capitalized for title case (but for small
+/// words), or lowercased for sentence case. The first word of the heading (tracked by `first`
+/// across text pieces) is always capitalized.
+fn recase(text: &str, title: bool, first: &mut bool) -> String {
+    let mut recased = String::with_capacity(text.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, recased: &mut String, first: &mut bool| {
+        if word.is_empty() {
+            return;
+        }
+        let mut chars = word.chars();
+        let initial = chars.next().unwrap_or_default();
+        let rest: String = chars.collect();
+        let plain = initial.is_alphabetic() && !rest.chars().any(char::is_uppercase);
+        let small = SMALL_WORDS.contains(&word.to_lowercase().as_str());
+        if plain && (*first || (title && !small)) {
+            recased.extend(initial.to_uppercase());
+        } else if plain {
+            recased.extend(initial.to_lowercase());
+        } else {
+            recased.push(initial);
+        }
+        recased.push_str(&rest);
+        *first = false;
+        word.clear();
+    };
+    for c in text.chars() {
+        if c.is_whitespace() {
+            flush(&mut word, &mut recased, first);
+            recased.push(c);
+        } else {
+            word.push(c);
+        }
+    }
+    flush(&mut word, &mut recased, first);
+    recased
+}
+
+fn inline_destination(link: &LinkUsage) -> String {
+    let destination = if link.destination.contains([' ', '(', ')']) {
+        format!("<{}>", link.destination)
+    } else {
+        link.destination.clone()
+    };
+    if link.title.is_empty() {
+        destination
+    } else {
+        format!("{} \"{}\"", destination, link.title.replace('"', "\\\""))
+    }
+}
+
+/// The label of an existing definition for the link's destination and
+/// title, or a new numeric label (defined by [`link_definition_edits`]).
+fn reference_label(text: &str, link: &LinkUsage) -> String {
+    let parser = Parser::new_ext(text, export::markdown_options());
+    let existing = parser
+        .reference_definitions()
+        .iter()
+        .find_map(|(label, def)| {
+            let title = def.title.as_deref().unwrap_or_default();
+            (*def.dest == *link.destination && title == link.title).then(|| label.to_string())
+        });
+    existing.unwrap_or_else(|| {
+        let index = new_references(text)
+            .iter()
+            .position(|(destination, title)| {
+                *destination == link.destination && *title == link.title
+            })
+            .unwrap_or_default();
+        (next_number(text) + index).to_string()
+    })
+}
+
+/// The destinations and titles of inline links that have no definition
+/// to reuse, in order and without repeats.
+fn new_references(text: &str) -> Vec<(String, String)> {
+    let parser = Parser::new_ext(text, export::markdown_options());
+    let definitions = parser.reference_definitions();
+    let mut new: Vec<(String, String)> = Vec::new();
+    for usage in usages(text, Rule::LinkStyle) {
+        let UsageKind::Link(link) = usage.kind else {
+            continue;
+        };
+        let defined = definitions.iter().any(|(_, def)| {
+            *def.dest == *link.destination && def.title.as_deref().unwrap_or_default() == link.title
+        });
+        let pair = (link.destination, link.title);
+        if usage.style == 0 && !defined && !new.contains(&pair) {
+            new.push(pair);
+        }
+    }
+    new
+}
+
+/// One more than the largest numeric reference label.
+fn next_number(text: &str) -> usize {
+    let parser = Parser::new_ext(text, export::markdown_options());
+    let largest = parser
+        .reference_definitions()
+        .iter()
+        .filter_map(|(label, _)| label.parse::<usize>().ok())
+        .max();
+    largest.map_or(1, |largest| largest + 1)
+}
+
+/// For inline links: removes the definitions, as the reference links that
+/// used them are converted, except those images use. For reference links:
+/// appends the definitions of the converted inline links.
+fn link_definition_edits(text: &str, target: usize) -> Vec<(Range<usize>, String)> {
+    if target == 1 {
+        let new = new_references(text);
+        if new.is_empty() {
+            return Vec::new();
+        }
+        let first = next_number(text);
+        let mut appended = String::from(if text.ends_with('\n') { "\n" } else { "\n\n" });
+        for (index, (destination, title)) in new.iter().enumerate() {
+            let link = LinkUsage {
+                range: 0..0,
+                text: 0..0,
+                destination: destination.clone(),
+                title: title.clone(),
+            };
+            appended.push_str(&format!(
+                "[{}]: {}\n",
+                first + index,
+                inline_destination(&link)
+            ));
+        }
+        return vec![(text.len()..text.len(), appended)];
+    }
+
+    // Images keep their definitions.
+    let mut image_labels = Vec::new();
+    for event in Parser::new_ext(text, export::markdown_options()) {
+        if let Event::Start(Tag::Image { link_type, id, .. }) = event {
+            if matches!(
+                link_type,
+                LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut
+            ) {
+                image_labels.push(id.to_lowercase());
+            }
+        }
+    }
+    let parser = Parser::new_ext(text, export::markdown_options());
+    parser
+        .reference_definitions()
+        .iter()
+        .filter(|(label, _)| !image_labels.contains(&label.to_lowercase()))
+        .map(|(_, def)| {
+            let mut span = def.span.clone();
+            if text[span.end..].starts_with('\n') {
+                span.end += 1;
+            }
+            (span, String::new())
+        })
+        .collect()
+}
@@ -0,0 +1,240 @@
+use crate::{export, peek};
+use egui::text::{LayoutJob, TextFormat};
+use egui::{Color32, FontId};
+use std::ops::Range;
+
+pub const ADDITION_COLOR: Color32 = Color32::from_rgb(70, 170, 90);
+pub const DELETION_COLOR: Color32 = Color32::from_rgb(210, 80, 80);
+pub const SUBSTITUTION_COLOR: Color32 = Color32::from_rgb(210, 150, 50);
+pub const NOTE_COLOR: Color32 = Color32::from_rgb(100, 140, 220);
+
+/// One CriticMarkup edit suggestion.
+#[derive(Clone, PartialEq)]
+pub enum ChangeKind {
+    /// `{++text++}`
+    Addition(String),
+    /// `{--text--}`
+    Deletion(String),
+    /// `{~~old~>new~~}`
+    Substitution { old: String, new: String },
+    /// `{>>text<<}`
+    Comment(String),
+    /// `{==text==}`
+    Highlight(String),
+}
+
+pub struct Change {
+    pub range: Range<usize>,
+    /// Zero-based line the markup starts on.
+    pub line: usize,
+    pub kind: ChangeKind,
+}
+
+impl Change {
+    /// Text that replaces the markup when the change is accepted or rejected.
+    pub fn resolve(&self, accept: bool) -> &str {
+        match (&self.kind, accept) {
+            (ChangeKind::Addition(text), true) | (ChangeKind::Deletion(text), false) => text,
+            (ChangeKind::Addition(_), false) | (ChangeKind::Deletion(_), true) => "",
+            (ChangeKind::Substitution { new, .. }, true) => new,
+            (ChangeKind::Substitution { old, .. }, false) => old,
+            (ChangeKind::Comment(_), _) => "",
+            (ChangeKind::Highlight(text), _) => text,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match &self.kind {
+            ChangeKind::Addition(text) => format!("+ {}", text),
+            ChangeKind::Deletion(text) => format!("− {}", text),
+            ChangeKind::Substitution { old, new } => format!("{} → {}", old, new),
+            ChangeKind::Comment(text) => format!("💬 {}", text),
+            ChangeKind::Highlight(text) => format!("▌{}", text),
+        }
+    }
+}
+
+const MARKERS: [(&str, &str); 5] = [
+    ("{++", "++}"),
+    ("{--", "--}"),
+    ("{~~", "~~}"),
+    ("{>>", "<<}"),
+    ("{==", "==}"),
+];
+
+/// Finds all CriticMarkup outside fenced code blocks, in source order.
+pub fn find_changes(text: &str) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let mut in_fence = false;
+    let mut offset = 0;
+    let mut segment_start = 0;
+    let mut segments = Vec::new();
+    for line in text.split_inclusive('\n') {
+        if export::is_fence(line) {
+            if !in_fence {
+                segments.push(segment_start..offset);
+            }
+            in_fence = !in_fence;
+            segment_start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    if !in_fence {
+        segments.push(segment_start..text.len());
+    }
+
+    let mut line = 0;
+    let mut counted = 0;
+    let mut line_of = |offset: usize| {
+        line += text[counted..offset].matches('\n').count();
+        counted = offset;
+        line
+    };
+    for segment in segments {
+        let mut cursor = segment.start;
+        while let Some(found) = text[cursor..segment.end].find('{') {
+            let start = cursor + found;
+            let rest = &text[start..segment.end];
+            let Some((open, close)) = MARKERS.iter().find(|(open, _)| rest.starts_with(open))
+            else {
+                cursor = start + 1;
+                continue;
+            };
+            let Some(inner_len) = rest[open.len()..].find(close) else {
+                cursor = start + 1;
+                continue;
+            };
+            let inner = &rest[open.len()..open.len() + inner_len];
+            let end = start + open.len() + inner_len + close.len();
+            let kind = match *open {
+                "{++" => ChangeKind::Addition(inner.to_string()),
+                "{--" => ChangeKind::Deletion(inner.to_string()),
+                "{~~" => match inner.split_once("~>") {
+                    Some((old, new)) => ChangeKind::Substitution {
+                        old: old.to_string(),
+                        new: new.to_string(),
+                    },
+                    None => {
+                        cursor = start + 1;
+                        continue;
+                    }
+                },
+                "{>>" => ChangeKind::Comment(inner.to_string()),
+                _ => ChangeKind::Highlight(inner.to_string()),
+            };
+            changes.push(Change {
+                range: start..end,
+                line: line_of(start),
+                kind,
+            });
+            cursor = end;
+        }
+    }
+    changes
+}
+
+/// Accepts or rejects every change in `text`.
+pub fn resolve_all(text: &str, accept: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for change in find_changes(text) {
+        out.push_str(&text[last..change.range.start]);
+        out.push_str(change.resolve(accept));
+        last = change.range.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// A preview block with pending suggestions, laid out in `font` with the
+/// suggestions colored as in the editor: additions green, deletions red and
+/// struck through, highlights yellow and comments as blue notes. The text
+/// around them is shown without its markdown syntax.
+pub fn layout_changes(markdown: &str, font: FontId, color: Color32, dark_mode: bool) -> LayoutJob {
+    let background = if dark_mode { 0.2 } else { 0.12 };
+    let plain = TextFormat {
+        font_id: font.clone(),
+        color,
+        ..Default::default()
+    };
+    let marked = |color: Color32| TextFormat {
+        font_id: font.clone(),
+        color,
+        background: color.gamma_multiply(background),
+        ..Default::default()
+    };
+    let deleted = TextFormat {
+        strikethrough: egui::Stroke::new(1.0, DELETION_COLOR),
+        ..marked(DELETION_COLOR)
+    };
+    let highlighted = TextFormat {
+        background: SUBSTITUTION_COLOR.gamma_multiply(0.35),
+        ..plain.clone()
+    };
+    let note = TextFormat {
+        italics: true,
+        ..marked(NOTE_COLOR)
+    };
+
+    let mut job = LayoutJob::default();
+    let mut last = 0;
+    for change in find_changes(markdown) {
+        job.append(
+            &plain_text(&markdown[last..change.range.start]),
+            0.0,
+            plain.clone(),
+        );
+        match &change.kind {
+            ChangeKind::Addition(text) => job.append(text, 0.0, marked(ADDITION_COLOR)),
+            ChangeKind::Deletion(text) => job.append(text, 0.0, deleted.clone()),
+            ChangeKind::Substitution { old, new } => {
+                job.append(old, 0.0, deleted.clone());
+                job.append(new, 0.0, marked(ADDITION_COLOR));
+            }
+            ChangeKind::Comment(text) => {
+                job.append(&format!(" 💬 {} ", text.trim()), 0.0, note.clone())
+            }
+            ChangeKind::Highlight(text) => job.append(text, 0.0, highlighted.clone()),
+        }
+        last = change.range.end;
+    }
+    job.append(plain_text(&markdown[last..]).trim_end(), 0.0, plain);
+    job
+}
+
+/// `markdown` as the viewer would show its text, keeping the blank space
+/// around it so it joins up with the suggestions next to it.
+fn plain_text(markdown: &str) -> String {
+    let trimmed = markdown.trim();
+    if trimmed.is_empty() {
+        return markdown.to_string();
+    }
+    let leading = &markdown[..markdown.len() - markdown.trim_start().len()];
+    let trailing = &markdown[markdown.trim_end().len()..];
+    let (text, _) = peek::targets(trimmed);
+    format!("{}{}{}", leading, text.trim_end(), trailing)
+}
+
+/// Rewrites CriticMarkup as inline HTML (`<ins>`, `<del>`, `<mark>`) for
+/// HTML and PDF exports.
+pub fn to_html_markup(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut last = 0;
+    for change in find_changes(markdown) {
+        out.push_str(&markdown[last..change.range.start]);
+        match &change.kind {
+            ChangeKind::Addition(text) => out.push_str(&format!("<ins>{}</ins>", text)),
+            ChangeKind::Deletion(text) => out.push_str(&format!("<del>{}</del>", text)),
+            ChangeKind::Substitution { old, new } => {
+                out.push_str(&format!("<del>{}</del><ins>{}</ins>", old, new))
+            }
+            ChangeKind::Comment(text) => {
+                out.push_str(&format!("<span class=\"critic-comment\">{}</span>", text))
+            }
+            ChangeKind::Highlight(text) => out.push_str(&format!("<mark>{}</mark>", text)),
+        }
+        last = change.range.end;
+    }
+    out.push_str(&markdown[last..]);
+    out
+}
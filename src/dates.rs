@@ -0,0 +1,161 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// UTC date and time of day of `time`: (year, month, day) and seconds since
+/// midnight.
+pub fn utc(time: SystemTime) -> ((i64, u32, u32), u64) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    (civil_from_days((secs / 86_400) as i64), secs % 86_400)
+}
+
+/// `YYYYMMDD-HHMMSS` in UTC, for file names.
+pub fn timestamp(time: SystemTime) -> String {
+    let ((year, month, day), seconds) = utc(time);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Today's UTC date as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let ((year, month, day), _) = utc(SystemTime::now());
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The current UTC time as `HH:MM`.
+pub fn time_of_day() -> String {
+    let (_, seconds) = utc(SystemTime::now());
+    format!("{:02}:{:02}", seconds / 3600, seconds / 60 % 60)
+}
+
+/// The UTC time of day of `time` as `HH:MM:SS`.
+pub fn clock_time(time: SystemTime) -> String {
+    let (_, seconds) = utc(time);
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// A UTC date and time read from front matter.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    days: i64,
+    seconds: u32,
+}
+
+impl DateTime {
+    /// Parses `YYYY-MM-DD`, optionally followed by `T` or a space and
+    /// `HH:MM` or `HH:MM:SS`. Anything after that, such as a time zone, is
+    /// ignored and the time is taken as UTC.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let number = |range: std::ops::Range<usize>| -> Option<u32> {
+            let digits = text.get(range)?;
+            if !digits.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            digits.parse().ok()
+        };
+        let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+        if text.get(4..5)? != "-" || text.get(7..8)? != "-" {
+            return None;
+        }
+        let days = days_from_civil(i64::from(year), month, day);
+        if civil_from_days(days) != (i64::from(year), month, day) {
+            return None;
+        }
+        let mut seconds = 0;
+        if matches!(text.get(10..11), Some("T" | " ")) && text.get(13..14) == Some(":") {
+            let (hour, minute) = (number(11..13)?, number(14..16)?);
+            let second = if text.get(16..17) == Some(":") {
+                number(17..19)?
+            } else {
+                0
+            };
+            if hour > 23 || minute > 59 || second > 60 {
+                return None;
+            }
+            seconds = hour * 3600 + minute * 60 + second;
+        }
+        Some(Self { days, seconds })
+    }
+
+    pub fn now() -> Self {
+        let (date, seconds) = utc(SystemTime::now());
+        Self {
+            days: days_from_civil(date.0, date.1, date.2),
+            seconds: seconds as u32,
+        }
+    }
+
+    /// `YYYY-MM-DDTHH:MM:SSZ`, as Atom feeds want it.
+    pub fn rfc3339(self) -> String {
+        let (year, month, day) = civil_from_days(self.days);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            self.seconds / 3600,
+            self.seconds / 60 % 60,
+            self.seconds % 60
+        )
+    }
+
+    /// `Tue, 02 Jan 2024 10:00:00 GMT`, as RSS feeds want it.
+    pub fn rfc822(self) -> String {
+        const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let (year, month, day) = civil_from_days(self.days);
+        format!(
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            WEEKDAYS[self.days.rem_euclid(7) as usize],
+            day,
+            MONTHS[month as usize - 1],
+            year,
+            self.seconds / 3600,
+            self.seconds / 60 % 60,
+            self.seconds % 60
+        )
+    }
+}
+
+/// Day count since 1970-01-01 of a Gregorian date; the inverse of
+/// [`civil_from_days`].
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Gregorian date of a day count since 1970-01-01 (Howard Hinnant's
+/// `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,119 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Severity::Error => "⛔",
+            Severity::Warning => "⚠",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Diagnostic {
+    pub source: String,
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    /// Zero-based line in `file`, when known.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+fn location_regex() -> &'static Regex {
+    static LOCATION: OnceLock<Regex> = OnceLock::new();
+    LOCATION.get_or_init(|| {
+        Regex::new(
+            r"^(?P<file>[^:\s][^:]*\.(?:md|markdown)):(?P<line>\d+)(?::\d+)?:?\s*(?P<msg>.*)$",
+        )
+        .unwrap()
+    })
+}
+
+/// Parses `path:line[:col]: message` lines as printed by markdownlint, rumdl
+/// and most other linters. `reported_path` is replaced by `actual_path` so
+/// temporary buffers map back to the open document.
+pub fn parse_tool_output(
+    source: &str,
+    output: &str,
+    base_dir: &Path,
+    reported_path: &Path,
+    actual_path: Option<&Path>,
+) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let caps = location_regex().captures(line.trim())?;
+            let file = PathBuf::from(&caps["file"]);
+            let file = if file.is_absolute() {
+                file
+            } else {
+                base_dir.join(file)
+            };
+            let file = if file == reported_path {
+                actual_path.map(Path::to_path_buf)
+            } else {
+                Some(file)
+            };
+            let message = caps["msg"].trim().to_string();
+            let severity = if message.to_lowercase().contains("error") {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            Some(Diagnostic {
+                source: source.to_string(),
+                severity,
+                file,
+                line: caps["line"]
+                    .parse::<usize>()
+                    .ok()
+                    .map(|line| line.saturating_sub(1)),
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Parses mdBook's `[ERROR]` / `[WARN]` log lines, resolving chapter paths
+/// mentioned in the message against `root`.
+pub fn parse_mdbook_output(output: &str, root: &Path) -> Vec<Diagnostic> {
+    static CHAPTER: OnceLock<Regex> = OnceLock::new();
+    let chapter = CHAPTER.get_or_init(|| Regex::new(r"([\w./\\-]+\.md)").unwrap());
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let severity = if line.contains("[ERROR]") || line.trim_start().starts_with("Error") {
+                Severity::Error
+            } else if line.contains("[WARN]") {
+                Severity::Warning
+            } else {
+                return None;
+            };
+            let message = line
+                .split_once("): ")
+                .map(|(_, message)| message)
+                .unwrap_or(line)
+                .trim()
+                .to_string();
+            let file = chapter
+                .captures(&message)
+                .map(|caps| root.join(caps[1].trim_start_matches("./")));
+            Some(Diagnostic {
+                source: "mdbook".to_string(),
+                severity,
+                file,
+                line: None,
+                message,
+            })
+        })
+        .collect()
+}
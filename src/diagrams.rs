@@ -0,0 +1,204 @@
+use crate::export;
+use egui::{ColorImage, TextureHandle, TextureOptions};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Queued diagrams that were not drawn for this long are dropped, and
+/// rendered ones are freed.
+const QUEUED_SECS: f64 = 2.0;
+const UNUSED_SECS: f64 = 60.0;
+
+/// The code of a ```` ```mermaid ```` fenced block, if `markdown` starts
+/// with one.
+pub fn mermaid_source(markdown: &str) -> Option<String> {
+    let mut lines = markdown.lines();
+    let opening = lines.next()?.trim_start();
+    let marker = opening.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let fence = opening.chars().take_while(|&c| c == marker).count();
+    if fence < 3 {
+        return None;
+    }
+    let info = opening[fence..].split_whitespace().next()?;
+    if !info.eq_ignore_ascii_case("mermaid") {
+        return None;
+    }
+    let code: Vec<&str> = lines
+        .take_while(|line| {
+            let line = line.trim();
+            line.chars().take_while(|&c| c == marker).count() < fence
+                || !line.trim_start_matches(marker).is_empty()
+        })
+        .collect();
+    Some(code.join("\n"))
+}
+
+pub enum Diagram<'a> {
+    Pending,
+    Ready(&'a TextureHandle),
+    Failed(&'a str),
+}
+
+enum State {
+    Queued,
+    Rendering,
+    Ready(TextureHandle),
+    Failed(String),
+}
+
+struct Entry {
+    source: String,
+    state: State,
+    last_used: f64,
+}
+
+type Slot = Arc<Mutex<Option<Result<ColorImage, String>>>>;
+
+/// Diagrams rendered to PNG by an external command, one at a time on a
+/// background thread, and kept as textures by source.
+#[derive(Default)]
+pub struct Diagrams {
+    command: Vec<String>,
+    entries: HashMap<u64, Entry>,
+    running: Option<(u64, Slot)>,
+}
+
+impl Diagrams {
+    /// The diagram for `source`, queued for rendering the first time it is
+    /// asked for.
+    pub fn get(&mut self, source: &str, now: f64) -> Diagram<'_> {
+        let entry = self.entries.entry(hash(source)).or_insert_with(|| Entry {
+            source: source.to_string(),
+            state: State::Queued,
+            last_used: now,
+        });
+        entry.last_used = now;
+        match &entry.state {
+            State::Queued | State::Rendering => Diagram::Pending,
+            State::Ready(texture) => Diagram::Ready(texture),
+            State::Failed(err) => Diagram::Failed(err),
+        }
+    }
+
+    /// Picks up a finished render and starts the next one with `command`
+    /// (`{input}` and `{output}` are the diagram source and the PNG to
+    /// write). The most recently drawn diagram goes first.
+    pub fn poll(&mut self, ctx: &egui::Context, command: &[String], now: f64) {
+        if command != self.command.as_slice() {
+            self.command = command.to_vec();
+            self.entries.clear();
+            self.running = None;
+        }
+
+        if let Some((key, slot)) = &self.running {
+            let result = slot.lock().ok().and_then(|mut slot| slot.take());
+            if let Some(result) = result {
+                if let Some(entry) = self.entries.get_mut(key) {
+                    entry.state = match result {
+                        Ok(image) => State::Ready(ctx.load_texture(
+                            format!("diagram-{}", key),
+                            image,
+                            TextureOptions::LINEAR,
+                        )),
+                        Err(err) => State::Failed(err),
+                    };
+                }
+                self.running = None;
+            }
+        }
+
+        let running = self.running.as_ref().map(|(key, _)| *key);
+        self.entries.retain(|key, entry| match entry.state {
+            State::Queued => now - entry.last_used < QUEUED_SECS,
+            State::Rendering => Some(*key) == running,
+            State::Ready(_) | State::Failed(_) => now - entry.last_used < UNUSED_SECS,
+        });
+
+        if self.running.is_some() || self.command.is_empty() {
+            return;
+        }
+        let next = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| matches!(entry.state, State::Queued))
+            .max_by(|(_, a), (_, b)| a.last_used.total_cmp(&b.last_used))
+            .map(|(key, _)| *key);
+        let Some(key) = next else {
+            return;
+        };
+        let Some(entry) = self.entries.get_mut(&key) else {
+            return;
+        };
+        entry.state = State::Rendering;
+
+        let slot: Slot = Arc::new(Mutex::new(None));
+        {
+            let slot = Arc::clone(&slot);
+            let source = entry.source.clone();
+            let command = self.command.clone();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let result = render(&source, &command);
+                if let Ok(mut slot) = slot.lock() {
+                    *slot = Some(result);
+                }
+                ctx.request_repaint();
+            });
+        }
+        self.running = Some((key, slot));
+    }
+}
+
+fn hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn render(source: &str, command: &[String]) -> Result<ColorImage, String> {
+    let dir = tempfile::tempdir().map_err(|err| format!("Failed to prepare temp dir: {}", err))?;
+    let input = dir.path().join("diagram.mmd");
+    let output = dir.path().join("diagram.png");
+    fs::write(&input, source).map_err(|err| format!("Failed to prepare temp file: {}", err))?;
+    export::run_converter(command, &input, &output)?;
+    load_png(&output)
+}
+
+fn load_png(path: &Path) -> Result<ColorImage, String> {
+    let file = fs::File::open(path).map_err(|err| format!("No image written: {}", err))?;
+    decode_png(BufReader::new(file))
+}
+
+/// Decodes a PNG image into RGBA pixels.
+pub fn decode_png(data: impl Read) -> Result<ColorImage, String> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|err| err.to_string())?;
+    let pixels = &buffer[..info.buffer_size()];
+    let rgba: Vec<u8> = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return Err("Unsupported PNG color type".to_string()),
+    };
+    Ok(ColorImage::from_rgba_unmultiplied(
+        [info.width as usize, info.height as usize],
+        &rgba,
+    ))
+}
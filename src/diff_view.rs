@@ -0,0 +1,171 @@
+use crate::critic::{ADDITION_COLOR, DELETION_COLOR};
+use egui::{Color32, RichText};
+use similar::{DiffTag, TextDiff};
+
+/// Unchanged runs longer than this are collapsed, keeping a few lines of
+/// context around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// A run of lines that is either unchanged or replaced.
+struct Block {
+    tag: DiffTag,
+    /// First line number (zero-based) in the old and new text.
+    old_start: usize,
+    new_start: usize,
+    old: Vec<String>,
+    new: Vec<String>,
+}
+
+/// A line diff of two texts, computed once and drawn unified or side by
+/// side.
+pub struct DiffView {
+    blocks: Vec<Block>,
+    pub changes: usize,
+}
+
+impl DiffView {
+    pub fn new(old: &str, new: &str) -> Self {
+        let diff = TextDiff::from_lines(old, new);
+        let lines = |slices: &[&str]| {
+            slices
+                .iter()
+                .map(|line| line.trim_end_matches(['\r', '\n']).to_string())
+                .collect()
+        };
+        let blocks: Vec<Block> = diff
+            .ops()
+            .iter()
+            .map(|op| Block {
+                tag: op.tag(),
+                old_start: op.old_range().start,
+                new_start: op.new_range().start,
+                old: lines(&diff.old_slices()[op.old_range()]),
+                new: lines(&diff.new_slices()[op.new_range()]),
+            })
+            .collect();
+        let changes = blocks
+            .iter()
+            .filter(|block| block.tag != DiffTag::Equal)
+            .count();
+        Self { blocks, changes }
+    }
+
+    pub fn show(&self, ui: &mut egui::Ui, side_by_side: bool) {
+        egui::ScrollArea::both()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                ui.spacing_mut().item_spacing.y = 0.0;
+                if side_by_side {
+                    self.show_side_by_side(ui);
+                } else {
+                    self.show_unified(ui);
+                }
+            });
+    }
+
+    fn show_unified(&self, ui: &mut egui::Ui) {
+        for (index, block) in self.blocks.iter().enumerate() {
+            if block.tag == DiffTag::Equal {
+                for context in self.context(index) {
+                    match context {
+                        Some((line, text)) => {
+                            row(ui, Some(block.old_start + line), ' ', text, None)
+                        }
+                        None => elided(ui),
+                    }
+                }
+                continue;
+            }
+            for (line, text) in block.old.iter().enumerate() {
+                let number = Some(block.old_start + line);
+                row(ui, number, '-', text, Some(DELETION_COLOR));
+            }
+            for (line, text) in block.new.iter().enumerate() {
+                let number = Some(block.new_start + line);
+                row(ui, number, '+', text, Some(ADDITION_COLOR));
+            }
+        }
+    }
+
+    fn show_side_by_side(&self, ui: &mut egui::Ui) {
+        egui::Grid::new("diff_view_grid")
+            .num_columns(2)
+            .spacing([12.0, 0.0])
+            .show(ui, |ui| {
+                for (index, block) in self.blocks.iter().enumerate() {
+                    if block.tag == DiffTag::Equal {
+                        for context in self.context(index) {
+                            match context {
+                                Some((line, text)) => {
+                                    row(ui, Some(block.old_start + line), ' ', text, None);
+                                    row(ui, Some(block.new_start + line), ' ', text, None);
+                                }
+                                None => {
+                                    elided(ui);
+                                    elided(ui);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                        continue;
+                    }
+                    for line in 0..block.old.len().max(block.new.len()) {
+                        match block.old.get(line) {
+                            Some(text) => {
+                                let number = Some(block.old_start + line);
+                                row(ui, number, '-', text, Some(DELETION_COLOR));
+                            }
+                            None => row(ui, None, ' ', "", None),
+                        }
+                        match block.new.get(line) {
+                            Some(text) => {
+                                let number = Some(block.new_start + line);
+                                row(ui, number, '+', text, Some(ADDITION_COLOR));
+                            }
+                            None => row(ui, None, ' ', "", None),
+                        }
+                        ui.end_row();
+                    }
+                }
+            });
+    }
+
+    /// Lines of the unchanged block at `index` next to a change; `None`
+    /// stands for the collapsed lines in between.
+    fn context(&self, index: usize) -> Vec<Option<(usize, &str)>> {
+        let block = &self.blocks[index];
+        let len = block.old.len();
+        let keep_head = if index > 0 { CONTEXT_LINES } else { 0 };
+        let keep_tail = if index + 1 < self.blocks.len() {
+            CONTEXT_LINES
+        } else {
+            0
+        };
+        if len <= keep_head + keep_tail {
+            return (0..len)
+                .map(|line| Some((line, block.old[line].as_str())))
+                .collect();
+        }
+        let mut lines: Vec<_> = (0..keep_head)
+            .map(|line| Some((line, block.old[line].as_str())))
+            .collect();
+        lines.push(None);
+        lines.extend((len - keep_tail..len).map(|line| Some((line, block.old[line].as_str()))));
+        lines
+    }
+}
+
+fn elided(ui: &mut egui::Ui) {
+    ui.weak("      ⋯");
+}
+
+fn row(ui: &mut egui::Ui, number: Option<usize>, sign: char, text: &str, color: Option<Color32>) {
+    let number = number.map_or(String::new(), |number| (number + 1).to_string());
+    let mut line = RichText::new(format!("{:>5} {} {}", number, sign, text)).monospace();
+    if let Some(color) = color {
+        line = line
+            .color(color)
+            .background_color(color.gamma_multiply(0.12));
+    }
+    ui.add(egui::Label::new(line).wrap(false));
+}
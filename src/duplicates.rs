@@ -0,0 +1,165 @@
+use crate::{export, front_matter};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Words per shingle: paragraphs are compared by the runs of this many
+/// words they share.
+const SHINGLE_WORDS: usize = 5;
+
+/// Shingles found in more paragraphs than this are boilerplate (license
+/// footers, template text) and do not make paragraphs candidates.
+const COMMON_SHINGLE: usize = 50;
+
+/// A paragraph of one of the documents.
+pub struct Paragraph {
+    pub path: PathBuf,
+    /// Zero-based line the paragraph starts on.
+    pub line: usize,
+    /// The paragraph's first line, trimmed, for the report.
+    pub excerpt: String,
+    shingles: HashSet<u64>,
+}
+
+/// Paragraphs that are the same or nearly so.
+pub struct Group {
+    pub paragraphs: Vec<Paragraph>,
+    /// The lowest similarity between two paragraphs found alike, from 0 to 1.
+    pub similarity: f32,
+}
+
+/// Groups the paragraphs of `documents` with at least `min_words` words
+/// whose shingles overlap by `threshold` or more (the Jaccard similarity of
+/// their sets of word runs). Groups with the most copies come first.
+pub fn find(documents: &[(PathBuf, String)], min_words: usize, threshold: f32) -> Vec<Group> {
+    let paragraphs: Vec<Paragraph> = documents
+        .iter()
+        .flat_map(|(path, text)| paragraphs(path, text, min_words.max(SHINGLE_WORDS)))
+        .collect();
+
+    let mut holders: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (index, paragraph) in paragraphs.iter().enumerate() {
+        for &shingle in &paragraph.shingles {
+            holders.entry(shingle).or_default().push(index);
+        }
+    }
+    let mut shared: HashMap<(usize, usize), usize> = HashMap::new();
+    for holders in holders.values() {
+        if holders.len() < 2 || holders.len() > COMMON_SHINGLE {
+            continue;
+        }
+        for (at, &a) in holders.iter().enumerate() {
+            for &b in &holders[at + 1..] {
+                *shared.entry((a, b)).or_default() += 1;
+            }
+        }
+    }
+
+    let mut parent: Vec<usize> = (0..paragraphs.len()).collect();
+    let mut similarity: HashMap<usize, f32> = HashMap::new();
+    for ((a, b), count) in shared {
+        let union = paragraphs[a].shingles.len() + paragraphs[b].shingles.len() - count;
+        let jaccard = count as f32 / union as f32;
+        if jaccard < threshold {
+            continue;
+        }
+        let (a, b) = (root(&mut parent, a), root(&mut parent, b));
+        let lowest = [similarity.get(&a), similarity.get(&b)]
+            .into_iter()
+            .flatten()
+            .fold(jaccard, |lowest, &other| lowest.min(other));
+        parent[b] = a;
+        similarity.insert(a, lowest);
+    }
+
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..paragraphs.len() {
+        let group = root(&mut parent, index);
+        members.entry(group).or_default().push(index);
+    }
+    let mut taken: Vec<Option<Paragraph>> = paragraphs.into_iter().map(Some).collect();
+    let mut groups: Vec<Group> = members
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(group, members)| Group {
+            paragraphs: members
+                .into_iter()
+                .filter_map(|index| taken[index].take())
+                .collect(),
+            similarity: similarity.get(&group).copied().unwrap_or(1.0),
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.paragraphs
+            .len()
+            .cmp(&a.paragraphs.len())
+            .then_with(|| a.paragraphs[0].path.cmp(&b.paragraphs[0].path))
+            .then_with(|| a.paragraphs[0].line.cmp(&b.paragraphs[0].line))
+    });
+    groups
+}
+
+fn root(parent: &mut [usize], mut index: usize) -> usize {
+    while parent[index] != index {
+        parent[index] = parent[parent[index]];
+        index = parent[index];
+    }
+    index
+}
+
+/// The paragraphs of `text` outside front matter and code blocks, runs of
+/// non-blank lines, that have at least `min_words` words.
+fn paragraphs(path: &Path, text: &str, min_words: usize) -> Vec<Paragraph> {
+    let skipped = text[..front_matter::body_start(text)].matches('\n').count();
+    let mut found = Vec::new();
+    let mut block: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut in_fence = false;
+    let lines = text.lines().chain(std::iter::once(""));
+    for (number, line) in lines.enumerate().skip(skipped) {
+        let fence = export::is_fence(line);
+        if fence {
+            in_fence = !in_fence;
+        } else if in_fence {
+            continue;
+        }
+        if !fence && !line.trim().is_empty() && export::heading_level(line).is_none() {
+            if block.is_empty() {
+                start = number;
+            }
+            block.push(line);
+            continue;
+        }
+        if let Some(paragraph) = paragraph(path, start, &block, min_words) {
+            found.push(paragraph);
+        }
+        block.clear();
+    }
+    found
+}
+
+fn paragraph(path: &Path, line: usize, block: &[&str], min_words: usize) -> Option<Paragraph> {
+    let text = block.join(" ").to_lowercase();
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.len() < min_words {
+        return None;
+    }
+    let shingles = words
+        .windows(SHINGLE_WORDS)
+        .map(|run| {
+            let mut hasher = DefaultHasher::new();
+            run.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect();
+    Some(Paragraph {
+        path: path.to_path_buf(),
+        line,
+        excerpt: block.first()?.trim().to_string(),
+        shingles,
+    })
+}
@@ -0,0 +1,216 @@
+use crate::comments;
+use crate::conflicts;
+use crate::critic::{self, ChangeKind};
+use egui::text::{Galley, LayoutJob, TextFormat};
+use egui::util::cache::{ComputerMut, FrameCache};
+use egui::{Align, Color32, FontId};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Inputs that affect how the editor text is decorated. Part of the layout
+/// cache key, so every field must be cheap to hash.
+#[derive(Clone, Hash, PartialEq)]
+pub struct EditorStyle {
+    pub text_color: Color32,
+    pub dim_color: Color32,
+    pub font_size_bits: u32,
+    pub fold_comments: bool,
+    pub dark_mode: bool,
+    /// Align rows to the right for right-to-left documents.
+    pub right_to_left: bool,
+    /// Dim comments and color review marks and conflicts. Off for large
+    /// files, which are laid out as plain text.
+    pub decorate: bool,
+}
+
+impl EditorStyle {
+    pub fn from_ui(ui: &egui::Ui, fold_comments: bool, right_to_left: bool) -> Self {
+        let visuals = ui.visuals();
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        Self {
+            text_color: visuals
+                .override_text_color
+                .unwrap_or_else(|| visuals.widgets.inactive.text_color()),
+            dim_color: visuals.weak_text_color(),
+            font_size_bits: font.size.to_bits(),
+            fold_comments,
+            dark_mode: visuals.dark_mode,
+            right_to_left,
+            decorate: true,
+        }
+    }
+
+    fn font(&self) -> FontId {
+        FontId::monospace(f32::from_bits(self.font_size_bits))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Decoration {
+    Comment,
+    FoldedComment,
+    Addition,
+    Deletion,
+    Substitution,
+    ReviewNote,
+    ConflictMarker,
+    ConflictOurs,
+    ConflictTheirs,
+}
+
+#[derive(Default)]
+struct Highlighter;
+
+impl ComputerMut<(&EditorStyle, &str), LayoutJob> for Highlighter {
+    fn compute(&mut self, (style, text): (&EditorStyle, &str)) -> LayoutJob {
+        build_job(style, text)
+    }
+}
+
+type LayoutCache = FrameCache<LayoutJob, Highlighter>;
+
+/// Returns the (cached) layout job for `text`, ready for `Fonts::layout_job`.
+fn layout_job(ctx: &egui::Context, style: &EditorStyle, text: &str, wrap_width: f32) -> LayoutJob {
+    let mut job = ctx.memory_mut(|memory| memory.caches.cache::<LayoutCache>().get((style, text)));
+    job.wrap.max_width = wrap_width;
+    if style.right_to_left {
+        job.halign = Align::RIGHT;
+    }
+    job
+}
+
+/// Lays out `text` for the editor. Right-aligned rows are laid out left of
+/// the origin, so they are shifted back to end at `wrap_width`, which is
+/// where `TextEdit` expects the galley to start.
+pub fn layout(ui: &egui::Ui, style: &EditorStyle, text: &str, wrap_width: f32) -> Arc<Galley> {
+    let job = layout_job(ui.ctx(), style, text, wrap_width);
+    let mut galley = ui.fonts(|fonts| fonts.layout_job(job));
+    if style.right_to_left && wrap_width.is_finite() {
+        let offset = egui::vec2(wrap_width, 0.0);
+        let shifted = Arc::make_mut(&mut galley);
+        for row in &mut shifted.rows {
+            row.rect = row.rect.translate(offset);
+            row.visuals.mesh.translate(offset);
+            row.visuals.mesh_bounds = row.visuals.mesh_bounds.translate(offset);
+            for glyph in &mut row.glyphs {
+                glyph.pos += offset;
+            }
+        }
+        shifted.rect = shifted.rect.translate(offset);
+        shifted.mesh_bounds = shifted.mesh_bounds.translate(offset);
+    }
+    galley
+}
+
+fn build_job(style: &EditorStyle, text: &str) -> LayoutJob {
+    if !style.decorate {
+        return LayoutJob::single_section(text.to_string(), plain_format(style));
+    }
+    let mut spans: Vec<(Range<usize>, Decoration)> = Vec::new();
+    for comment in comments::find_comments(text) {
+        if style.fold_comments {
+            // Keep the first line readable and shrink the rest so a long
+            // comment collapses to roughly a single row.
+            let first_line_end = text[comment.range.clone()]
+                .find('\n')
+                .map(|index| comment.range.start + index)
+                .unwrap_or(comment.range.end);
+            spans.push((comment.range.start..first_line_end, Decoration::Comment));
+            if first_line_end < comment.range.end {
+                spans.push((first_line_end..comment.range.end, Decoration::FoldedComment));
+            }
+        } else {
+            spans.push((comment.range, Decoration::Comment));
+        }
+    }
+
+    for change in critic::find_changes(text) {
+        let decoration = match change.kind {
+            ChangeKind::Addition(_) => Decoration::Addition,
+            ChangeKind::Deletion(_) => Decoration::Deletion,
+            ChangeKind::Substitution { .. } => Decoration::Substitution,
+            ChangeKind::Comment(_) | ChangeKind::Highlight(_) => Decoration::ReviewNote,
+        };
+        spans.push((change.range, decoration));
+    }
+    for conflict in conflicts::find_conflicts(text) {
+        for marker in conflict.marker_ranges() {
+            spans.push((marker, Decoration::ConflictMarker));
+        }
+        if let Some(base) = conflict.base {
+            spans.push((base, Decoration::Comment));
+        }
+        spans.push((conflict.ours, Decoration::ConflictOurs));
+        spans.push((conflict.theirs, Decoration::ConflictTheirs));
+    }
+    spans.retain(|(range, _)| !range.is_empty());
+    spans.sort_by_key(|(range, _)| range.start);
+
+    let mut job = LayoutJob::default();
+    let mut last = 0;
+    for (range, decoration) in spans {
+        if range.start < last {
+            // Overlapping markup (e.g. CriticMarkup inside a comment) keeps
+            // the outer decoration.
+            continue;
+        }
+        if range.start > last {
+            job.append(&text[last..range.start], 0.0, plain_format(style));
+        }
+        job.append(
+            &text[range.clone()],
+            0.0,
+            decorated_format(style, decoration),
+        );
+        last = range.end;
+    }
+    if last < text.len() || text.is_empty() {
+        job.append(&text[last..], 0.0, plain_format(style));
+    }
+    job
+}
+
+fn plain_format(style: &EditorStyle) -> TextFormat {
+    TextFormat::simple(style.font(), style.text_color)
+}
+
+fn decorated_format(style: &EditorStyle, decoration: Decoration) -> TextFormat {
+    match decoration {
+        Decoration::Comment => TextFormat {
+            font_id: style.font(),
+            color: style.dim_color,
+            italics: true,
+            ..Default::default()
+        },
+        Decoration::FoldedComment => TextFormat {
+            font_id: FontId::monospace(3.0),
+            color: style.dim_color,
+            ..Default::default()
+        },
+        Decoration::Addition => review_format(style, critic::ADDITION_COLOR),
+        Decoration::Deletion => TextFormat {
+            strikethrough: egui::Stroke::new(1.0, critic::DELETION_COLOR),
+            ..review_format(style, critic::DELETION_COLOR)
+        },
+        Decoration::Substitution => review_format(style, critic::SUBSTITUTION_COLOR),
+        Decoration::ReviewNote => review_format(style, critic::NOTE_COLOR),
+        Decoration::ConflictMarker => TextFormat {
+            font_id: style.font(),
+            color: critic::SUBSTITUTION_COLOR,
+            background: critic::SUBSTITUTION_COLOR.gamma_multiply(0.25),
+            ..Default::default()
+        },
+        Decoration::ConflictOurs => review_format(style, critic::ADDITION_COLOR),
+        Decoration::ConflictTheirs => review_format(style, critic::NOTE_COLOR),
+    }
+}
+
+fn review_format(style: &EditorStyle, color: Color32) -> TextFormat {
+    let background = if style.dark_mode { 0.2 } else { 0.12 };
+    TextFormat {
+        font_id: style.font(),
+        color,
+        background: color.gamma_multiply(background),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,332 @@
+use crate::{front_matter, html_markdown};
+use base64::Engine;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Stand-ins for `<en-todo>` boxes while the note is converted, which
+/// would otherwise come out escaped.
+const CHECKED: char = '\u{E000}';
+const UNCHECKED: char = '\u{E001}';
+
+/// An attachment of a note, by the MD5 hash `<en-media>` refers to it by.
+struct Resource {
+    path: String,
+    mime: String,
+}
+
+/// The notes of the Evernote export at `source` (an `.enex` file) as
+/// markdown files with their attachments, all in a folder named after the
+/// export, ready for [`crate::bundle::import`].
+pub fn read_enex(source: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let text =
+        fs::read_to_string(source).map_err(|err| format!("{}: {}", source.display(), err))?;
+    let notebook = source.file_stem().map_or("Evernote".to_string(), |stem| {
+        safe_name(&stem.to_string_lossy())
+    });
+    let mut files = Vec::new();
+    let mut taken = HashSet::new();
+    for note in elements(&text, "note") {
+        // Everything but the content and attachments, so their markup is
+        // never mistaken for a field.
+        let (content, rest) = match note.find("<content>").zip(note.find("</content>")) {
+            Some((start, end)) if start < end => (
+                &note[start + 9..end],
+                format!("{}{}", &note[..start], &note[end..]),
+            ),
+            _ => ("", note.to_string()),
+        };
+        let fields = match rest.find("<resource>") {
+            Some(at) => &rest[..at],
+            None => rest.as_str(),
+        };
+        let title = element(fields, "title").map_or(String::new(), |title| unescape(title.trim()));
+        let title = if title.is_empty() {
+            "Untitled".to_string()
+        } else {
+            title
+        };
+
+        let mut resources = HashMap::new();
+        for resource in elements(note, "resource") {
+            let Some(data) = element(resource, "data") else {
+                continue;
+            };
+            let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+            let Ok(data) = base64::engine::general_purpose::STANDARD.decode(data) else {
+                continue;
+            };
+            let mime = element(resource, "mime").map_or(String::new(), unescape);
+            let file_name = element(resource, "file-name")
+                .map(|name| safe_name(&unescape(name)))
+                .filter(|name| name.contains('.'))
+                .unwrap_or_else(|| format!("attachment.{}", extension(&mime)));
+            let path = unique(&mut taken, &format!("{}/assets/{}", notebook, file_name));
+            let hash: String = md5(&data)
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            resources.insert(
+                hash,
+                Resource {
+                    path: path.clone(),
+                    mime,
+                },
+            );
+            files.push((path, data));
+        }
+
+        let content = content.trim();
+        let html = match content.strip_prefix("<![CDATA[") {
+            Some(cdata) => cdata.strip_suffix("]]>").unwrap_or(cdata).to_string(),
+            None => unescape(content),
+        };
+        let body = html_markdown::convert(&replace_media(&html, &notebook, &resources), None)
+            .replace(CHECKED, "- [x] ")
+            .replace(UNCHECKED, "- [ ] ");
+        let mut markdown = String::new();
+        if let Some(created) = element(fields, "created").and_then(date) {
+            markdown.push_str(&format!("created: {}\n", created));
+        }
+        if let Some(url) = element(fields, "source-url") {
+            markdown.push_str(&format!("source: {}\n", unescape(url)));
+        }
+        let tags: Vec<String> = elements(fields, "tag").map(unescape).collect();
+        if !tags.is_empty() {
+            markdown.push_str(&front_matter::list_lines("tags", &tags));
+        }
+        if !markdown.is_empty() {
+            markdown = format!("---\n{}---\n\n", markdown);
+        }
+        markdown.push_str(&format!("# {}\n\n{}", title, body.trim_start()));
+        let path = unique(
+            &mut taken,
+            &format!("{}/{}.md", notebook, safe_name(&title)),
+        );
+        files.push((path, markdown.into_bytes()));
+    }
+    if files.is_empty() {
+        return Err(format!("{} has no notes in it.", source.display()));
+    }
+    Ok(files)
+}
+
+/// The contents of every `<name>` element of `text`, in order.
+fn elements<'a>(text: &'a str, name: &str) -> impl Iterator<Item = &'a str> {
+    let (open, close) = (format!("<{}", name), format!("</{}>", name));
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let start = loop {
+            let at = rest.find(&open)? + open.len();
+            let tag = &rest[at..];
+            // `<name>` or `<name attribute="…">`, not `<name-suffix>`.
+            if tag.starts_with('>') || tag.starts_with(char::is_whitespace) {
+                break at + tag.find('>')? + 1;
+            }
+            rest = tag;
+        };
+        let end = start + rest[start..].find(&close)?;
+        let inner = &rest[start..end];
+        rest = &rest[end + close.len()..];
+        Some(inner)
+    })
+}
+
+fn element<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    elements(text, name).next()
+}
+
+/// `<en-media>` attachments as images and links, `<en-todo>` boxes as
+/// [`CHECKED`] and [`UNCHECKED`], and the `<en-note>` around it all as a
+/// `<div>`.
+fn replace_media(html: &str, notebook: &str, resources: &HashMap<String, Resource>) -> String {
+    static MEDIA: OnceLock<Regex> = OnceLock::new();
+    static TODO: OnceLock<Regex> = OnceLock::new();
+    static HASH: OnceLock<Regex> = OnceLock::new();
+    static NOTE: OnceLock<Regex> = OnceLock::new();
+    let media =
+        MEDIA.get_or_init(|| Regex::new(r"(?s)<en-media\b([^>]*?)/?>(?:</en-media>)?").unwrap());
+    let todo =
+        TODO.get_or_init(|| Regex::new(r"(?s)<en-todo\b([^>]*?)/?>(?:</en-todo>)?").unwrap());
+    let hash = HASH.get_or_init(|| Regex::new(r#"hash="([0-9a-fA-F]+)""#).unwrap());
+    let note = NOTE.get_or_init(|| Regex::new(r"<(/?)en-note\b[^>]*>").unwrap());
+    let html = note.replace_all(html, "<${1}div>");
+    let html = media.replace_all(&html, |caps: &regex::Captures| {
+        let resource = hash
+            .captures(&caps[1])
+            .and_then(|found| resources.get(&found[1].to_ascii_lowercase()));
+        let Some(resource) = resource else {
+            return String::new();
+        };
+        let relative = resource.path[notebook.len() + 1..].replace(' ', "%20");
+        let name = resource.path.rsplit('/').next().unwrap_or_default();
+        if resource.mime.starts_with("image/") {
+            format!("<img src=\"{}\" alt=\"{}\">", relative, name)
+        } else {
+            format!("<a href=\"{}\">{}</a>", relative, name)
+        }
+    });
+    todo.replace_all(&html, |caps: &regex::Captures| {
+        if caps[1].contains("checked=\"true\"") {
+            CHECKED.to_string()
+        } else {
+            UNCHECKED.to_string()
+        }
+    })
+    .into_owned()
+}
+
+/// `20240131T094500Z` as `2024-01-31T09:45:00Z`.
+fn date(text: &str) -> Option<String> {
+    let text = text.trim().get(..15).filter(|text| text.is_ascii())?;
+    if &text[8..9] != "T" {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &text[..4],
+        &text[4..6],
+        &text[6..8],
+        &text[9..11],
+        &text[11..13],
+        &text[13..15]
+    ))
+}
+
+fn extension(mime: &str) -> &str {
+    match mime {
+        "image/jpeg" => "jpg",
+        "image/svg+xml" => "svg",
+        "application/pdf" => "pdf",
+        "text/plain" => "txt",
+        _ => match mime.split_once('/') {
+            Some(("image" | "audio" | "video", kind)) if !kind.is_empty() => kind,
+            _ => "bin",
+        },
+    }
+}
+
+/// `name` without the characters file systems refuse, for a file name.
+fn safe_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .take(100)
+        .collect();
+    let name = name.trim().trim_matches('.').trim();
+    if name.is_empty() {
+        "Untitled".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// `path`, or `path` with a number added when another file already has it.
+fn unique(taken: &mut HashSet<String>, path: &str) -> String {
+    let (stem, extension) = path.rsplit_once('.').unwrap_or((path, ""));
+    let mut candidate = path.to_string();
+    let mut n = 2;
+    while !taken.insert(candidate.to_lowercase()) {
+        candidate = format!("{}-{}.{}", stem, n, extension);
+        n += 1;
+    }
+    candidate
+}
+
+/// XML text with its entities and character references decoded.
+fn unescape(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        decoded.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let Some(end) = rest.find(';').filter(|end| *end <= 10) else {
+            decoded.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => entity
+                    .strip_prefix('#')
+                    .and_then(|digits| digits.parse().ok()),
+            }
+            .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The MD5 digest of `data` (RFC 1321), which Evernote names attachments
+/// by.
+fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4_294_967_296.0) as u32)
+        .collect();
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    for chunk in message.chunks(64) {
+        let words: Vec<u32> = chunk
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let shift = SHIFTS[(i / 16) * 4 + i % 4];
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(shift);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+        for (value, added) in state.iter_mut().zip([a, b, c, d]) {
+            *value = value.wrapping_add(added);
+        }
+    }
+    let mut digest = [0; 16];
+    for (bytes, value) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+    digest
+}
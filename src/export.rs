@@ -0,0 +1,713 @@
+use crate::book::Book;
+use crate::dates;
+use crate::front_matter;
+use crate::language::{Direction, Language};
+use crate::patterns::IgnorePatterns;
+use crate::protect;
+use pulldown_cmark::{html, Options, Parser};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use tempfile::Builder;
+use walkdir::WalkDir;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Pdf,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [
+        ExportFormat::Markdown,
+        ExportFormat::Html,
+        ExportFormat::Pdf,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Html => "HTML",
+            ExportFormat::Pdf => "PDF",
+        }
+    }
+
+    /// `html`, `pdf`, or `md` (or `markdown`), as given on the command line.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "html" | "htm" => Some(ExportFormat::Html),
+            "pdf" => Some(ExportFormat::Pdf),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+}
+
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| {
+            matches!(
+                ext.to_lowercase().as_str(),
+                "md" | "markdown" | "mdown" | "mkd"
+            )
+        })
+        .unwrap_or(false)
+}
+
+/// The markdown files under `root`, leaving out what `ignore` matches.
+/// Ignored folders are not entered.
+pub fn markdown_files_except(root: &Path, ignore: &IgnorePatterns) -> Vec<PathBuf> {
+    WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !(entry.file_name().to_string_lossy().starts_with('.')
+                    || ignore.matches(entry.path(), root, entry.file_type().is_dir()))
+        })
+        .flatten()
+        .filter(|entry| entry.file_type().is_file() && is_markdown(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+pub fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS
+}
+
+pub fn markdown_to_html_body(markdown: &str) -> String {
+    let markdown = expand_page_breaks(markdown);
+    let mut body = String::new();
+    html::push_html(&mut body, Parser::new_ext(&markdown, markdown_options()));
+    body
+}
+
+const PAGE_BREAK_HTML: &str = "<div class=\"page-break\"></div>";
+
+/// A block that only forces a page break: `\newpage`, `\pagebreak`, or an
+/// empty `<div>` styled `page-break-after: always` / `break-after: page`
+/// (or `before`), or with class `page-break`.
+pub fn is_page_break(block: &str) -> bool {
+    static DIV: OnceLock<Regex> = OnceLock::new();
+    let block = block.trim();
+    matches!(block, "\\newpage" | "\\pagebreak")
+        || DIV
+            .get_or_init(|| {
+                Regex::new(
+                    r#"(?i)^<div\s+(style\s*=\s*"[^"]*(page-break-(after|before)\s*:\s*always|break-(after|before)\s*:\s*page)[^"]*"|class\s*=\s*"page-break")\s*(/>|>\s*</div>)$"#,
+                )
+                .unwrap()
+            })
+            .is_match(block)
+}
+
+/// Replaces `\newpage` and `\pagebreak` lines outside code blocks with a
+/// page-break `<div>`.
+fn expand_page_breaks(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut in_fence = false;
+    for line in markdown.split_inclusive('\n') {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+        if !in_fence && matches!(line.trim(), "\\newpage" | "\\pagebreak") {
+            out.push('\n');
+            out.push_str(PAGE_BREAK_HTML);
+            out.push_str("\n\n");
+        } else {
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+/// Print typography for exported HTML and PDF.
+#[derive(Clone)]
+pub struct Typography {
+    pub justify: bool,
+    /// Hyphenate by the document language, as the browser knows it.
+    pub hyphenate: bool,
+    /// Multiple of the font size.
+    pub line_height: f32,
+    /// Space between paragraphs, in em.
+    pub paragraph_spacing: f32,
+    /// `lang` for documents without one in their front matter.
+    pub language: String,
+}
+
+impl Default for Typography {
+    fn default() -> Self {
+        Self {
+            justify: false,
+            hyphenate: false,
+            line_height: 1.5,
+            paragraph_spacing: 1.0,
+            language: String::new(),
+        }
+    }
+}
+
+impl Typography {
+    fn css(&self) -> String {
+        let mut css = format!(
+            "body {{ line-height: {}; }}\np {{ margin: 0 0 {}em; }}\n",
+            self.line_height, self.paragraph_spacing
+        );
+        if self.justify {
+            css.push_str("p, li, blockquote { text-align: justify; }\n");
+        }
+        if self.hyphenate {
+            css.push_str(
+                "p, li, blockquote { hyphens: auto; -webkit-hyphens: auto; overflow-wrap: break-word; }\n",
+            );
+        }
+        css
+    }
+}
+
+/// Everything besides the markdown that shapes exported HTML.
+#[derive(Clone, Default)]
+pub struct HtmlOptions {
+    pub typography: Typography,
+    /// A user template replacing the built-in page, see [`TEMPLATE_SLOT`].
+    pub template: Option<String>,
+    /// Encrypt HTML files behind this passphrase, see
+    /// [`protect::protect_html`].
+    pub passphrase: Option<String>,
+}
+
+/// Where a template receives the rendered document. Templates may also use
+/// `{{title}}`, `{{lang}}`, `{{dir}}` (`ltr` or `rtl`) and `{{style}}` (the
+/// built-in stylesheet).
+pub const TEMPLATE_SLOT: &str = "{{content}}";
+
+/// Reads an export template, which must contain [`TEMPLATE_SLOT`].
+pub fn load_template(path: &Path) -> Result<String, String> {
+    let template = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read template {}: {}", path.display(), err))?;
+    if !template.contains(TEMPLATE_SLOT) {
+        return Err(format!(
+            "Template {} has no {} slot.",
+            path.display(),
+            TEMPLATE_SLOT
+        ));
+    }
+    Ok(template)
+}
+
+pub fn markdown_to_html(markdown: &str, title: &str, options: &HtmlOptions) -> String {
+    let typography = &options.typography;
+    let language = Language::of_document(markdown, &typography.language);
+    let rtl = Direction::of_document(markdown, &language).is_rtl();
+    if let Some(template) = &options.template {
+        // The content goes in last so text in the document that looks like
+        // a placeholder is left alone.
+        return template
+            .replace("{{title}}", &escape_html(title))
+            .replace("{{lang}}", &escape_html(&language.tag))
+            .replace("{{dir}}", if rtl { "rtl" } else { "ltr" })
+            .replace("{{style}}", &format!("{}{}", DEFAULT_CSS, typography.css()))
+            .replace(TEMPLATE_SLOT, &markdown_to_html_body(markdown));
+    }
+
+    let mut html_attributes = String::new();
+    if !language.tag.is_empty() {
+        html_attributes.push_str(&format!(" lang=\"{}\"", escape_html(&language.tag)));
+    }
+    if rtl {
+        html_attributes.push_str(" dir=\"rtl\"");
+    }
+    format!(
+        "<!DOCTYPE html>\n<html{}>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        html_attributes,
+        escape_html(title),
+        DEFAULT_CSS,
+        typography.css(),
+        markdown_to_html_body(markdown)
+    )
+}
+
+/// Adds a `<base>` to `html` so relative links and images resolve against
+/// `dir` wherever the file is written.
+pub fn with_base(html: &str, dir: &Path) -> String {
+    let mut url = normalize(dir).to_string_lossy().replace('\\', "/");
+    if !url.starts_with('/') {
+        url.insert(0, '/');
+    }
+    if !url.ends_with('/') {
+        url.push('/');
+    }
+    let base = format!(
+        "<head>\n<base href=\"file://{}\">",
+        escape_html(&url.replace(' ', "%20"))
+    );
+    html.replacen("<head>", &base, 1)
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const DEFAULT_CSS: &str =
+    "body { max-width: 48em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; }
+pre, code { font-family: monospace; background: #f4f4f4; }
+pre { padding: 0.75em; overflow-x: auto; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; }
+img { max-width: 100%; }
+ins { background: #e6ffec; color: #1a7f37; text-decoration: none; }
+del { background: #ffebe9; color: #cf222e; }
+mark { background: #fff8c5; }
+.critic-comment { color: #57606a; font-style: italic; }
+.page-break { break-after: page; page-break-after: always; }
+@media screen { .page-break, div[style*=\"break-\"] { border-top: 1px dashed #ccc; margin: 2em 0; } }
+";
+
+/// Orders files by the book manifest (`book.toml` / `SUMMARY.md`) in `root`
+/// when one lists them, otherwise by numeric filename prefix (`2-intro.md`
+/// before `10-usage.md`).
+pub fn order_files(mut files: Vec<PathBuf>, root: &Path) -> Vec<PathBuf> {
+    let manifest = Book::detect(root)
+        .map(|book| book.chapter_paths())
+        .unwrap_or_default();
+    files.sort_by(|a, b| {
+        let rank_a = manifest.iter().position(|p| same_file(p, a));
+        let rank_b = manifest.iter().position(|p| same_file(p, b));
+        match (rank_a, rank_b) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => natural_key(a).cmp(&natural_key(b)),
+        }
+    });
+    files
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    normalize(a) == normalize(b)
+}
+
+fn natural_key(path: &Path) -> (Vec<String>, u64, String) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let digits: String = name.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let number = digits.parse().unwrap_or(u64::MAX);
+    let parents = path
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    (parents, number, name)
+}
+
+pub fn link_regex() -> &'static Regex {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    LINK.get_or_init(|| Regex::new(r"(!?\[[^\]]*\])\(\s*<?([^)\s>]+)>?((?:\s+[^)]*)?)\)").unwrap())
+}
+
+pub fn is_external(target: &str) -> bool {
+    target.starts_with('#')
+        || target.starts_with('/')
+        || target.contains("://")
+        || target.starts_with("mailto:")
+}
+
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c == ' ' || c == '-' || c == '_' || c == '/' || c == '.') && !slug.ends_with('-')
+        {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+/// Concatenates `files` into one markdown document. Each file's headings are
+/// shifted so its top level becomes `top_level`, links between combined files
+/// become in-document anchors, and other relative links are rebased onto
+/// `output_dir`.
+pub fn combine(files: &[PathBuf], top_level: usize, output_dir: &Path) -> Result<String, String> {
+    let anchors: HashMap<PathBuf, String> = files
+        .iter()
+        .map(|file| (normalize(file), file_anchor(file)))
+        .collect();
+
+    let mut combined = String::new();
+    for file in files {
+        let text = fs::read_to_string(file)
+            .map_err(|err| format!("Failed to read {}: {}", file.display(), err))?;
+        let base_dir = file.parent().unwrap_or(Path::new("."));
+        let shifted = shift_headings(&text, top_level);
+        let linked = rewrite_links(&shifted, base_dir, output_dir, &anchors);
+
+        if !combined.is_empty() {
+            combined.push_str("\n\n");
+        }
+        combined.push_str(&format!("<a id=\"{}\"></a>\n\n", file_anchor(file)));
+        combined.push_str(linked.trim_end());
+        combined.push('\n');
+    }
+    Ok(combined)
+}
+
+/// Rebases the relative links of a document in `base_dir` onto
+/// `output_dir`, so they still resolve from the exported file.
+pub fn rebase_links(text: &str, base_dir: &Path, output_dir: &Path) -> String {
+    rewrite_links(text, base_dir, output_dir, &HashMap::new())
+}
+
+/// Expands an `[export]` file name or folder pattern for a document:
+/// `{name}` is the file stem, `{title}` the front matter title (or first
+/// heading, or file stem), `{slug}` the title as a slug, `{date}` the
+/// front matter date (or today), `{today}` today's date, and any other
+/// `{field}` the front matter entry of that name (empty if missing).
+/// Characters not allowed in file names become `-`.
+pub fn expand_pattern(pattern: &str, markdown: &str, stem: &str) -> String {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    let placeholder = PLACEHOLDER.get_or_init(|| Regex::new(r"\{([A-Za-z0-9_-]+)\}").unwrap());
+    let title = || {
+        front_matter::get(markdown, "title")
+            .or_else(|| atx_headings(markdown).next().map(|(_, text)| text))
+            .unwrap_or_else(|| stem.to_string())
+    };
+    placeholder
+        .replace_all(pattern, |caps: &regex::Captures| {
+            let value = match &caps[1] {
+                "name" => stem.to_string(),
+                "title" => title(),
+                "slug" => slugify(&title()),
+                "today" => dates::today(),
+                "date" => front_matter::get(markdown, "date")
+                    .map(|date| date.chars().take(10).collect())
+                    .unwrap_or_else(dates::today),
+                field => front_matter::get(markdown, field).unwrap_or_default(),
+            };
+            value.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "-")
+        })
+        .into_owned()
+}
+
+/// File name for exporting a document as `format`: `pattern` expanded,
+/// or the file stem if that comes out empty, with the format's extension.
+pub fn output_name(pattern: &str, markdown: &str, stem: &str, format: ExportFormat) -> PathBuf {
+    let name = expand_pattern(pattern, markdown, stem);
+    let name = if name.trim().is_empty() {
+        stem
+    } else {
+        name.as_str()
+    };
+    Path::new(name).with_extension(format.extension())
+}
+
+fn file_anchor(file: &Path) -> String {
+    let stem = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("file-{}", slugify(&stem))
+}
+
+pub fn shift_headings(text: &str, top_level: usize) -> String {
+    let min_level = atx_headings(text).map(|(level, _)| level).min();
+    let Some(min_level) = min_level else {
+        return text.to_string();
+    };
+    let delta = top_level as isize - min_level as isize;
+    if delta == 0 {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut in_fence = false;
+    for line in text.split_inclusive('\n') {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+        match (in_fence, heading_level(line)) {
+            (false, Some(level)) => {
+                let new_level = (level as isize + delta).clamp(1, 6) as usize;
+                out.push_str(&"#".repeat(new_level));
+                out.push_str(&line.trim_start()[level..]);
+            }
+            _ => out.push_str(line),
+        }
+    }
+    out
+}
+
+pub fn is_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+/// `markdown` in runs, each with the zero-based line it is on and whether
+/// it is code: a fenced block, fences included, or inline code, backticks
+/// included. Every other run of text between backticks is inline code.
+/// The runs join back into `markdown`.
+pub fn code_runs(markdown: &str) -> Vec<(usize, &str, bool)> {
+    let mut runs = Vec::new();
+    let mut in_fence = false;
+    for (line_number, line) in markdown.split_inclusive('\n').enumerate() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+            runs.push((line_number, line, true));
+            continue;
+        }
+        if in_fence {
+            runs.push((line_number, line, true));
+            continue;
+        }
+        let mut at = 0;
+        for (index, part) in line.split('`').enumerate() {
+            if index > 0 {
+                runs.push((line_number, &line[at..at + 1], true));
+                at += 1;
+            }
+            runs.push((line_number, part, index % 2 == 1));
+            at += part.len();
+        }
+    }
+    runs
+}
+
+/// `markdown` with its text outside code, see [`code_runs`], passed
+/// through `rewrite`, which appends the new text to the string it is given.
+pub fn map_outside_code(markdown: &str, mut rewrite: impl FnMut(&str, &mut String)) -> String {
+    let mut mapped = String::with_capacity(markdown.len());
+    for (_, text, code) in code_runs(markdown) {
+        if code {
+            mapped.push_str(text);
+        } else {
+            rewrite(text, &mut mapped);
+        }
+    }
+    mapped
+}
+
+pub fn heading_level(line: &str) -> Option<usize> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    let rest = &trimmed[level..];
+    if (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t', '\n', '\r'])) {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+/// Yields `(level, title)` for every ATX heading outside fenced code blocks.
+pub fn atx_headings(text: &str) -> impl Iterator<Item = (usize, String)> + '_ {
+    let mut in_fence = false;
+    text.lines().filter_map(move |line| {
+        if is_fence(line) {
+            in_fence = !in_fence;
+            return None;
+        }
+        if in_fence {
+            return None;
+        }
+        heading_level(line).map(|level| {
+            let title = line.trim_start()[level..]
+                .trim()
+                .trim_end_matches('#')
+                .trim();
+            (level, title.to_string())
+        })
+    })
+}
+
+/// Rebases the relative links of `text` outside code from `base_dir` to
+/// `output_dir`, or points them at `anchors` for documents combined into
+/// the same output.
+fn rewrite_links(
+    text: &str,
+    base_dir: &Path,
+    output_dir: &Path,
+    anchors: &HashMap<PathBuf, String>,
+) -> String {
+    map_outside_code(text, |prose, rewritten| {
+        rewritten.push_str(&rewrite_prose_links(prose, base_dir, output_dir, anchors))
+    })
+}
+
+fn rewrite_prose_links(
+    text: &str,
+    base_dir: &Path,
+    output_dir: &Path,
+    anchors: &HashMap<PathBuf, String>,
+) -> String {
+    link_regex()
+        .replace_all(text, |caps: &regex::Captures| {
+            let label = &caps[1];
+            let target = &caps[2];
+            let title = &caps[3];
+            if is_external(target) {
+                return caps[0].to_string();
+            }
+            let (path_part, fragment) = match target.split_once('#') {
+                Some((path, fragment)) => (path, Some(fragment)),
+                None => (target, None),
+            };
+            let resolved = normalize(&base_dir.join(path_part));
+            if let Some(anchor) = anchors.get(&resolved) {
+                let anchor = fragment
+                    .map(str::to_string)
+                    .unwrap_or_else(|| anchor.clone());
+                return format!("{}(#{}{})", label, anchor, title);
+            }
+            let rebased = relative_path(output_dir, &resolved);
+            let mut new_target = rebased.to_string_lossy().replace('\\', "/");
+            if let Some(fragment) = fragment {
+                new_target.push('#');
+                new_target.push_str(fragment);
+            }
+            format!("{}({}{})", label, new_target, title)
+        })
+        .into_owned()
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    let mut out = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+pub fn relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from = normalize(from_dir);
+    let to = normalize(to);
+    let from_parts: Vec<_> = from.components().collect();
+    let to_parts: Vec<_> = to.components().collect();
+    let common = from_parts
+        .iter()
+        .zip(&to_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return to;
+    }
+    let mut relative = PathBuf::new();
+    for _ in common..from_parts.len() {
+        relative.push("..");
+    }
+    for part in &to_parts[common..] {
+        relative.push(part.as_os_str());
+    }
+    relative
+}
+
+/// Writes `markdown` to `output` in the requested format. PDF output renders
+/// HTML to a temporary file and hands it to `pdf_command`, substituting the
+/// `{input}` and `{output}` placeholders.
+pub fn write_document(
+    markdown: &str,
+    title: &str,
+    format: ExportFormat,
+    output: &Path,
+    pdf_command: Option<&[String]>,
+    html: &HtmlOptions,
+) -> Result<String, String> {
+    match format {
+        ExportFormat::Markdown => fs::write(output, markdown)
+            .map(|_| format!("Wrote {}", output.display()))
+            .map_err(|err| format!("Failed to write {}: {}", output.display(), err)),
+        ExportFormat::Html => {
+            let mut page = markdown_to_html(markdown, title, html);
+            if let Some(passphrase) = &html.passphrase {
+                page = protect::protect_html(&page, title, passphrase)?;
+            }
+            fs::write(output, page)
+                .map(|_| format!("Wrote {}", output.display()))
+                .map_err(|err| format!("Failed to write {}: {}", output.display(), err))
+        }
+        ExportFormat::Pdf => {
+            let command = pdf_command.filter(|command| !command.is_empty()).ok_or(
+                "No PDF command configured. Add an [export] pdf_command entry to config.toml.",
+            )?;
+            let mut html_file = Builder::new()
+                .suffix(".html")
+                .tempfile_in(output.parent().unwrap_or(Path::new(".")))
+                .map_err(|err| format!("Failed to prepare temp file: {}", err))?;
+            html_file
+                .write_all(markdown_to_html(markdown, title, html).as_bytes())
+                .and_then(|_| html_file.flush())
+                .map_err(|err| format!("Failed to prepare temp file: {}", err))?;
+            run_converter(command, html_file.path(), output)
+        }
+    }
+}
+
+pub fn run_converter(command: &[String], input: &Path, output: &Path) -> Result<String, String> {
+    let args: Vec<String> = command
+        .iter()
+        .map(|arg| {
+            arg.replace("{input}", &input.display().to_string())
+                .replace("{output}", &output.display().to_string())
+        })
+        .collect();
+    let result = Command::new(&args[0])
+        .args(&args[1..])
+        .output()
+        .map_err(|err| format!("Failed to run '{}': {}", args[0], err))?;
+    let mut message = format!("$ {}\nStatus: {:?}\n", args.join(" "), result.status);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if !stderr.trim().is_empty() {
+        message.push_str("\nstderr:\n");
+        message.push_str(stderr.trim_end());
+        message.push('\n');
+    }
+    if result.status.success() {
+        Ok(message)
+    } else {
+        Err(message)
+    }
+}
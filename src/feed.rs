@@ -0,0 +1,174 @@
+use crate::dates::DateTime;
+use crate::export::{self, escape_html};
+use crate::front_matter;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+}
+
+impl FeedFormat {
+    pub const ALL: [Self; 2] = [Self::Rss, Self::Atom];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Rss => "RSS 2.0",
+            Self::Atom => "Atom",
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Rss => "feed.xml",
+            Self::Atom => "atom.xml",
+        }
+    }
+}
+
+/// What the feed says about the site as a whole.
+pub struct Channel<'a> {
+    pub title: &'a str,
+    pub description: &'a str,
+    pub author: &'a str,
+    /// Where the folder is published; posts are linked below it.
+    pub site_url: &'a str,
+}
+
+/// A post: a markdown file with a `date` in its front matter.
+pub struct Item {
+    pub title: String,
+    pub date: DateTime,
+    pub link: String,
+    pub summary: Option<String>,
+    pub html: String,
+}
+
+/// The posts among `files`, newest first. Files without a valid front
+/// matter `date`, and drafts (`draft: true`), are left out. Each post is
+/// linked as its path below `root` with an `.html` extension, or as its
+/// `url` front matter entry.
+pub fn items(files: &[PathBuf], root: &Path, site_url: &str) -> Vec<Item> {
+    let mut items: Vec<Item> = files
+        .iter()
+        .filter_map(|path| item(path, root, site_url))
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.date));
+    items
+}
+
+fn item(path: &Path, root: &Path, site_url: &str) -> Option<Item> {
+    let text = fs::read_to_string(path).ok()?;
+    let date = DateTime::parse(&front_matter::get(&text, "date")?)?;
+    if front_matter::get(&text, "draft").is_some_and(|draft| draft == "true") {
+        return None;
+    }
+    let body = &text[front_matter::body_start(&text)..];
+    let title = front_matter::get(&text, "title")
+        .or_else(|| export::atx_headings(body).next().map(|(_, title)| title))
+        .or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default();
+    let link = front_matter::get(&text, "url").unwrap_or_else(|| {
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .with_extension("html");
+        let relative: Vec<String> = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy().replace(' ', "%20"))
+            .collect();
+        format!("{}/{}", site_url.trim_end_matches('/'), relative.join("/"))
+    });
+    let summary =
+        front_matter::get(&text, "description").or_else(|| front_matter::get(&text, "summary"));
+    Some(Item {
+        title,
+        date,
+        link,
+        summary,
+        html: export::markdown_to_html_body(body),
+    })
+}
+
+/// An RSS 2.0 or Atom document for the first `limit` of `items`, with the
+/// full HTML of each post.
+pub fn render(format: FeedFormat, channel: &Channel, items: &[Item], limit: usize) -> String {
+    let items = &items[..items.len().min(limit)];
+    let updated = items.first().map_or_else(DateTime::now, |item| item.date);
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    match format {
+        FeedFormat::Rss => {
+            xml.push_str("<rss version=\"2.0\">\n<channel>\n");
+            xml.push_str(&element("title", channel.title));
+            xml.push_str(&element("link", channel.site_url));
+            xml.push_str(&element("description", channel.description));
+            xml.push_str(&element("lastBuildDate", &updated.rfc822()));
+            xml.push_str(&element("generator", "md-echo"));
+            for item in items {
+                xml.push_str("<item>\n");
+                xml.push_str(&element("title", &item.title));
+                xml.push_str(&element("link", &item.link));
+                xml.push_str(&format!(
+                    "<guid isPermaLink=\"true\">{}</guid>\n",
+                    escape_html(&item.link)
+                ));
+                xml.push_str(&element("pubDate", &item.date.rfc822()));
+                xml.push_str(&element(
+                    "description",
+                    item.summary.as_deref().unwrap_or(&item.html),
+                ));
+                xml.push_str("</item>\n");
+            }
+            xml.push_str("</channel>\n</rss>\n");
+        }
+        FeedFormat::Atom => {
+            xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+            xml.push_str(&element("title", channel.title));
+            if !channel.description.is_empty() {
+                xml.push_str(&element("subtitle", channel.description));
+            }
+            xml.push_str(&format!(
+                "<link href=\"{}\"/>\n",
+                escape_html(channel.site_url)
+            ));
+            xml.push_str(&element("id", channel.site_url));
+            xml.push_str(&element("updated", &updated.rfc3339()));
+            if !channel.author.is_empty() {
+                xml.push_str(&format!(
+                    "<author>\n{}</author>\n",
+                    element("name", channel.author)
+                ));
+            }
+            xml.push_str(&element("generator", "md-echo"));
+            for item in items {
+                xml.push_str("<entry>\n");
+                xml.push_str(&element("title", &item.title));
+                xml.push_str(&format!("<link href=\"{}\"/>\n", escape_html(&item.link)));
+                xml.push_str(&element("id", &item.link));
+                xml.push_str(&element("updated", &item.date.rfc3339()));
+                if let Some(summary) = &item.summary {
+                    xml.push_str(&element("summary", summary));
+                }
+                // Relative links and images in the post resolve against
+                // the post's own address.
+                xml.push_str(&format!(
+                    "<content type=\"html\" xml:base=\"{}\">{}</content>\n",
+                    escape_html(&item.link),
+                    escape_html(&item.html)
+                ));
+                xml.push_str("</entry>\n");
+            }
+            xml.push_str("</feed>\n");
+        }
+    }
+    xml
+}
+
+fn element(name: &str, text: &str) -> String {
+    format!("<{}>{}</{}>\n", name, escape_html(text), name)
+}
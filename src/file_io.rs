@@ -0,0 +1,163 @@
+use crate::text_format::{self, TextFormat};
+use encoding_rs::Encoding;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+/// What a finished file job did.
+pub enum Outcome {
+    Opened {
+        path: PathBuf,
+        /// The line to put the cursor on.
+        line: Option<usize>,
+        result: Result<(String, TextFormat), String>,
+        mtime: Option<SystemTime>,
+    },
+    Saved {
+        path: PathBuf,
+        /// The text handed to the job, before formatting.
+        submitted: String,
+        /// The text written, which the buffer may have moved on from.
+        text: String,
+        /// Why the text was saved without formatting.
+        warning: Option<String>,
+        format: TextFormat,
+        result: Result<(), String>,
+        mtime: Option<SystemTime>,
+    },
+}
+
+/// Reading or writing the document on a background thread, so a slow disk
+/// or network share does not freeze the window.
+pub struct FileJob {
+    /// What is happening, for the status bar.
+    pub description: String,
+    pending: Arc<Mutex<Option<Outcome>>>,
+}
+
+impl FileJob {
+    /// Reads `path`, decoding it in `fallback` when it is not UTF-8.
+    pub fn open(path: &Path, line: Option<usize>, fallback: &'static Encoding) -> Self {
+        let description = format!("Opening {}…", file_name(path));
+        let path = path.to_path_buf();
+        Self::start(description, move || {
+            let result = text_format::read(&path, fallback)
+                .map_err(|err| format!("Could not open {}: {}", path.display(), err));
+            Outcome::Opened {
+                mtime: modified_time(&path),
+                path,
+                line,
+                result,
+            }
+        })
+    }
+
+    /// Writes `text` to `path` in `format`, passing it through `formatter`
+    /// first when there is one.
+    pub fn save(
+        path: &Path,
+        submitted: String,
+        format: TextFormat,
+        formatter: Option<Formatter>,
+    ) -> Self {
+        let description = format!("Saving {}…", file_name(path));
+        let path = path.to_path_buf();
+        Self::start(description, move || {
+            let (text, warning) = match formatter.map(|formatter| formatter.run(&submitted)) {
+                Some(Ok(formatted)) => (formatted, None),
+                Some(Err(err)) => (submitted.clone(), Some(err)),
+                None => (submitted.clone(), None),
+            };
+            let result = text_format::encode(&text, &format).and_then(|bytes| {
+                fs::write(&path, bytes)
+                    .map_err(|err| format!("Could not save {}: {}", path.display(), err))
+            });
+            Outcome::Saved {
+                mtime: modified_time(&path),
+                path,
+                submitted,
+                text,
+                warning,
+                format,
+                result,
+            }
+        })
+    }
+
+    fn start(description: String, work: impl FnOnce() -> Outcome + Send + 'static) -> Self {
+        let pending = Arc::new(Mutex::new(None));
+        {
+            let pending = Arc::clone(&pending);
+            thread::spawn(move || {
+                let outcome = work();
+                if let Ok(mut slot) = pending.lock() {
+                    *slot = Some(outcome);
+                }
+            });
+        }
+        Self {
+            description,
+            pending,
+        }
+    }
+
+    /// The outcome, once the job has finished.
+    pub fn poll(&self) -> Option<Outcome> {
+        self.pending.lock().ok().and_then(|mut slot| slot.take())
+    }
+}
+
+/// The format tool, run on a temporary copy of the text before saving.
+pub struct Formatter {
+    /// The expanded command; the file path is appended.
+    pub command: Vec<String>,
+    pub dir: PathBuf,
+}
+
+impl Formatter {
+    fn run(&self, text: &str) -> Result<String, String> {
+        let Some((program, args)) = self.command.split_first() else {
+            return Err("The format command is empty.".to_string());
+        };
+        let error = |err: std::io::Error| format!("Could not format before saving: {}", err);
+        let mut file = tempfile::Builder::new()
+            .suffix(".md")
+            .tempfile()
+            .map_err(error)?;
+        file.write_all(text.as_bytes()).map_err(error)?;
+        file.flush().map_err(error)?;
+        let mut command = Command::new(program);
+        command.args(args).arg(file.path());
+        if self.dir.is_dir() {
+            command.current_dir(&self.dir);
+        }
+        let output = command
+            .output()
+            .map_err(|err| format!("Failed to run '{}' before saving: {}", program, err))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "'{}' failed ({}), so the document was saved unformatted: {}",
+                program,
+                output.status,
+                stderr.trim()
+            ));
+        }
+        fs::read_to_string(file.path()).map_err(error)
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
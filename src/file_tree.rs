@@ -0,0 +1,217 @@
+use crate::export;
+use crate::patterns::IgnorePatterns;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+/// The sorted contents of one directory.
+pub struct Listing {
+    pub dirs: Vec<PathBuf>,
+    pub files: Vec<PathBuf>,
+    pub error: Option<String>,
+}
+
+/// Which entries the tree hides. Listings are cached unfiltered, so
+/// changing the filter never triggers a rescan.
+#[derive(Default)]
+pub struct TreeFilter {
+    markdown_only: bool,
+    hide_hidden: bool,
+    ignore: IgnorePatterns,
+}
+
+impl TreeFilter {
+    /// The filter, and a message for each pattern that is not a valid glob
+    /// and was left out.
+    pub fn new(markdown_only: bool, hide_hidden: bool, patterns: &[String]) -> (Self, Vec<String>) {
+        let (ignore, invalid) = IgnorePatterns::new(patterns);
+        let filter = Self {
+            markdown_only,
+            hide_hidden,
+            ignore,
+        };
+        (filter, invalid)
+    }
+
+    fn matches_settings(
+        &self,
+        markdown_only: bool,
+        hide_hidden: bool,
+        patterns: &[String],
+    ) -> bool {
+        self.markdown_only == markdown_only
+            && self.hide_hidden == hide_hidden
+            && self.ignore.patterns() == patterns
+    }
+
+    pub fn shows(&self, path: &Path, root: &Path, is_dir: bool) -> bool {
+        let name = path.file_name().unwrap_or_default();
+        if self.hide_hidden && name.to_string_lossy().starts_with('.') {
+            return false;
+        }
+        if self.markdown_only && !is_dir && !export::is_markdown(path) {
+            return false;
+        }
+        !self.ignore.matches(path, root, is_dir)
+    }
+}
+
+/// Cached directory listings for the file tree. Directories are read on a
+/// background thread the first time they are shown and again on refresh, so
+/// drawing the tree never touches the filesystem.
+pub struct FileTree {
+    requests: Sender<(PathBuf, egui::Context)>,
+    results: Receiver<(PathBuf, Listing)>,
+    listings: HashMap<PathBuf, Arc<Listing>>,
+    /// Directories queued on the worker and not answered yet.
+    requested: HashSet<PathBuf>,
+    /// Cached directories that should be re-read when next shown.
+    stale: HashSet<PathBuf>,
+    expanded: HashSet<PathBuf>,
+    root: Option<PathBuf>,
+    filter: TreeFilter,
+    last_refresh: f64,
+}
+
+impl Default for FileTree {
+    fn default() -> Self {
+        let (requests, jobs) = channel::<(PathBuf, egui::Context)>();
+        let (sender, results) = channel();
+        thread::spawn(move || {
+            for (dir, ctx) in jobs {
+                let listing = read_listing(&dir);
+                if sender.send((dir, listing)).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+
+        Self {
+            requests,
+            results,
+            listings: HashMap::new(),
+            requested: HashSet::new(),
+            stale: HashSet::new(),
+            expanded: HashSet::new(),
+            root: None,
+            filter: TreeFilter::default(),
+            last_refresh: 0.0,
+        }
+    }
+}
+
+impl FileTree {
+    /// Takes in finished listings and schedules a refresh every
+    /// `interval_secs` (0 disables the timer).
+    pub fn poll(&mut self, now: f64, interval_secs: f64) {
+        for (dir, listing) in self.results.try_iter() {
+            self.requested.remove(&dir);
+            self.listings.insert(dir, Arc::new(listing));
+        }
+        if interval_secs > 0.0 && now - self.last_refresh >= interval_secs {
+            self.last_refresh = now;
+            self.refresh();
+        }
+    }
+
+    /// The cached listing of `dir`, requesting a (re)scan when it is missing
+    /// or stale. Stale listings are returned until the new one arrives.
+    pub fn listing(&mut self, dir: &Path, ctx: &egui::Context) -> Option<Arc<Listing>> {
+        let cached = self.listings.get(dir).cloned();
+        let needs_scan = cached.is_none() || self.stale.remove(dir);
+        if needs_scan && self.requested.insert(dir.to_path_buf()) {
+            let _ = self.requests.send((dir.to_path_buf(), ctx.clone()));
+        }
+        cached
+    }
+
+    /// Marks every cached directory for re-reading.
+    pub fn refresh(&mut self) {
+        self.stale.extend(self.listings.keys().cloned());
+    }
+
+    /// Starts over with an expanded `root` when the working directory
+    /// changes.
+    pub fn set_root(&mut self, root: &Path) {
+        if self.root.as_deref() != Some(root) {
+            self.listings.clear();
+            self.stale.clear();
+            self.expanded.clear();
+            self.expanded.insert(root.to_path_buf());
+            self.root = Some(root.to_path_buf());
+        }
+    }
+
+    /// Rebuilds the filter when the settings changed, returning the
+    /// problems with the new patterns.
+    pub fn set_filter(
+        &mut self,
+        markdown_only: bool,
+        hide_hidden: bool,
+        patterns: &[String],
+    ) -> Vec<String> {
+        if self
+            .filter
+            .matches_settings(markdown_only, hide_hidden, patterns)
+        {
+            return Vec::new();
+        }
+        let (filter, invalid) = TreeFilter::new(markdown_only, hide_hidden, patterns);
+        self.filter = filter;
+        invalid
+    }
+
+    pub fn shows(&self, path: &Path, is_dir: bool) -> bool {
+        match &self.root {
+            Some(root) => self.filter.shows(path, root, is_dir),
+            None => true,
+        }
+    }
+
+    pub fn is_expanded(&self, dir: &Path) -> bool {
+        self.expanded.contains(dir)
+    }
+
+    pub fn set_expanded(&mut self, dir: &Path, expanded: bool) {
+        if expanded {
+            self.expanded.insert(dir.to_path_buf());
+        } else {
+            self.expanded.remove(dir);
+        }
+    }
+}
+
+fn read_listing(dir: &Path) -> Listing {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            return Listing {
+                dirs: Vec::new(),
+                files: Vec::new(),
+                error: Some(format!("Cannot read {}: {}", dir.display(), err)),
+            }
+        }
+    };
+
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            dirs.push(path);
+        } else {
+            files.push(path);
+        }
+    }
+    dirs.sort();
+    files.sort();
+    Listing {
+        dirs,
+        files,
+        error: None,
+    }
+}
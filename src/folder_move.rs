@@ -0,0 +1,176 @@
+use crate::patterns::IgnorePatterns;
+use crate::{bundle, export, wikilinks};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A document whose links change when the folder moves.
+pub struct Edit {
+    /// Where the document is now.
+    pub file: PathBuf,
+    /// Where it will be after the move.
+    pub destination: PathBuf,
+    pub text: String,
+    /// Each rewritten link target, before and after.
+    pub changes: Vec<(String, String)>,
+}
+
+/// Moving the folder `from` to `to`, with the link changes that keep the
+/// documents under `root` pointing at the same files.
+pub struct FolderMove {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub edits: Vec<Edit>,
+    /// Documents that could not be read, and why.
+    pub failures: Vec<String>,
+}
+
+impl FolderMove {
+    /// Works out the move of `from` into `target_dir` without changing
+    /// anything. Documents `ignore` matches are not updated. `open` is the
+    /// document being edited and its unsaved text, used instead of what is
+    /// on disk.
+    pub fn plan(
+        root: &Path,
+        ignore: &IgnorePatterns,
+        from: &Path,
+        target_dir: &Path,
+        open: Option<(&Path, &str)>,
+    ) -> Result<Self, String> {
+        let from = export::normalize(from);
+        let name = from
+            .file_name()
+            .ok_or_else(|| format!("{} cannot be moved.", from.display()))?;
+        let to = export::normalize(target_dir).join(name);
+        if to == from {
+            return Err(format!("{} is already there.", from.display()));
+        }
+        if to.starts_with(&from) {
+            return Err("A folder cannot be moved into itself.".to_string());
+        }
+        if to.exists() {
+            return Err(format!("{} already exists.", to.display()));
+        }
+        let mut plan = Self {
+            from,
+            to,
+            edits: Vec::new(),
+            failures: Vec::new(),
+        };
+        for file in export::markdown_files_except(root, ignore) {
+            let file = export::normalize(&file);
+            let text = match open.filter(|(path, _)| export::normalize(path) == file) {
+                Some((_, text)) => text.to_string(),
+                None => match fs::read_to_string(&file) {
+                    Ok(text) => text,
+                    Err(err) => {
+                        plan.failures.push(format!("{}: {}", file.display(), err));
+                        continue;
+                    }
+                },
+            };
+            let destination = plan.moved(&file);
+            let mut changes = Vec::new();
+            let text = bundle::rewrite_targets(&text, |target| {
+                let rewritten = plan.relink(target, &file, &destination)?;
+                changes.push((target.to_string(), rewritten.clone()));
+                Some(rewritten)
+            });
+            if !changes.is_empty() {
+                plan.edits.push(Edit {
+                    file,
+                    destination,
+                    text,
+                    changes,
+                });
+            }
+        }
+        plan.edits.sort_by(|a, b| a.file.cmp(&b.file));
+        Ok(plan)
+    }
+
+    /// Where `path` is once the folder has moved.
+    fn moved(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.from) {
+            Ok(relative) => self.to.join(relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    /// `target`, linked from `file`, as it has to be written once `file` is
+    /// at `destination`, if it changes. Links to missing files are left as
+    /// they are.
+    fn relink(&self, target: &str, file: &Path, destination: &Path) -> Option<String> {
+        if export::is_external(target)
+            || target.starts_with("data:")
+            || target.starts_with(wikilinks::SCHEME)
+        {
+            return None;
+        }
+        let (path_part, suffix) = match target.find(['?', '#']) {
+            Some(at) => target.split_at(at),
+            None => (target, ""),
+        };
+        let dir = file.parent()?;
+        let linked = export::normalize(&dir.join(bundle::percent_decode(path_part)));
+        if path_part.is_empty() || !linked.exists() {
+            return None;
+        }
+        let relative = export::relative_path(destination.parent()?, &self.moved(&linked));
+        let relative = wikilinks::encode(&relative.to_string_lossy().replace('\\', "/"));
+        if bundle::percent_decode(&relative) == bundle::percent_decode(path_part) {
+            return None;
+        }
+        Some(format!("{}{}", relative, suffix))
+    }
+
+    /// What [`FolderMove::apply`] would do, for checking before it does.
+    pub fn report(&self, root: &Path) -> String {
+        let shown = |path: &Path| {
+            path.strip_prefix(root)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        let mut report = format!("Move {} -> {}\n", shown(&self.from), shown(&self.to));
+        if self.edits.is_empty() {
+            report.push_str("\nNo links need to change.\n");
+        }
+        for edit in &self.edits {
+            report.push_str(&format!("\n{}\n", shown(&edit.file)));
+            for (old, new) in &edit.changes {
+                report.push_str(&format!("  {} -> {}\n", old, new));
+            }
+        }
+        for failure in &self.failures {
+            report.push_str(&format!("\nnot checked: {}\n", failure));
+        }
+        report
+    }
+
+    /// Moves the folder, then writes the documents with their links
+    /// rewritten, except `open`, whose new text is returned for the editor.
+    /// Returns the documents that could not be written, and why.
+    pub fn apply(self, open: Option<&Path>) -> Result<(Option<String>, Vec<String>), String> {
+        fs::rename(&self.from, &self.to).map_err(|err| {
+            format!(
+                "Failed to move {} to {}: {}",
+                self.from.display(),
+                self.to.display(),
+                err
+            )
+        })?;
+        let open = open.map(export::normalize);
+        let mut open_text = None;
+        let mut failures = Vec::new();
+        for edit in self.edits {
+            if open.as_ref() == Some(&edit.file) {
+                open_text = Some(edit.text);
+                continue;
+            }
+            if let Err(err) = fs::write(&edit.destination, &edit.text) {
+                failures.push(format!("{}: {}", edit.destination.display(), err));
+            }
+        }
+        Ok((open_text, failures))
+    }
+}
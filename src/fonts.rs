@@ -0,0 +1,95 @@
+use egui::{FontData, FontDefinitions, FontFamily};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Common system fonts covering symbols, box drawing and non-Latin scripts.
+/// Whichever of them are installed are used.
+pub fn default_fallbacks() -> Vec<String> {
+    [
+        "DejaVuSans.ttf",
+        "NotoSansSymbols2-Regular.ttf",
+        "NotoSansArabic-Regular.ttf",
+        "NotoSansHebrew-Regular.ttf",
+        "NotoSansCJK-Regular.ttc",
+        "seguisym.ttf",
+        "msyh.ttc",
+        "Apple Symbols.ttf",
+        "Arial Unicode.ttf",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect()
+}
+
+fn font_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/fonts"),
+        PathBuf::from("/usr/local/share/fonts"),
+        PathBuf::from("/System/Library/Fonts"),
+        PathBuf::from("/Library/Fonts"),
+    ];
+    if let Some(home) = env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        dirs.push(home.join(".local/share/fonts"));
+        dirs.push(home.join(".fonts"));
+        dirs.push(home.join("Library/Fonts"));
+    }
+    if let Some(windows) = env::var_os("WINDIR") {
+        dirs.push(PathBuf::from(windows).join("Fonts"));
+    }
+    dirs
+}
+
+/// Font files in the system font folders, by lowercase file name.
+fn installed_fonts() -> HashMap<String, PathBuf> {
+    font_dirs()
+        .iter()
+        .filter(|dir| dir.is_dir())
+        .flat_map(|dir| WalkDir::new(dir).max_depth(4).into_iter().flatten())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            (name, entry.into_path())
+        })
+        .collect()
+}
+
+/// Appends `fallbacks` (font file paths, or file names looked up in the
+/// system font folders) to egui's fonts, so glyphs missing from the
+/// built-in fonts render instead of showing as boxes. Returns the entries
+/// that could not be loaded.
+pub fn install(ctx: &egui::Context, fallbacks: &[String]) -> Vec<String> {
+    let mut definitions = FontDefinitions::default();
+    let mut installed = None;
+    let mut missing = Vec::new();
+    for entry in fallbacks {
+        let path = Path::new(entry);
+        let path = if path.is_file() {
+            Some(path.to_path_buf())
+        } else {
+            installed
+                .get_or_insert_with(installed_fonts)
+                .get(&entry.to_lowercase())
+                .cloned()
+        };
+        let Some(bytes) = path.and_then(|path| fs::read(path).ok()) else {
+            missing.push(entry.clone());
+            continue;
+        };
+        definitions
+            .font_data
+            .insert(entry.clone(), FontData::from_owned(bytes));
+        for family in [FontFamily::Proportional, FontFamily::Monospace] {
+            definitions
+                .families
+                .entry(family)
+                .or_default()
+                .push(entry.clone());
+        }
+    }
+    ctx.set_fonts(definitions);
+    missing
+}
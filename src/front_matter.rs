@@ -0,0 +1,162 @@
+use std::ops::Range;
+
+/// Byte range of the `key: value` lines of a leading `---` front matter
+/// block, excluding the delimiters.
+pub fn block(text: &str) -> Option<Range<usize>> {
+    let mut lines = text.split_inclusive('\n');
+    let first = lines.next()?;
+    if first.trim_end() != "---" {
+        return None;
+    }
+    let mut offset = first.len();
+    for line in lines {
+        if matches!(line.trim_end(), "---" | "...") {
+            return Some(first.len()..offset);
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Byte offset where the document body starts (0 without front matter).
+pub fn body_start(text: &str) -> usize {
+    block(text)
+        .map(|block| {
+            text[block.end..]
+                .find('\n')
+                .map_or(text.len(), |end| block.end + end + 1)
+        })
+        .unwrap_or(0)
+}
+
+/// The value of a top-level scalar `key`, without surrounding quotes.
+pub fn get(text: &str, key: &str) -> Option<String> {
+    let block = block(text)?;
+    text[block].lines().find_map(|line| {
+        let value = line.strip_prefix(key)?.trim_start().strip_prefix(':')?;
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+/// Returns `text` with `key` set to `value`, adding a front matter block if
+/// the document has none.
+pub fn set(text: &str, key: &str, value: &str) -> String {
+    let entry = format!("{}: {}\n", key, value);
+    let Some(block) = block(text) else {
+        return format!("---\n{}---\n\n{}", entry, text);
+    };
+
+    let mut offset = block.start;
+    for line in text[block.clone()].split_inclusive('\n') {
+        let is_key = line
+            .strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with(':'));
+        if is_key {
+            let mut updated = text.to_string();
+            updated.replace_range(offset..offset + line.len(), &entry);
+            return updated;
+        }
+        offset += line.len();
+    }
+    let mut updated = text.to_string();
+    updated.insert_str(block.end, &entry);
+    updated
+}
+
+/// A top-level front matter entry: its `key: value` line and the indented
+/// lines below it (lists, nested maps, block scalars).
+pub struct Entry {
+    pub key: String,
+    /// Everything after the colon and the spaces after it on the key line,
+    /// as written.
+    pub value: String,
+    /// Byte range of the entry's lines in the document.
+    pub range: Range<usize>,
+    /// The indented lines below the key line.
+    pub nested: String,
+}
+
+impl Entry {
+    /// The items of a block list (`- item` lines below the key).
+    pub fn list(&self) -> Option<Vec<String>> {
+        if !self.value.trim().is_empty() || self.nested.trim().is_empty() {
+            return None;
+        }
+        self.nested
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let item = line.trim().strip_prefix('-')?;
+                Some(item.trim().to_string())
+            })
+            .collect()
+    }
+}
+
+/// The top-level entries of the front matter, in order. Comments and lines
+/// that are not `key: value` are skipped.
+pub fn entries(text: &str) -> Vec<Entry> {
+    let Some(block) = block(text) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut offset = block.start;
+    for line in text[block].split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let indented = line.starts_with([' ', '\t']) || line.trim_start().starts_with('-');
+        if indented || line.trim().is_empty() {
+            if let Some(entry) = entries.last_mut() {
+                entry.range.end = offset;
+                entry.nested.push_str(line);
+            }
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        entries.push(Entry {
+            key: key.trim().to_string(),
+            value: value
+                .trim_start()
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+            range: start..offset,
+            nested: String::new(),
+        });
+    }
+    // Blank lines after the last item belong to no entry.
+    for entry in &mut entries {
+        let lines: Vec<&str> = entry.nested.split_inclusive('\n').collect();
+        let kept = lines.len()
+            - lines
+                .iter()
+                .rev()
+                .take_while(|line| line.trim().is_empty())
+                .count();
+        let len: usize = lines[..kept].iter().map(|line| line.len()).sum();
+        entry.range.end -= entry.nested.len() - len;
+        entry.nested.truncate(len);
+    }
+    entries
+}
+
+/// Returns `text` with the lines of `entry` replaced by `lines`.
+pub fn replace_entry(text: &str, entry: &Entry, lines: &str) -> String {
+    let mut updated = text.to_string();
+    updated.replace_range(entry.range.clone(), lines);
+    updated
+}
+
+/// The lines of a `key` entry holding `items` as a block list.
+pub fn list_lines(key: &str, items: &[String]) -> String {
+    let mut lines = format!("{}:\n", key);
+    for item in items {
+        lines.push_str(&format!("  - {}\n", item));
+    }
+    lines
+}
@@ -0,0 +1,35 @@
+/// Scores `candidate` against `query` when every character of the query
+/// appears in it in order, ignoring case. Runs of consecutive characters,
+/// matches at the start of a word, and matches in the file name (after the
+/// last `/`) score higher; longer candidates score slightly lower. Also
+/// returns the char indices that matched, for highlighting.
+pub fn score(query: &str, candidate: &str) -> Option<(i32, Vec<usize>)> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let name_start = chars
+        .iter()
+        .rposition(|&c| c == '/')
+        .map_or(0, |index| index + 1);
+    let mut score = 0;
+    let mut matched = Vec::new();
+    let mut next = 0;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        let wanted = wanted.to_lowercase().next().unwrap_or(wanted);
+        let found = (next..chars.len())
+            .find(|&index| chars[index].to_lowercase().next() == Some(wanted))?;
+        let word_start = found == 0 || matches!(chars[found - 1], '/' | '-' | '_' | ' ' | '.');
+        score += 1;
+        if matched.last().is_some_and(|&last| last + 1 == found) {
+            score += 5;
+        }
+        if word_start {
+            score += 8;
+        }
+        if found >= name_start {
+            score += 2;
+        }
+        matched.push(found);
+        next = found + 1;
+    }
+    score -= (chars.len() / 8) as i32;
+    Some((score, matched))
+}
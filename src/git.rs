@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs `git` in `dir` and returns its stdout.
+fn run(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| format!("Failed to run git: {}", err))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Contents of `file` as of `revision` (e.g. `HEAD`, `HEAD~2`, a tag).
+pub fn show(file: &Path, revision: &str) -> Result<String, String> {
+    let dir = file.parent().unwrap_or(Path::new("."));
+    let name = file
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file", file.display()))?;
+    let spec = format!("{}:./{}", revision.trim(), name.to_string_lossy());
+    run(dir, &["show", &spec]).map_err(|err| format!("git show {} failed: {}", spec, err))
+}
+
+/// A `git config` value as seen from `dir`, such as `user.name`.
+pub fn config(dir: &Path, key: &str) -> Option<String> {
+    let value = run(dir, &["config", "--get", key]).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// The checked out branch of the repository containing `dir`, or `None`
+/// outside a repository or on a detached HEAD.
+pub fn branch(dir: &Path) -> Option<String> {
+    let branch = run(dir, &["rev-parse", "--abbrev-ref", "HEAD"]).ok()?;
+    let branch = branch.trim();
+    (!branch.is_empty() && branch != "HEAD").then(|| branch.to_string())
+}
+
+/// The web address of the `origin` remote: SSH remotes such as
+/// `git@host:owner/repo.git` become `https://host/owner/repo`.
+pub fn remote_url(dir: &Path) -> Option<String> {
+    let url = config(dir, "remote.origin.url")?;
+    let url = url.strip_suffix(".git").unwrap_or(&url);
+    if url.contains("://") {
+        let url = url.strip_prefix("ssh://").map_or(url.to_string(), |rest| {
+            let rest = rest.split_once('@').map_or(rest, |(_, host)| host);
+            format!("https://{}", rest)
+        });
+        return Some(url);
+    }
+    match url
+        .split_once('@')
+        .map_or(url, |(_, rest)| rest)
+        .split_once(':')
+    {
+        Some((host, path)) => Some(format!("https://{}/{}", host, path)),
+        None => Some(url.to_string()),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FileStatus {
+    Modified,
+    Staged,
+    Untracked,
+    Conflicted,
+}
+
+impl FileStatus {
+    /// Badge shown next to the file in the tree.
+    pub fn badge(self) -> &'static str {
+        match self {
+            Self::Modified => "M",
+            Self::Staged => "S",
+            Self::Untracked => "U",
+            Self::Conflicted => "!",
+        }
+    }
+
+    pub fn color(self) -> egui::Color32 {
+        match self {
+            Self::Modified => egui::Color32::from_rgb(220, 170, 60),
+            Self::Staged => egui::Color32::from_rgb(70, 170, 90),
+            Self::Untracked => egui::Color32::from_rgb(100, 140, 220),
+            Self::Conflicted => egui::Color32::from_rgb(210, 80, 80),
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Modified => "Modified",
+            Self::Staged => "Staged",
+            Self::Untracked => "Untracked",
+            Self::Conflicted => "Conflicted",
+        }
+    }
+
+    /// From the two-letter `XY` code of `git status --porcelain`.
+    fn from_code(index: char, worktree: char) -> Option<Self> {
+        match (index, worktree) {
+            ('?', '?') => Some(Self::Untracked),
+            ('!', '!') => None,
+            ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => Some(Self::Conflicted),
+            (_, ' ') if index != ' ' => Some(Self::Staged),
+            (_, worktree) if worktree != ' ' => Some(Self::Modified),
+            _ => None,
+        }
+    }
+}
+
+/// `git status` of the repository containing a directory.
+pub struct RepoStatus {
+    pub branch: Option<String>,
+    /// Changed files, as paths under the directory the status was taken for.
+    pub files: HashMap<PathBuf, FileStatus>,
+    /// Folders containing changed files.
+    pub dirs: HashSet<PathBuf>,
+}
+
+/// Status of the repository containing `dir`, with paths joined onto `dir`
+/// so they compare equal to the file tree's entries.
+pub fn status(dir: &Path) -> Result<RepoStatus, String> {
+    let top = run(dir, &["rev-parse", "--show-toplevel"])?;
+    let top = fs::canonicalize(top.trim()).map_err(|err| err.to_string())?;
+    let prefix = fs::canonicalize(dir)
+        .ok()
+        .and_then(|canonical| canonical.strip_prefix(&top).ok().map(Path::to_path_buf))
+        .unwrap_or_default();
+    let output = run(
+        dir,
+        &[
+            "status",
+            "--porcelain=v1",
+            "--branch",
+            "-z",
+            "--untracked-files=all",
+        ],
+    )?;
+
+    let mut status = RepoStatus {
+        branch: None,
+        files: HashMap::new(),
+        dirs: HashSet::new(),
+    };
+    let mut entries = output.split('\0');
+    while let Some(entry) = entries.next() {
+        if let Some(branch) = entry.strip_prefix("## ") {
+            status.branch = parse_branch(branch);
+            continue;
+        }
+        let mut chars = entry.chars();
+        let (Some(index), Some(worktree), Some(' ')) = (chars.next(), chars.next(), chars.next())
+        else {
+            continue;
+        };
+        if index == 'R' || index == 'C' {
+            // Renames and copies are followed by the original path.
+            entries.next();
+        }
+        let Some(file_status) = FileStatus::from_code(index, worktree) else {
+            continue;
+        };
+        let Ok(relative) = Path::new(chars.as_str()).strip_prefix(&prefix) else {
+            continue;
+        };
+        let path = dir.join(relative);
+        for ancestor in path.ancestors().skip(1) {
+            if ancestor == dir || !status.dirs.insert(ancestor.to_path_buf()) {
+                break;
+            }
+        }
+        status.files.insert(path, file_status);
+    }
+    Ok(status)
+}
+
+/// Branch name from the `## main...origin/main [ahead 1]` header.
+fn parse_branch(header: &str) -> Option<String> {
+    let header = header
+        .strip_prefix("No commits yet on ")
+        .or_else(|| header.strip_prefix("Initial commit on "))
+        .unwrap_or(header);
+    if header.starts_with("HEAD (no branch)") {
+        return Some("detached HEAD".to_string());
+    }
+    let name = header.split("...").next()?.split(' ').next()?;
+    (!name.is_empty()).then(|| name.to_string())
+}
@@ -0,0 +1,85 @@
+use crate::git::{self, FileStatus, RepoStatus};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Pending = Arc<Mutex<Option<Result<RepoStatus, String>>>>;
+
+/// `git status` of the working directory, refreshed on a background thread.
+#[derive(Default)]
+pub struct GitStatus {
+    dir: Option<PathBuf>,
+    current: Option<RepoStatus>,
+    pending: Option<Pending>,
+    last_refresh: f64,
+    stale: bool,
+}
+
+impl GitStatus {
+    /// Picks up finished results and starts a new run when `dir` changed,
+    /// a refresh was requested or `interval_secs` passed (0 disables the
+    /// timer).
+    pub fn poll(&mut self, dir: &Path, now: f64, interval_secs: f64, ctx: &egui::Context) {
+        if self.dir.as_deref() != Some(dir) {
+            self.dir = Some(dir.to_path_buf());
+            self.current = None;
+            self.pending = None;
+            self.stale = true;
+        }
+
+        if let Some(pending) = &self.pending {
+            match pending.lock().ok().and_then(|mut slot| slot.take()) {
+                Some(result) => {
+                    // Outside a repository (or without git) there is simply
+                    // nothing to show.
+                    self.current = result.ok();
+                    self.pending = None;
+                }
+                None => return,
+            }
+        }
+
+        let due = interval_secs > 0.0 && now - self.last_refresh >= interval_secs;
+        if self.stale || due {
+            self.stale = false;
+            self.last_refresh = now;
+            self.start(dir, ctx.clone());
+        }
+    }
+
+    /// Re-runs `git status` on the next poll, e.g. after saving.
+    pub fn refresh(&mut self) {
+        self.stale = true;
+    }
+
+    fn start(&mut self, dir: &Path, ctx: egui::Context) {
+        let result = Arc::new(Mutex::new(None));
+        {
+            let result = Arc::clone(&result);
+            let dir = dir.to_path_buf();
+            thread::spawn(move || {
+                let status = git::status(&dir);
+                if let Ok(mut slot) = result.lock() {
+                    *slot = Some(status);
+                }
+                ctx.request_repaint();
+            });
+        }
+        self.pending = Some(result);
+    }
+
+    pub fn branch(&self) -> Option<&str> {
+        self.current.as_ref()?.branch.as_deref()
+    }
+
+    pub fn file(&self, path: &Path) -> Option<FileStatus> {
+        self.current.as_ref()?.files.get(path).copied()
+    }
+
+    /// Whether `dir` contains changed files.
+    pub fn dir_changed(&self, dir: &Path) -> bool {
+        self.current
+            .as_ref()
+            .is_some_and(|status| status.dirs.contains(dir))
+    }
+}
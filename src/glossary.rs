@@ -0,0 +1,174 @@
+use crate::export;
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The heading of the section Insert Glossary writes.
+const SECTION_TITLE: &str = "Glossary";
+
+/// Terms and their definitions, written as abbreviation lines
+/// (`*[HTML]: HyperText Markup Language`) in the glossary file or in the
+/// document itself.
+#[derive(Default)]
+pub struct Glossary {
+    terms: BTreeMap<String, String>,
+    source: Option<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Glossary {
+    /// Reloads the glossary file when it is a different file or has
+    /// changed since it was read.
+    pub fn refresh(&mut self, path: &Path) {
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        let current = self
+            .source
+            .as_ref()
+            .is_some_and(|(loaded, loaded_at)| loaded == path && *loaded_at == modified);
+        if !current {
+            self.terms = fs::read_to_string(path)
+                .map(|text| definitions(&text))
+                .unwrap_or_default();
+            self.source = Some((path.to_path_buf(), modified));
+        }
+    }
+
+    /// The glossary's terms with the document's own definitions added,
+    /// which win over the file's.
+    pub fn with_document(&self, document: &str) -> BTreeMap<String, String> {
+        let mut terms = self.terms.clone();
+        terms.extend(definitions(document));
+        terms
+    }
+}
+
+/// The term and definition of an abbreviation line.
+fn definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim().strip_prefix("*[")?;
+    let (term, definition) = rest.split_once("]:")?;
+    let term = term.trim();
+    (!term.is_empty()).then(|| (term, definition.trim()))
+}
+
+/// The definitions in `text`, outside code blocks.
+pub fn definitions(text: &str) -> BTreeMap<String, String> {
+    let mut terms = BTreeMap::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        if export::is_fence(line) {
+            in_fence = !in_fence;
+        } else if let Some((term, meaning)) = definition(line).filter(|_| !in_fence) {
+            terms.insert(term.to_string(), meaning.to_string());
+        }
+    }
+    terms
+}
+
+/// `markdown` with its definition lines blanked, so they do not show in
+/// the preview. Line numbers stay the same.
+pub fn hide_definitions(markdown: &str) -> String {
+    if !markdown.contains("*[") {
+        return markdown.to_string();
+    }
+    markdown
+        .split('\n')
+        .map(|line| if definition(line).is_some() { "" } else { line })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The char ranges in `text` where a term appears as a whole word,
+/// longest terms first where they overlap. Terms are matched exactly.
+pub fn find(text: &str, terms: &BTreeMap<String, String>) -> Vec<(Range<usize>, String)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut terms: Vec<Vec<char>> = terms.keys().map(|term| term.chars().collect()).collect();
+    terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+    let boundary = |c: Option<&char>| !c.is_some_and(|c| c.is_alphanumeric());
+
+    let mut found = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let at_word_start = index == 0 || boundary(chars.get(index - 1));
+        let term = terms.iter().find(|term| {
+            at_word_start
+                && chars[index..].starts_with(term)
+                && boundary(chars.get(index + term.len()))
+        });
+        match term {
+            Some(term) => {
+                found.push((index..index + term.len(), term.iter().collect()));
+                index += term.len();
+            }
+            None => index += 1,
+        }
+    }
+    found
+}
+
+/// `document` with a glossary section listing the defined terms it uses,
+/// replacing an earlier section of that name or else appended at the
+/// end.
+pub fn insert_section(document: &str, terms: &BTreeMap<String, String>) -> String {
+    let (start, end) = section_range(document).unwrap_or((document.len(), document.len()));
+    let before = &document[..start];
+    let after = &document[end..];
+    // Only uses outside the definitions and the old section count.
+    let prose = hide_definitions(&format!("{}{}", before, after));
+    let used: BTreeMap<&String, &String> = terms
+        .iter()
+        .filter(|(term, _)| {
+            let single = BTreeMap::from([((*term).clone(), String::new())]);
+            !find(&prose, &single).is_empty()
+        })
+        .collect();
+
+    let mut section = format!("## {}\n\n", SECTION_TITLE);
+    if used.is_empty() {
+        section.push_str("No glossary terms are used in this document.\n");
+    }
+    for (term, meaning) in used {
+        section.push_str(&format!("- **{}**: {}\n", term, meaning));
+    }
+
+    let mut updated = before.trim_end().to_string();
+    if !updated.is_empty() {
+        updated.push_str("\n\n");
+    }
+    updated.push_str(&section);
+    if !after.trim().is_empty() {
+        updated.push('\n');
+        updated.push_str(after.trim_start_matches('\n'));
+    }
+    updated
+}
+
+/// The byte range of an existing glossary section: its heading up to the
+/// next heading of the same or a higher level.
+fn section_range(document: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    let mut start: Option<(usize, usize)> = None;
+    let mut in_fence = false;
+    for line in document.split_inclusive('\n') {
+        if export::is_fence(line) {
+            in_fence = !in_fence;
+        }
+        let level = export::heading_level(line).filter(|_| !in_fence);
+        if let Some(level) = level {
+            match start {
+                Some((begin, section_level)) if level <= section_level => {
+                    return Some((begin, offset));
+                }
+                None => {
+                    let title = line.trim().trim_start_matches('#').trim();
+                    if title.eq_ignore_ascii_case(SECTION_TITLE) {
+                        start = Some((offset, level));
+                    }
+                }
+                _ => {}
+            }
+        }
+        offset += line.len();
+    }
+    start.map(|(begin, _)| (begin, document.len()))
+}
@@ -0,0 +1,78 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// User-perceived characters in `text`, so `é` written as `e` plus a
+/// combining accent or a flag emoji count once.
+pub fn count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// Characters written without spaces between words, each counted as a word.
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'   // Hiragana, Katakana
+        | '\u{3400}'..='\u{4dbf}' // CJK Extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK Unified Ideographs
+        | '\u{f900}'..='\u{faff}' // CJK Compatibility Ideographs
+        | '\u{ff66}'..='\u{ff9f}' // Halfwidth Katakana
+        | '\u{20000}'..='\u{2fa1f}')
+}
+
+/// Byte offset of the `index`-th char, or the end of `text`.
+pub fn char_to_byte(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(byte, _)| byte)
+}
+
+/// Byte offset of the start of zero-based `line`, or the end of `text`.
+pub fn line_to_byte(text: &str, line: usize) -> usize {
+    if line == 0 {
+        return 0;
+    }
+    text.match_indices('\n')
+        .nth(line - 1)
+        .map_or(text.len(), |(newline, _)| newline + 1)
+}
+
+/// Moves a cursor at char `index` that ended up inside a grapheme cluster to
+/// the cluster's end (`forward`) or start.
+pub fn snap_to_boundary(text: &str, index: usize, forward: bool) -> usize {
+    let byte = char_to_byte(text, index);
+    // Clusters never span lines apart from `\r\n`, which is kept whole by
+    // including the newline in the searched line.
+    let line_start = text[..byte].rfind('\n').map_or(0, |newline| newline + 1);
+    let line_end = text[byte..]
+        .find('\n')
+        .map_or(text.len(), |newline| byte + newline + 1);
+    let line = &text[line_start..line_end];
+    let offset = byte - line_start;
+
+    for (cluster_start, cluster) in line.grapheme_indices(true) {
+        let cluster_end = cluster_start + cluster.len();
+        if offset <= cluster_start {
+            break;
+        }
+        if offset < cluster_end {
+            return if forward {
+                index + line[offset..cluster_end].chars().count()
+            } else {
+                index - line[cluster_start..offset].chars().count()
+            };
+        }
+    }
+    index
+}
+
+/// Zero-based column of char `offset` in `line`, in grapheme clusters.
+pub fn column(line: &str, offset: usize) -> usize {
+    count(&line[..char_to_byte(line, offset)])
+}
+
+/// Char offset of grapheme `column` in `line`; the inverse of [`column`].
+/// Columns past the end give the end of the line.
+pub fn column_to_char(line: &str, column: usize) -> usize {
+    line.graphemes(true)
+        .take(column)
+        .map(|grapheme| grapheme.chars().count())
+        .sum()
+}
@@ -0,0 +1,127 @@
+use crate::git;
+use similar::{DiffTag, TextDiff};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum HunkKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl HunkKind {
+    pub fn color(self) -> egui::Color32 {
+        match self {
+            Self::Added => egui::Color32::from_rgb(70, 170, 90),
+            Self::Modified => egui::Color32::from_rgb(100, 140, 220),
+            Self::Removed => egui::Color32::from_rgb(210, 80, 80),
+        }
+    }
+}
+
+/// A run of lines that differs from the committed version.
+pub struct Hunk {
+    pub kind: HunkKind,
+    /// Lines in the `HEAD` version.
+    pub old_lines: Range<usize>,
+    /// Lines in the buffer. Empty for removals.
+    pub new_lines: Range<usize>,
+}
+
+/// Line-level differences between the buffer and the file at `HEAD`.
+#[derive(Default)]
+pub struct GitGutter {
+    path: Option<PathBuf>,
+    head: Option<String>,
+    content_hash: u64,
+    pub hunks: Vec<Hunk>,
+}
+
+impl GitGutter {
+    /// Recomputes hunks when the file or text changed. Files outside a git
+    /// repository (or not yet committed) have no hunks.
+    pub fn update(&mut self, path: Option<&Path>, text: &str) {
+        if self.path.as_deref() != path {
+            self.path = path.map(Path::to_path_buf);
+            self.reload();
+        }
+
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        self.head.hash(&mut hasher);
+        let hash = hasher.finish();
+        if hash == self.content_hash {
+            return;
+        }
+        self.content_hash = hash;
+        self.hunks = match &self.head {
+            Some(head) => diff_hunks(head, text),
+            None => Vec::new(),
+        };
+    }
+
+    /// Re-reads the committed version, e.g. after saving or committing.
+    pub fn reload(&mut self) {
+        self.head = self
+            .path
+            .as_deref()
+            .and_then(|path| git::show(path, "HEAD").ok());
+        self.content_hash = 0;
+    }
+
+    /// The `HEAD` text of `hunk`.
+    pub fn head_text(&self, hunk: &Hunk) -> String {
+        self.head
+            .as_deref()
+            .map(|head| {
+                head.split_inclusive('\n')
+                    .skip(hunk.old_lines.start)
+                    .take(hunk.old_lines.len())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns `text` with `hunk` replaced by its `HEAD` version.
+    pub fn revert(&self, text: &str, hunk: &Hunk) -> String {
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let start = hunk.new_lines.start.min(lines.len());
+        let end = hunk.new_lines.end.min(lines.len());
+        let mut reverted = lines[..start].concat();
+        let mut old = self.head_text(hunk);
+        // Keep line breaks intact when the hunk sits at the end of a file
+        // without a trailing newline.
+        if end < lines.len() && !old.is_empty() && !old.ends_with('\n') {
+            old.push('\n');
+        }
+        if start > 0 && !reverted.ends_with('\n') && !old.is_empty() {
+            reverted.push('\n');
+        }
+        reverted.push_str(&old);
+        reverted.push_str(&lines[end..].concat());
+        reverted
+    }
+}
+
+fn diff_hunks(head: &str, text: &str) -> Vec<Hunk> {
+    TextDiff::from_lines(head, text)
+        .ops()
+        .iter()
+        .filter_map(|op| {
+            let kind = match op.tag() {
+                DiffTag::Equal => return None,
+                DiffTag::Insert => HunkKind::Added,
+                DiffTag::Delete => HunkKind::Removed,
+                DiffTag::Replace => HunkKind::Modified,
+            };
+            Some(Hunk {
+                kind,
+                old_lines: op.old_range(),
+                new_lines: op.new_range(),
+            })
+        })
+        .collect()
+}
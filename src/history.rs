@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+
+/// Keystrokes closer together than this, in seconds, undo as one step.
+const TYPING_PAUSE: f64 = 1.0;
+
+/// Keeps dropping the oldest steps while the history holds more text than this.
+const MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// One change to the document: the text at byte `at` that was `removed`
+/// and what was `inserted` in its place.
+struct Step {
+    at: usize,
+    removed: String,
+    inserted: String,
+}
+
+impl Step {
+    /// The change that turns `old` into `new`, keeping only the part that
+    /// differs.
+    fn between(old: &str, new: &str) -> Step {
+        let at = common_prefix(old, new);
+        let suffix = common_suffix(&old[at..], &new[at..]);
+        Step {
+            at,
+            removed: old[at..old.len() - suffix].to_string(),
+            inserted: new[at..new.len() - suffix].to_string(),
+        }
+    }
+
+    /// Turns the text after this step back into the text before it.
+    fn revert(&self, text: &mut String) {
+        text.replace_range(self.at..self.at + self.inserted.len(), &self.removed);
+    }
+
+    /// Turns the text before this step into the text after it.
+    fn apply(&self, text: &mut String) {
+        text.replace_range(self.at..self.at + self.removed.len(), &self.inserted);
+    }
+
+    fn bytes(&self) -> usize {
+        self.removed.len() + self.inserted.len()
+    }
+}
+
+/// The document's changes, for undo and redo. Unlike the editor's own
+/// undo, this sees every change to the text, including tools and commands
+/// that replace the whole buffer. Only the changed part of each step is
+/// kept, so long documents do not cost a copy per step.
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Step>,
+    redo: Vec<Step>,
+    /// The text as last seen.
+    current: Option<String>,
+    /// The latest step is typing that more keystrokes may join.
+    typing: bool,
+    last_change: f64,
+    /// When the text was last compared with `current`.
+    last_observed: f64,
+    /// Counts the changes seen, so results worked out from the text can be
+    /// kept until it changes.
+    generation: u64,
+}
+
+impl History {
+    /// Forgets every step, for a different document.
+    pub fn clear(&mut self) {
+        *self = History {
+            generation: self.generation + 1,
+            ..History::default()
+        };
+    }
+
+    /// Changes whenever the observed text does.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Records `text` as a new step if it changed since the last call,
+    /// keeping at most `depth` steps. Typing a character at a time joins
+    /// the latest step until a pause or a new line.
+    pub fn observe(&mut self, text: &str, now: f64, depth: usize) {
+        self.last_observed = now;
+        let Some(current) = &self.current else {
+            self.current = Some(text.to_string());
+            self.generation += 1;
+            return;
+        };
+        if current == text {
+            return;
+        }
+        self.generation += 1;
+        let typed = typed_char(current, text);
+        let joins = self.typing
+            && now - self.last_change < TYPING_PAUSE
+            && typed.is_some_and(|c| c != '\n');
+        let latest = match self.undo.pop_back() {
+            Some(step) if joins => {
+                let mut before = current.clone();
+                step.revert(&mut before);
+                Step::between(&before, text)
+            }
+            Some(step) => {
+                self.undo.push_back(step);
+                Step::between(current, text)
+            }
+            None => Step::between(current, text),
+        };
+        self.undo.push_back(latest);
+        let mut bytes: usize = self.undo.iter().map(Step::bytes).sum();
+        while self.undo.len() > depth.max(1) || (bytes > MAX_BYTES && self.undo.len() > 1) {
+            if let Some(oldest) = self.undo.pop_front() {
+                bytes -= oldest.bytes();
+            }
+        }
+        self.redo.clear();
+        self.typing = typed.is_some();
+        self.last_change = now;
+        self.current = Some(text.to_string());
+    }
+
+    /// Seconds since the text was last observed.
+    pub fn since_observed(&self, now: f64) -> f64 {
+        now - self.last_observed
+    }
+
+    /// The text before the latest step, with `text` kept for redo.
+    pub fn undo(&mut self, text: &str) -> Option<String> {
+        let step = self.undo.pop_back()?;
+        let mut previous = text.to_string();
+        step.revert(&mut previous);
+        self.redo.push(step);
+        self.current = Some(previous.clone());
+        self.generation += 1;
+        self.typing = false;
+        Some(previous)
+    }
+
+    /// The text the latest undo went back from.
+    pub fn redo(&mut self, text: &str) -> Option<String> {
+        let step = self.redo.pop()?;
+        let mut next = text.to_string();
+        step.apply(&mut next);
+        self.undo.push_back(step);
+        self.current = Some(next.clone());
+        self.generation += 1;
+        self.typing = false;
+        Some(next)
+    }
+}
+
+/// The character that `new` adds to or removes from `old`, when they
+/// differ by exactly one.
+fn typed_char(old: &str, new: &str) -> Option<char> {
+    let (longer, shorter) = if new.len() > old.len() {
+        (new, old)
+    } else {
+        (old, new)
+    };
+    let prefix = common_prefix(longer, shorter);
+    let c = longer[prefix..].chars().next()?;
+    (longer[prefix + c.len_utf8()..] == shorter[prefix..]).then_some(c)
+}
+
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((index, _), _)| index)
+}
+
+/// The char index just past the part of `new` that differs from `old`,
+/// where the cursor goes after undo or redo.
+pub fn changed_end(old: &str, new: &str) -> usize {
+    let prefix = common_prefix(old, new);
+    let suffix = common_suffix(&old[prefix..], &new[prefix..]);
+    new[..new.len() - suffix].chars().count()
+}
+
+/// The length in bytes of the longest run of whole chars that `a` and `b`
+/// both end with.
+fn common_suffix(a: &str, b: &str) -> usize {
+    a.chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum()
+}
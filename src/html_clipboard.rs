@@ -0,0 +1,60 @@
+use crate::{assets, bundle, export, preview_server};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Images larger than this stay linked rather than embedded.
+const MAX_EMBEDDED_IMAGE: u64 = 10 * 1024 * 1024;
+
+fn image_regex() -> &'static Regex {
+    static IMAGE: OnceLock<Regex> = OnceLock::new();
+    IMAGE.get_or_init(|| Regex::new(r#"(?i)(<img\b[^>]*?\bsrc\s*=\s*)"([^"]*)""#).unwrap())
+}
+
+/// `markdown`, a document in `dir`, as an HTML fragment to paste into
+/// another program. Local images are embedded as data URLs, since the
+/// program pasted into cannot resolve paths relative to the document.
+pub fn to_html(markdown: &str, dir: &Path) -> String {
+    let html = export::markdown_to_html_body(markdown);
+    image_regex()
+        .replace_all(&html, |caps: &regex::Captures| {
+            match embed(&caps[2].replace("&amp;", "&"), dir) {
+                Some(data) => format!("{}\"{}\"", &caps[1], data),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// The local image `src` points at, relative to `dir`, as a data URL.
+fn embed(src: &str, dir: &Path) -> Option<String> {
+    if export::is_external(src) || src.starts_with("data:") {
+        return None;
+    }
+    let path_part = &src[..src.find(['?', '#']).unwrap_or(src.len())];
+    let path = dir.join(bundle::percent_decode(path_part));
+    let size = fs::metadata(&path).ok()?.len();
+    if !assets::is_image(&path) || size > MAX_EMBEDDED_IMAGE {
+        return None;
+    }
+    let data = fs::read(&path).ok()?;
+    Some(format!(
+        "data:{};base64,{}",
+        preview_server::content_type(&path),
+        STANDARD.encode(data)
+    ))
+}
+
+/// Puts `html` on the clipboard, with `text` for programs that only take
+/// plain text. The clipboard is returned because on Linux its contents
+/// are served by the program that set them, for as long as it is kept.
+pub fn copy(html: &str, text: &str) -> Result<arboard::Clipboard, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|err| err.to_string())?;
+    clipboard
+        .set_html(html, Some(text))
+        .map_err(|err| err.to_string())?;
+    Ok(clipboard)
+}
@@ -0,0 +1,394 @@
+use regex::Regex;
+use scraper::{ElementRef, Html, Node};
+use std::sync::OnceLock;
+use url::Url;
+
+/// Converts an HTML page or fragment to markdown: headings, paragraphs,
+/// emphasis, links, images, lists, quotes, code, and simple tables.
+/// Scripts, styles, forms and navigation are dropped, and relative links
+/// are resolved against `base` when given.
+pub fn convert(html: &str, base: Option<&Url>) -> String {
+    let start = html
+        .trim_start()
+        .get(..9)
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let document = if start.starts_with("<!doctype") || start.starts_with("<html") {
+        Html::parse_document(html)
+    } else {
+        Html::parse_fragment(html)
+    };
+    let converter = Converter { base };
+    let mut blocks = Vec::new();
+    converter.blocks(document.root_element(), &mut blocks);
+    let mut markdown = blocks.join("\n\n");
+    markdown.push('\n');
+    markdown
+}
+
+/// Whether `html` has structure worth keeping as markdown: headings, lists,
+/// links, tables, emphasis, quotes, images or code. HTML that is only
+/// styled `<div>`s and `<span>`s, as code editors copy, reads better as
+/// its plain text.
+pub fn has_markup(html: &str) -> bool {
+    static MARKUP: OnceLock<Regex> = OnceLock::new();
+    MARKUP
+        .get_or_init(|| {
+            Regex::new(
+                r"(?i)<(h[1-6]|ul|ol|table|blockquote|pre|code|strong|em|b|i|img|a\s[^>]*\bhref)\b",
+            )
+            .unwrap()
+        })
+        .is_match(html)
+}
+
+struct Converter<'a> {
+    base: Option<&'a Url>,
+}
+
+/// Elements whose content is not part of the text.
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "head", "title", "meta", "link", "nav", "form",
+    "button", "svg", "iframe", "select", "input", "textarea",
+];
+
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "h1" | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "p"
+            | "pre"
+            | "blockquote"
+            | "ul"
+            | "ol"
+            | "hr"
+            | "table"
+            | "div"
+            | "section"
+            | "article"
+            | "main"
+            | "header"
+            | "footer"
+            | "aside"
+            | "figure"
+            | "figcaption"
+            | "body"
+            | "html"
+            | "dl"
+            | "dt"
+            | "dd"
+            | "details"
+            | "summary"
+            | "li"
+    )
+}
+
+impl Converter<'_> {
+    /// Appends the markdown blocks for the children of `element`.
+    fn blocks(&self, element: ElementRef, out: &mut Vec<String>) {
+        let mut paragraph = String::new();
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => paragraph.push_str(&collapse(text)),
+                Node::Element(_) => {
+                    let Some(child) = ElementRef::wrap(child) else {
+                        continue;
+                    };
+                    let name = child.value().name();
+                    if SKIPPED.contains(&name) {
+                        continue;
+                    }
+                    if is_block(name) {
+                        flush(&mut paragraph, out);
+                        self.block(child, out);
+                    } else {
+                        paragraph.push_str(&self.inline(child));
+                    }
+                }
+                _ => {}
+            }
+        }
+        flush(&mut paragraph, out);
+    }
+
+    fn block(&self, element: ElementRef, out: &mut Vec<String>) {
+        let name = element.value().name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse().unwrap_or(1);
+                let text = self.inline_children(element);
+                let text = text.trim();
+                if !text.is_empty() {
+                    out.push(format!("{} {}", "#".repeat(level), text.replace('\n', " ")));
+                }
+            }
+            "p" | "dt" | "summary" | "figcaption" => {
+                let text = self.inline_children(element);
+                if !text.trim().is_empty() {
+                    out.push(trim_lines(&text));
+                }
+            }
+            "pre" => {
+                let code: String = element.text().collect();
+                let language = element
+                    .children()
+                    .filter_map(ElementRef::wrap)
+                    .chain(std::iter::once(element))
+                    .flat_map(|element| element.value().classes())
+                    .find_map(|class| {
+                        class
+                            .strip_prefix("language-")
+                            .or_else(|| class.strip_prefix("lang-"))
+                    })
+                    .unwrap_or_default();
+                let fence = if code.contains("```") { "~~~" } else { "```" };
+                out.push(format!(
+                    "{}{}\n{}\n{}",
+                    fence,
+                    language,
+                    code.trim_end_matches('\n'),
+                    fence
+                ));
+            }
+            "blockquote" => {
+                let mut inner = Vec::new();
+                self.blocks(element, &mut inner);
+                if !inner.is_empty() {
+                    out.push(prefix_lines(&inner.join("\n\n"), "> ", "> "));
+                }
+            }
+            "ul" | "ol" => {
+                let list = self.list(element, name == "ol");
+                if !list.is_empty() {
+                    out.push(list);
+                }
+            }
+            "hr" => out.push("---".to_string()),
+            "table" => {
+                let table = self.table(element);
+                if !table.is_empty() {
+                    out.push(table);
+                }
+            }
+            _ => self.blocks(element, out),
+        }
+    }
+
+    fn list(&self, element: ElementRef, ordered: bool) -> String {
+        let start: usize = element
+            .value()
+            .attr("start")
+            .and_then(|start| start.parse().ok())
+            .unwrap_or(1);
+        let mut items = Vec::new();
+        let children = element
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|child| child.value().name() == "li");
+        for (index, item) in children.enumerate() {
+            let marker = if ordered {
+                format!("{}. ", start + index)
+            } else {
+                "- ".to_string()
+            };
+            let mut inner = Vec::new();
+            self.blocks(item, &mut inner);
+            // Nested lists follow their item's text directly, keeping the
+            // list tight.
+            let mut text = String::new();
+            for (index, block) in inner.iter().enumerate() {
+                if index > 0 {
+                    let nested = block.starts_with("- ")
+                        || block
+                            .split_once(". ")
+                            .is_some_and(|(number, _)| number.chars().all(|c| c.is_ascii_digit()));
+                    text.push_str(if nested { "\n" } else { "\n\n" });
+                }
+                text.push_str(block);
+            }
+            let indent = " ".repeat(marker.len());
+            items.push(prefix_lines(&text, &marker, &indent));
+        }
+        items.join("\n")
+    }
+
+    fn table(&self, element: ElementRef) -> String {
+        let rows: Vec<Vec<String>> = element
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .filter(|row| row.value().name() == "tr")
+            .map(|row| {
+                row.children()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                    .map(|cell| {
+                        self.inline_children(cell)
+                            .replace('\n', " ")
+                            .replace('|', "\\|")
+                            .trim()
+                            .to_string()
+                    })
+                    .collect()
+            })
+            .filter(|cells: &Vec<String>| !cells.is_empty())
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return String::new();
+        }
+        let line = |cells: &[String]| {
+            let mut cells = cells.to_vec();
+            cells.resize(columns, String::new());
+            format!("| {} |", cells.join(" | "))
+        };
+        let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+        lines.extend(rows[1..].iter().map(|row| line(row)));
+        lines.join("\n")
+    }
+
+    fn inline_children(&self, element: ElementRef) -> String {
+        let mut text = String::new();
+        for child in element.children() {
+            match child.value() {
+                Node::Text(value) => text.push_str(&collapse(value)),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        if !SKIPPED.contains(&child.value().name()) {
+                            text.push_str(&self.inline(child));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        text
+    }
+
+    fn inline(&self, element: ElementRef) -> String {
+        let value = element.value();
+        match value.name() {
+            "br" => "\\\n".to_string(),
+            "strong" | "b" => wrap(&self.inline_children(element), "**"),
+            "em" | "i" => wrap(&self.inline_children(element), "*"),
+            "del" | "s" | "strike" => wrap(&self.inline_children(element), "~~"),
+            "code" | "kbd" | "samp" => {
+                let code: String = element.text().collect();
+                let code = code.replace('\n', " ");
+                if code.trim().is_empty() {
+                    return code;
+                }
+                let ticks = if code.contains('`') { "``" } else { "`" };
+                format!("{}{}{}", ticks, code, ticks)
+            }
+            "a" => {
+                let text = self.inline_children(element);
+                match value.attr("href").map(|href| self.resolve(href)) {
+                    Some(href) if !href.starts_with("javascript:") && !text.trim().is_empty() => {
+                        let (lead, inner, trail) = split_spaces(&text);
+                        format!("{}[{}]({}){}", lead, inner, href.replace(' ', "%20"), trail)
+                    }
+                    _ => text,
+                }
+            }
+            "img" => match value.attr("src") {
+                Some(src) => format!(
+                    "![{}]({})",
+                    value
+                        .attr("alt")
+                        .unwrap_or_default()
+                        .replace(['[', ']'], ""),
+                    self.resolve(src).replace(' ', "%20")
+                ),
+                None => String::new(),
+            },
+            _ => self.inline_children(element),
+        }
+    }
+
+    fn resolve(&self, link: &str) -> String {
+        let link = link.trim();
+        match self.base.and_then(|base| base.join(link).ok()) {
+            Some(url) => url.to_string(),
+            None => link.to_string(),
+        }
+    }
+}
+
+/// Text with runs of whitespace turned into single spaces and characters
+/// that would read as markdown escaped.
+fn collapse(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            collapsed.push(' ');
+            space = false;
+        }
+        if matches!(c, '*' | '`' | '[' | ']' | '\\') {
+            collapsed.push('\\');
+        }
+        collapsed.push(c);
+    }
+    if space {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+fn flush(paragraph: &mut String, out: &mut Vec<String>) {
+    if !paragraph.trim().is_empty() {
+        out.push(trim_lines(paragraph));
+    }
+    paragraph.clear();
+}
+
+/// Trims each line, and the text as a whole.
+fn trim_lines(text: &str) -> String {
+    let lines: Vec<&str> = text.trim().lines().map(str::trim).collect();
+    lines.join("\n")
+}
+
+/// `text` between `marker`s, with surrounding spaces kept outside.
+fn wrap(text: &str, marker: &str) -> String {
+    let (lead, inner, trail) = split_spaces(text);
+    if inner.is_empty() {
+        return text.to_string();
+    }
+    format!("{}{}{}{}{}", lead, marker, inner, marker, trail)
+}
+
+fn split_spaces(text: &str) -> (&str, &str, &str) {
+    let inner = text.trim();
+    let lead = &text[..text.len() - text.trim_start().len()];
+    let trail = &text[text.trim_end().len()..];
+    (
+        if lead.is_empty() { "" } else { " " },
+        inner,
+        if trail.is_empty() { "" } else { " " },
+    )
+}
+
+/// Prefixes the first line of `text` with `first` and the others with
+/// `rest`, without trailing spaces on blank lines.
+fn prefix_lines(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| {
+            let prefix = if index == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A Hunspell dictionary (a `.dic` word list and the `.aff` file next to
+/// it), expanded up front into every word form its prefix and suffix
+/// rules produce. Compound words and continuation classes are not
+/// supported.
+pub struct Dictionary {
+    words: HashSet<String>,
+    /// Letters tried when looking for corrections, most common first.
+    pub try_chars: Vec<char>,
+}
+
+#[derive(Clone, Copy)]
+enum FlagMode {
+    /// One character per flag, the default.
+    Char,
+    /// Two characters per flag (`FLAG long`).
+    Long,
+    /// Comma separated numbers (`FLAG num`).
+    Numeric,
+}
+
+enum CharClass {
+    Any,
+    Set { chars: Vec<char>, negated: bool },
+}
+
+impl CharClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharClass::Any => true,
+            CharClass::Set { chars, negated } => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+struct Rule {
+    strip: String,
+    add: String,
+    condition: Vec<CharClass>,
+}
+
+struct Affix {
+    prefix: bool,
+    /// Combines with affixes of the other kind.
+    cross: bool,
+    rules: Vec<Rule>,
+}
+
+impl Rule {
+    fn apply(&self, word: &str, prefix: bool) -> Option<String> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() < self.condition.len() {
+            return None;
+        }
+        if prefix {
+            let rest = word.strip_prefix(self.strip.as_str())?;
+            let matches = self
+                .condition
+                .iter()
+                .zip(&chars)
+                .all(|(class, &c)| class.matches(c));
+            (matches && !rest.is_empty()).then(|| format!("{}{}", self.add, rest))
+        } else {
+            let rest = word.strip_suffix(self.strip.as_str())?;
+            let tail = &chars[chars.len() - self.condition.len()..];
+            let matches = self
+                .condition
+                .iter()
+                .zip(tail)
+                .all(|(class, &c)| class.matches(c));
+            (matches && !rest.is_empty()).then(|| format!("{}{}", rest, self.add))
+        }
+    }
+}
+
+impl Dictionary {
+    /// Loads `dic` and the `.aff` file of the same name.
+    pub fn load(dic: &Path) -> Result<Self, String> {
+        let aff = dic.with_extension("aff");
+        let aff = fs::read(&aff).map_err(|err| format!("{}: {}", aff.display(), err))?;
+        let dic_bytes = fs::read(dic).map_err(|err| format!("{}: {}", dic.display(), err))?;
+        let utf8 = String::from_utf8_lossy(&aff)
+            .lines()
+            .find_map(|line| line.strip_prefix("SET "))
+            .is_none_or(|set| set.trim().eq_ignore_ascii_case("UTF-8"));
+        Ok(Self::parse(&decode(&aff, utf8), &decode(&dic_bytes, utf8)))
+    }
+
+    fn parse(aff: &str, dic: &str) -> Self {
+        let mut mode = FlagMode::Char;
+        let mut try_chars = Vec::new();
+        let mut affixes: HashMap<String, Affix> = HashMap::new();
+        let mut need_affix = None;
+        let mut skip = Vec::new();
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => mode = FlagMode::Long,
+                ["FLAG", "num", ..] => mode = FlagMode::Numeric,
+                ["TRY", chars, ..] => try_chars = chars.chars().collect(),
+                ["NEEDAFFIX" | "PSEUDOROOT", flag, ..] => need_affix = Some(flag.to_string()),
+                ["FORBIDDENWORD" | "ONLYINCOMPOUND", flag, ..] => skip.push(flag.to_string()),
+                [kind @ ("PFX" | "SFX"), flag, cross, count]
+                    if !affixes.contains_key(*flag) && count.parse::<usize>().is_ok() =>
+                {
+                    affixes.insert(
+                        flag.to_string(),
+                        Affix {
+                            prefix: *kind == "PFX",
+                            cross: *cross == "Y",
+                            rules: Vec::new(),
+                        },
+                    );
+                }
+                ["PFX" | "SFX", flag, strip, add, rest @ ..] => {
+                    let Some(affix) = affixes.get_mut(*flag) else {
+                        continue;
+                    };
+                    // Continuation flags after `/` are not supported.
+                    let add = add.split('/').next().unwrap_or_default();
+                    let empty = |text: &str| {
+                        if text == "0" {
+                            String::new()
+                        } else {
+                            text.to_string()
+                        }
+                    };
+                    affix.rules.push(Rule {
+                        strip: empty(strip),
+                        add: empty(add),
+                        condition: parse_condition(rest.first().copied().unwrap_or(".")),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let mut words = HashSet::new();
+        // The first line holds the approximate word count.
+        for line in dic.lines().skip(1) {
+            let Some(entry) = line.split_whitespace().next() else {
+                continue;
+            };
+            let (word, flags) = match entry.split_once('/') {
+                Some((word, flags)) => (word, parse_flags(flags, mode)),
+                None => (entry, Vec::new()),
+            };
+            if flags.iter().any(|flag| skip.contains(flag)) {
+                continue;
+            }
+            if need_affix.as_ref().is_none_or(|flag| !flags.contains(flag)) {
+                words.insert(word.to_string());
+            }
+
+            let rules = |prefix: bool| {
+                flags
+                    .iter()
+                    .filter_map(|flag| affixes.get(flag))
+                    .filter(move |affix| affix.prefix == prefix)
+            };
+            let mut cross_forms = Vec::new();
+            for affix in rules(false) {
+                for form in affix
+                    .rules
+                    .iter()
+                    .filter_map(|rule| rule.apply(word, false))
+                {
+                    if affix.cross {
+                        cross_forms.push(form.clone());
+                    }
+                    words.insert(form);
+                }
+            }
+            for affix in rules(true) {
+                for rule in &affix.rules {
+                    words.extend(rule.apply(word, true));
+                    if affix.cross {
+                        words.extend(cross_forms.iter().filter_map(|form| rule.apply(form, true)));
+                    }
+                }
+            }
+        }
+
+        if try_chars.is_empty() {
+            try_chars = "esianrtolcdugmphbyfvkwzESIANRTOLCDUGMPHBYFVKWZ'"
+                .chars()
+                .collect();
+        }
+        Self { words, try_chars }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word)
+    }
+}
+
+/// Hunspell files are UTF-8 or, in older dictionaries, a Latin charset.
+fn decode(bytes: &[u8], utf8: bool) -> String {
+    if utf8 {
+        String::from_utf8_lossy(bytes).into_owned()
+    } else {
+        bytes.iter().map(|&byte| byte as char).collect()
+    }
+}
+
+fn parse_flags(flags: &str, mode: FlagMode) -> Vec<String> {
+    match mode {
+        FlagMode::Char => flags.chars().map(String::from).collect(),
+        FlagMode::Long => {
+            let chars: Vec<char> = flags.chars().collect();
+            chars.chunks(2).map(|pair| pair.iter().collect()).collect()
+        }
+        FlagMode::Numeric => flags
+            .split(',')
+            .map(|flag| flag.trim().to_string())
+            .collect(),
+    }
+}
+
+/// A condition such as `[^aeiou]y` or `.`, one class per char.
+fn parse_condition(condition: &str) -> Vec<CharClass> {
+    let mut classes = Vec::new();
+    let mut chars = condition.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => classes.push(CharClass::Any),
+            '[' => {
+                let mut set: Vec<char> = chars.by_ref().take_while(|&c| c != ']').collect();
+                let negated = set.first() == Some(&'^');
+                if negated {
+                    set.remove(0);
+                }
+                classes.push(CharClass::Set {
+                    chars: set,
+                    negated,
+                });
+            }
+            c => classes.push(CharClass::Set {
+                chars: vec![c],
+                negated: false,
+            }),
+        }
+    }
+    // A lone `.` means no condition.
+    if matches!(classes.as_slice(), [CharClass::Any]) {
+        classes.clear();
+    }
+    classes
+}
+
+fn dictionary_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/hunspell"),
+        PathBuf::from("/usr/share/myspell"),
+        PathBuf::from("/usr/share/myspell/dicts"),
+        PathBuf::from("/usr/local/share/hunspell"),
+        PathBuf::from("/Library/Spelling"),
+    ];
+    if let Some(home) = env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        dirs.push(home.join(".local/share/hunspell"));
+        dirs.push(home.join("Library/Spelling"));
+    }
+    dirs
+}
+
+/// The installed `.dic` file for a language tag: `de_CH.dic` for `de-CH`,
+/// else `de.dic` or `de_DE.dic` (`en_US.dic` for English), else any
+/// `de_*.dic`.
+pub fn find(tag: &str) -> Option<PathBuf> {
+    let tag = tag.replace('-', "_");
+    let primary = tag
+        .split('_')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let region = if primary == "en" {
+        "US".to_string()
+    } else {
+        primary.to_ascii_uppercase()
+    };
+    let preferred = [
+        format!("{}.dic", tag),
+        format!("{}.dic", primary),
+        format!("{}_{}.dic", primary, region),
+    ];
+    let dirs: Vec<PathBuf> = dictionary_dirs()
+        .into_iter()
+        .filter(|dir| dir.is_dir())
+        .collect();
+    for name in &preferred {
+        if let Some(path) = dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+        {
+            return Some(path);
+        }
+    }
+    let prefix = format!("{}_", primary);
+    let mut others: Vec<PathBuf> = dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|extension| extension == "dic")
+                && path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        .collect();
+    others.sort();
+    others.into_iter().next()
+}
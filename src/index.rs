@@ -0,0 +1,475 @@
+use crate::patterns::IgnorePatterns;
+use crate::{export, front_matter, outline, status, wikilinks};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often the stop flag is checked between scans.
+const STOP_CHECK: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Heading {
+    /// Zero-based source line.
+    pub line: usize,
+    pub level: usize,
+    pub title: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Link {
+    pub line: usize,
+    /// The destination as written, or the `Target#Heading` of a wikilink.
+    pub target: String,
+    pub wiki: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tag {
+    pub line: usize,
+    /// Without the `#`.
+    pub name: String,
+}
+
+/// What the index knows about one markdown file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    modified: SystemTime,
+    pub headings: Vec<Heading>,
+    pub links: Vec<Link>,
+    pub tags: Vec<Tag>,
+    /// The front matter `status`, empty without one. Not optional in the
+    /// cache, so caches written before it was indexed are rebuilt.
+    pub status: String,
+}
+
+impl FileEntry {
+    pub fn parse(text: &str, modified: SystemTime) -> Self {
+        let headings = outline::headings(text)
+            .into_iter()
+            .map(|heading| Heading {
+                line: heading.line,
+                level: heading.level,
+                title: heading.title,
+            })
+            .collect();
+        let (links, tags) = links_and_tags(text);
+        Self {
+            modified,
+            headings,
+            links,
+            tags,
+            status: status::get(text).unwrap_or_default(),
+        }
+    }
+}
+
+/// A link to a file from another file.
+pub struct Backlink {
+    pub source: PathBuf,
+    pub line: usize,
+}
+
+#[derive(Default)]
+struct State {
+    files: BTreeMap<PathBuf, FileEntry>,
+    /// Set once the first scan has finished.
+    ready: bool,
+    /// Why the cache could not be written, until picked up.
+    cache_error: Option<String>,
+}
+
+/// The headings, links and tags of every markdown file under a folder,
+/// kept current by a background thread that rescans changed files, and
+/// stored in a cache file between sessions so only files changed since
+/// are parsed again on startup. Dropping the index stops the thread.
+pub struct WorkspaceIndex {
+    root: PathBuf,
+    /// Files and folders left out of the index.
+    ignore: IgnorePatterns,
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl WorkspaceIndex {
+    pub fn start(
+        root: &Path,
+        ignore: IgnorePatterns,
+        cache: Option<PathBuf>,
+        interval: Duration,
+        ctx: egui::Context,
+    ) -> Self {
+        let root = root.to_path_buf();
+        let state = Arc::new(Mutex::new(State::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let root = root.clone();
+            let ignore = ignore.clone();
+            let state = Arc::clone(&state);
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                if let (Some(cache), Ok(mut state)) = (&cache, state.lock()) {
+                    state.files = load(cache, &root);
+                }
+                // Only the first failure to write the cache is reported.
+                let mut cache_failed = false;
+                while !stop.load(Ordering::Relaxed) {
+                    if let Some(files) = scan(&root, &ignore, &state) {
+                        if let Some(cache) = &cache {
+                            if let Err(err) = save(cache, &root, &files) {
+                                if !cache_failed {
+                                    cache_failed = true;
+                                    if let Ok(mut state) = state.lock() {
+                                        state.cache_error = Some(err);
+                                    }
+                                }
+                            }
+                        }
+                        ctx.request_repaint();
+                    }
+                    let mut waited = Duration::ZERO;
+                    while waited < interval && !stop.load(Ordering::Relaxed) {
+                        thread::sleep(STOP_CHECK);
+                        waited += STOP_CHECK;
+                    }
+                }
+            });
+        }
+        Self {
+            root,
+            ignore,
+            state,
+            stop,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn ignore(&self) -> &IgnorePatterns {
+        &self.ignore
+    }
+
+    /// Whether the first scan has finished.
+    pub fn is_ready(&self) -> bool {
+        self.with(|state| state.ready)
+    }
+
+    /// Re-indexes `path` from `text` right away, e.g. after a save.
+    pub fn update(&self, path: &Path, text: &str) {
+        if !export::is_markdown(path)
+            || !path.starts_with(&self.root)
+            || self.ignore.covers(path, &self.root)
+        {
+            return;
+        }
+        let modified = fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+        let entry = FileEntry::parse(text, modified);
+        if let Ok(mut state) = self.state.lock() {
+            state.files.insert(path.to_path_buf(), entry);
+        }
+    }
+
+    /// Why the cache file could not be written, once.
+    pub fn take_cache_error(&self) -> Option<String> {
+        self.state.lock().ok()?.cache_error.take()
+    }
+
+    /// Every indexed file, sorted.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.with(|state| state.files.keys().cloned().collect())
+    }
+
+    /// The links from other files that lead to `path`, by file and line.
+    pub fn backlinks(&self, path: &Path) -> Vec<Backlink> {
+        self.with(|state| {
+            let files: Vec<PathBuf> = state.files.keys().cloned().collect();
+            let mut backlinks = Vec::new();
+            for (source, entry) in &state.files {
+                if source == path {
+                    continue;
+                }
+                for link in &entry.links {
+                    if resolve(&self.root, &files, source, link).as_deref() == Some(path) {
+                        backlinks.push(Backlink {
+                            source: source.clone(),
+                            line: link.line,
+                        });
+                    }
+                }
+            }
+            backlinks
+        })
+    }
+
+    /// Every tag with the files and lines it appears on.
+    pub fn tags(&self) -> BTreeMap<String, Vec<(PathBuf, usize)>> {
+        self.with(|state| {
+            let mut tags: BTreeMap<String, Vec<(PathBuf, usize)>> = BTreeMap::new();
+            for (path, entry) in &state.files {
+                for tag in &entry.tags {
+                    tags.entry(tag.name.clone())
+                        .or_default()
+                        .push((path.clone(), tag.line));
+                }
+            }
+            tags
+        })
+    }
+
+    /// The front matter `status` of `path`, if it has one.
+    pub fn status(&self, path: &Path) -> Option<String> {
+        self.with(|state| {
+            state
+                .files
+                .get(path)
+                .filter(|entry| !entry.status.is_empty())
+                .map(|entry| entry.status.clone())
+        })
+    }
+
+    /// Up to `limit` wikilink targets for notes whose name contains
+    /// `partial`, names starting with it first. A note is named by its
+    /// file stem, or by its path below the root when another note shares
+    /// the stem.
+    pub fn complete(&self, partial: &str, limit: usize) -> Vec<String> {
+        let partial = partial.trim().to_lowercase();
+        let files = self.files();
+        let mut stems: HashMap<String, usize> = HashMap::new();
+        for path in &files {
+            *stems.entry(stem(path).to_lowercase()).or_default() += 1;
+        }
+        let mut names: Vec<String> = files
+            .iter()
+            .map(|path| {
+                let stem = stem(path);
+                if stems.get(&stem.to_lowercase()).copied().unwrap_or(0) > 1 {
+                    let relative = path.strip_prefix(&self.root).unwrap_or(path);
+                    relative
+                        .with_extension("")
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                } else {
+                    stem
+                }
+            })
+            .filter(|name| name.to_lowercase().contains(&partial))
+            .collect();
+        names.sort_by_key(|name| (!name.to_lowercase().starts_with(&partial), name.clone()));
+        names.dedup();
+        names.truncate(limit);
+        names
+    }
+
+    fn with<T>(&self, read: impl FnOnce(&State) -> T) -> T {
+        match self.state.lock() {
+            Ok(state) => read(&state),
+            Err(poisoned) => read(&poisoned.into_inner()),
+        }
+    }
+}
+
+impl Drop for WorkspaceIndex {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// The file a link in `source` leads to, if it is a local markdown file.
+pub fn resolve(root: &Path, files: &[PathBuf], source: &Path, link: &Link) -> Option<PathBuf> {
+    if link.wiki {
+        let target = link.target.split('#').next().unwrap_or_default().trim();
+        if target.is_empty() {
+            return Some(source.to_path_buf());
+        }
+        return wikilinks::resolve(root, files, target);
+    }
+    if export::is_external(&link.target) {
+        return None;
+    }
+    let target = link.target.split('#').next().unwrap_or_default();
+    let target = target.replace("%20", " ");
+    let path = export::normalize(&source.parent()?.join(target));
+    export::is_markdown(&path).then_some(path)
+}
+
+/// Reparses the files under `root` whose modification time differs from
+/// the indexed one and drops deleted and ignored ones. Returns the files to cache after
+/// the first scan and whenever anything changed.
+fn scan(
+    root: &Path,
+    ignore: &IgnorePatterns,
+    state: &Mutex<State>,
+) -> Option<BTreeMap<PathBuf, FileEntry>> {
+    let mut known: HashMap<PathBuf, SystemTime> = state
+        .lock()
+        .ok()?
+        .files
+        .iter()
+        .map(|(path, entry)| (path.clone(), entry.modified))
+        .collect();
+    let mut updated = Vec::new();
+    for path in export::markdown_files_except(root, ignore) {
+        let Ok(modified) = fs::metadata(&path).and_then(|meta| meta.modified()) else {
+            continue;
+        };
+        if known.remove(&path) == Some(modified) {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        updated.push((path, FileEntry::parse(&text, modified)));
+    }
+
+    let mut state = state.lock().ok()?;
+    let first = !state.ready;
+    state.ready = true;
+    if updated.is_empty() && known.is_empty() && !first {
+        return None;
+    }
+    // What is left in `known` was deleted.
+    for path in known.keys() {
+        state.files.remove(path);
+    }
+    state.files.extend(updated);
+    Some(state.files.clone())
+}
+
+/// The cached entries for `root`, keyed by absolute path.
+fn load(cache: &Path, root: &Path) -> BTreeMap<PathBuf, FileEntry> {
+    let Ok(text) = fs::read_to_string(cache) else {
+        return BTreeMap::new();
+    };
+    let relative: BTreeMap<PathBuf, FileEntry> = serde_json::from_str(&text).unwrap_or_default();
+    relative
+        .into_iter()
+        .map(|(path, entry)| (root.join(path), entry))
+        .collect()
+}
+
+fn save(cache: &Path, root: &Path, files: &BTreeMap<PathBuf, FileEntry>) -> Result<(), String> {
+    let relative: BTreeMap<&Path, &FileEntry> = files
+        .iter()
+        .filter_map(|(path, entry)| Some((path.strip_prefix(root).ok()?, entry)))
+        .collect();
+    let Ok(json) = serde_json::to_string(&relative) else {
+        return Ok(());
+    };
+    if let Some(dir) = cache.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    fs::write(cache, json).map_err(|err| format!("Could not write {}: {}", cache.display(), err))
+}
+
+/// The markdown links, wikilinks and `#tags` of `text`, outside code, and
+/// the tags listed under `tags` (or Obsidian's `tag` and Zettlr's
+/// `keywords`) in its front matter.
+fn links_and_tags(text: &str) -> (Vec<Link>, Vec<Tag>) {
+    let mut links = Vec::new();
+    let mut tags = Vec::new();
+    for entry in front_matter::entries(text) {
+        let key = entry.key.to_ascii_lowercase();
+        if !matches!(key.as_str(), "tags" | "tag" | "keywords") {
+            continue;
+        }
+        let value = entry.value.trim();
+        let items = entry.list().unwrap_or_else(|| {
+            value
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(',')
+                .map(str::to_string)
+                .collect()
+        });
+        let line = text[..entry.range.start].matches('\n').count();
+        for item in items {
+            let name = item
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .trim_start_matches('#');
+            if !name.is_empty() {
+                tags.push(Tag {
+                    line,
+                    name: name.to_string(),
+                });
+            }
+        }
+    }
+
+    let body_start = front_matter::body_start(text);
+    let first_line = text[..body_start].matches('\n').count();
+    for (index, part, code) in export::code_runs(&text[body_start..]) {
+        let line_number = first_line + index;
+        if code {
+            continue;
+        }
+        for captures in export::link_regex().captures_iter(part) {
+            if captures[1].starts_with('!') {
+                continue;
+            }
+            links.push(Link {
+                line: line_number,
+                target: captures[2].to_string(),
+                wiki: false,
+            });
+        }
+        let mut rest = part;
+        while let Some(start) = rest.find("[[") {
+            let Some(end) = rest[start + 2..].find("]]") else {
+                break;
+            };
+            let inner = &rest[start + 2..start + 2 + end];
+            if let Some(link) = wikilinks::WikiLink::parse(inner) {
+                let target = match link.heading {
+                    Some(heading) => format!("{}#{}", link.target, heading),
+                    None => link.target.to_string(),
+                };
+                links.push(Link {
+                    line: line_number,
+                    target,
+                    wiki: true,
+                });
+            }
+            rest = &rest[start + end + 4..];
+        }
+        tags.extend(inline_tags(part).map(|name| Tag {
+            line: line_number,
+            name,
+        }));
+    }
+    (links, tags)
+}
+
+/// `#tag`s at the start of `text` or after whitespace. Tags are letters,
+/// digits, `-`, `_` and `/`, with at least one letter, so `#1` and
+/// headings are not tags.
+fn inline_tags(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.match_indices('#').filter_map(move |(index, _)| {
+        let before = text[..index].chars().next_back();
+        if before.is_some_and(|c| !c.is_whitespace()) {
+            return None;
+        }
+        let name: String = text[index + 1..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '/'))
+            .collect();
+        let name = name.trim_end_matches('/');
+        name.chars()
+            .any(char::is_alphabetic)
+            .then(|| name.to_string())
+    })
+}
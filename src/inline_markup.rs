@@ -0,0 +1,215 @@
+use std::ops::Range;
+
+/// Inline formatting applied by the toolbar and its shortcuts.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    Bold,
+    Italic,
+    Strikethrough,
+    Code,
+    Link,
+}
+
+impl Markup {
+    pub const ALL: [Markup; 5] = [
+        Markup::Bold,
+        Markup::Italic,
+        Markup::Strikethrough,
+        Markup::Code,
+        Markup::Link,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Markup::Bold => "Bold",
+            Markup::Italic => "Italic",
+            Markup::Strikethrough => "Strikethrough",
+            Markup::Code => "Inline Code",
+            Markup::Link => "Link",
+        }
+    }
+
+    pub fn shortcut(self) -> &'static str {
+        match self {
+            Markup::Bold => "Ctrl+B",
+            Markup::Italic => "Ctrl+I",
+            Markup::Strikethrough => "Ctrl+Shift+D",
+            Markup::Code => "Ctrl+Shift+K",
+            Markup::Link => "Ctrl+K",
+        }
+    }
+
+    fn placeholder(self) -> &'static str {
+        match self {
+            Markup::Bold => "bold text",
+            Markup::Italic => "italic text",
+            Markup::Strikethrough => "deleted text",
+            Markup::Code => "code",
+            Markup::Link => "link text",
+        }
+    }
+
+    /// Opening and closing markers around `selected`.
+    fn markers(self, selected: &str) -> (&'static str, &'static str) {
+        match self {
+            Markup::Bold => ("**", "**"),
+            Markup::Italic => ("*", "*"),
+            Markup::Strikethrough => ("~~", "~~"),
+            Markup::Code if selected.contains('\n') => ("```\n", "\n```"),
+            Markup::Code if selected.contains('`') => ("`` ", " ``"),
+            Markup::Code => ("`", "`"),
+            Markup::Link => ("[", "]"),
+        }
+    }
+}
+
+/// The text after applying markup, with the char range to select.
+pub struct Edit {
+    pub text: String,
+    pub selection: Range<usize>,
+}
+
+const URL_PLACEHOLDER: &str = "https://";
+
+/// Applies `markup` to the char range `selection` of `text`. An empty
+/// selection takes the word around the cursor, or inserts selected
+/// placeholder text. Markup already around the selection is removed
+/// instead.
+pub fn apply(text: &str, selection: Range<usize>, markup: Markup) -> Edit {
+    let chars: Vec<char> = text.chars().collect();
+    let len = chars.len();
+    let (mut start, mut end) = (selection.start.min(len), selection.end.min(len));
+    if start > end {
+        std::mem::swap(&mut start, &mut end);
+    }
+    if start == end {
+        while start > 0 && is_word(chars[start - 1]) {
+            start -= 1;
+        }
+        while end < len && is_word(chars[end]) {
+            end += 1;
+        }
+    }
+    // Markers have to touch the text they format.
+    while start < end && chars[start].is_whitespace() {
+        start += 1;
+    }
+    while end > start && chars[end - 1].is_whitespace() {
+        end -= 1;
+    }
+
+    let selected: String = chars[start..end].iter().collect();
+    if markup == Markup::Link {
+        return link(&chars, start..end, &selected);
+    }
+
+    let (open, close) = markup.markers(&selected);
+    let open_len = open.chars().count();
+    let close_len = close.chars().count();
+    let surrounded = start >= open_len
+        && end + close_len <= len
+        && matches(&chars[start - open_len..start], open)
+        && matches(&chars[end..end + close_len], close)
+        && run_matches(&chars[..start], open, true)
+        && run_matches(&chars[end..], close, false);
+    if surrounded {
+        return replace(
+            &chars,
+            start - open_len..end + close_len,
+            &selected,
+            start - open_len..end - open_len,
+        );
+    }
+
+    let inner = chars[start..end].to_vec();
+    let enclosed = inner.len() >= open_len + close_len
+        && matches(&inner[..open_len], open)
+        && matches(&inner[inner.len() - close_len..], close)
+        && run_matches(&inner[..inner.len() - close_len], open, false)
+        && run_matches(&inner[open_len..], close, true);
+    if enclosed {
+        let stripped: String = inner[open_len..inner.len() - close_len].iter().collect();
+        let stripped_len = stripped.chars().count();
+        return replace(&chars, start..end, &stripped, start..start + stripped_len);
+    }
+
+    let body = if selected.is_empty() {
+        markup.placeholder()
+    } else {
+        selected.as_str()
+    };
+    let body_start = start + open_len;
+    let body_end = body_start + body.chars().count();
+    let replacement = format!("{}{}{}", open, body, close);
+    replace(&chars, start..end, &replacement, body_start..body_end)
+}
+
+/// `[selected](https://)` with the destination selected, or, when the
+/// selection is itself a URL, `[link text](url)` with the text selected.
+fn link(chars: &[char], range: Range<usize>, selected: &str) -> Edit {
+    let start = range.start;
+    if selected.is_empty() || looks_like_url(selected) {
+        let text = Markup::Link.placeholder();
+        let destination = if selected.is_empty() {
+            URL_PLACEHOLDER
+        } else {
+            selected
+        };
+        let replacement = format!("[{}]({})", text, destination);
+        let selection = start + 1..start + 1 + text.chars().count();
+        return replace(chars, range, &replacement, selection);
+    }
+    let replacement = format!("[{}]({})", selected, URL_PLACEHOLDER);
+    let destination_start = start + selected.chars().count() + 3;
+    let selection = destination_start..destination_start + URL_PLACEHOLDER.len();
+    replace(chars, range, &replacement, selection)
+}
+
+fn looks_like_url(text: &str) -> bool {
+    !text.contains(char::is_whitespace)
+        && (text.starts_with("http://")
+            || text.starts_with("https://")
+            || text.starts_with("mailto:")
+            || text.starts_with("www."))
+}
+
+fn replace(
+    chars: &[char],
+    range: Range<usize>,
+    replacement: &str,
+    selection: Range<usize>,
+) -> Edit {
+    let mut text: String = chars[..range.start].iter().collect();
+    text.push_str(replacement);
+    text.extend(&chars[range.end..]);
+    Edit { text, selection }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\''
+}
+
+fn matches(chars: &[char], marker: &str) -> bool {
+    chars.iter().copied().eq(marker.chars())
+}
+
+/// For markers made of one repeated char, checks that the run of that char
+/// next to the text is exactly the marker, so `*` does not take half of
+/// `**`. `***` counts as both bold and italic. `before` means the run ends
+/// at the end of `chars`, otherwise it starts at the beginning.
+fn run_matches(chars: &[char], marker: &str, before: bool) -> bool {
+    let mut marker_chars = marker.chars();
+    let Some(first) = marker_chars.next() else {
+        return true;
+    };
+    if !marker_chars.all(|c| c == first) {
+        return true;
+    }
+    let run = if before {
+        chars.iter().rev().take_while(|&&c| c == first).count()
+    } else {
+        chars.iter().take_while(|&&c| c == first).count()
+    };
+    let len = marker.chars().count();
+    run == len || (first == '*' && run == 3)
+}
@@ -0,0 +1,91 @@
+use crate::wikilinks;
+use regex::Regex;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Folders Joplin keeps attachments in: a sync target's `.resource`, and
+/// the `resources` and `_resources` of its exports.
+const RESOURCE_FOLDERS: [&str; 3] = [".resource", "resources", "_resources"];
+
+fn field_regex() -> &'static Regex {
+    static FIELD: OnceLock<Regex> = OnceLock::new();
+    FIELD.get_or_init(|| Regex::new(r"^[a-z][a-z0-9_]*: ?.*$").unwrap())
+}
+
+fn link_regex() -> &'static Regex {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    LINK.get_or_init(|| Regex::new(r"\]\(:/([0-9a-f]{32})((?:#[^)\s]*)?(?:\s+[^)]*)?)\)").unwrap())
+}
+
+/// Byte range of the block of `key: value` lines Joplin ends every note
+/// with (`id: …` through `type_: 1`), from the line break before it.
+pub fn metadata(text: &str) -> Option<Range<usize>> {
+    let body = text.trim_end_matches(['\n', '\r']);
+    let mut start = body.len();
+    let mut has_id = false;
+    for raw in body.rsplit('\n') {
+        let line = raw.trim_end_matches('\r');
+        if !field_regex().is_match(line) {
+            if !line.trim().is_empty() {
+                return None;
+            }
+            break;
+        }
+        has_id |= line.strip_prefix("id: ").is_some_and(is_id);
+        // A file of nothing but fields is a resource or folder, not a note.
+        start = start.checked_sub(raw.len() + 1)?;
+    }
+    let last = body.rsplit('\n').next()?;
+    (has_id && last.starts_with("type_: ")).then_some(start..text.len())
+}
+
+fn is_id(text: &str) -> bool {
+    text.trim().len() == 32 && text.trim().bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// `markdown` with the lines of a Joplin metadata block emptied, so the
+/// preview leaves it out while every line keeps its number.
+pub fn hide_metadata(markdown: &str) -> String {
+    match metadata(markdown) {
+        Some(range) => {
+            let blank = "\n".repeat(markdown[range.clone()].matches('\n').count());
+            format!("{}{}", &markdown[..range.start], blank)
+        }
+        None => markdown.to_string(),
+    }
+}
+
+/// Rewrites Joplin's `[text](:/id)` links to notes and attachments as
+/// wikilink URLs, so clicking one looks the ID up.
+pub fn render_links(markdown: &str) -> String {
+    if !markdown.contains("](:/") {
+        return markdown.to_string();
+    }
+    link_regex()
+        .replace_all(markdown, |caps: &regex::Captures| {
+            format!("]({}{}{})", wikilinks::SCHEME, &caps[1], &caps[2])
+        })
+        .into_owned()
+}
+
+/// The attachment with the Joplin ID `id` in a resource folder of one of
+/// `dirs`.
+pub fn resource(dirs: &[&Path], id: &str) -> Option<PathBuf> {
+    if !is_id(id) {
+        return None;
+    }
+    dirs.iter()
+        .flat_map(|dir| RESOURCE_FOLDERS.iter().map(|folder| dir.join(folder)))
+        .filter_map(|folder| fs::read_dir(folder).ok())
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_file()
+                && path
+                    .file_stem()
+                    .is_some_and(|stem| stem.to_string_lossy() == id)
+        })
+}
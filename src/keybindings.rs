@@ -0,0 +1,242 @@
+use crate::inline_markup::Markup;
+use crate::shortcut;
+use egui::{InputState, KeyboardShortcut};
+use std::collections::BTreeMap;
+
+/// A command that can be bound to a key chord in the `[keybindings]`
+/// section of the config.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    New,
+    Open,
+    QuickOpen,
+    Save,
+    SaveAs,
+    Quit,
+    Lint,
+    Format,
+    SearchInFiles,
+    ToggleFileTree,
+    ToggleTerminal,
+    PasteImage,
+    PreviousChapter,
+    NextChapter,
+    /// Undo and redo and markup only run while the editor has focus.
+    Undo,
+    Redo,
+    Markup(Markup),
+}
+
+impl Action {
+    pub const ALL: [Action; 21] = [
+        Action::New,
+        Action::Open,
+        Action::QuickOpen,
+        Action::Save,
+        Action::SaveAs,
+        Action::Quit,
+        Action::Lint,
+        Action::Format,
+        Action::SearchInFiles,
+        Action::ToggleFileTree,
+        Action::ToggleTerminal,
+        Action::PasteImage,
+        Action::PreviousChapter,
+        Action::NextChapter,
+        Action::Undo,
+        Action::Redo,
+        Action::Markup(Markup::Bold),
+        Action::Markup(Markup::Italic),
+        Action::Markup(Markup::Strikethrough),
+        Action::Markup(Markup::Code),
+        Action::Markup(Markup::Link),
+    ];
+
+    /// The key of the action in `[keybindings]`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::New => "new",
+            Action::Open => "open",
+            Action::QuickOpen => "quick_open",
+            Action::Save => "save",
+            Action::SaveAs => "save_as",
+            Action::Quit => "quit",
+            Action::Lint => "lint",
+            Action::Format => "format",
+            Action::SearchInFiles => "search_in_files",
+            Action::ToggleFileTree => "toggle_file_tree",
+            Action::ToggleTerminal => "toggle_terminal",
+            Action::PasteImage => "paste_image",
+            Action::PreviousChapter => "previous_chapter",
+            Action::NextChapter => "next_chapter",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Markup(Markup::Bold) => "bold",
+            Action::Markup(Markup::Italic) => "italic",
+            Action::Markup(Markup::Strikethrough) => "strikethrough",
+            Action::Markup(Markup::Code) => "inline_code",
+            Action::Markup(Markup::Link) => "link",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::New => "New File",
+            Action::Open => "Open File",
+            Action::QuickOpen => "Quick Open",
+            Action::Save => "Save",
+            Action::SaveAs => "Save As",
+            Action::Quit => "Quit",
+            Action::Lint => "Lint Markdown",
+            Action::Format => "Format Markdown",
+            Action::SearchInFiles => "Search in Files",
+            Action::ToggleFileTree => "Show/Hide File Tree",
+            Action::ToggleTerminal => "Show/Hide Terminal",
+            Action::PasteImage => "Paste Image",
+            Action::PreviousChapter => "Previous Chapter",
+            Action::NextChapter => "Next Chapter",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Markup(markup) => markup.label(),
+        }
+    }
+
+    pub fn default_chord(self) -> &'static str {
+        match self {
+            Action::New => "Ctrl+N",
+            Action::Open => "Ctrl+O",
+            Action::QuickOpen => "Ctrl+P",
+            Action::Save => "Ctrl+S",
+            Action::SaveAs => "Ctrl+Shift+S",
+            Action::Quit => "Ctrl+Q",
+            Action::Lint => "Ctrl+Shift+L",
+            Action::Format => "Alt+Shift+F",
+            Action::SearchInFiles => "Ctrl+Shift+F",
+            Action::ToggleFileTree => "Ctrl+Shift+E",
+            Action::ToggleTerminal => "Ctrl+Backtick",
+            Action::PasteImage => "Ctrl+Shift+I",
+            Action::PreviousChapter => "Ctrl+PageUp",
+            Action::NextChapter => "Ctrl+PageDown",
+            Action::Undo => "Ctrl+Z",
+            Action::Redo => "Ctrl+Shift+Z",
+            Action::Markup(markup) => markup.shortcut(),
+        }
+    }
+
+    fn needs_editor(self) -> bool {
+        matches!(self, Action::Undo | Action::Redo | Action::Markup(_))
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// The chord of every action: the config's entry for it, or else its
+/// default. An empty entry leaves the action unbound.
+pub struct Keymap {
+    chords: Vec<(Action, Option<KeyboardShortcut>)>,
+}
+
+impl Keymap {
+    /// The keymap for the `[keybindings]` entries in `overrides`, and
+    /// what is wrong with them. Invalid chords keep the default.
+    pub fn new(overrides: &BTreeMap<String, String>) -> (Keymap, Vec<String>) {
+        let mut errors: Vec<String> = overrides
+            .keys()
+            .filter(|name| Action::from_name(name).is_none())
+            .map(|name| format!("Unknown keybinding action '{}'", name))
+            .collect();
+        let chords = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let text = overrides.get(action.name()).map(String::as_str);
+                let chord = match text.map(str::trim) {
+                    Some("") => None,
+                    Some(text) => shortcut::parse(text).or_else(|| {
+                        errors.push(format!(
+                            "Invalid key chord '{}' for {}",
+                            text,
+                            action.name()
+                        ));
+                        shortcut::parse(action.default_chord())
+                    }),
+                    None => shortcut::parse(action.default_chord()),
+                };
+                (action, chord)
+            })
+            .collect();
+        let keymap = Keymap { chords };
+        errors.extend(keymap.conflicts().into_iter().map(|(first, second)| {
+            format!(
+                "{} and {} are both bound to {}",
+                first.name(),
+                second.name(),
+                keymap.label(first)
+            )
+        }));
+        (keymap, errors)
+    }
+
+    pub fn chord(&self, action: Action) -> Option<KeyboardShortcut> {
+        self.chords
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .and_then(|(_, chord)| *chord)
+    }
+
+    /// The chord of `action` as shown in menus, or an empty string when it
+    /// is unbound.
+    pub fn label(&self, action: Action) -> String {
+        self.chord(action)
+            .map(|chord| shortcut::format(&chord))
+            .unwrap_or_default()
+    }
+
+    /// Pairs of actions bound to the same chord.
+    pub fn conflicts(&self) -> Vec<(Action, Action)> {
+        let mut conflicts = Vec::new();
+        for (index, (first, chord)) in self.chords.iter().enumerate() {
+            let Some(chord) = chord else {
+                continue;
+            };
+            for (second, other) in &self.chords[index + 1..] {
+                // Compared as written, so Cmd and Ctrl count as the same.
+                if other.is_some_and(|other| shortcut::format(chord) == shortcut::format(&other)) {
+                    conflicts.push((*first, *second));
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Consumes the chords pressed this frame and returns their actions.
+    /// Editor actions are only included when `editing`. Chords with more
+    /// modifiers are tried first, since egui ignores extra Shift and Alt.
+    pub fn pressed(&self, input: &mut InputState, editing: bool) -> Vec<Action> {
+        let mut chords: Vec<(Action, KeyboardShortcut)> = self
+            .chords
+            .iter()
+            .filter(|(action, _)| editing || !action.needs_editor())
+            .filter_map(|(action, chord)| Some((*action, (*chord)?)))
+            .collect();
+        chords.sort_by_key(|(_, chord)| std::cmp::Reverse(modifier_count(chord)));
+        chords
+            .into_iter()
+            .filter(|(_, chord)| input.consume_shortcut(chord))
+            .map(|(action, _)| action)
+            .collect()
+    }
+}
+
+fn modifier_count(chord: &KeyboardShortcut) -> usize {
+    let modifiers = chord.modifiers;
+    [
+        modifiers.ctrl || modifiers.command,
+        modifiers.shift,
+        modifiers.alt,
+    ]
+    .into_iter()
+    .filter(|&held| held)
+    .count()
+}
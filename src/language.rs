@@ -0,0 +1,228 @@
+use crate::export;
+use crate::front_matter;
+
+/// Languages offered by the status bar picker.
+pub const CHOICES: [(&str, &str); 15] = [
+    ("en", "English"),
+    ("de", "Deutsch"),
+    ("fr", "Français"),
+    ("es", "Español"),
+    ("it", "Italiano"),
+    ("pt", "Português"),
+    ("nl", "Nederlands"),
+    ("sv", "Svenska"),
+    ("fi", "Suomi"),
+    ("pl", "Polski"),
+    ("ru", "Русский"),
+    ("ja", "日本語"),
+    ("zh", "中文"),
+    ("ar", "العربية"),
+    ("he", "עברית"),
+];
+
+/// The language a document is written in, as a BCP 47 tag such as `de` or
+/// `pt-BR`.
+#[derive(Clone, PartialEq)]
+pub struct Language {
+    pub tag: String,
+}
+
+/// Writing direction of a document.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+impl Direction {
+    /// From the `dir: rtl` front matter entry, a right-to-left `lang`, or
+    /// else the first strongly directional letter of the body.
+    pub fn of_document(text: &str, language: &Language) -> Self {
+        match front_matter::get(text, "dir").as_deref() {
+            Some("rtl") => return Self::RightToLeft,
+            Some("ltr") => return Self::LeftToRight,
+            _ => {}
+        }
+        if language.is_right_to_left() {
+            return Self::RightToLeft;
+        }
+        text[front_matter::body_start(text)..]
+            .chars()
+            .find(|c| c.is_alphabetic())
+            .filter(|&c| is_rtl_letter(c))
+            .map_or(Self::LeftToRight, |_| Self::RightToLeft)
+    }
+
+    pub fn is_rtl(self) -> bool {
+        self == Self::RightToLeft
+    }
+}
+
+/// Hebrew, Arabic, Syriac, Thaana, N'Ko and their presentation forms.
+fn is_rtl_letter(c: char) -> bool {
+    matches!(c,
+        '\u{0590}'..='\u{08ff}'
+        | '\u{fb1d}'..='\u{fdff}'
+        | '\u{fe70}'..='\u{feff}')
+}
+
+/// Opening and closing marks for double and single quotes.
+pub struct QuoteStyle {
+    pub double: (char, char),
+    pub single: (char, char),
+}
+
+impl Language {
+    /// The `lang` (or `language`) front matter entry, falling back to
+    /// `default`.
+    pub fn of_document(text: &str, default: &str) -> Self {
+        let tag = front_matter::get(text, "lang")
+            .or_else(|| front_matter::get(text, "language"))
+            .unwrap_or_else(|| default.to_string());
+        Self { tag }
+    }
+
+    /// `de` for `de-CH`.
+    pub fn primary(&self) -> String {
+        self.tag
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    }
+
+    pub fn is_right_to_left(&self) -> bool {
+        matches!(
+            self.primary().as_str(),
+            "ar" | "he" | "fa" | "ur" | "yi" | "ps" | "sd" | "ug" | "dv"
+        )
+    }
+
+    /// Languages written without spaces between words; reading time is
+    /// estimated from characters instead.
+    pub fn counts_characters(&self) -> bool {
+        matches!(self.primary().as_str(), "ja" | "zh")
+    }
+
+    /// Average silent reading speed, in words (or characters for
+    /// [`Self::counts_characters`]) per minute.
+    pub fn reading_speed(&self) -> usize {
+        match self.primary().as_str() {
+            "de" | "pt" => 180,
+            "fr" => 195,
+            "es" | "sv" => 215,
+            "it" => 190,
+            "fi" => 160,
+            "pl" => 165,
+            "ru" => 185,
+            "zh" => 255,
+            "ja" => 350,
+            _ => 200,
+        }
+    }
+
+    pub fn quote_style(&self) -> QuoteStyle {
+        let (double, single) = match self.primary().as_str() {
+            "de" => (('„', '“'), ('‚', '‘')),
+            "fr" | "es" | "it" | "pt" | "ru" => (('«', '»'), ('“', '”')),
+            "pl" => (('„', '”'), ('«', '»')),
+            "nl" => (('“', '”'), ('‘', '’')),
+            "sv" | "fi" => (('”', '”'), ('’', '’')),
+            "ja" | "zh" => (('「', '」'), ('『', '』')),
+            _ => (('“', '”'), ('‘', '’')),
+        };
+        QuoteStyle { double, single }
+    }
+}
+
+/// Replaces straight quotes with the typographic quotes of `language`,
+/// leaving front matter, code, HTML tags and link destinations alone.
+/// Apostrophes inside words become `’`.
+pub fn smart_quotes(text: &str, language: &Language) -> String {
+    let style = language.quote_style();
+    let body = front_matter::body_start(text);
+    let mut result = String::with_capacity(text.len());
+    result.push_str(&text[..body]);
+
+    let mut in_fence = false;
+    for line in text[body..].split_inclusive('\n') {
+        if export::is_fence(line) {
+            in_fence = !in_fence;
+            result.push_str(line);
+            continue;
+        }
+        if in_fence || line.starts_with("    ") || line.starts_with('\t') {
+            result.push_str(line);
+            continue;
+        }
+        convert_line(line, &style, &mut result);
+    }
+    result
+}
+
+fn convert_line(line: &str, style: &QuoteStyle, out: &mut String) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut code_ticks = 0;
+    let mut in_tag = false;
+    let mut in_destination = false;
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let prev = index.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(index + 1).copied();
+
+        if c == '`' {
+            let run = chars[index..].iter().take_while(|&&c| c == '`').count();
+            if code_ticks == 0 {
+                code_ticks = run;
+            } else if run == code_ticks {
+                code_ticks = 0;
+            }
+            out.extend(std::iter::repeat_n('`', run));
+            index += run;
+            continue;
+        }
+
+        if code_ticks > 0 {
+            out.push(c);
+        } else if in_tag {
+            in_tag = c != '>';
+            out.push(c);
+        } else if in_destination {
+            in_destination = c != ')';
+            out.push(c);
+        } else if c == '<' && next.is_some_and(|n| n.is_ascii_alphabetic() || n == '/' || n == '!')
+        {
+            in_tag = true;
+            out.push(c);
+        } else if c == '(' && prev == Some(']') {
+            in_destination = true;
+            out.push(c);
+        } else if c == '"' {
+            out.push(if opens(prev) {
+                style.double.0
+            } else {
+                style.double.1
+            });
+        } else if c == '\'' {
+            let in_word =
+                prev.is_some_and(char::is_alphanumeric) && next.is_some_and(char::is_alphanumeric);
+            out.push(if in_word {
+                '’'
+            } else if opens(prev) {
+                style.single.0
+            } else {
+                style.single.1
+            });
+        } else {
+            out.push(c);
+        }
+        index += 1;
+    }
+}
+
+/// A quote opens when it starts the line or follows whitespace or an
+/// opening bracket or dash.
+fn opens(prev: Option<char>) -> bool {
+    prev.is_none_or(|prev| prev.is_whitespace() || "([{<-–—".contains(prev))
+}
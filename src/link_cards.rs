@@ -0,0 +1,251 @@
+use crate::diagrams;
+use egui::{ColorImage, TextureHandle, TextureOptions};
+use scraper::{Html, Selector};
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use url::Url;
+
+/// Pages are fetched this many at a time.
+const MAX_FETCHES: usize = 4;
+/// Only the start of a page is read; the head is all a card needs.
+const MAX_PAGE_BYTES: u64 = 512 * 1024;
+const MAX_ICON_BYTES: u64 = 128 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The URL a block consists of alone, written bare or as `<url>`.
+pub fn bare_url(markdown: &str) -> Option<String> {
+    let text = markdown.trim();
+    let text = text
+        .strip_prefix('<')
+        .and_then(|text| text.strip_suffix('>'))
+        .unwrap_or(text);
+    let is_web = text.starts_with("https://") || text.starts_with("http://");
+    (is_web && !text.contains(char::is_whitespace)).then(|| text.to_string())
+}
+
+/// What a card shows for a page.
+#[derive(Clone)]
+pub struct Card {
+    pub title: String,
+    pub description: String,
+    /// The site's name, or its host.
+    pub site: String,
+    pub icon: Option<TextureHandle>,
+}
+
+pub enum CardState {
+    Pending,
+    Ready(Card),
+    Failed,
+}
+
+struct Fetched {
+    title: String,
+    description: String,
+    site: String,
+    icon: Option<ColorImage>,
+}
+
+type Slot = Arc<Mutex<Option<Result<Fetched, String>>>>;
+
+enum State {
+    Queued,
+    Fetching(Slot),
+    Ready(Card),
+    Failed,
+}
+
+/// Preview cards for bare links, fetched on background threads the first
+/// time a link is drawn and kept for the session.
+#[derive(Default)]
+pub struct LinkCards {
+    cards: HashMap<String, State>,
+}
+
+impl LinkCards {
+    /// The card for `url`, queued for fetching the first time it is asked
+    /// for.
+    pub fn get(&mut self, url: &str) -> CardState {
+        match self.cards.entry(url.to_string()).or_insert(State::Queued) {
+            State::Queued | State::Fetching(_) => CardState::Pending,
+            State::Ready(card) => CardState::Ready(card.clone()),
+            State::Failed => CardState::Failed,
+        }
+    }
+
+    /// Picks up finished fetches and starts queued ones.
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        for (url, state) in &mut self.cards {
+            let State::Fetching(slot) = state else {
+                continue;
+            };
+            let result = slot.lock().ok().and_then(|mut slot| slot.take());
+            *state = match result {
+                None => continue,
+                Some(Ok(fetched)) => State::Ready(Card {
+                    title: fetched.title,
+                    description: fetched.description,
+                    site: fetched.site,
+                    icon: fetched.icon.map(|icon| {
+                        ctx.load_texture(format!("link-card-{}", url), icon, TextureOptions::LINEAR)
+                    }),
+                }),
+                Some(Err(_)) => State::Failed,
+            };
+        }
+
+        let fetching = self
+            .cards
+            .values()
+            .filter(|state| matches!(state, State::Fetching(_)))
+            .count();
+        let queued: Vec<String> = self
+            .cards
+            .iter()
+            .filter(|(_, state)| matches!(state, State::Queued))
+            .map(|(url, _)| url.clone())
+            .take(MAX_FETCHES.saturating_sub(fetching))
+            .collect();
+        for url in queued {
+            let slot: Slot = Arc::new(Mutex::new(None));
+            {
+                let slot = Arc::clone(&slot);
+                let url = url.clone();
+                let ctx = ctx.clone();
+                thread::spawn(move || {
+                    let result = fetch(&url);
+                    if let Ok(mut slot) = slot.lock() {
+                        *slot = Some(result);
+                    }
+                    ctx.request_repaint();
+                });
+            }
+            self.cards.insert(url, State::Fetching(slot));
+        }
+    }
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .user_agent(concat!("md-echo/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// The start of the page at `url`, and the address it was fetched from
+/// after redirects.
+fn fetch_page(agent: &ureq::Agent, url: &str) -> Result<(Url, Html), String> {
+    let response = agent.get(url).call().map_err(|err| err.to_string())?;
+    let base = Url::parse(response.get_url()).map_err(|err| err.to_string())?;
+    if !response.content_type().contains("html") {
+        return Err(format!("Not a web page: {}", response.content_type()));
+    }
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_PAGE_BYTES)
+        .read_to_end(&mut body)
+        .map_err(|err| err.to_string())?;
+    Ok((base, Html::parse_document(&String::from_utf8_lossy(&body))))
+}
+
+/// The text, or the value of `attribute`, of the first element matching
+/// `selector` that has any, with whitespace collapsed.
+fn select(page: &Html, selector: &str, attribute: Option<&str>) -> Option<String> {
+    let selector = Selector::parse(selector).ok()?;
+    page.select(&selector).find_map(|element| {
+        let text = match attribute {
+            Some(attribute) => element.value().attr(attribute)?.to_string(),
+            None => element.text().collect(),
+        };
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+fn page_title(page: &Html) -> Option<String> {
+    select(page, "meta[property='og:title']", Some("content"))
+        .or_else(|| select(page, "title", None))
+}
+
+/// Reads the title, description, site name and icon from the head of the
+/// page at `url`, preferring Open Graph entries.
+fn fetch(url: &str) -> Result<Fetched, String> {
+    let agent = agent();
+    let (base, page) = fetch_page(&agent, url)?;
+    let select = |selector: &str, attribute: Option<&str>| select(&page, selector, attribute);
+    let title = page_title(&page).unwrap_or_else(|| url.to_string());
+    let description = select("meta[property='og:description']", Some("content"))
+        .or_else(|| select("meta[name='description']", Some("content")))
+        .unwrap_or_default();
+    let site = select("meta[property='og:site_name']", Some("content"))
+        .or_else(|| {
+            base.host_str()
+                .map(|host| host.trim_start_matches("www.").to_string())
+        })
+        .unwrap_or_default();
+    let icon_url = select("link[rel~='icon']", Some("href"))
+        .and_then(|href| base.join(&href).ok())
+        .or_else(|| base.join("/favicon.ico").ok());
+    let icon = icon_url.and_then(|icon_url| fetch_icon(&agent, icon_url.as_str()));
+    Ok(Fetched {
+        title,
+        description,
+        site,
+        icon,
+    })
+}
+
+/// The site icon, when it is a PNG (or an ICO holding one).
+fn fetch_icon(agent: &ureq::Agent, url: &str) -> Option<ColorImage> {
+    let response = agent.get(url).call().ok()?;
+    let mut data = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_ICON_BYTES)
+        .read_to_end(&mut data)
+        .ok()?;
+    const PNG: &[u8] = b"\x89PNG";
+    let start = data.windows(PNG.len()).position(|window| window == PNG)?;
+    diagrams::decode_png(&data[start..]).ok()
+}
+
+/// The title of a page fetched on a background thread, for a link pasted
+/// at `at` (a char index in the editor) to be labelled with.
+pub struct TitleFetch {
+    pub url: String,
+    pub at: usize,
+    result: Arc<Mutex<Option<Result<String, String>>>>,
+}
+
+impl TitleFetch {
+    pub fn start(url: &str, at: usize, ctx: egui::Context) -> Self {
+        let result = Arc::new(Mutex::new(None));
+        {
+            let result = Arc::clone(&result);
+            let url = url.to_string();
+            thread::spawn(move || {
+                let title = fetch_page(&agent(), &url).and_then(|(_, page)| {
+                    page_title(&page).ok_or_else(|| "The page has no title".to_string())
+                });
+                if let Ok(mut result) = result.lock() {
+                    *result = Some(title);
+                }
+                ctx.request_repaint();
+            });
+        }
+        Self {
+            url: url.to_string(),
+            at,
+            result,
+        }
+    }
+
+    /// The title, or why there is none, once the fetch has finished.
+    pub fn take(&self) -> Option<Result<String, String>> {
+        self.result.lock().ok()?.take()
+    }
+}
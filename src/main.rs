@@ -1,44 +1,361 @@
+mod acronyms;
+mod alt_text;
+mod annotate;
+mod assets;
+mod assistant;
+mod batch_export;
+mod blame;
+mod book;
+mod bundle;
+mod capture;
+mod clipper;
+mod comments;
+mod conflicts;
+mod consistency;
+mod critic;
+mod dates;
+mod diagnostics;
+mod diagrams;
+mod diff_view;
+mod duplicates;
+mod editor_layout;
+mod evernote;
+mod export;
+mod feed;
+mod file_io;
+mod file_tree;
+mod folder_move;
+mod fonts;
+mod front_matter;
+mod fuzzy;
+mod git;
+mod git_status;
+mod glossary;
+mod graphemes;
+mod gutter;
+mod history;
+mod html_clipboard;
+mod html_markdown;
+mod hunspell;
+mod index;
+mod inline_markup;
+mod joplin;
+mod keybindings;
+mod language;
+mod link_cards;
+mod mdbook;
+mod media;
+mod notify;
+mod notion;
+mod numbering;
+mod obsidian;
+mod on_save;
+mod outline;
+mod pandoc;
+mod patterns;
+mod pdf_preview;
+mod peek;
+mod preview;
+mod preview_server;
+mod profiler;
+mod protect;
+mod scroll_sync;
+mod search;
+mod session;
+mod shortcut;
+mod site;
+mod spellcheck;
+mod splitter;
+mod stats;
+mod status;
+mod summarize;
+mod suppression;
+mod table;
+mod tasks;
+mod templates;
+mod terminal;
+mod text_format;
+mod tool_config;
+mod translate;
+mod vim;
+mod watch;
+mod wikilinks;
+mod wordnet;
+mod workspace_job;
+mod zip;
+
+use batch_export::BatchExportJob;
+use book::Book;
+use conflicts::Resolution;
+use diagnostics::{Diagnostic, Severity};
 use directories::ProjectDirs;
 use eframe::egui;
 use egui::{CentralPanel, Color32, Context, TextEdit, TopBottomPanel, Visuals};
 use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
 use egui_extras::StripBuilder;
+use export::ExportFormat;
+use inline_markup::Markup;
+use keybindings::{Action, Keymap};
+use language::{Direction, Language};
+use mdbook::MdBookServer;
+use notify::Notifications;
+use outline::Outline;
+use preview::PreviewOptions;
 use rfd::FileDialog;
+use scroll_sync::{PreviewBlock, SourceMap};
+use search::SearchJob;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+use suppression::Suppressions;
+use table::TableAction;
 use tempfile::{Builder, NamedTempFile};
+use terminal::TerminalSession;
 
 fn main() -> eframe::Result<()> {
     let args: Vec<String> = env::args().collect();
-    let app = if args.len() > 1 {
-        let file_path = &args[1];
-        match fs::read_to_string(file_path) {
-            Ok(content) => MarkdownApp {
-                content: content.clone(),
-                original_content: content,
-                file_path: Some(file_path.clone()),
-                ..Default::default()
-            },
-            Err(e) => {
-                eprintln!("Error reading file '{}': {}", file_path, e);
-                MarkdownApp::default()
+    if args.get(1).map(String::as_str) == Some("export") {
+        match export_command(&args[2..]) {
+            Ok(message) => {
+                println!("{}", message);
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("md-echo export: {}", err);
+                std::process::exit(2);
             }
         }
-    } else {
-        MarkdownApp::default()
-    };
+    }
+    // `md-echo watch [DIR]` exports without a window, so it runs before the
+    // app loads its config and session; watching a single file opens one.
+    let watch_file = args.len() == 3 && Path::new(&args[2]).is_file();
+    if args.get(1).map(String::as_str) == Some("watch") && !watch_file {
+        if let Err(err) = watch_command(&args[2..]) {
+            eprintln!("md-echo watch: {}", err);
+            std::process::exit(2);
+        }
+        std::process::exit(0);
+    }
+    let mut app = MarkdownApp::default();
+    let mut title = "md-echo - edit/preview";
+    match args.get(1).map(String::as_str) {
+        // `md-echo watch FILE.md`: a preview of one file, reloaded whenever
+        // an external editor saves it.
+        Some("watch") => {
+            app.preview_only = true;
+            app.watching = true;
+            app.open_file_from_path(Path::new(&args[2]), None);
+            title = "md-echo - preview";
+        }
+        Some(_) => {
+            app.open_command_line(&args[1..]);
+            app.restore_session(false);
+        }
+        None => app.restore_session(true),
+    }
     let options = eframe::NativeOptions::default();
-    eframe::run_native(
-        "md-echo - edit/preview",
-        options,
-        Box::new(move |_cc| Box::new(app)),
+    eframe::run_native(title, options, Box::new(move |_cc| Box::new(app)))
+}
+
+/// `md-echo export INPUT.md [--to html|pdf|md] -o OUT`: exports one
+/// document the way Export → Document does, without opening a window. The
+/// format defaults to the extension of OUT, then HTML.
+fn export_command(args: &[String]) -> Result<String, String> {
+    let mut format = None;
+    let mut output = None;
+    let mut input = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" | "-t" => {
+                let name = args.next().ok_or("--to needs html, pdf or md")?;
+                format = Some(
+                    ExportFormat::from_name(name)
+                        .ok_or_else(|| format!("unknown format '{}'", name))?,
+                );
+            }
+            "--out" | "-o" => output = Some(PathBuf::from(args.next().ok_or("-o needs a file")?)),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+    let input = input.ok_or("usage: md-echo export INPUT.md [--to html|pdf|md] -o OUT")?;
+    let output = output.ok_or("no output file; pass -o OUT")?;
+    let format = format
+        .or_else(|| {
+            let extension = output.extension()?.to_str()?;
+            ExportFormat::from_name(extension)
+        })
+        .unwrap_or(ExportFormat::Html);
+
+    let mut problems = Notifications::default();
+    let (config, _) = MarkdownApp::load_config(&mut problems);
+    for problem in problems.entries() {
+        eprintln!("md-echo export: {}: {}", problem.context, problem.message);
+    }
+    let fallback = text_format::encoding_for(&config.editor.fallback_encoding);
+    let (markdown, _) = text_format::read(&input, fallback)
+        .map_err(|err| format!("{}: {}", input.display(), err))?;
+    let input = fs::canonicalize(&input).map_err(|err| format!("{}: {}", input.display(), err))?;
+    let output_dir = match output
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        Some(parent) => fs::create_dir_all(parent)
+            .and_then(|_| fs::canonicalize(parent))
+            .map_err(|err| format!("{}: {}", parent.display(), err))?,
+        None => env::current_dir().map_err(|err| err.to_string())?,
+    };
+    let base_dir = input.parent().unwrap_or(Path::new("."));
+    let markdown = export::rebase_links(&markdown, base_dir, &output_dir);
+    let stem = input.file_stem().map_or("Untitled".to_string(), |stem| {
+        stem.to_string_lossy().into_owned()
+    });
+    let working_dir = env::current_dir().map_err(|err| err.to_string())?;
+    export::write_document(
+        &markdown,
+        &stem,
+        format,
+        &output,
+        config.export.pdf_command.as_deref(),
+        &config.html_options(&working_dir)?,
     )
 }
 
+/// `md-echo watch [--format html|pdf|md] [--out DIR] [DIR]`: exports the
+/// markdown files under DIR (default `.`) and re-exports them on change.
+fn watch_command(args: &[String]) -> Result<(), String> {
+    let mut format = ExportFormat::Html;
+    let mut output = None;
+    let mut root = PathBuf::from(".");
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" | "-f" => {
+                let name = args.next().ok_or("--format needs html, pdf or md")?;
+                format = ExportFormat::from_name(name)
+                    .ok_or_else(|| format!("unknown format '{}'", name))?;
+            }
+            "--out" | "-o" => {
+                output = Some(PathBuf::from(args.next().ok_or("--out needs a folder")?))
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => root = PathBuf::from(arg),
+        }
+    }
+    let root = fs::canonicalize(&root).map_err(|err| format!("{}: {}", root.display(), err))?;
+
+    let mut problems = Notifications::default();
+    let (config, _) = MarkdownApp::load_config(&mut problems);
+    for problem in problems.entries() {
+        eprintln!("md-echo watch: {}: {}", problem.context, problem.message);
+    }
+    let (destination, ignore) = match (output, &config.export.output_dir) {
+        (Some(output), _) => fs::create_dir_all(&output)
+            .and_then(|_| fs::canonicalize(&output))
+            .map(|output| {
+                let destination = batch_export::Destination::Mirror {
+                    source: root.clone(),
+                    output: output.clone(),
+                };
+                (destination, Some(output))
+            })
+            .map_err(|err| format!("{}: {}", output.display(), err))?,
+        (None, Some(dir)) => (
+            batch_export::Destination::Pattern {
+                root: root.clone(),
+                dir: dir.clone(),
+            },
+            watch::pattern_root(&root, dir),
+        ),
+        (None, None) => {
+            return Err("no output folder; pass --out DIR or set [export] output_dir".to_string())
+        }
+    };
+    let settings = batch_export::Settings {
+        format,
+        filename_pattern: config.export.filename_pattern.clone(),
+        destination,
+        pdf_command: config.export.pdf_command.clone(),
+        html: config.html_options(&root)?,
+    };
+    let (patterns, invalid) =
+        patterns::IgnorePatterns::for_workspace(&root, &config.workspace.ignore);
+    for problem in invalid {
+        eprintln!("md-echo watch: {}", problem);
+    }
+    watch::run(watch::Watcher::new(&root, ignore, patterns), &settings)
+}
+
+/// Actions that replace or close the current document and therefore wait for
+/// the unsaved-changes prompt when the buffer is modified.
+enum DeferredAction {
+    New,
+    NewFromTemplate(PathBuf),
+    /// A new document converted from the file with pandoc.
+    ImportWithPandoc(PathBuf),
+    Open,
+    OpenPath(PathBuf),
+    OpenAtLine(PathBuf, usize),
+    Exit,
+}
+
+/// Where File → Import takes notes from.
+#[derive(Clone, Copy)]
+enum NoteImport {
+    /// A zip archive or folder of markdown and assets.
+    Bundle,
+    Notion,
+    Evernote,
+}
+
+/// Columns of the central panel, left to right unless mirrored.
+#[derive(Clone, Copy)]
+enum Pane {
+    Tree,
+    TreeHandle,
+    Editor,
+    EditorHandle,
+    Preview,
+}
+
+/// Formatter output shown as a diff before it replaces the buffer.
+struct FormatReview {
+    /// Name of the tool, for the window title.
+    tool: String,
+    /// The buffer the formatter ran on.
+    original: String,
+    formatted: String,
+    diff: diff_view::DiffView,
+}
+
+/// An unclosed `[[` before the editor cursor, offered note names to
+/// complete it with.
+struct LinkCompletion {
+    /// Char range of the text typed after `[[`.
+    start: usize,
+    end: usize,
+    partial: String,
+    /// Where the popup goes, below the cursor.
+    pos: egui::Pos2,
+}
+
+/// A word from the editor looked up in the dictionary.
+struct WordLookup {
+    /// Char range of the word in the document, for replacing it.
+    range: std::ops::Range<usize>,
+    word: String,
+    /// Show the synonyms to pick from rather than the definitions.
+    synonyms: bool,
+    senses: Result<Vec<wordnet::Sense>, String>,
+}
+
 struct MarkdownApp {
     content: String,
     original_content: String,
@@ -47,21 +364,205 @@ struct MarkdownApp {
     config_path: Option<PathBuf>,
     config: AppConfig,
     cache: CommonMarkCache,
+    preview_text: preview::PreviewText,
+    /// Status bar counts and the history generation they were counted for.
+    stats: Option<(u64, stats::DocumentStats)>,
+    /// The merge conflicts in the text, by history generation.
+    conflicts: (u64, Vec<conflicts::Conflict>),
     modified: bool,
-    show_exit_confirm: bool,
+    /// How the document is stored on disk, and how it was when last read
+    /// or saved.
+    text_format: text_format::TextFormat,
+    saved_format: text_format::TextFormat,
+    discard_prompt: Option<DeferredAction>,
     pending_new: bool,
     pending_open: bool,
     pending_save: bool,
     pending_save_as: bool,
     pending_exit: bool,
+    allow_close: bool,
     pending_lint: bool,
     pending_format: bool,
+    pending_custom_tool: Option<usize>,
+    pending_image_paste: bool,
+    /// Image files dropped on the window, linked by the editor this frame.
+    pending_image_drop: Vec<PathBuf>,
+    /// Formatter output waiting to be accepted or rejected.
+    format_review: Option<FormatReview>,
+    format_diff_side_by_side: bool,
     tool_output: Option<String>,
     show_tool_output: bool,
+    /// The session being restored, until its document has been opened and
+    /// scrolled to.
+    restoring: Option<session::Session>,
+    /// The PDF double-clicked in the file tree, shown in a side panel.
+    pdf_preview: Option<pdf_preview::PdfPreview>,
+    /// Failures shown as toasts and kept in the error log.
+    notifications: Notifications,
+    show_error_log: bool,
     theme_applied: bool,
     scroll_left: f32,
     scroll_right: f32,
-    current_line: usize,
+    /// Editor scroll offset the preview should follow.
+    editor_scrolled_to: Option<f32>,
+    source_map: SourceMap,
+    sync_editor_to: Option<f32>,
+    show_terminal: bool,
+    terminal: Option<TerminalSession>,
+    terminal_input: String,
+    file_mtime: Option<SystemTime>,
+    last_disk_check: f64,
+    external_change: bool,
+    /// Only the preview is shown, and changes on disk are reloaded without
+    /// asking.
+    preview_only: bool,
+    /// Started by `md-echo watch FILE.md`, so the session is left alone.
+    watching: bool,
+    tree_selection: Vec<PathBuf>,
+    tree_anchor: Option<PathBuf>,
+    tree_visible_files: Vec<PathBuf>,
+    file_tree: file_tree::FileTree,
+    /// Ignore patterns being edited in the tree filter menu, one per line.
+    tree_ignore_text: String,
+    /// Only files with this front matter status show in the tree; an
+    /// empty status shows the files without one.
+    tree_status_filter: Option<String>,
+    confirm_batch_delete: bool,
+    show_combined_export: bool,
+    combined_export_files: Vec<PathBuf>,
+    combined_export_format: ExportFormat,
+    show_feed_export: bool,
+    feed_export_files: Vec<PathBuf>,
+    feed_export_format: feed::FeedFormat,
+    show_batch_export: bool,
+    show_protected_export: bool,
+    protected_export_passphrase: String,
+    /// The folder of File → New Numbered File while it asks for a title.
+    numbered_file_dir: Option<PathBuf>,
+    /// A folder move waiting for its link changes to be confirmed, and
+    /// their report.
+    folder_move: Option<(folder_move::FolderMove, String)>,
+    numbered_file_title: String,
+    protected_export_confirm: String,
+    batch_export_files: Vec<PathBuf>,
+    batch_export_format: ExportFormat,
+    batch_export_job: Option<BatchExportJob>,
+    /// Export → Watch and Export: the watched working directory and the
+    /// settings of each run.
+    watch_export: Option<(watch::Watcher, batch_export::Settings)>,
+    watch_export_job: Option<BatchExportJob>,
+    clipboard_capture: Option<capture::ClipboardCapture>,
+    web_clipper: Option<clipper::Clipper>,
+    preview_server: Option<preview_server::PreviewServer>,
+    /// Holds what Copy as HTML put on the clipboard; see
+    /// [`html_clipboard::copy`].
+    html_clipboard: Option<arboard::Clipboard>,
+    /// A missing wikilink target, offered for creation.
+    wikilink_to_create: Option<PathBuf>,
+    workspace_index: Option<index::WorkspaceIndex>,
+    /// The working directory's `.gitignore` and `[workspace] ignore`
+    /// patterns, with the directory and configured patterns they were
+    /// read for.
+    workspace_ignore: (PathBuf, Vec<String>, patterns::IgnorePatterns),
+    show_links_panel: bool,
+    link_completion: Option<LinkCompletion>,
+    /// The popup text for the hovered footnote or link in the preview, by
+    /// document and target.
+    hover_preview: Option<(Option<String>, peek::Target, String)>,
+    /// The link destination Ctrl+hovered in the editor, with its popup text.
+    editor_peek: Option<(Option<String>, String, String)>,
+    keymap: Keymap,
+    /// The chords being edited in the Keyboard Shortcuts window, in the
+    /// order of `Action::ALL`.
+    keybindings_draft: Option<Vec<String>>,
+    /// The Consistency Check window's reports, and the files a fix could
+    /// not be written to.
+    consistency: Option<(Vec<consistency::Report>, Vec<String>)>,
+    consistency_job: Option<workspace_job::WorkspaceJob<(Vec<consistency::Report>, Vec<String>)>>,
+    /// The Duplicate Paragraphs window's groups.
+    duplicates: Option<Vec<duplicates::Group>>,
+    duplicates_job: Option<workspace_job::WorkspaceJob<Vec<duplicates::Group>>>,
+    glossary: glossary::Glossary,
+    history: history::History,
+    vim: vim::Vim,
+    /// The selection last shown for the Vim cursor, and the cursor. A
+    /// different selection in the editor means the user clicked.
+    vim_cursor: Option<(std::ops::Range<usize>, usize)>,
+    show_quick_open: bool,
+    quick_open_query: String,
+    quick_open_selected: usize,
+    watch_export_next: f64,
+    /// Outcome of the last run, shown in the status bar.
+    watch_export_status: String,
+    show_diff_export: bool,
+    diff_base_revision: String,
+    diff_target_revision: String,
+    diff_export_format: ExportFormat,
+    book: Option<Book>,
+    /// Obsidian's conventions apply in the working directory.
+    obsidian: bool,
+    show_outline: bool,
+    outline: Outline,
+    outline_section: Option<usize>,
+    pending_chapter: Option<bool>,
+    pending_goto_line: Option<usize>,
+    diagnostics: Vec<Diagnostic>,
+    show_diagnostics: bool,
+    mdbook_server: Option<MdBookServer>,
+    show_comments_panel: bool,
+    show_front_matter_panel: bool,
+    /// The front matter panel shows the block as text instead of fields.
+    front_matter_raw: bool,
+    front_matter_new_key: String,
+    /// A list field being typed in: its key and text.
+    front_matter_list_edit: Option<(String, String)>,
+    show_review_panel: bool,
+    /// Keys of the headings folded in the preview, see [`section_keys`].
+    collapsed_sections: HashSet<String>,
+    diagrams: diagrams::Diagrams,
+    link_cards: link_cards::LinkCards,
+    /// Page titles being fetched for URLs pasted on their own.
+    link_title_fetches: Vec<link_cards::TitleFetch>,
+    profiler: profiler::Profiler,
+    /// Players started from the preview, by media source.
+    media_players: HashMap<String, media::Player>,
+    spell_checker: spellcheck::SpellChecker,
+    /// The misspelling right-clicked in the editor and its suggestions.
+    spelling_menu: Option<(spellcheck::Misspelling, Vec<String>)>,
+    /// The word right-clicked or selected in the editor, with its char
+    /// range, offered for Define and Synonyms.
+    context_word: Option<(std::ops::Range<usize>, String)>,
+    word_lookup: Option<WordLookup>,
+    translation: Option<translate::Translation>,
+    file_job: Option<file_io::FileJob>,
+    show_assistant: bool,
+    /// Extra instructions sent along with the assistant task.
+    assistant_instruction: String,
+    assistant_key_draft: String,
+    assistant_request: Option<assistant::Request>,
+    summary: Option<summarize::Summary>,
+    show_alt_text: bool,
+    /// Alt text typed or suggested for images, by line and source.
+    alt_text_drafts: HashMap<(usize, String), String>,
+    captions: Vec<alt_text::Caption>,
+    /// What to do once the running save has succeeded.
+    after_save: Option<DeferredAction>,
+    cursor_position: Option<(usize, usize)>,
+    /// Char index of the editor cursor last frame, to tell which way it moved.
+    editor_cursor: Option<usize>,
+    /// An IME composition (e.g. CJK input) is in progress in the editor.
+    ime_composing: bool,
+    show_search: bool,
+    search_focus: bool,
+    search_query: String,
+    search_case_sensitive: bool,
+    search_regex: bool,
+    search_job: Option<SearchJob>,
+    search_error: Option<String>,
+    git_gutter: gutter::GitGutter,
+    git_status: git_status::GitStatus,
+    show_blame: bool,
+    blame: Option<blame::Blame>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -70,6 +571,44 @@ struct AppConfig {
     working_dir: Option<PathBuf>,
     theme: ThemeConfig,
     tools: ToolsConfig,
+    terminal: TerminalConfig,
+    recent_files: Vec<PathBuf>,
+    recent_files_limit: usize,
+    /// Reopen the last document, with its cursor, scroll positions and
+    /// panels, on start.
+    restore_session: bool,
+    /// `obsidian` to understand Obsidian vaults (`![[embeds]]`,
+    /// `%%comments%%`, a hidden `.obsidian` folder), `auto` to do so in
+    /// working directories that are vaults, or `commonmark`.
+    flavor: String,
+    export: ExportConfig,
+    mdbook: MdBookConfig,
+    pandoc: PandocConfig,
+    editor: EditorConfig,
+    preview: PreviewConfig,
+    layout: LayoutConfig,
+    file_tree: FileTreeConfig,
+    fonts: FontsConfig,
+    assets: AssetsConfig,
+    spellcheck: SpellcheckConfig,
+    capture: CaptureConfig,
+    clipper: ClipperConfig,
+    status: StatusConfig,
+    feed: FeedConfig,
+    index: IndexConfig,
+    workspace: WorkspaceConfig,
+    debug: DebugConfig,
+    glossary: GlossaryConfig,
+    acronyms: AcronymsConfig,
+    duplicates: DuplicatesConfig,
+    dictionary: DictionaryConfig,
+    translate: TranslateConfig,
+    assistant: AssistantConfig,
+    alt_text: AltTextConfig,
+    save: SaveConfig,
+    /// Key chords by action name, for the actions whose chord differs from
+    /// the default. An empty chord unbinds the action.
+    keybindings: BTreeMap<String, String>,
 }
 
 impl Default for AppConfig {
@@ -78,8 +617,79 @@ impl Default for AppConfig {
             working_dir: None,
             theme: ThemeConfig::default(),
             tools: ToolsConfig::default(),
+            terminal: TerminalConfig::default(),
+            recent_files: Vec::new(),
+            recent_files_limit: 10,
+            restore_session: true,
+            flavor: "auto".to_string(),
+            export: ExportConfig::default(),
+            mdbook: MdBookConfig::default(),
+            pandoc: PandocConfig::default(),
+            editor: EditorConfig::default(),
+            preview: PreviewConfig::default(),
+            layout: LayoutConfig::default(),
+            file_tree: FileTreeConfig::default(),
+            fonts: FontsConfig::default(),
+            assets: AssetsConfig::default(),
+            spellcheck: SpellcheckConfig::default(),
+            capture: CaptureConfig::default(),
+            clipper: ClipperConfig::default(),
+            status: StatusConfig::default(),
+            feed: FeedConfig::default(),
+            index: IndexConfig::default(),
+            workspace: WorkspaceConfig::default(),
+            debug: DebugConfig::default(),
+            glossary: GlossaryConfig::default(),
+            acronyms: AcronymsConfig::default(),
+            duplicates: DuplicatesConfig::default(),
+            dictionary: DictionaryConfig::default(),
+            translate: TranslateConfig::default(),
+            assistant: AssistantConfig::default(),
+            alt_text: AltTextConfig::default(),
+            save: SaveConfig::default(),
+            keybindings: BTreeMap::new(),
+        }
+    }
+}
+
+impl AppConfig {
+    fn typography(&self) -> export::Typography {
+        let preview = &self.preview;
+        export::Typography {
+            justify: preview.justify,
+            hyphenate: preview.hyphenate,
+            line_height: preview.line_height,
+            paragraph_spacing: preview.paragraph_spacing,
+            language: self.editor.language.clone(),
         }
     }
+
+    /// The export template chosen for `working_dir`, resolved against it.
+    fn export_template(&self, working_dir: &Path) -> Option<PathBuf> {
+        let template = self
+            .export
+            .templates
+            .get(&working_dir.display().to_string())?;
+        Some(working_dir.join(template))
+    }
+
+    fn html_options(&self, working_dir: &Path) -> Result<export::HtmlOptions, String> {
+        let template = match self.export_template(working_dir) {
+            Some(path) => {
+                let template = export::load_template(&path)?;
+                Some(templates::expand(
+                    &template,
+                    &templates::variables(working_dir),
+                ))
+            }
+            None => None,
+        };
+        Ok(export::HtmlOptions {
+            typography: self.typography(),
+            template,
+            passphrase: None,
+        })
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -157,6 +767,34 @@ struct ToolsConfig {
     lint_use_open_file: bool,
     format: Option<Vec<String>>,
     format_use_open_file: bool,
+    /// Config file names searched from the document's folder up to the
+    /// working directory and substituted for `{config}` in tool commands.
+    config_files: Vec<String>,
+    /// Extra entries for the Tools menu.
+    custom: Vec<CustomTool>,
+}
+
+/// A user-defined `[[tools.custom]]` entry.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CustomTool {
+    name: String,
+    command: String,
+    args: Vec<String>,
+    /// The tool rewrites the file; its result is offered as a diff.
+    modifies_content: bool,
+    /// Run on the saved open file instead of a temporary copy of the buffer.
+    use_open_file: bool,
+    /// e.g. `Ctrl+Alt+K`
+    hotkey: Option<String>,
+}
+
+impl CustomTool {
+    fn command_line(&self) -> Vec<String> {
+        std::iter::once(self.command.clone())
+            .chain(self.args.iter().cloned())
+            .collect()
+    }
 }
 
 impl Default for ToolsConfig {
@@ -166,509 +804,8636 @@ impl Default for ToolsConfig {
             lint_use_open_file: false,
             format: default_format_command(),
             format_use_open_file: false,
+            config_files: tool_config::default_config_files(),
+            custom: Vec::new(),
         }
     }
 }
 
-fn default_lint_command() -> Option<Vec<String>> {
-    Some(vec!["rumdl".to_string(), "check".to_string()])
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct TerminalConfig {
+    shell: Option<String>,
 }
 
-fn default_format_command() -> Option<Vec<String>> {
-    Some(vec!["rumdl".to_string(), "fmt".to_string()])
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct ExportConfig {
+    pdf_command: Option<Vec<String>>,
+    combined_heading_level: usize,
+    /// HTML templates for HTML and PDF export, by working directory.
+    /// Relative template paths are resolved against the working directory.
+    templates: BTreeMap<String, PathBuf>,
+    /// Folder documents are exported to without asking, relative to the
+    /// working directory. Takes the same placeholders as `filename_pattern`.
+    output_dir: Option<String>,
+    /// Exported file name; see [`export::expand_pattern`]. The format's
+    /// extension is added.
+    filename_pattern: String,
 }
 
-fn parse_color(value: &str) -> Option<Color32> {
-    let hex = value.trim().trim_start_matches('#');
-    if hex.len() == 6 {
-        let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
-        let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
-        let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
-        Some(Color32::from_rgb(r, g, b))
-    } else if hex.len() == 8 {
-        let a = u8::from_str_radix(&hex[0..2], 16).ok()?;
-        let r = u8::from_str_radix(&hex[2..4], 16).ok()?;
-        let g = u8::from_str_radix(&hex[4..6], 16).ok()?;
-        let b = u8::from_str_radix(&hex[6..8], 16).ok()?;
-        Some(Color32::from_rgba_unmultiplied(r, g, b, a))
-    } else {
-        None
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            pdf_command: Some(vec![
+                "pandoc".to_string(),
+                "{input}".to_string(),
+                "-o".to_string(),
+                "{output}".to_string(),
+            ]),
+            combined_heading_level: 1,
+            templates: BTreeMap::new(),
+            output_dir: None,
+            filename_pattern: "{name}".to_string(),
+        }
     }
 }
 
-impl Default for MarkdownApp {
-    fn default() -> Self {
-        let (mut config, config_path) = MarkdownApp::load_config();
-        let working_dir = MarkdownApp::initial_working_directory(&mut config);
-        let app = Self {
-            content: String::new(),
-            original_content: String::new(),
-            file_path: None,
-            working_dir,
-            config_path,
-            config,
-            cache: CommonMarkCache::default(),
-            modified: false,
-            show_exit_confirm: false,
-            pending_new: false,
-            pending_open: false,
-            pending_save: false,
-            pending_save_as: false,
-            pending_exit: false,
-            pending_lint: false,
-            pending_format: false,
-            tool_output: None,
-            show_tool_output: false,
-            theme_applied: false,
-            scroll_left: 0.0,
-            scroll_right: 0.0,
-            current_line: 0,
-        };
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct PandocConfig {
+    /// The pandoc executable, for Export → Pandoc and Import → With Pandoc.
+    command: String,
+    /// Added to every pandoc export, such as `--reference-doc=style.docx`
+    /// or `--toc`.
+    export_args: Vec<String>,
+    /// The markdown dialect imports are written in, by pandoc's name.
+    import_format: String,
+}
 
-        if app
-            .config_path
-            .as_ref()
-            .map(|path| !path.exists())
-            .unwrap_or(false)
-        {
-            app.save_config();
+impl Default for PandocConfig {
+    fn default() -> Self {
+        Self {
+            command: "pandoc".to_string(),
+            export_args: Vec::new(),
+            import_format: "gfm".to_string(),
         }
-
-        app
     }
 }
 
-impl eframe::App for MarkdownApp {
-    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
-        self.ensure_theme(ctx);
-        // Handle hotkeys
-        ctx.input(|i| {
-            if i.key_pressed(egui::Key::Q) && i.modifiers.ctrl {
-                if self.modified {
-                    self.show_exit_confirm = true;
-                } else {
-                    self.pending_exit = true;
-                }
-            }
-            if i.key_pressed(egui::Key::N) && i.modifiers.ctrl {
-                self.pending_new = true;
-            }
-            if i.key_pressed(egui::Key::O) && i.modifiers.ctrl {
-                self.pending_open = true;
-            }
-            if i.key_pressed(egui::Key::S) && i.modifiers.ctrl {
-                if i.modifiers.shift {
-                    self.pending_save_as = true;
-                } else {
-                    self.pending_save = true;
-                }
-            }
-            if i.key_pressed(egui::Key::L) && i.modifiers.ctrl && i.modifiers.shift {
-                self.pending_lint = true;
-            }
-            if i.key_pressed(egui::Key::F) && i.modifiers.ctrl && i.modifiers.shift {
-                self.pending_format = true;
-            }
-        });
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct MdBookConfig {
+    command: String,
+    port: u16,
+}
 
-        // ==== MENU BAR ====
-        TopBottomPanel::top("menu_bar").show(ctx, |ui| {
-            egui::menu::bar(ui, |ui| {
-                ui.menu_button("File", |ui| {
-                    if ui.button("New").clicked() {
-                        if self.confirm_discard(ui) {
-                            self.content.clear();
-                            self.original_content.clear();
-                            self.file_path = None;
-                            self.modified = false;
-                        }
-                        ui.close_menu();
-                    }
+impl Default for MdBookConfig {
+    fn default() -> Self {
+        Self {
+            command: "mdbook".to_string(),
+            port: 3000,
+        }
+    }
+}
 
-                    if ui.button("Open").clicked() {
-                        if self.confirm_discard(ui) {
-                            if let Some(path) = FileDialog::new().pick_file() {
-                                self.open_file_from_path(&path);
-                            }
-                        }
-                        ui.close_menu();
-                    }
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct EditorConfig {
+    /// Collapse multi-line HTML comments to a single row in the editor.
+    fold_comments: bool,
+    /// Mark lines changed since the last git commit.
+    git_gutter: bool,
+    /// Language of documents without a `lang` front matter entry.
+    language: String,
+    /// Show the formatting toolbar above the editor.
+    toolbar: bool,
+    /// Modal Vim-style editing.
+    vim: bool,
+    /// How many steps Undo can go back.
+    undo_depth: usize,
+    /// Documents larger than this, in KB, open in large-file mode.
+    large_file_kb: usize,
+    /// The encoding of files that are neither UTF-8 nor start with a byte
+    /// order mark.
+    fallback_encoding: String,
+    /// Paste formatted text copied from web pages and documents as
+    /// markdown, and a URL pasted over selected text as a link to it.
+    /// Ctrl+Shift+V always pastes plain text.
+    smart_paste: bool,
+    /// Fetch the title of the page a URL pasted on its own leads to, and
+    /// turn the URL into a link labelled with it.
+    paste_link_titles: bool,
+}
 
-                    if ui.button("Save").clicked() {
-                        self.save_file(false);
-                        ui.close_menu();
-                    }
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self {
+            fold_comments: true,
+            git_gutter: true,
+            language: "en".to_string(),
+            toolbar: true,
+            vim: false,
+            undo_depth: 200,
+            large_file_kb: 512,
+            fallback_encoding: "windows-1252".to_string(),
+            smart_paste: true,
+            paste_link_titles: false,
+        }
+    }
+}
 
-                    if ui.button("Save As...").clicked() {
-                        self.save_file(true);
-                        ui.close_menu();
-                    }
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct PreviewConfig {
+    /// Render HTML comments as notes instead of hiding them.
+    show_comments: bool,
+    /// Justify paragraphs in exported HTML and PDF.
+    justify: bool,
+    /// Hyphenate paragraphs in exported HTML and PDF by document language.
+    hyphenate: bool,
+    /// Line height of exported HTML and PDF, as a multiple of the font size.
+    line_height: f32,
+    /// Space between paragraphs in em, in the preview and exports.
+    paragraph_spacing: f32,
+    /// Renders ```mermaid blocks to PNG; `{input}` is the diagram source
+    /// and `{output}` the image to write. Unset to show them as code.
+    mermaid_command: Option<Vec<String>>,
+    /// Color schemes for fenced code blocks with dark and light themes.
+    code_theme_dark: String,
+    code_theme_light: String,
+    /// Draw links that stand alone in a paragraph as cards with the page's
+    /// title, description and icon, fetched from the web. A document's
+    /// `link_cards` front matter entry overrides this.
+    link_cards: bool,
+    /// How long typing has to pause before the preview follows, in
+    /// milliseconds.
+    update_delay_ms: u64,
+    /// mpv, or a player that takes the same options, for audio and video
+    /// embedded in the preview.
+    media_player: String,
+    /// Renders the first page of a PDF double-clicked in the file tree to
+    /// PNG; `{input}` is the PDF and `{output}` the image to write.
+    pdf_command: Option<Vec<String>>,
+    /// Port of Tools → Serve Preview on 127.0.0.1.
+    serve_port: u16,
+}
 
-                    ui.separator();
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            show_comments: false,
+            justify: false,
+            hyphenate: false,
+            line_height: 1.5,
+            paragraph_spacing: 1.0,
+            mermaid_command: Some(
+                [
+                    "mmdc",
+                    "-i",
+                    "{input}",
+                    "-o",
+                    "{output}",
+                    "-b",
+                    "transparent",
+                ]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect(),
+            ),
+            code_theme_dark: "base16-ocean.dark".to_string(),
+            code_theme_light: "base16-ocean.light".to_string(),
+            link_cards: false,
+            update_delay_ms: 200,
+            media_player: "mpv".to_string(),
+            pdf_command: Some(
+                [
+                    "pdftoppm",
+                    "-png",
+                    "-singlefile",
+                    "-f",
+                    "1",
+                    "-scale-to",
+                    "800",
+                    "{input}",
+                    "{output}",
+                ]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect(),
+            ),
+            serve_port: 7312,
+        }
+    }
+}
 
-                    if ui.button("Exit").clicked() {
-                        if self.modified {
-                            self.show_exit_confirm = true;
-                        } else {
-                            std::process::exit(0);
-                        }
-                    }
-                });
+/// Widths of the file tree and editor as fractions of the central panel; the
+/// preview takes the rest.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct LayoutConfig {
+    tree_ratio: f32,
+    editor_ratio: f32,
+    tree_collapsed: bool,
+    /// Order the panes preview, editor, tree (right-to-left UI).
+    mirrored: bool,
+}
 
-                ui.menu_button("Tools", |ui| {
-                    if ui.button("Lint Markdown	Ctrl+Shift+L").clicked() {
-                        self.pending_lint = true;
-                        ui.close_menu();
-                    }
-                    if ui.button("Format Markdown	Ctrl+Shift+F").clicked() {
-                        self.pending_format = true;
-                        ui.close_menu();
-                    }
-                });
-            });
-        });
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            tree_ratio: 0.25,
+            editor_ratio: 0.35,
+            tree_collapsed: false,
+            mirrored: false,
+        }
+    }
+}
 
-        // ==== CENTRAL PANEL ====
-        CentralPanel::default().show(ctx, |ui| {
-            // Handle pending actions from hotkeys
-            if self.pending_exit {
-                self.pending_exit = false;
-                std::process::exit(0);
-            }
-            if self.pending_new {
-                self.pending_new = false;
-                if self.confirm_discard(ui) {
-                    self.content.clear();
-                    self.original_content.clear();
-                    self.file_path = None;
-                    self.modified = false;
-                }
-            }
-            if self.pending_open {
-                self.pending_open = false;
-                if self.confirm_discard(ui) {
-                    if let Some(path) = FileDialog::new().pick_file() {
-                        self.open_file_from_path(&path);
-                    }
-                }
-            }
-            if self.pending_save {
-                self.pending_save = false;
-                self.save_file(false);
-            }
-            if self.pending_save_as {
-                self.pending_save_as = false;
-                self.save_file(true);
-            }
-            if self.pending_lint {
-                self.pending_lint = false;
-                self.run_lint_tool();
-            }
-            if self.pending_format {
-                self.pending_format = false;
-                self.run_format_tool();
-            }
+impl LayoutConfig {
+    const MIN_RATIO: f32 = 0.1;
 
-            StripBuilder::new(ui)
-                .size(egui_extras::Size::relative(0.25).at_least(160.0))
-                .size(egui_extras::Size::relative(0.35))
-                .size(egui_extras::Size::relative(0.40))
-                .horizontal(|mut strip| {
-                    strip.cell(|ui| {
-                        ui.vertical(|ui| {
-                            self.show_file_tree(ui);
-                        });
-                    });
+    /// Pixel widths of (tree, editor, preview) for `usable` width, excluding
+    /// the splitter handles. The tree width is zero while it is collapsed.
+    fn widths(&self, usable: f32) -> (f32, f32, f32) {
+        self.clamped().split(usable)
+    }
 
-                    // MIDDLE: Editor inside ScrollArea
-                    strip.cell(|ui| {
-                        let scroll =
-                            egui::ScrollArea::vertical()
-                                .auto_shrink([false; 2])
-                                .show(ui, |ui| {
-                                    let editor_output = TextEdit::multiline(&mut self.content)
-                                        .desired_width(f32::INFINITY)
-                                        .code_editor()
-                                        .show(ui);
+    fn split(&self, usable: f32) -> (f32, f32, f32) {
+        if self.tree_collapsed {
+            let editor = usable * self.editor_ratio / (1.0 - self.tree_ratio);
+            (0.0, editor, usable - editor)
+        } else {
+            let tree = usable * self.tree_ratio;
+            let editor = usable * self.editor_ratio;
+            (tree, editor, usable - tree - editor)
+        }
+    }
 
-                                    let response = editor_output.response;
+    /// Moves the boundary between the file tree and the editor by `delta`
+    /// pixels, leaving the preview width unchanged.
+    fn drag_tree(&mut self, delta: f32, usable: f32) {
+        *self = self.clamped();
+        let delta = delta / usable.max(1.0);
+        let combined = self.tree_ratio + self.editor_ratio;
+        self.tree_ratio =
+            (self.tree_ratio + delta).clamp(Self::MIN_RATIO, combined - Self::MIN_RATIO);
+        self.editor_ratio = combined - self.tree_ratio;
+    }
 
-                                    if response.has_focus() {
-                                        if let Some(cursor_range) = editor_output.cursor_range {
-                                            self.current_line = cursor_range.primary.rcursor.row;
-                                        }
-                                    }
+    /// Moves the boundary between the editor and the preview by `delta`
+    /// pixels.
+    fn drag_editor(&mut self, delta: f32, usable: f32) {
+        *self = self.clamped();
+        let mut delta = delta / usable.max(1.0);
+        if self.tree_collapsed {
+            delta *= 1.0 - self.tree_ratio;
+        }
+        let max = 1.0 - self.tree_ratio - Self::MIN_RATIO;
+        self.editor_ratio = (self.editor_ratio + delta).clamp(Self::MIN_RATIO, max);
+    }
 
-                                    if response.changed() {
-                                        self.modified = self.content != self.original_content;
-                                    }
-                                });
-                        self.scroll_left = scroll.state.offset.y;
-                    });
+    /// Ratios from a hand-edited config can be out of range; keep every pane
+    /// visible.
+    fn clamped(&self) -> Self {
+        let tree_ratio = self
+            .tree_ratio
+            .clamp(Self::MIN_RATIO, 1.0 - 2.0 * Self::MIN_RATIO);
+        let editor_ratio = self
+            .editor_ratio
+            .clamp(Self::MIN_RATIO, 1.0 - tree_ratio - Self::MIN_RATIO);
+        Self {
+            tree_ratio,
+            editor_ratio,
+            tree_collapsed: self.tree_collapsed,
+            mirrored: self.mirrored,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct FileTreeConfig {
+    /// Seconds between background rescans of expanded folders; 0 only
+    /// rescans on demand.
+    refresh_secs: f64,
+    /// Only list markdown files (folders are always shown).
+    markdown_only: bool,
+    /// Hide files and folders whose name starts with a dot.
+    hide_hidden: bool,
+    /// `.gitignore`-style patterns for entries to hide, e.g. `target/` or
+    /// `*.html`.
+    ignore: Vec<String>,
+}
+
+impl Default for FileTreeConfig {
+    fn default() -> Self {
+        Self {
+            refresh_secs: 5.0,
+            markdown_only: false,
+            hide_hidden: false,
+            ignore: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct FontsConfig {
+    /// Fonts used for glyphs the built-in fonts lack (emoji, box drawing,
+    /// other scripts): file paths, or file names found in the system font
+    /// folders.
+    fallbacks: Vec<String>,
+}
+
+impl Default for FontsConfig {
+    fn default() -> Self {
+        Self {
+            fallbacks: fonts::default_fallbacks(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct AssetsConfig {
+    /// Folder for pasted images, relative to the document. `{name}` is the
+    /// document's file name without extension.
+    folder: String,
+    /// File name of a pasted image; `{timestamp}` is the UTC time.
+    paste_name: String,
+}
+
+impl Default for AssetsConfig {
+    fn default() -> Self {
+        Self {
+            folder: "assets".to_string(),
+            paste_name: "{name}-{timestamp}.png".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct CaptureConfig {
+    /// Where Tools → Capture Clipboard collects copied text, relative to
+    /// the working directory.
+    file: String,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            file: "captures.md".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct StatusConfig {
+    /// The states a document's `status` front matter entry moves through,
+    /// in order.
+    states: Vec<String>,
+    /// Badge colors by state, as `#rrggbb`; other states are gray.
+    colors: BTreeMap<String, String>,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            states: ["draft", "review", "published"]
+                .iter()
+                .map(|state| state.to_string())
+                .collect(),
+            colors: [
+                ("draft", "#d08a2e"),
+                ("review", "#4a8fd6"),
+                ("published", "#3fa35a"),
+            ]
+            .iter()
+            .map(|(state, color)| (state.to_string(), color.to_string()))
+            .collect(),
+        }
+    }
+}
+
+impl StatusConfig {
+    fn color(&self, status: &str) -> Color32 {
+        self.colors
+            .iter()
+            .find(|(state, _)| state.eq_ignore_ascii_case(status))
+            .and_then(|(_, color)| parse_color(color))
+            .unwrap_or(Color32::GRAY)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct ClipperConfig {
+    /// Listen for pages sent by the web clipper bookmarklet.
+    enabled: bool,
+    port: u16,
+    /// Where clipped pages are filed, relative to the working directory.
+    inbox: String,
+    /// Shared with the bookmarklet; generated when the clipper is first
+    /// turned on.
+    token: String,
+}
+
+impl Default for ClipperConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7311,
+            inbox: "inbox.md".to_string(),
+            token: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct FeedConfig {
+    /// Feed title; the working directory's name when empty.
+    title: String,
+    description: String,
+    author: String,
+    /// Where the folder is published as HTML; posts are linked below it.
+    site_url: String,
+    /// Newest posts included in the feed.
+    limit: usize,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            description: String::new(),
+            author: String::new(),
+            site_url: String::new(),
+            limit: 20,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct IndexConfig {
+    /// Seconds between checks of the working directory for files changed
+    /// outside the editor.
+    refresh_secs: f64,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self { refresh_secs: 10.0 }
+    }
+}
+
+/// Settings for diagnosing md-echo itself, not shown in the menus.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct DebugConfig {
+    /// Show frame timings by subsystem over the window.
+    profiler: bool,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct WorkspaceConfig {
+    /// `.gitignore`-style patterns for files and folders left out of the
+    /// tree, the workspace index and everything built on it, and search.
+    ignore: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct GlossaryConfig {
+    /// The file of `*[TERM]: definition` lines, relative to the working
+    /// directory.
+    file: PathBuf,
+}
+
+impl Default for GlossaryConfig {
+    fn default() -> Self {
+        Self {
+            file: PathBuf::from("glossary.md"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct AcronymsConfig {
+    /// Capitalized words the acronym check never reports.
+    ignore: Vec<String>,
+}
+
+impl Default for AcronymsConfig {
+    fn default() -> Self {
+        Self {
+            ignore: ["OK", "TODO", "FIXME"].map(String::from).to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct DuplicatesConfig {
+    /// Paragraphs shorter than this many words are not compared.
+    min_words: usize,
+    /// How much of two paragraphs' wording must match, from 0 to 1, for
+    /// them to be reported as duplicates.
+    threshold: f32,
+}
+
+impl Default for DuplicatesConfig {
+    fn default() -> Self {
+        Self {
+            min_words: 12,
+            threshold: 0.8,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct DictionaryConfig {
+    /// Directory of the WordNet database (`index.noun`, `data.noun`, ...)
+    /// used by Define and Synonyms.
+    wordnet_dir: PathBuf,
+}
+
+impl Default for DictionaryConfig {
+    fn default() -> Self {
+        Self {
+            wordnet_dir: PathBuf::from("/usr/share/wordnet"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct TranslateConfig {
+    /// Translates the text on its standard input (or in a `{text}`
+    /// argument) into `{target}` and prints the result.
+    command: Option<Vec<String>>,
+    /// The language to translate into, substituted for `{target}`.
+    target: String,
+}
+
+impl Default for TranslateConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            target: "en".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct AssistantConfig {
+    /// Offer the assistant in the Edit menu.
+    enabled: bool,
+    /// `openai` for an OpenAI-compatible API, or `llama.cpp` for a
+    /// llama.cpp server.
+    backend: String,
+    endpoint: String,
+    model: String,
+    /// The environment variable holding the API key. Without it the key
+    /// is read from `assistant.key` next to the config file, never from
+    /// the config itself.
+    api_key_env: String,
+    max_tokens: u32,
+}
+
+impl Default for AssistantConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: assistant::OPENAI.to_string(),
+            endpoint: "https://api.openai.com/v1".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            api_key_env: "OPENAI_API_KEY".to_string(),
+            max_tokens: 1024,
+        }
+    }
+}
+
+/// Changes made to the document each time it is saved.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SaveConfig {
+    /// Strip spaces and tabs from line ends, keeping hard line breaks.
+    trim_trailing_whitespace: bool,
+    /// End the file with exactly one newline.
+    final_newline: bool,
+    /// Run the `[tools] format` command over the document before writing.
+    format: bool,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct AltTextConfig {
+    /// Describes the image at `{image}` (a path or URL) and prints the
+    /// description, for suggested alt text.
+    command: Option<Vec<String>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct SpellcheckConfig {
+    /// Underline misspelled words in the editor.
+    enabled: bool,
+    /// Hunspell `.dic` files (with the `.aff` next to them) by language
+    /// tag, for languages without an installed dictionary.
+    dictionaries: BTreeMap<String, PathBuf>,
+    /// Words to accept, one per line, relative to the working directory.
+    word_list: String,
+}
+
+impl Default for SpellcheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dictionaries: BTreeMap::new(),
+            word_list: ".spelling".to_string(),
+        }
+    }
+}
+
+/// Width of the blame column left of the editor.
+const BLAME_WIDTH: f32 = 170.0;
+
+/// Seconds between checks of a document in large-file mode for changes to
+/// record for undo.
+const LARGE_FILE_HISTORY_INTERVAL: f64 = 1.0;
+
+fn default_lint_command() -> Option<Vec<String>> {
+    Some(
+        [
+            "rumdl",
+            "check",
+            "--config",
+            tool_config::CONFIG_PLACEHOLDER,
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect(),
+    )
+}
+
+fn default_format_command() -> Option<Vec<String>> {
+    Some(
+        ["rumdl", "fmt", "--config", tool_config::CONFIG_PLACEHOLDER]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect(),
+    )
+}
+
+fn parse_color(value: &str) -> Option<Color32> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() == 6 {
+        let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+        let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+        let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+        Some(Color32::from_rgb(r, g, b))
+    } else if hex.len() == 8 {
+        let a = u8::from_str_radix(&hex[0..2], 16).ok()?;
+        let r = u8::from_str_radix(&hex[2..4], 16).ok()?;
+        let g = u8::from_str_radix(&hex[4..6], 16).ok()?;
+        let b = u8::from_str_radix(&hex[6..8], 16).ok()?;
+        Some(Color32::from_rgba_unmultiplied(r, g, b, a))
+    } else {
+        None
+    }
+}
+
+/// Char index just after an unclosed `[[` that the text between it and
+/// the cursor at `cursor` could be completing.
+fn link_completion_start(text: &str, cursor: usize) -> Option<usize> {
+    let end = graphemes::char_to_byte(text, cursor);
+    let line_start = text[..end].rfind('\n').map_or(0, |index| index + 1);
+    let open = text[line_start..end].rfind("[[")? + line_start + 2;
+    let partial = &text[open..end];
+    if partial.contains([']', '[', '|', '#']) || partial.len() > 80 {
+        return None;
+    }
+    Some(cursor - partial.chars().count())
+}
+
+/// A framed card for a link: the site icon and name, the page title as
+/// the link, and the description.
+fn show_link_card(ui: &mut egui::Ui, url: &str, card: &link_cards::Card) {
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(ui.available_width().min(560.0));
+        ui.horizontal(|ui| {
+            if let Some(icon) = &card.icon {
+                ui.add(egui::Image::from_texture(icon).max_size(egui::vec2(16.0, 16.0)));
+            }
+            ui.weak(&card.site);
+        });
+        ui.hyperlink_to(egui::RichText::new(&card.title).strong(), url)
+            .on_hover_text(url);
+        if !card.description.is_empty() {
+            let description: String = card.description.chars().take(240).collect();
+            ui.label(description);
+        }
+    });
+}
+
+/// What the user did with a media player in the preview.
+enum MediaAction {
+    Play,
+    Pause(bool),
+    Seek(f64),
+    Stop,
+    Open,
+}
+
+/// A player bar for audio or video: play/pause, a seek bar with the time,
+/// stop, and a button to open the file in the system's player.
+fn show_media_player(
+    ui: &mut egui::Ui,
+    embed: &media::Embed,
+    player: Option<&mut media::Player>,
+) -> Option<MediaAction> {
+    let mut action = None;
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(ui.available_width().min(560.0));
+        ui.horizontal(|ui| {
+            ui.label(embed.kind.icon());
+            ui.strong(&embed.title).on_hover_text(&embed.src);
+        });
+        ui.horizontal(|ui| {
+            let status = player.map(|player| player.status());
+            match status.filter(|status| !status.finished) {
+                Some(status) => {
+                    let (icon, hover) = if status.paused {
+                        ("▶", "Play")
+                    } else {
+                        ("⏸", "Pause")
+                    };
+                    if ui.button(icon).on_hover_text(hover).clicked() {
+                        action = Some(MediaAction::Pause(!status.paused));
+                    }
+                    if ui.button("⏹").on_hover_text("Stop").clicked() {
+                        action = Some(MediaAction::Stop);
+                    }
+                    let duration = status.duration.unwrap_or(0.0);
+                    let mut position = status.position.min(duration);
+                    let slider =
+                        egui::Slider::new(&mut position, 0.0..=duration.max(0.1)).show_value(false);
+                    let response = ui.add_enabled(duration > 0.0, slider);
+                    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                        action = Some(MediaAction::Seek(position));
+                    }
+                    ui.monospace(format!(
+                        "{} / {}",
+                        media::format_time(status.position),
+                        media::format_time(duration)
+                    ));
+                    ui.ctx()
+                        .request_repaint_after(std::time::Duration::from_millis(250));
+                }
+                None => {
+                    if ui.button("▶").on_hover_text("Play").clicked() {
+                        action = Some(MediaAction::Play);
+                    }
+                }
+            }
+            if ui
+                .button("Open")
+                .on_hover_text("Open in your media player")
+                .clicked()
+            {
+                action = Some(MediaAction::Open);
+            }
+        });
+    });
+    action
+}
+
+/// Underlines misspelled words in the editor with a wavy line.
+fn show_squiggles(
+    ui: &egui::Ui,
+    galley: &egui::Galley,
+    galley_pos: egui::Pos2,
+    misspellings: &[spellcheck::Misspelling],
+) {
+    let clip = ui.clip_rect();
+    let stroke = egui::Stroke::new(1.0, Color32::from_rgb(220, 60, 60));
+    for misspelling in misspellings {
+        let start = galley.pos_from_ccursor(egui::text::CCursor::new(misspelling.range.start));
+        let end = galley.pos_from_ccursor(egui::text::CCursor::new(misspelling.range.end));
+        let y = galley_pos.y + start.bottom() - 1.0;
+        // Words broken across rows are left alone.
+        if (start.top() - end.top()).abs() > 0.5 || y < clip.top() || y > clip.bottom() + 4.0 {
+            continue;
+        }
+        let (left, right) = (galley_pos.x + start.left(), galley_pos.x + end.left());
+        let points: Vec<egui::Pos2> = (0..)
+            .map(|step| left + step as f32 * 2.0)
+            .take_while(|&x| x <= right)
+            .enumerate()
+            .map(|(step, x)| egui::pos2(x, y + if step % 2 == 0 { 0.0 } else { 1.5 }))
+            .collect();
+        ui.painter().add(egui::Shape::line(points, stroke));
+    }
+}
+
+/// A block with pending suggestions, drawn with them colored since the
+/// viewer has no colors of its own.
+fn show_review_block(ui: &mut egui::Ui, markdown: &str, text_style: egui::TextStyle) {
+    let font = text_style.resolve(ui.style());
+    let space = font.size;
+    let visuals = ui.visuals();
+    let job = critic::layout_changes(markdown, font, visuals.text_color(), visuals.dark_mode);
+    ui.label(job);
+    // As much space as the viewer leaves after a block.
+    ui.add_space(space);
+}
+
+/// A dashed rule where a page-break marker forces a new page in print.
+fn show_page_break(ui: &mut egui::Ui) {
+    let height = ui.text_style_height(&egui::TextStyle::Small) * 2.0;
+    let (rect, _) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width(), height),
+        egui::Sense::hover(),
+    );
+    let color = ui.visuals().weak_text_color();
+    let y = rect.center().y;
+    ui.painter().extend(egui::Shape::dashed_line(
+        &[egui::pos2(rect.left(), y), egui::pos2(rect.right(), y)],
+        egui::Stroke::new(1.0, color),
+        6.0,
+        4.0,
+    ));
+    let label = ui.painter().layout_no_wrap(
+        "Page break".to_string(),
+        egui::TextStyle::Small.resolve(ui.style()),
+        color,
+    );
+    let label_rect = egui::Align2::CENTER_CENTER.anchor_size(rect.center(), label.size());
+    ui.painter()
+        .rect_filled(label_rect.expand(4.0), 0.0, ui.visuals().panel_fill);
+    ui.painter().galley(label_rect.min, label, color);
+}
+
+/// Identifies each heading block by its text and how many identical headings
+/// precede it, so folds survive edits elsewhere in the document.
+fn section_keys(blocks: &[scroll_sync::SourceBlock]) -> Vec<Option<String>> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    blocks
+        .iter()
+        .map(|block| {
+            block.heading_level?;
+            let heading = block.markdown.lines().next().unwrap_or_default().trim();
+            let occurrence = seen.entry(heading.to_string()).or_default();
+            *occurrence += 1;
+            Some(format!("{}#{}", heading, occurrence))
+        })
+        .collect()
+}
+
+impl Default for MarkdownApp {
+    fn default() -> Self {
+        let mut notifications = Notifications::default();
+        let (mut config, config_path) = MarkdownApp::load_config(&mut notifications);
+        let working_dir = MarkdownApp::initial_working_directory(&mut config, &mut notifications);
+        let book = Book::detect(&working_dir);
+        let obsidian = obsidian::applies(&config.flavor, &working_dir);
+        let tree_ignore_text = config.file_tree.ignore.join("\n");
+        let (keymap, keymap_errors) = Keymap::new(&config.keybindings);
+        for error in keymap_errors {
+            notifications.warning("Keybindings", error);
+        }
+        let mut app = Self {
+            content: String::new(),
+            original_content: String::new(),
+            file_path: None,
+            working_dir,
+            config_path,
+            config,
+            cache: CommonMarkCache::default(),
+            preview_text: preview::PreviewText::default(),
+            stats: None,
+            conflicts: (0, Vec::new()),
+            modified: false,
+            text_format: text_format::TextFormat::default(),
+            saved_format: text_format::TextFormat::default(),
+            discard_prompt: None,
+            pending_new: false,
+            pending_open: false,
+            pending_save: false,
+            pending_save_as: false,
+            pending_exit: false,
+            allow_close: false,
+            pending_lint: false,
+            pending_format: false,
+            pending_custom_tool: None,
+            pending_image_paste: false,
+            pending_image_drop: Vec::new(),
+            format_review: None,
+            format_diff_side_by_side: false,
+            tool_output: None,
+            show_tool_output: false,
+            restoring: None,
+            pdf_preview: None,
+            notifications,
+            show_error_log: false,
+            theme_applied: false,
+            scroll_left: 0.0,
+            scroll_right: 0.0,
+            editor_scrolled_to: None,
+            source_map: SourceMap::default(),
+            sync_editor_to: None,
+            show_terminal: false,
+            terminal: None,
+            terminal_input: String::new(),
+            file_mtime: None,
+            last_disk_check: 0.0,
+            external_change: false,
+            preview_only: false,
+            watching: false,
+            tree_selection: Vec::new(),
+            tree_anchor: None,
+            tree_visible_files: Vec::new(),
+            file_tree: file_tree::FileTree::default(),
+            tree_ignore_text,
+            tree_status_filter: None,
+            confirm_batch_delete: false,
+            show_combined_export: false,
+            combined_export_files: Vec::new(),
+            combined_export_format: ExportFormat::Html,
+            show_feed_export: false,
+            feed_export_files: Vec::new(),
+            feed_export_format: feed::FeedFormat::Rss,
+            show_batch_export: false,
+            show_protected_export: false,
+            protected_export_passphrase: String::new(),
+            numbered_file_dir: None,
+            folder_move: None,
+            numbered_file_title: String::new(),
+            protected_export_confirm: String::new(),
+            batch_export_files: Vec::new(),
+            batch_export_format: ExportFormat::Html,
+            batch_export_job: None,
+            watch_export: None,
+            watch_export_job: None,
+            clipboard_capture: None,
+            web_clipper: None,
+            preview_server: None,
+            html_clipboard: None,
+            wikilink_to_create: None,
+            workspace_index: None,
+            workspace_ignore: Default::default(),
+            show_links_panel: false,
+            link_completion: None,
+            hover_preview: None,
+            editor_peek: None,
+            keymap,
+            keybindings_draft: None,
+            consistency: None,
+            consistency_job: None,
+            duplicates: None,
+            duplicates_job: None,
+            glossary: glossary::Glossary::default(),
+            history: history::History::default(),
+            vim: vim::Vim::default(),
+            vim_cursor: None,
+            show_quick_open: false,
+            quick_open_query: String::new(),
+            quick_open_selected: 0,
+            watch_export_next: 0.0,
+            watch_export_status: String::new(),
+            show_diff_export: false,
+            diff_base_revision: String::new(),
+            diff_target_revision: String::new(),
+            diff_export_format: ExportFormat::Html,
+            show_outline: book.is_some(),
+            outline: Outline::default(),
+            outline_section: None,
+            pending_chapter: None,
+            pending_goto_line: None,
+            diagnostics: Vec::new(),
+            show_diagnostics: false,
+            mdbook_server: None,
+            show_comments_panel: false,
+            show_front_matter_panel: false,
+            front_matter_raw: false,
+            front_matter_new_key: String::new(),
+            front_matter_list_edit: None,
+            collapsed_sections: HashSet::new(),
+            diagrams: diagrams::Diagrams::default(),
+            link_cards: link_cards::LinkCards::default(),
+            link_title_fetches: Vec::new(),
+            profiler: profiler::Profiler::default(),
+            media_players: HashMap::new(),
+            spell_checker: spellcheck::SpellChecker::default(),
+            spelling_menu: None,
+            context_word: None,
+            word_lookup: None,
+            translation: None,
+            file_job: None,
+            show_assistant: false,
+            assistant_instruction: String::new(),
+            assistant_key_draft: String::new(),
+            assistant_request: None,
+            summary: None,
+            show_alt_text: false,
+            alt_text_drafts: HashMap::new(),
+            captions: Vec::new(),
+            after_save: None,
+            show_review_panel: false,
+            cursor_position: None,
+            editor_cursor: None,
+            ime_composing: false,
+            show_search: false,
+            search_focus: false,
+            search_query: String::new(),
+            search_case_sensitive: false,
+            search_regex: false,
+            search_job: None,
+            search_error: None,
+            git_gutter: gutter::GitGutter::default(),
+            git_status: git_status::GitStatus::default(),
+            show_blame: false,
+            blame: None,
+            book,
+            obsidian,
+        };
+
+        if app
+            .config_path
+            .as_ref()
+            .map(|path| !path.exists())
+            .unwrap_or(false)
+        {
+            app.save_config();
+        }
+
+        app
+    }
+}
+
+impl eframe::App for MarkdownApp {
+    fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        self.profiler.begin_frame(self.config.debug.profiler);
+        let started = Instant::now();
+        self.ensure_theme(ctx);
+        self.check_external_changes(ctx);
+        let now = ctx.input(|i| i.time);
+        self.git_status.poll(
+            &self.working_dir,
+            now,
+            self.config.file_tree.refresh_secs,
+            ctx,
+        );
+        self.poll_watch_export(ctx, now);
+        self.poll_clipboard_capture();
+        self.poll_web_clipper(ctx);
+        self.update_served_preview();
+        self.poll_link_titles(ctx);
+        self.poll_workspace_jobs();
+        self.poll_workspace_index(ctx);
+        self.poll_file_job(ctx);
+        self.glossary
+            .refresh(&self.working_dir.join(&self.config.glossary.file));
+        self.observe_history(now);
+        self.profiler.record("Background jobs", started);
+
+        // Route the window close button through the unsaved-changes dialog.
+        if ctx.input(|i| i.viewport().close_requested()) && !self.allow_close {
+            if self.modified {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                self.discard_prompt = Some(DeferredAction::Exit);
+            } else {
+                self.persist_on_exit();
+                self.allow_close = true;
+            }
+        }
+
+        // Handle hotkeys. Custom tool hotkeys come first and consume their
+        // keys so they can override the bound ones.
+        ctx.input_mut(|i| {
+            for (index, tool) in self.config.tools.custom.iter().enumerate() {
+                let shortcut = tool.hotkey.as_deref().and_then(shortcut::parse);
+                if shortcut.is_some_and(|shortcut| i.consume_shortcut(&shortcut)) {
+                    self.pending_custom_tool = Some(index);
+                }
+            }
+        });
+        // Markup chords only apply in the editor. They are consumed so the
+        // editor's own Ctrl+K (delete to end of line) does not also run.
+        let editing = ctx.memory(|memory| memory.has_focus(Self::editor_id()));
+        let actions = ctx.input_mut(|i| self.keymap.pressed(i, editing));
+        for action in actions {
+            self.run_action(ctx, action);
+        }
+
+        self.handle_dropped_files(ctx);
+
+        // ==== MENU BAR ====
+        TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("New").clicked() {
+                        self.pending_new = true;
+                        ui.close_menu();
+                    }
+
+                    ui.menu_button("New from Template", |ui| {
+                        let workspace = templates::workspace_templates(&self.working_dir);
+                        let mut picked = None;
+                        for (folder, path) in workspace.iter() {
+                            let label = format!("{}/", folder);
+                            let hover = format!("Used for new notes in {}", label);
+                            if ui.button(label).on_hover_text(hover).clicked() {
+                                picked = Some(path.clone());
+                                ui.close_menu();
+                            }
+                        }
+                        if !workspace.is_empty() {
+                            ui.separator();
+                        }
+                        picked = picked.or_else(|| Self::show_template_files(ui, "templates"));
+                        if let Some(path) = picked {
+                            self.request_action(ui.ctx(), DeferredAction::NewFromTemplate(path));
+                        }
+                    });
+
+                    if ui
+                        .button("New Numbered File...")
+                        .on_hover_text(
+                            "Number a new file after the highest NNNN- prefix in the folder, \
+                             as for ADRs, RFCs and meeting notes",
+                        )
+                        .clicked()
+                    {
+                        let dir = self
+                            .file_path
+                            .as_ref()
+                            .and_then(|path| Path::new(path).parent())
+                            .unwrap_or(&self.working_dir)
+                            .to_path_buf();
+                        self.numbered_file_dir = Some(dir);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Open").clicked() {
+                        self.pending_open = true;
+                        ui.close_menu();
+                    }
+
+                    let label = format!("Quick Open...\t{}", self.keymap.label(Action::QuickOpen));
+                    if ui.button(label).clicked() {
+                        self.open_quick_open();
+                        ui.close_menu();
+                    }
+
+                    ui.menu_button("Open Recent", |ui| {
+                        self.show_recent_files_menu(ui);
+                    });
+
+                    if ui.button("Save").clicked() {
+                        self.save_file(false);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Save As...").clicked() {
+                        self.save_file(true);
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    ui.menu_button("Import", |ui| {
+                        let kinds = [
+                            (NoteImport::Bundle, "Bundle (Zip)...", "zip"),
+                            (NoteImport::Notion, "Notion Export (Zip)...", "zip"),
+                            (NoteImport::Evernote, "Evernote Notes (.enex)...", "enex"),
+                        ];
+                        for (kind, label, extension) in kinds {
+                            if ui.button(label).clicked() {
+                                ui.close_menu();
+                                let picked = FileDialog::new()
+                                    .add_filter(label.trim_end_matches('.'), &[extension])
+                                    .pick_file();
+                                if let Some(source) = picked {
+                                    self.import_notes(ui.ctx(), &source, kind);
+                                }
+                            }
+                        }
+                        if ui.button("Folder...").clicked() {
+                            ui.close_menu();
+                            if let Some(source) = FileDialog::new().pick_folder() {
+                                self.import_notes(ui.ctx(), &source, NoteImport::Bundle);
+                            }
+                        }
+                        ui.separator();
+                        if ui
+                            .button("With Pandoc...")
+                            .on_hover_text(
+                                "Convert a Word, OpenDocument, HTML or other file to markdown \
+                                 in a new document",
+                            )
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            let picked = FileDialog::new()
+                                .add_filter("Documents", &pandoc::IMPORT_EXTENSIONS)
+                                .pick_file();
+                            if let Some(source) = picked {
+                                self.request_action(
+                                    ui.ctx(),
+                                    DeferredAction::ImportWithPandoc(source),
+                                );
+                            }
+                        }
+                    });
+
+                    ui.separator();
+
+                    if ui.button("Keyboard Shortcuts...").clicked() {
+                        self.open_keybindings();
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    if ui.button("Exit").clicked() {
+                        self.pending_exit = true;
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Edit", |ui| {
+                    if ui
+                        .button(format!("Undo\t{}", self.keymap.label(Action::Undo)))
+                        .clicked()
+                    {
+                        self.step_history(ui.ctx(), false);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button(format!("Redo\t{}", self.keymap.label(Action::Redo)))
+                        .clicked()
+                    {
+                        self.step_history(ui.ctx(), true);
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui
+                        .button("Copy as HTML")
+                        .on_hover_text(
+                            "Copy the selection, or the whole document, as formatted text \
+                             for pasting into email and word processors",
+                        )
+                        .clicked()
+                    {
+                        self.copy_as_html(ui.ctx());
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Translate Selection")
+                        .on_hover_text(format!(
+                            "Translate the selected text into '{}'",
+                            self.config.translate.target
+                        ))
+                        .clicked()
+                    {
+                        self.translate_selection(ui.ctx());
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(self.config.assistant.enabled, egui::Button::new("Assistant…"))
+                        .on_disabled_hover_text(
+                            "Set [assistant] enabled = true in config.toml to use the assistant",
+                        )
+                        .clicked()
+                    {
+                        self.show_assistant = true;
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Tools", |ui| {
+                    let label = format!("Lint Markdown\t{}", self.keymap.label(Action::Lint));
+                    if ui.button(label).clicked() {
+                        self.pending_lint = true;
+                        ui.close_menu();
+                    }
+                    let label = format!("Format Markdown\t{}", self.keymap.label(Action::Format));
+                    if ui.button(label).clicked() {
+                        self.pending_format = true;
+                        ui.close_menu();
+                    }
+                    if !self.config.tools.custom.is_empty() {
+                        ui.separator();
+                    }
+                    for (index, tool) in self.config.tools.custom.iter().enumerate() {
+                        let label = match &tool.hotkey {
+                            Some(hotkey) => format!("{}\t{}", tool.name, hotkey),
+                            None => tool.name.clone(),
+                        };
+                        let button = ui.button(label);
+                        let button = match &tool.hotkey {
+                            Some(hotkey) if shortcut::parse(hotkey).is_none() => {
+                                button.on_hover_text(format!("Invalid hotkey '{}'", hotkey))
+                            }
+                            _ => button.on_hover_text(tool.command_line().join(" ")),
+                        };
+                        if button.clicked() {
+                            self.pending_custom_tool = Some(index);
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    if ui
+                        .button(format!(
+                            "Paste Image\t{}",
+                            self.keymap.label(Action::PasteImage)
+                        ))
+                        .on_hover_text("Save the clipboard image next to the document and link it")
+                        .clicked()
+                    {
+                        self.pending_image_paste = true;
+                        ui.close_menu();
+                    }
+                    let mut capturing = self.clipboard_capture.is_some();
+                    if ui
+                        .checkbox(&mut capturing, "Capture Clipboard")
+                        .on_hover_text(format!(
+                            "Append everything you copy to {} with a timestamp",
+                            self.config.capture.file
+                        ))
+                        .clicked()
+                    {
+                        self.clipboard_capture =
+                            capturing.then(|| capture::ClipboardCapture::start(ui.ctx().clone()));
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Web Clipper", |ui| self.show_web_clipper_menu(ui));
+                    let mut serving = self.preview_server.is_some();
+                    if ui
+                        .checkbox(&mut serving, "Serve Preview")
+                        .on_hover_text(format!(
+                            "Show the document at http://127.0.0.1:{}/, updating as you type",
+                            self.config.preview.serve_port
+                        ))
+                        .clicked()
+                    {
+                        self.toggle_preview_server(serving);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Check Acronyms")
+                        .on_hover_text("Report acronyms used before they are defined, or never defined")
+                        .clicked()
+                    {
+                        self.check_acronyms();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Find Duplicate Paragraphs")
+                        .on_hover_text("List paragraphs copied, nearly word for word, across the workspace")
+                        .clicked()
+                    {
+                        self.find_duplicates(ctx);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Check Alt Text")
+                        .on_hover_text("List images without alt text and fill it in")
+                        .clicked()
+                    {
+                        self.check_alt_text();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Summarize")
+                        .on_hover_text(if self.config.assistant.enabled {
+                            "Summarize the document as bullet points with the assistant"
+                        } else {
+                            "Pick the document's key sentences as bullet points"
+                        })
+                        .clicked()
+                    {
+                        self.summarize(ui.ctx(), self.config.assistant.enabled);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Insert Glossary")
+                        .on_hover_text("List the glossary terms this document uses, with their definitions")
+                        .clicked()
+                    {
+                        let terms = self.glossary.with_document(&self.content);
+                        let updated = glossary::insert_section(&self.content, &terms);
+                        if updated != self.content {
+                            self.content = updated;
+                            self.update_modified();
+                        }
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Insert Snippet", |ui| {
+                        if let Some(path) = Self::show_template_files(ui, "snippets") {
+                            match templates::render(&path, &self.working_dir) {
+                                Ok(snippet) => self.insert_at_cursor(ui.ctx(), &snippet),
+                                Err(err) => self.show_tool_message(err),
+                            }
+                        }
+                    });
+                    ui.menu_button("Table", |ui| {
+                        for action in TableAction::ALL {
+                            if ui.button(action.label()).clicked() {
+                                self.apply_table_action(ui.ctx(), action);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    if ui
+                        .button("Smart Quotes")
+                        .on_hover_text("Use the document language's quotation marks")
+                        .clicked()
+                    {
+                        self.apply_smart_quotes();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Check Consistency...")
+                        .on_hover_text(
+                            "Find headings, lists, emphasis and links styled differently across the workspace",
+                        )
+                        .clicked()
+                    {
+                        self.run_consistency_check(ctx, Vec::new());
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Review", |ui| {
+                    if ui
+                        .checkbox(&mut self.show_review_panel, "Suggestions Panel")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Accept All Changes").clicked() {
+                        self.resolve_review(None, true);
+                        ui.close_menu();
+                    }
+                    if ui.button("Reject All Changes").clicked() {
+                        self.resolve_review(None, false);
+                        ui.close_menu();
+                    }
+                });
+
+                if self
+                    .book
+                    .as_ref()
+                    .map(|book| book.is_mdbook)
+                    .unwrap_or(false)
+                {
+                    ui.menu_button("mdBook", |ui| {
+                        self.show_mdbook_menu(ui);
+                    });
+                }
+
+                ui.menu_button("Export", |ui| {
+                    for format in ExportFormat::ALL {
+                        if ui
+                            .button(format!("Document as {}...", format.label()))
+                            .clicked()
+                        {
+                            self.export_document(format, None);
+                            ui.close_menu();
+                        }
+                    }
+                    ui.menu_button("Pandoc", |ui| {
+                        for format in pandoc::Format::ALL {
+                            if ui
+                                .button(format!("Document as {}...", format.label()))
+                                .clicked()
+                            {
+                                self.export_with_pandoc(format);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    if ui
+                        .button("Document as Protected HTML...")
+                        .on_hover_text(
+                            "HTML that asks for a passphrase before showing the document",
+                        )
+                        .clicked()
+                    {
+                        self.show_protected_export = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Bundle...")
+                        .on_hover_text(
+                            "A zip with the document and the local images and attachments it \
+                             links to",
+                        )
+                        .clicked()
+                    {
+                        self.export_bundle();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    ui.menu_button("HTML Template", |ui| self.show_template_menu(ui));
+                    ui.separator();
+                    if ui
+                        .button("Print Preview")
+                        .on_hover_text(
+                            "Open the document in your browser with the print typography",
+                        )
+                        .clicked()
+                    {
+                        self.open_print_preview();
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Watch and Export", |ui| self.show_watch_export_menu(ui));
+                    if ui.button("Combined...").clicked() {
+                        self.open_combined_export();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Feed...")
+                        .on_hover_text(
+                            "RSS or Atom feed of the selected files (or the working directory) \
+                             that have a front matter date",
+                        )
+                        .clicked()
+                    {
+                        self.feed_export_files = self.export_candidates();
+                        self.show_feed_export = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Static Site...")
+                        .on_hover_text(
+                            "Every markdown file in the working directory as a linked HTML page, \
+                             with an index",
+                        )
+                        .clicked()
+                    {
+                        self.export_site();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Batch...")
+                        .on_hover_text("Export each selected file (or book chapter) separately")
+                        .clicked()
+                    {
+                        self.open_batch_export();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.file_path.is_some(),
+                            egui::Button::new("Annotated Diff..."),
+                        )
+                        .clicked()
+                    {
+                        self.show_diff_export = true;
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("View", |ui| {
+                    if ui
+                        .checkbox(
+                            &mut self.show_search,
+                            format!(
+                                "Search in Files\t{}",
+                                self.keymap.label(Action::SearchInFiles)
+                            ),
+                        )
+                        .clicked()
+                    {
+                        self.search_focus = self.show_search;
+                        ui.close_menu();
+                    }
+                    let mut tree_visible = !self.config.layout.tree_collapsed;
+                    if ui
+                        .checkbox(
+                            &mut tree_visible,
+                            format!("File Tree\t{}", self.keymap.label(Action::ToggleFileTree)),
+                        )
+                        .clicked()
+                    {
+                        self.toggle_file_tree();
+                        ui.close_menu();
+                    }
+                    if ui.checkbox(&mut self.preview_only, "Preview Only").clicked() {
+                        ui.close_menu();
+                    }
+                    if ui.checkbox(&mut self.show_outline, "Outline").clicked() {
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(&mut self.show_front_matter_panel, "Front Matter")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(&mut self.config.layout.mirrored, "Mirror Layout")
+                        .on_hover_text("Preview on the left, file tree on the right")
+                        .clicked()
+                    {
+                        self.save_config();
+                        ui.close_menu();
+                    }
+                    ui.add_enabled_ui(self.book.is_some(), |ui| {
+                        if ui
+                            .button(format!(
+                                "Previous Chapter\t{}",
+                                self.keymap.label(Action::PreviousChapter)
+                            ))
+                            .clicked()
+                        {
+                            self.pending_chapter = Some(false);
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button(format!(
+                                "Next Chapter\t{}",
+                                self.keymap.label(Action::NextChapter)
+                            ))
+                            .clicked()
+                        {
+                            self.pending_chapter = Some(true);
+                            ui.close_menu();
+                        }
+                    });
+                    ui.separator();
+                    if ui.checkbox(&mut self.show_blame, "Git Blame").clicked() {
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(&mut self.config.spellcheck.enabled, "Spell Check")
+                        .clicked()
+                    {
+                        self.save_config();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(&mut self.config.editor.toolbar, "Formatting Toolbar")
+                        .clicked()
+                    {
+                        self.save_config();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(&mut self.config.editor.vim, "Vim Mode")
+                        .clicked()
+                    {
+                        self.vim = vim::Vim::default();
+                        self.save_config();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(&mut self.config.editor.fold_comments, "Fold Comments")
+                        .clicked()
+                    {
+                        self.save_config();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(
+                            &mut self.config.preview.show_comments,
+                            "Show Comments in Preview",
+                        )
+                        .clicked()
+                    {
+                        self.save_config();
+                        ui.close_menu();
+                    }
+                    let mut link_cards = self.link_cards_enabled();
+                    if ui
+                        .checkbox(&mut link_cards, "Link Cards")
+                        .on_hover_text(
+                            "Show links that stand alone in a paragraph as cards with the page \
+                             title and description, for this document",
+                        )
+                        .clicked()
+                    {
+                        self.content = front_matter::set(
+                            &self.content,
+                            "link_cards",
+                            if link_cards { "true" } else { "false" },
+                        );
+                        self.update_modified();
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Code Theme", |ui| self.show_code_theme_menu(ui));
+                    if ui
+                        .checkbox(&mut self.show_comments_panel, "Comments")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(&mut self.show_links_panel, "Links and Tags")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Collapse All Sections").clicked() {
+                        self.collapsed_sections = section_keys(&self.source_map.blocks)
+                            .into_iter()
+                            .flatten()
+                            .collect();
+                        ui.close_menu();
+                    }
+                    if ui.button("Expand All Sections").clicked() {
+                        self.collapsed_sections.clear();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui
+                        .checkbox(&mut self.show_diagnostics, "Diagnostics")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(
+                            &mut self.show_terminal,
+                            format!("Terminal\t{}", self.keymap.label(Action::ToggleTerminal)),
+                        )
+                        .clicked()
+                    {
+                        ui.close_menu();
+                    }
+                    if ui.checkbox(&mut self.show_error_log, "Error Log").clicked() {
+                        ui.close_menu();
+                    }
+                });
+            });
+        });
+
+        if !self.document_conflicts().is_empty() {
+            let conflicts = std::mem::take(&mut self.conflicts.1);
+            TopBottomPanel::top("conflict_bar").show(ctx, |ui| {
+                self.show_conflict_bar(ui, &conflicts);
+            });
+            self.conflicts.1 = conflicts;
+        }
+
+        let started = Instant::now();
+        if self.show_search {
+            egui::SidePanel::left("search_panel")
+                .resizable(true)
+                .default_width(280.0)
+                .show(ctx, |ui| {
+                    self.show_search_panel(ui);
+                });
+        }
+
+        if self.pdf_preview.is_some() {
+            egui::SidePanel::right("pdf_preview_panel")
+                .resizable(true)
+                .default_width(320.0)
+                .show(ctx, |ui| {
+                    self.show_pdf_preview_panel(ui);
+                });
+        }
+
+        if self.show_outline {
+            egui::SidePanel::right("outline_panel")
+                .resizable(true)
+                .default_width(220.0)
+                .show(ctx, |ui| {
+                    self.show_outline_panel(ui);
+                });
+        }
+
+        if self.show_front_matter_panel {
+            egui::SidePanel::right("front_matter_panel")
+                .resizable(true)
+                .default_width(260.0)
+                .show(ctx, |ui| {
+                    self.show_front_matter_panel(ui);
+                });
+        }
+
+        if self.show_review_panel {
+            egui::SidePanel::right("review_panel")
+                .resizable(true)
+                .default_width(240.0)
+                .show(ctx, |ui| {
+                    self.show_review_panel(ui);
+                });
+        }
+
+        if self.show_comments_panel {
+            egui::SidePanel::right("comments_panel")
+                .resizable(true)
+                .default_width(220.0)
+                .show(ctx, |ui| {
+                    self.show_comments_panel(ui);
+                });
+        }
+
+        if self.show_links_panel {
+            egui::SidePanel::right("links_panel")
+                .resizable(true)
+                .default_width(240.0)
+                .show(ctx, |ui| {
+                    self.show_links_panel(ui);
+                });
+        }
+
+        if self.show_diagnostics {
+            TopBottomPanel::bottom("diagnostics_panel")
+                .resizable(true)
+                .default_height(140.0)
+                .show(ctx, |ui| {
+                    self.show_diagnostics_panel(ui);
+                });
+        }
+
+        if self.show_terminal {
+            TopBottomPanel::bottom("terminal_panel")
+                .resizable(true)
+                .default_height(200.0)
+                .show(ctx, |ui| {
+                    self.show_terminal_panel(ui);
+                });
+        }
+
+        self.profiler.record("Side panels", started);
+
+        // ==== CENTRAL PANEL ====
+        CentralPanel::default().show(ctx, |ui| {
+            // Handle pending actions from hotkeys
+            if self.pending_exit {
+                self.pending_exit = false;
+                self.request_action(ui.ctx(), DeferredAction::Exit);
+            }
+            if self.pending_new {
+                self.pending_new = false;
+                self.request_action(ui.ctx(), DeferredAction::New);
+            }
+            if self.pending_open {
+                self.pending_open = false;
+                self.request_action(ui.ctx(), DeferredAction::Open);
+            }
+            if let Some(forward) = self.pending_chapter.take() {
+                self.go_to_chapter(ui, forward);
+            }
+            if self.pending_save {
+                self.pending_save = false;
+                self.save_file(false);
+            }
+            if self.pending_save_as {
+                self.pending_save_as = false;
+                self.save_file(true);
+            }
+            if self.pending_lint {
+                self.pending_lint = false;
+                self.run_lint_tool();
+            }
+            if self.pending_format {
+                self.pending_format = false;
+                self.run_format_tool();
+            }
+            if let Some(index) = self.pending_custom_tool.take() {
+                self.run_custom_tool(index);
+            }
+            if self.pending_image_paste {
+                self.pending_image_paste = false;
+                self.paste_image(ctx);
+            }
+
+            if self.preview_only {
+                self.show_preview_pane(ui);
+                return;
+            }
+            let tree_visible = !self.config.layout.tree_collapsed;
+            let handles = if tree_visible { 2.0 } else { 1.0 };
+            let usable = (ui.available_width() - handles * splitter::HANDLE_WIDTH).max(1.0);
+            let (tree_width, editor_width, _) = self.config.layout.widths(usable);
+            let mut panes = Vec::with_capacity(5);
+            if tree_visible {
+                panes.extend([Pane::Tree, Pane::TreeHandle]);
+            }
+            panes.extend([Pane::Editor, Pane::EditorHandle, Pane::Preview]);
+            // Splitters move the other way when the panes are mirrored.
+            let drag_sign = if self.config.layout.mirrored {
+                panes.reverse();
+                -1.0
+            } else {
+                1.0
+            };
+
+            let mut builder = StripBuilder::new(ui);
+            for pane in &panes {
+                builder = builder.size(match pane {
+                    Pane::Tree => egui_extras::Size::exact(tree_width),
+                    Pane::TreeHandle | Pane::EditorHandle => {
+                        egui_extras::Size::exact(splitter::HANDLE_WIDTH)
+                    }
+                    Pane::Editor => egui_extras::Size::exact(editor_width),
+                    Pane::Preview => egui_extras::Size::remainder(),
+                });
+            }
+            let mut layout_changed = false;
+            builder.horizontal(|mut strip| {
+                for pane in panes {
+                    match pane {
+                        Pane::Tree => strip.cell(|ui| {
+                            let started = Instant::now();
+                            ui.vertical(|ui| {
+                                self.show_file_tree(ui);
+                            });
+                            self.profiler.record("File tree", started);
+                        }),
+                        Pane::TreeHandle => strip.cell(|ui| {
+                            let handle =
+                                splitter::vertical_handle(ui, egui::Id::new("tree_splitter"));
+                            if handle.dragged() {
+                                let delta = drag_sign * handle.drag_delta().x;
+                                self.config.layout.drag_tree(delta, usable);
+                            }
+                            layout_changed |= handle.drag_stopped();
+                        }),
+                        Pane::Editor => strip.cell(|ui| self.show_editor_pane(ui)),
+                        Pane::EditorHandle => strip.cell(|ui| {
+                            let handle =
+                                splitter::vertical_handle(ui, egui::Id::new("editor_splitter"));
+                            if handle.dragged() {
+                                let delta = drag_sign * handle.drag_delta().x;
+                                self.config.layout.drag_editor(delta, usable);
+                            }
+                            layout_changed |= handle.drag_stopped();
+                        }),
+                        Pane::Preview => strip.cell(|ui| self.show_preview_pane(ui)),
+                    }
+                }
+            });
+            if layout_changed {
+                self.save_config();
+            }
+        });
+
+        // ==== STATUS BAR ====
+        TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let name = self.file_path.as_deref().unwrap_or("Untitled");
+                ui.label(format!(
+                    "{}{}",
+                    if self.modified { "🟡 " } else { "🟢 " },
+                    name
+                ));
+                if let Some(job) = &self.file_job {
+                    ui.separator();
+                    ui.spinner();
+                    ui.label(&job.description);
+                }
+                let workspace_jobs = [
+                    self.consistency_job.as_ref().map(|job| job.description),
+                    self.duplicates_job.as_ref().map(|job| job.description),
+                ];
+                for description in workspace_jobs.into_iter().flatten() {
+                    ui.separator();
+                    ui.spinner();
+                    ui.label(description);
+                }
+                if self.notifications.unread > 0 {
+                    ui.separator();
+                    let badge = format!("⛔ {}", self.notifications.unread);
+                    if ui
+                        .button(egui::RichText::new(badge).color(notify::Level::Error.color()))
+                        .on_hover_text("Problems since the error log was last opened")
+                        .clicked()
+                    {
+                        self.show_error_log = true;
+                    }
+                }
+                if let Some(status) = status::get(&self.content) {
+                    ui.separator();
+                    self.show_status_badge(ui, &status);
+                }
+                ui.separator();
+                let stats = self.document_stats();
+                ui.label(format!("✍️ {} chars", stats.chars));
+                ui.separator();
+                ui.label(format!("{} words", stats.words));
+                ui.separator();
+                ui.label(format!("{} lines", stats.lines));
+                ui.separator();
+                let language = self.document_language();
+                ui.label(format!("⏱ {} min read", stats.reading_minutes(&language)));
+                if let Some((line, column)) = self.cursor_position {
+                    ui.separator();
+                    ui.label(format!("Ln {}, Col {}", line + 1, column + 1));
+                }
+                if self.config.editor.vim {
+                    ui.separator();
+                    ui.monospace(self.vim.status());
+                }
+                if let Some(branch) = self.git_status.branch() {
+                    ui.separator();
+                    ui.label(format!("⎇ {}", branch));
+                }
+                ui.separator();
+                ui.menu_button(self.text_format.label(), |ui| {
+                    self.show_text_format_menu(ui);
+                })
+                .response
+                .on_hover_text("Encoding and line endings, kept when saving. Click to convert.");
+                if self.is_large_file() {
+                    ui.separator();
+                    ui.label("🐘 Large file").on_hover_text(
+                        "Editor highlighting, spell check, the git gutter and glossary \
+                         underlines are off, the preview waits longer after typing and \
+                         only draws what is in view",
+                    );
+                }
+                ui.separator();
+                self.show_language_picker(ui, &language);
+                self.show_direction_toggle(ui);
+                if self.config.spellcheck.enabled {
+                    ui.separator();
+                    match self.spell_checker.problem(&language) {
+                        Some(problem) => {
+                            ui.weak("No spell check").on_hover_text(problem);
+                        }
+                        None => {
+                            let count = self.spell_checker.misspellings().len();
+                            ui.label(format!("{} misspelled", count));
+                        }
+                    }
+                }
+                if let Some(clipper) = &self.web_clipper {
+                    ui.separator();
+                    ui.label(format!("✂ {} clipped", clipper.count))
+                        .on_hover_text(format!(
+                            "Web clipper listening on 127.0.0.1:{}",
+                            clipper.port
+                        ));
+                }
+                if let Some(server) = &self.preview_server {
+                    ui.separator();
+                    let url = server.url();
+                    if ui
+                        .link(format!("🌐 Serving :{}", server.port))
+                        .on_hover_text(format!("Open {} in the browser", url))
+                        .clicked()
+                    {
+                        if let Err(err) = mdbook::open_in_browser(&url) {
+                            self.notifications
+                                .error("Opening the served preview", err.to_string());
+                        }
+                    }
+                }
+                if let Some(capture) = &self.clipboard_capture {
+                    ui.separator();
+                    ui.label(format!("📋 Capturing ({})", capture.count))
+                        .on_hover_text(self.capture_file().display().to_string());
+                }
+                if let Some((_, settings)) = &self.watch_export {
+                    ui.separator();
+                    let label = format!("👁 Exporting {} on change", settings.format.label());
+                    let response = ui.label(label);
+                    if !self.watch_export_status.is_empty() {
+                        response.on_hover_text(&self.watch_export_status);
+                    }
+                }
+            });
+        });
+
+        if self.show_tool_output {
+            let mut open = true;
+            egui::Window::new("Tool Output")
+                .open(&mut open)
+                .resizable(true)
+                .vscroll(true)
+                .show(ctx, |ui| {
+                    if let Some(output) = &self.tool_output {
+                        ui.monospace(output);
+                    } else {
+                        ui.label("No output available.");
+                    }
+                });
+            if !open {
+                self.show_tool_output = false;
+            }
+        }
+
+        if self.show_error_log {
+            let mut open = true;
+            self.notifications.show_log(ctx, &mut open);
+            self.show_error_log = open;
+        }
+        if self.notifications.show_toasts(ctx) {
+            self.show_error_log = true;
+        }
+
+        let started = Instant::now();
+        if self.external_change {
+            self.show_external_change_prompt(ctx);
+        }
+
+        if self.confirm_batch_delete {
+            self.show_batch_delete_confirm(ctx);
+        }
+
+        if self.show_combined_export {
+            self.show_combined_export_window(ctx);
+        }
+
+        if self.show_feed_export {
+            self.show_feed_export_window(ctx);
+        }
+
+        if self.show_batch_export {
+            self.show_batch_export_window(ctx);
+        }
+
+        if self.show_protected_export {
+            self.show_protected_export_window(ctx);
+        }
+
+        self.show_numbered_file_window(ctx);
+        self.show_folder_move_window(ctx);
+
+        if self.wikilink_to_create.is_some() {
+            self.show_create_wikilink_window(ctx);
+        }
+
+        if self.show_quick_open {
+            self.show_quick_open_window(ctx);
+        }
+
+        if self.keybindings_draft.is_some() {
+            self.show_keybindings_window(ctx);
+        }
+
+        if self.word_lookup.is_some() {
+            self.show_word_lookup_window(ctx);
+        }
+
+        if self.translation.is_some() {
+            self.show_translation_window(ctx);
+        }
+
+        if self.show_assistant {
+            self.show_assistant_window(ctx);
+        }
+
+        if self.summary.is_some() {
+            self.show_summary_window(ctx);
+        }
+
+        if self.show_alt_text {
+            self.show_alt_text_window(ctx);
+        }
+
+        if self.consistency.is_some() {
+            self.show_consistency_window(ctx);
+        }
+
+        if self.duplicates.is_some() {
+            self.show_duplicates_window(ctx);
+        }
+
+        if self.show_diff_export {
+            self.show_diff_export_window(ctx);
+        }
+
+        if self.format_review.is_some() {
+            self.show_format_review_window(ctx);
+        }
+
+        // ==== UNSAVED CHANGES ====
+        if self.discard_prompt.is_some() {
+            self.show_discard_prompt(ctx);
+        }
+        self.profiler.record("Windows", started);
+
+        if self.config.debug.profiler {
+            let context = format!(
+                "{} lines, {} KB{}",
+                self.content.lines().count(),
+                self.content.len() / 1024,
+                if self.is_large_file() {
+                    ", large-file mode"
+                } else {
+                    ""
+                }
+            );
+            self.profiler.show(ctx, &context);
+        }
+        self.profiler.end_frame(frame.info().cpu_usage);
+    }
+}
+
+impl MarkdownApp {
+    fn request_action(&mut self, ctx: &Context, action: DeferredAction) {
+        if self.modified {
+            self.discard_prompt = Some(action);
+        } else {
+            self.perform_action(ctx, action);
+        }
+    }
+
+    fn perform_action(&mut self, ctx: &Context, action: DeferredAction) {
+        match action {
+            DeferredAction::New => {
+                self.history.clear();
+                self.content.clear();
+                self.original_content.clear();
+                self.file_path = None;
+                self.file_mtime = None;
+                self.modified = false;
+                self.text_format = text_format::TextFormat::default();
+                self.saved_format = self.text_format;
+                self.collapsed_sections.clear();
+                self.media_players.clear();
+            }
+            DeferredAction::NewFromTemplate(path) => {
+                match templates::render(&path, &self.working_dir) {
+                    Ok(content) => {
+                        self.perform_action(ctx, DeferredAction::New);
+                        self.modified = !content.is_empty();
+                        self.content = content;
+                    }
+                    Err(err) => self.show_tool_message(err),
+                }
+            }
+            DeferredAction::ImportWithPandoc(source) => {
+                let config = &self.config.pandoc;
+                let imported = pandoc::import(
+                    &config.command,
+                    &config.import_format,
+                    &source,
+                    &self.working_dir,
+                );
+                match imported {
+                    Ok((content, warnings)) => {
+                        self.perform_action(ctx, DeferredAction::New);
+                        self.modified = !content.is_empty();
+                        self.content = content;
+                        self.file_tree.refresh();
+                        if let Some(warnings) = warnings {
+                            self.show_tool_message(warnings);
+                        }
+                    }
+                    Err(err) => self.show_tool_message(err),
+                }
+            }
+            DeferredAction::Open => {
+                if let Some(path) = FileDialog::new().pick_file() {
+                    self.open_file_from_path(&path, None);
+                }
+            }
+            DeferredAction::OpenPath(path) => self.open_file_from_path(&path, None),
+            DeferredAction::OpenAtLine(path, line) => {
+                self.open_file_from_path(&path, Some(line));
+            }
+            DeferredAction::Exit => self.request_exit(ctx),
+        }
+    }
+
+    /// Rewrites a paste into the editor before the editor takes it: a URL
+    /// pasted over selected text links the text, a URL pasted on its own
+    /// has its page title fetched, and formatted HTML on the clipboard is
+    /// pasted as markdown. Shift (Ctrl+Shift+V) keeps the plain text.
+    fn handle_smart_paste(&mut self, ctx: &Context) {
+        let pasted = ctx.input(|i| {
+            if i.modifiers.shift {
+                return None;
+            }
+            i.events.iter().find_map(|event| match event {
+                egui::Event::Paste(text) => Some(text.clone()),
+                _ => None,
+            })
+        });
+        let Some(pasted) = pasted else {
+            return;
+        };
+        if !ctx.memory(|memory| memory.has_focus(Self::editor_id())) {
+            return;
+        }
+        let replacement = match link_cards::bare_url(&pasted) {
+            Some(url) => self.paste_url(ctx, &url),
+            None => Self::clipboard_html_as_markdown(),
+        };
+        if let Some(replacement) = replacement {
+            ctx.input_mut(|i| {
+                for event in &mut i.events {
+                    if let egui::Event::Paste(text) = event {
+                        *text = replacement.clone();
+                    }
+                }
+            });
+        }
+    }
+
+    /// What to paste for `url`: a link around the selected text, if any.
+    /// With nothing selected the URL is pasted as it is, and its page title
+    /// fetched to label it once it arrives.
+    fn paste_url(&mut self, ctx: &Context, url: &str) -> Option<String> {
+        let Some(range) = Self::editor_selection(ctx) else {
+            if self.config.editor.paste_link_titles {
+                let state = TextEdit::load_state(ctx, Self::editor_id());
+                let at = state
+                    .and_then(|state| state.cursor.char_range())
+                    .map_or(0, |range| range.primary.index);
+                self.link_title_fetches
+                    .push(link_cards::TitleFetch::start(url, at, ctx.clone()));
+            }
+            return None;
+        };
+        let start = graphemes::char_to_byte(&self.content, range.start);
+        let end = graphemes::char_to_byte(&self.content, range.end);
+        let selected = &self.content[start..end];
+        if selected.contains('\n') || link_cards::bare_url(selected).is_some() {
+            return None;
+        }
+        Some(format!("[{}]({})", selected, url))
+    }
+
+    /// The HTML on the clipboard as markdown, unless it is only styled
+    /// text that reads better plain.
+    fn clipboard_html_as_markdown() -> Option<String> {
+        let html = arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get().html())
+            .ok()?;
+        if !html_markdown::has_markup(&html) {
+            return None;
+        }
+        let markdown = html_markdown::convert(&html, None);
+        let markdown = markdown.trim_end();
+        (!markdown.trim().is_empty()).then(|| markdown.to_string())
+    }
+
+    /// Labels URLs pasted on their own with the page titles that arrived.
+    fn poll_link_titles(&mut self, ctx: &Context) {
+        let mut arrived = Vec::new();
+        self.link_title_fetches.retain(|fetch| match fetch.take() {
+            Some(result) => {
+                arrived.push((fetch.url.clone(), fetch.at, result));
+                false
+            }
+            None => true,
+        });
+        for (url, at, result) in arrived {
+            match result {
+                Ok(title) => self.label_pasted_url(ctx, &url, at, &title),
+                Err(err) => self
+                    .notifications
+                    .warning("Link title", format!("{}: {}", url, err)),
+            }
+        }
+    }
+
+    /// Turns the bare `url` nearest to char index `at`, where it was
+    /// pasted, into a link labelled `title`. A URL already made into a link
+    /// in the meantime is left alone.
+    fn label_pasted_url(&mut self, ctx: &Context, url: &str, at: usize, title: &str) {
+        let pasted_at = graphemes::char_to_byte(&self.content, at);
+        let bare = self
+            .content
+            .match_indices(url)
+            .map(|(start, _)| start)
+            .filter(|&start| {
+                let before = self.content[..start].chars().next_back();
+                let after = self.content[start + url.len()..].chars().next();
+                !matches!(before, Some('(' | '<' | '[' | '"' | '\'' | '/'))
+                    && !matches!(after, Some(')' | '>' | ']' | '"' | '\''))
+            })
+            .min_by_key(|&start| start.abs_diff(pasted_at));
+        let Some(start) = bare else {
+            return;
+        };
+        let link = format!("[{}]({})", title.replace(['[', ']'], ""), url);
+        let start_char = self.content[..start].chars().count();
+        let added = link.chars().count() - url.chars().count();
+        self.content.replace_range(start..start + url.len(), &link);
+        self.update_modified();
+
+        // Keep the cursor on the same text when it is after the link.
+        let id = Self::editor_id();
+        if let Some(mut state) = TextEdit::load_state(ctx, id) {
+            if let Some(range) = state.cursor.char_range() {
+                let shift = |cursor: egui::text::CCursor| {
+                    if cursor.index > start_char {
+                        egui::text::CCursor::new(cursor.index + added)
+                    } else {
+                        cursor
+                    }
+                };
+                state
+                    .cursor
+                    .set_char_range(Some(egui::text::CCursorRange::two(
+                        shift(range.secondary),
+                        shift(range.primary),
+                    )));
+                TextEdit::store_state(ctx, id, state);
+            }
+        }
+    }
+
+    fn editor_id() -> egui::Id {
+        egui::Id::new("markdown_editor")
+    }
+
+    fn show_editor_pane(&mut self, ui: &mut egui::Ui) {
+        let started = Instant::now();
+        if self.config.editor.toolbar {
+            self.show_formatting_toolbar(ui);
+        }
+        let goto_line = self.pending_goto_line.take();
+        if let Some(line) = goto_line {
+            self.place_cursor_at_line(ui.ctx(), line);
+            self.sync_editor_to = Some(self.source_map.line_to_editor_offset(line as f32));
+        }
+        if self.config.editor.smart_paste {
+            self.handle_smart_paste(ui.ctx());
+        }
+        if self.config.editor.vim {
+            self.handle_vim_keys(ui.ctx());
+        }
+        let programmatic = self.sync_editor_to.take();
+        let mut area = egui::ScrollArea::vertical()
+            .id_source("editor_scroll")
+            .auto_shrink([false; 2]);
+        if let Some(offset) = programmatic {
+            area = area.vertical_scroll_offset(offset);
+        }
+        let large = self.is_large_file();
+        let mut style = editor_layout::EditorStyle::from_ui(
+            ui,
+            self.config.editor.fold_comments,
+            self.document_direction().is_rtl(),
+        );
+        style.decorate = !large;
+        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+            editor_layout::layout(ui, &style, text, wrap_width)
+        };
+        let blame_width = if self.show_blame { BLAME_WIDTH } else { 0.0 };
+        let language = self.document_language();
+        let misspellings = if self.config.spellcheck.enabled && !large {
+            let spellcheck = &self.config.spellcheck;
+            self.spell_checker
+                .set_word_list(self.working_dir.join(&spellcheck.word_list));
+            self.spell_checker
+                .check(&self.content, &language, &spellcheck.dictionaries, ui.ctx())
+                .to_vec()
+        } else {
+            Vec::new()
+        };
+        let scroll = area.show(ui, |ui| {
+            let origin_y = ui.min_rect().top();
+            let editor_output = ui
+                .horizontal_top(|ui| {
+                    if blame_width > 0.0 {
+                        ui.allocate_exact_size(egui::vec2(blame_width, 0.0), egui::Sense::hover());
+                    }
+                    TextEdit::multiline(&mut self.content)
+                        .id(Self::editor_id())
+                        .desired_width(f32::INFINITY)
+                        .code_editor()
+                        .layouter(&mut layouter)
+                        .show(ui)
+                })
+                .inner;
+            self.source_map
+                .record_editor_rows(&editor_output.galley, editor_output.galley_pos.y - origin_y);
+
+            if editor_output.response.changed() {
+                self.update_modified();
+            }
+            if self.config.editor.git_gutter && !large {
+                self.show_git_gutter(ui, editor_output.response.rect, origin_y);
+            }
+            if !misspellings.is_empty() {
+                show_squiggles(
+                    ui,
+                    &editor_output.galley,
+                    editor_output.galley_pos,
+                    &misspellings,
+                );
+            }
+            if editor_output.response.secondary_clicked() {
+                let index = ui.input(|i| i.pointer.interact_pos()).map(|pos| {
+                    let cursor = editor_output
+                        .galley
+                        .cursor_from_pos(pos - editor_output.galley_pos);
+                    cursor.ccursor.index
+                });
+                self.spelling_menu = index
+                    .and_then(|index| spellcheck::at(&misspellings, index))
+                    .map(|misspelling| {
+                        let suggestions = self.spell_checker.suggest(&misspelling.word, &language);
+                        (misspelling.clone(), suggestions)
+                    });
+                let selection = editor_output
+                    .cursor_range
+                    .map(|range| range.as_sorted_char_range())
+                    .filter(|range| !range.is_empty());
+                self.context_word = match selection {
+                    Some(range) => {
+                        let word: String = self
+                            .content
+                            .chars()
+                            .skip(range.start)
+                            .take(range.len())
+                            .collect();
+                        let is_phrase = word.len() <= 60
+                            && word
+                                .chars()
+                                .all(|c| c.is_alphabetic() || matches!(c, ' ' | '-' | '\'' | '’'));
+                        is_phrase.then_some((range, word))
+                    }
+                    None => index.and_then(|index| wordnet::word_at(&self.content, index)),
+                };
+            }
+            self.show_editor_peek(ui, &editor_output);
+            if self.spelling_menu.is_some() || self.context_word.is_some() {
+                let menu = editor_output.response.context_menu(|ui| {
+                    self.show_spelling_menu(ui);
+                    self.show_word_menu(ui);
+                });
+                if menu.is_none() {
+                    self.spelling_menu = None;
+                    self.context_word = None;
+                }
+            }
+            if self.show_blame {
+                let column = egui::Rect::from_min_max(
+                    egui::pos2(
+                        editor_output.response.rect.left() - blame_width,
+                        editor_output.response.rect.top(),
+                    ),
+                    editor_output.response.rect.left_bottom(),
+                );
+                self.show_blame_column(ui, column, origin_y);
+            }
+            if !self.pending_image_drop.is_empty() {
+                let drop_pos = ui
+                    .input(|i| i.pointer.latest_pos())
+                    .filter(|pos| ui.clip_rect().contains(*pos));
+                let index = drop_pos.map(|pos| {
+                    let cursor = editor_output
+                        .galley
+                        .cursor_from_pos(pos - editor_output.galley_pos);
+                    cursor.ccursor.index
+                });
+                self.insert_dropped_images(ui.ctx(), index);
+            }
+            if editor_output.response.has_focus() {
+                self.link_completion = editor_output.cursor_range.and_then(|range| {
+                    let end = range.primary.ccursor.index;
+                    let start = link_completion_start(&self.content, end)?;
+                    let byte_start = graphemes::char_to_byte(&self.content, start);
+                    let byte_end = graphemes::char_to_byte(&self.content, end);
+                    let rect = editor_output.galley.pos_from_ccursor(range.primary.ccursor);
+                    Some(LinkCompletion {
+                        start,
+                        end,
+                        partial: self.content[byte_start..byte_end].to_string(),
+                        pos: editor_output.galley_pos + rect.left_bottom().to_vec2(),
+                    })
+                });
+            }
+            self.track_ime(ui.ctx());
+            self.snap_editor_cursor(ui.ctx(), editor_output.state.clone());
+            if let Some(range) = editor_output.cursor_range {
+                let cursor = range.primary.pcursor;
+                let line = self.content.split('\n').nth(cursor.paragraph);
+                let column = line
+                    .map(|line| graphemes::column(line, cursor.offset))
+                    .unwrap_or(cursor.offset);
+                self.cursor_position = Some((cursor.paragraph, column));
+            }
+        });
+
+        self.show_link_completion(ui.ctx());
+
+        let offset = scroll.state.offset.y;
+        let expected = programmatic.unwrap_or(self.scroll_left);
+        if (offset - expected).abs() > 0.5 || goto_line.is_some() {
+            self.editor_scrolled_to = Some(offset);
+            ui.ctx().request_repaint();
+        }
+        self.scroll_left = offset;
+        self.profiler.record("Editor", started);
+    }
+
+    /// Note names from the workspace index for the `[[` link being typed;
+    /// clicking one completes the link.
+    fn show_link_completion(&mut self, ctx: &Context) {
+        let Some(completion) = &self.link_completion else {
+            return;
+        };
+        let names = match &self.workspace_index {
+            Some(index) => index.complete(&completion.partial, 8),
+            None => Vec::new(),
+        };
+        if names.is_empty() || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.link_completion = None;
+            return;
+        }
+        let mut chosen = None;
+        let area = egui::Area::new(egui::Id::new("link_completion"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(completion.pos)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    for name in names {
+                        if ui.selectable_label(false, &name).clicked() {
+                            chosen = Some(name);
+                        }
+                    }
+                })
+            });
+        let editor_focused = ctx.memory(|memory| memory.has_focus(Self::editor_id()));
+        let clicked_away = ctx.input(|i| i.pointer.any_pressed()) && !area.response.hovered();
+        if let Some(name) = chosen {
+            let (start, end) = (completion.start, completion.end);
+            let mut state = TextEdit::load_state(ctx, Self::editor_id()).unwrap_or_default();
+            state
+                .cursor
+                .set_char_range(Some(egui::text::CCursorRange::two(
+                    egui::text::CCursor::new(start),
+                    egui::text::CCursor::new(end),
+                )));
+            TextEdit::store_state(ctx, Self::editor_id(), state);
+            self.insert_at_cursor(ctx, &format!("{}]]", name));
+            self.link_completion = None;
+        } else if !editor_focused && clicked_away {
+            self.link_completion = None;
+        }
+    }
+
+    /// Corrections for the right-clicked word, and ways to accept it.
+    fn show_spelling_menu(&mut self, ui: &mut egui::Ui) {
+        let Some((misspelling, suggestions)) = self.spelling_menu.clone() else {
+            return;
+        };
+        if suggestions.is_empty() {
+            ui.weak("No suggestions");
+        }
+        for suggestion in suggestions {
+            if ui.button(&suggestion).clicked() {
+                let start = graphemes::char_to_byte(&self.content, misspelling.range.start);
+                let end = graphemes::char_to_byte(&self.content, misspelling.range.end);
+                self.content.replace_range(start..end, &suggestion);
+                self.update_modified();
+                self.spelling_menu = None;
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+        if ui.button("Add to Word List").clicked() {
+            if let Err(err) = self.spell_checker.add_word(&misspelling.word) {
+                self.show_tool_message(err);
+            }
+            self.spelling_menu = None;
+            ui.close_menu();
+        }
+        if ui.button("Ignore").clicked() {
+            self.spell_checker.ignore(&misspelling.word);
+            self.spelling_menu = None;
+            ui.close_menu();
+        }
+    }
+
+    fn show_word_menu(&mut self, ui: &mut egui::Ui) {
+        let Some((range, word)) = self.context_word.clone() else {
+            return;
+        };
+        if self.spelling_menu.is_some() {
+            ui.separator();
+        }
+        for (label, synonyms) in [("Define", false), ("Synonyms", true)] {
+            if ui.button(format!("{} \"{}\"", label, word)).clicked() {
+                let senses = wordnet::lookup(&self.config.dictionary.wordnet_dir, &word);
+                self.word_lookup = Some(WordLookup {
+                    range: range.clone(),
+                    word: word.clone(),
+                    synonyms,
+                    senses,
+                });
+                self.context_word = None;
+                ui.close_menu();
+            }
+        }
+    }
+
+    /// The definitions of a looked-up word, or its synonyms, which replace
+    /// the word when clicked.
+    fn show_word_lookup_window(&mut self, ctx: &Context) {
+        let Some(lookup) = &mut self.word_lookup else {
+            return;
+        };
+        let mut open = true;
+        let mut replacement = None;
+        egui::Window::new(format!("\"{}\"", lookup.word))
+            .id(egui::Id::new("word_lookup"))
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut lookup.synonyms, false, "Definitions");
+                    ui.selectable_value(&mut lookup.synonyms, true, "Synonyms");
+                });
+                ui.separator();
+                let senses = match &lookup.senses {
+                    Ok(senses) if senses.is_empty() => {
+                        ui.weak("Not in the dictionary.");
+                        return;
+                    }
+                    Ok(senses) => senses,
+                    Err(err) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                        ui.weak("Install WordNet or set [dictionary] wordnet_dir in config.toml.");
+                        return;
+                    }
+                };
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        if lookup.synonyms {
+                            let mut seen = HashSet::new();
+                            ui.horizontal_wrapped(|ui| {
+                                let words = senses.iter().flat_map(|sense| &sense.synonyms);
+                                for synonym in words.filter(|word| seen.insert(*word)) {
+                                    if ui
+                                        .button(synonym)
+                                        .on_hover_text("Replace the word")
+                                        .clicked()
+                                    {
+                                        replacement = Some(synonym.clone());
+                                    }
+                                }
+                            });
+                            if seen.is_empty() {
+                                ui.weak("No synonyms.");
+                            }
+                        } else {
+                            for (number, sense) in senses.iter().enumerate() {
+                                ui.horizontal_wrapped(|ui| {
+                                    ui.weak(format!("{}. {}", number + 1, sense.part_of_speech));
+                                    ui.label(&sense.gloss);
+                                });
+                                if !sense.synonyms.is_empty() {
+                                    ui.weak(format!("    also: {}", sense.synonyms.join(", ")));
+                                }
+                            }
+                        }
+                    });
+            });
+
+        if !open {
+            self.word_lookup = None;
+        }
+        let Some(synonym) = replacement else {
+            return;
+        };
+        if let Some(lookup) = self.word_lookup.take() {
+            // Keep a capitalized word capitalized.
+            let synonym = match (lookup.word.chars().next(), synonym.chars().next()) {
+                (Some(first), Some(start)) if first.is_uppercase() => start
+                    .to_uppercase()
+                    .chain(synonym.chars().skip(1))
+                    .collect(),
+                _ => synonym,
+            };
+            let start = graphemes::char_to_byte(&self.content, lookup.range.start);
+            let end = graphemes::char_to_byte(&self.content, lookup.range.end);
+            if self.content.get(start..end) == Some(lookup.word.as_str()) {
+                self.content.replace_range(start..end, &synonym);
+                self.update_modified();
+            }
+        }
+    }
+
+    /// The char range selected in the editor, unless nothing is.
+    fn editor_selection(ctx: &Context) -> Option<std::ops::Range<usize>> {
+        TextEdit::load_state(ctx, Self::editor_id())
+            .and_then(|state| state.cursor.char_range())
+            .map(|range| {
+                let (a, b) = (range.primary.index, range.secondary.index);
+                a.min(b)..a.max(b)
+            })
+            .filter(|range| !range.is_empty())
+    }
+
+    /// Copies the selection, or the whole document, as HTML along with its
+    /// markdown as the plain text version.
+    fn copy_as_html(&mut self, ctx: &Context) {
+        let text = match Self::editor_selection(ctx) {
+            Some(range) => {
+                let start = graphemes::char_to_byte(&self.content, range.start);
+                let end = graphemes::char_to_byte(&self.content, range.end);
+                self.content[start..end].to_string()
+            }
+            None => self.content[front_matter::body_start(&self.content)..].to_string(),
+        };
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let dir = document
+            .as_deref()
+            .and_then(|path| path.parent())
+            .unwrap_or(&self.working_dir)
+            .to_path_buf();
+        let html = html_clipboard::to_html(&text, &dir);
+        // Dropped first so the old handle does not take the clipboard back.
+        self.html_clipboard = None;
+        match html_clipboard::copy(&html, &text) {
+            Ok(clipboard) => self.html_clipboard = Some(clipboard),
+            Err(err) => self.notifications.error("Copying as HTML", err),
+        }
+    }
+
+    fn translate_selection(&mut self, ctx: &Context) {
+        let Some(command) = self.config.translate.command.clone() else {
+            self.show_tool_message(
+                "No translation command configured. Add a [translate] command to config.toml.",
+            );
+            return;
+        };
+        let Some(range) = Self::editor_selection(ctx) else {
+            self.show_tool_message("Select the text to translate in the editor first.");
+            return;
+        };
+        let start = graphemes::char_to_byte(&self.content, range.start);
+        let end = graphemes::char_to_byte(&self.content, range.end);
+        let source = self.content[start..end].to_string();
+        self.translation = Some(translate::Translation::start(
+            &command,
+            &self.config.translate.target,
+            range,
+            source,
+            ctx.clone(),
+        ));
+    }
+
+    /// The translated selection, to put in place of the original or in a
+    /// paragraph after it.
+    fn show_translation_window(&mut self, ctx: &Context) {
+        let Some(translation) = &mut self.translation else {
+            return;
+        };
+        translation.poll();
+        let mut open = true;
+        let mut apply = None;
+        egui::Window::new("Translation")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                let preview: String = translation.source.chars().take(200).collect();
+                ui.weak(if preview.len() < translation.source.len() {
+                    format!("{}…", preview)
+                } else {
+                    preview
+                });
+                ui.separator();
+                match &translation.result {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!(
+                                "Translating into '{}'…",
+                                self.config.translate.target
+                            ));
+                        });
+                    }
+                    Some(Err(err)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                    Some(Ok(translated)) => {
+                        egui::ScrollArea::vertical()
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                ui.add(
+                                    TextEdit::multiline(&mut translated.as_str())
+                                        .desired_width(f32::INFINITY),
+                                );
+                            });
+                        ui.horizontal(|ui| {
+                            if ui.button("Replace").clicked() {
+                                apply = Some(true);
+                            }
+                            if ui
+                                .button("Insert Below")
+                                .on_hover_text(
+                                    "Add the translation as a new paragraph after the selection",
+                                )
+                                .clicked()
+                            {
+                                apply = Some(false);
+                            }
+                            if ui.button("Copy").clicked() {
+                                ui.output_mut(|output| output.copied_text = translated.clone());
+                            }
+                        });
+                    }
+                }
+            });
+
+        if let Some(replace) = apply {
+            let translated = match &translation.result {
+                Some(Ok(translated)) => translated.clone(),
+                _ => return,
+            };
+            let start = graphemes::char_to_byte(&self.content, translation.range.start);
+            let end = graphemes::char_to_byte(&self.content, translation.range.end);
+            if self.content.get(start..end) != Some(translation.source.as_str()) {
+                self.show_tool_message("The selection changed since it was translated.");
+            } else if replace {
+                self.content.replace_range(start..end, &translated);
+            } else {
+                let line_end = self.content[end..]
+                    .find('\n')
+                    .map_or(self.content.len(), |offset| end + offset);
+                self.content
+                    .insert_str(line_end, &format!("\n\n{}", translated));
+            }
+            self.update_modified();
+            self.translation = None;
+        } else if !open {
+            self.translation = None;
+        }
+    }
+
+    fn assistant_backend(&self) -> assistant::Backend {
+        let config = &self.config.assistant;
+        assistant::Backend {
+            kind: config.backend.clone(),
+            endpoint: config.endpoint.clone(),
+            model: config.model.clone(),
+            api_key: assistant::load_key(&config.api_key_env),
+            max_tokens: config.max_tokens,
+        }
+    }
+
+    fn start_assistant(&mut self, ctx: &Context, task: assistant::Task) {
+        let Some(range) = Self::editor_selection(ctx) else {
+            self.show_tool_message("Select the text for the assistant in the editor first.");
+            return;
+        };
+        let start = graphemes::char_to_byte(&self.content, range.start);
+        let end = graphemes::char_to_byte(&self.content, range.end);
+        let source = self.content[start..end].to_string();
+        self.assistant_request = Some(assistant::Request::start(
+            self.assistant_backend(),
+            task,
+            &self.assistant_instruction,
+            range,
+            source,
+            ctx.clone(),
+        ));
+    }
+
+    /// Rewrites, summarizes or continues the selection through the
+    /// configured backend, showing the proposal as a diff to accept or
+    /// reject.
+    fn show_assistant_window(&mut self, ctx: &Context) {
+        if let Some(request) = &mut self.assistant_request {
+            request.poll();
+        }
+        let needs_key = self.config.assistant.backend == assistant::OPENAI
+            && assistant::load_key(&self.config.assistant.api_key_env).is_none();
+        let mut open = true;
+        let mut task = None;
+        let mut accept = false;
+        let mut reject = false;
+        let mut store_key = false;
+        egui::Window::new("Assistant")
+            .open(&mut open)
+            .resizable(true)
+            .default_size([560.0, 420.0])
+            .show(ctx, |ui| {
+                ui.weak(format!(
+                    "{} · {}",
+                    self.config.assistant.backend, self.config.assistant.model
+                ));
+                if needs_key {
+                    ui.label(format!(
+                        "No API key found. Set {} or save a key here; it is kept in its own \
+                         file, not in config.toml.",
+                        self.config.assistant.api_key_env
+                    ));
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            TextEdit::singleline(&mut self.assistant_key_draft)
+                                .password(true)
+                                .hint_text("API key"),
+                        );
+                        store_key = ui
+                            .add_enabled(
+                                !self.assistant_key_draft.trim().is_empty(),
+                                egui::Button::new("Save Key"),
+                            )
+                            .clicked();
+                    });
+                    ui.separator();
+                }
+                ui.add(
+                    TextEdit::singleline(&mut self.assistant_instruction)
+                        .desired_width(f32::INFINITY)
+                        .hint_text("Extra instructions (optional), e.g. \"more formal\""),
+                );
+                let busy = self
+                    .assistant_request
+                    .as_ref()
+                    .is_some_and(|request| request.result.is_none());
+                ui.horizontal(|ui| {
+                    for choice in assistant::Task::ALL {
+                        let label = format!("{} Selection", choice.label());
+                        if ui.add_enabled(!busy, egui::Button::new(label)).clicked() {
+                            task = Some(choice);
+                        }
+                    }
+                });
+                let Some(request) = &self.assistant_request else {
+                    return;
+                };
+                ui.separator();
+                match (&request.result, &request.diff) {
+                    (None, _) => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("{}…", request.task.label()));
+                        });
+                    }
+                    (Some(Err(err)), _) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                    (Some(Ok(_)), Some(diff)) => {
+                        ui.horizontal(|ui| {
+                            accept = ui.button("Accept").clicked();
+                            reject = ui.button("Reject").clicked();
+                            ui.separator();
+                            ui.radio_value(&mut self.format_diff_side_by_side, false, "Unified");
+                            ui.radio_value(
+                                &mut self.format_diff_side_by_side,
+                                true,
+                                "Side by Side",
+                            );
+                        });
+                        ui.separator();
+                        diff.show(ui, self.format_diff_side_by_side);
+                    }
+                    (Some(Ok(_)), None) => {}
+                }
+            });
+
+        if store_key {
+            match assistant::store_key(&self.assistant_key_draft) {
+                Ok(()) => self.assistant_key_draft.clear(),
+                Err(err) => self.show_tool_message(err),
+            }
+        }
+        if let Some(task) = task {
+            self.start_assistant(ctx, task);
+        }
+        if accept {
+            if let Some(request) = self.assistant_request.take() {
+                let start = graphemes::char_to_byte(&self.content, request.range.start);
+                let end = graphemes::char_to_byte(&self.content, request.range.end);
+                match request.result {
+                    Some(Ok(proposal))
+                        if self.content.get(start..end) == Some(request.source.as_str()) =>
+                    {
+                        self.content.replace_range(start..end, &proposal);
+                        self.update_modified();
+                    }
+                    _ => self.show_tool_message(
+                        "The selection changed since it was sent to the assistant.",
+                    ),
+                }
+            }
+        }
+        if reject {
+            self.assistant_request = None;
+        }
+        if !open {
+            self.show_assistant = false;
+            self.assistant_request = None;
+        }
+    }
+
+    /// Summarizes the document with the assistant, or from its own
+    /// sentences without it.
+    fn summarize(&mut self, ctx: &Context, use_assistant: bool) {
+        let backend = use_assistant.then(|| self.assistant_backend());
+        self.summary = Some(summarize::Summary::start(
+            backend,
+            &self.content,
+            ctx.clone(),
+        ));
+    }
+
+    /// The bullet summary, to put under a summary heading or copy.
+    fn show_summary_window(&mut self, ctx: &Context) {
+        let Some(summary) = &mut self.summary else {
+            return;
+        };
+        summary.poll();
+        let mut open = true;
+        let mut insert = false;
+        let mut fallback = false;
+        egui::Window::new("Summary")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.weak(summary.method);
+                ui.separator();
+                match &summary.result {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Summarizing…");
+                        });
+                    }
+                    Some(Err(err)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                        fallback = ui.button("Use Key Sentences Instead").clicked();
+                    }
+                    Some(Ok(bullets)) => {
+                        egui::ScrollArea::vertical()
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                ui.add(
+                                    TextEdit::multiline(&mut bullets.as_str())
+                                        .desired_width(f32::INFINITY),
+                                );
+                            });
+                        ui.horizontal(|ui| {
+                            insert = ui
+                                .button(format!("Insert under {}", summarize::HEADING))
+                                .on_hover_text(
+                                    "Replaces an earlier summary, or goes after the title",
+                                )
+                                .clicked();
+                            if ui.button("Copy").clicked() {
+                                ui.output_mut(|output| output.copied_text = bullets.clone());
+                            }
+                        });
+                    }
+                }
+            });
+
+        if insert {
+            if let Some(Ok(bullets)) = self.summary.take().and_then(|summary| summary.result) {
+                self.content = summarize::insert(&self.content, &bullets);
+                self.update_modified();
+            }
+        } else if fallback {
+            self.summarize(ctx, false);
+        } else if !open {
+            self.summary = None;
+        }
+    }
+
+    /// The images without alt text, each with a field to describe it, a
+    /// suggestion from the captioning command and a button to insert it.
+    fn show_alt_text_window(&mut self, ctx: &Context) {
+        let mut finished = Vec::new();
+        self.captions.retain_mut(|caption| {
+            caption.poll();
+            match caption.result.take() {
+                Some(result) => {
+                    finished.push((caption.image.clone(), result));
+                    false
+                }
+                None => true,
+            }
+        });
+        for (image, result) in finished {
+            match result {
+                Ok(text) => {
+                    self.alt_text_drafts.insert((image.line, image.src), text);
+                }
+                Err(err) => self.show_tool_message(err),
+            }
+        }
+
+        let images = alt_text::missing(&self.content);
+        let can_suggest = self.config.alt_text.command.is_some();
+        let suggest_hint = "Set [alt_text] command in config.toml to get suggestions";
+        let captioning: Vec<alt_text::Image> = self
+            .captions
+            .iter()
+            .map(|caption| caption.image.clone())
+            .collect();
+        let mut open = true;
+        let mut goto = None;
+        let mut suggest = None;
+        let mut insert = None;
+        egui::Window::new("Alt Text")
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                if images.is_empty() {
+                    ui.label("Every image has alt text.");
+                    return;
+                }
+                ui.label("Describe each image for readers who cannot see it.");
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("alt_text_grid")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for image in &images {
+                                    let key = (image.line, image.src.clone());
+                                    if ui.link(format!("Line {}", image.line + 1)).clicked() {
+                                        goto = Some(image.line);
+                                    }
+                                    ui.label(&image.src);
+                                    let draft = self.alt_text_drafts.entry(key).or_default();
+                                    ui.add(
+                                        TextEdit::singleline(draft)
+                                            .hint_text("Alt text")
+                                            .desired_width(220.0),
+                                    );
+                                    ui.horizontal(|ui| {
+                                        let pending = captioning.contains(image);
+                                        if pending {
+                                            ui.spinner();
+                                        } else if ui
+                                            .add_enabled(can_suggest, egui::Button::new("Suggest"))
+                                            .on_disabled_hover_text(suggest_hint)
+                                            .clicked()
+                                        {
+                                            suggest = Some(image.clone());
+                                        }
+                                        if ui
+                                            .add_enabled(
+                                                !draft.trim().is_empty(),
+                                                egui::Button::new("Insert"),
+                                            )
+                                            .clicked()
+                                        {
+                                            insert = Some((image.clone(), draft.clone()));
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+        if let Some(line) = goto {
+            self.pending_goto_line = Some(line);
+        }
+        if let (Some(image), Some(command)) = (suggest, &self.config.alt_text.command) {
+            let dir = self
+                .file_path
+                .as_ref()
+                .and_then(|path| Path::new(path).parent().map(Path::to_path_buf))
+                .unwrap_or_else(|| self.working_dir.clone());
+            let location = alt_text::locate(&image.src, &dir);
+            self.captions.push(alt_text::Caption::start(
+                command,
+                image,
+                &location,
+                ctx.clone(),
+            ));
+        }
+        if let Some((image, alt)) = insert {
+            match alt_text::insert(&self.content, &image, &alt) {
+                Some(content) => {
+                    self.content = content;
+                    self.update_modified();
+                    self.alt_text_drafts.remove(&(image.line, image.src));
+                    if self
+                        .diagnostics
+                        .iter()
+                        .any(|diagnostic| diagnostic.source == alt_text::SOURCE)
+                    {
+                        let file = self.file_path.as_deref().map(PathBuf::from);
+                        self.set_diagnostics(
+                            alt_text::SOURCE,
+                            alt_text::check(&self.content, file),
+                        );
+                    }
+                }
+                None => self.show_tool_message("The image moved since the list was made."),
+            }
+        }
+        if !open {
+            self.show_alt_text = false;
+            self.alt_text_drafts.clear();
+            self.captions.clear();
+        }
+    }
+
+    /// The preview, rendered block by block to build the source map.
+    fn show_preview_pane(&mut self, ui: &mut egui::Ui) {
+        let started = Instant::now();
+        let editor_scrolled_to = self.editor_scrolled_to.take();
+        let base_dir = self
+            .file_path
+            .as_ref()
+            .and_then(|path| Path::new(path).parent())
+            .unwrap_or(&self.working_dir);
+        let options = PreviewOptions {
+            show_comments: self.config.preview.show_comments,
+            obsidian: self.obsidian,
+            base_dir: export::normalize(base_dir),
+        };
+        let now = ui.input(|i| i.time);
+        let mut delay = self.config.preview.update_delay_ms as f64 / 1000.0;
+        if self.is_large_file() {
+            delay = delay.max(1.0);
+        }
+        if let Some(wait) = self.preview_text.update(&self.content, now, delay) {
+            ui.ctx()
+                .request_repaint_after(Duration::from_secs_f64(wait));
+        }
+        self.source_map
+            .update_blocks(self.preview_text.text(), &options);
+        self.profiler.record("Preview parsing", started);
+        let started = Instant::now();
+        let mut programmatic = editor_scrolled_to.and_then(|offset| {
+            let line = self.source_map.editor_offset_to_line(offset);
+            self.source_map.line_to_preview_offset(line)
+        });
+        // A restored session scrolls the preview once it shows the document.
+        let caught_up = self.file_job.is_none() && self.preview_text.text() == self.content;
+        if let (Some(session), true) = (&self.restoring, caught_up) {
+            programmatic = Some(session.preview_scroll);
+            self.restoring = None;
+        }
+        let mut area = egui::ScrollArea::vertical()
+            .id_source("preview_scroll")
+            .auto_shrink([false; 2]);
+        if let Some(offset) = programmatic {
+            area = area.vertical_scroll_offset(offset);
+        }
+        let rtl = self.document_direction().is_rtl();
+        let layout = egui::Layout::top_down(if rtl {
+            egui::Align::Max
+        } else {
+            egui::Align::Min
+        });
+        let scroll = area.show(ui, |ui| {
+            ui.with_layout(layout, |ui| self.show_preview_blocks(ui, rtl))
+        });
+        // Wikilinks and links to local documents open in the editor rather
+        // than the browser.
+        let followed = ui.ctx().output_mut(|output| {
+            let clicked = output.open_url.as_ref().is_some_and(|open| {
+                let local = preview::file_url_path(&open.url)
+                    .is_some_and(|path| export::is_markdown(&path) && path.is_file());
+                open.url.starts_with(wikilinks::SCHEME) || local
+            });
+            if clicked {
+                output.open_url.take()
+            } else {
+                None
+            }
+        });
+        if let Some(open) = followed {
+            if open.url.starts_with(wikilinks::SCHEME) {
+                self.follow_wikilink(ui.ctx(), &open.url);
+            } else {
+                self.follow_local_link(ui.ctx(), &open.url);
+            }
+        }
+
+        let offset = scroll.state.offset.y;
+        if !rtl && !self.is_large_file() {
+            self.show_glossary_underlines(ui, scroll.inner_rect, offset);
+        }
+        self.show_hover_preview(ui, scroll.inner_rect, offset);
+        let expected = programmatic.unwrap_or(self.scroll_right);
+        if (offset - expected).abs() > 0.5 && editor_scrolled_to.is_none() {
+            if let Some(line) = self.source_map.preview_offset_to_line(offset) {
+                self.sync_editor_to = Some(self.source_map.line_to_editor_offset(line));
+                ui.ctx().request_repaint();
+            }
+        }
+        self.scroll_right = offset;
+        self.profiler.record("Preview rendering", started);
+    }
+
+    fn open_keybindings(&mut self) {
+        let chords = Action::ALL
+            .into_iter()
+            .map(|action| match self.config.keybindings.get(action.name()) {
+                Some(chord) => chord.clone(),
+                None => action.default_chord().to_string(),
+            })
+            .collect();
+        self.keybindings_draft = Some(chords);
+    }
+
+    /// Lets the chord of each action be changed, flagging chords that do
+    /// not parse or that are bound twice. Only chords that differ from the
+    /// defaults are written to `[keybindings]`.
+    fn show_keybindings_window(&mut self, ctx: &Context) {
+        let Some(draft) = &mut self.keybindings_draft else {
+            return;
+        };
+        let overrides: BTreeMap<String, String> = Action::ALL
+            .into_iter()
+            .zip(draft.iter())
+            .filter(|(action, chord)| chord.trim() != action.default_chord())
+            .map(|(action, chord)| (action.name().to_string(), chord.trim().to_string()))
+            .collect();
+        let (keymap, _) = Keymap::new(&overrides);
+        let conflicts = keymap.conflicts();
+        let invalid = draft
+            .iter()
+            .any(|chord| !chord.trim().is_empty() && shortcut::parse(chord.trim()).is_none());
+        let tools = &self.config.tools.custom;
+
+        let mut open = true;
+        let mut save = false;
+        let mut cancel = false;
+        egui::Window::new("Keyboard Shortcuts")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("keybindings_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (action, chord) in Action::ALL.into_iter().zip(draft.iter_mut()) {
+                            ui.label(action.label());
+                            ui.add(
+                                TextEdit::singleline(chord)
+                                    .desired_width(140.0)
+                                    .hint_text("Unbound"),
+                            );
+                            let conflict = conflicts.iter().find_map(|&(first, second)| {
+                                if first == action {
+                                    Some(second)
+                                } else if second == action {
+                                    Some(first)
+                                } else {
+                                    None
+                                }
+                            });
+                            let label = keymap.label(action);
+                            let tool = tools.iter().find(|tool| {
+                                !label.is_empty()
+                                    && tool
+                                        .hotkey
+                                        .as_deref()
+                                        .and_then(shortcut::parse)
+                                        .is_some_and(|hotkey| shortcut::format(&hotkey) == label)
+                            });
+                            if !chord.trim().is_empty() && shortcut::parse(chord.trim()).is_none() {
+                                ui.colored_label(ui.visuals().error_fg_color, "Invalid chord");
+                            } else if let Some(other) = conflict {
+                                ui.colored_label(
+                                    ui.visuals().error_fg_color,
+                                    format!("Also bound to {}", other.label()),
+                                );
+                            } else if let Some(tool) = tool {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    format!("Taken by the tool {}", tool.name),
+                                );
+                            } else {
+                                ui.label("");
+                            }
+                            ui.end_row();
+                        }
+                    });
+                ui.weak("Write chords like Ctrl+Shift+K or Alt+F5. Leave one empty to unbind it.");
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Reset to Defaults").clicked() {
+                        for (action, chord) in Action::ALL.into_iter().zip(draft.iter_mut()) {
+                            *chord = action.default_chord().to_string();
+                        }
+                    }
+                    save = ui
+                        .add_enabled(!invalid && conflicts.is_empty(), egui::Button::new("Save"))
+                        .clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if save {
+            self.config.keybindings = overrides;
+            self.keymap = keymap;
+            self.save_config();
+            self.keybindings_draft = None;
+        } else if cancel || !open {
+            self.keybindings_draft = None;
+        }
+    }
+
+    fn open_quick_open(&mut self) {
+        self.show_quick_open = true;
+        self.quick_open_query.clear();
+        self.quick_open_selected = 0;
+    }
+
+    /// A Ctrl+P popup that fuzzy-matches the indexed files by path and
+    /// opens the chosen one. Arrow keys move the selection, Enter opens it,
+    /// Escape closes the popup.
+    fn show_quick_open_window(&mut self, ctx: &Context) {
+        const MAX_RESULTS: usize = 50;
+        let files = self
+            .workspace_index
+            .as_ref()
+            .map(|index| index.files())
+            .unwrap_or_default();
+        let mut matches: Vec<(i32, String, Vec<usize>, PathBuf)> = files
+            .into_iter()
+            .filter_map(|path| {
+                let relative = path
+                    .strip_prefix(&self.working_dir)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                let (score, matched) = fuzzy::score(&self.quick_open_query, &relative)?;
+                Some((score, relative, matched, path))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        matches.truncate(MAX_RESULTS);
+
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            )
+        });
+        if up {
+            self.quick_open_selected = self.quick_open_selected.saturating_sub(1);
+        }
+        if down {
+            self.quick_open_selected += 1;
+        }
+        self.quick_open_selected = self
+            .quick_open_selected
+            .min(matches.len().saturating_sub(1));
+
+        let mut chosen = enter
+            .then(|| matches.get(self.quick_open_selected))
+            .flatten()
+            .map(|(_, _, _, path)| path.clone());
+        let indexing = !self
+            .workspace_index
+            .as_ref()
+            .is_some_and(|index| index.is_ready());
+        egui::Window::new("Quick Open")
+            .title_bar(false)
+            .resizable(false)
+            .fixed_size(egui::vec2(480.0, 0.0))
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.quick_open_query)
+                        .hint_text("Go to file")
+                        .desired_width(f32::INFINITY),
+                );
+                response.request_focus();
+                if response.changed() {
+                    self.quick_open_selected = 0;
+                }
+                if indexing {
+                    ui.weak("Indexing the working directory…");
+                } else if matches.is_empty() {
+                    ui.weak("No matching files");
+                }
+                let highlight = ui.visuals().strong_text_color();
+                let normal = ui.visuals().text_color();
+                let font = egui::TextStyle::Body.resolve(ui.style());
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        for (index, (_, relative, matched, path)) in matches.iter().enumerate() {
+                            let mut job = egui::text::LayoutJob::default();
+                            for (position, c) in relative.chars().enumerate() {
+                                let hit = matched.contains(&position);
+                                job.append(
+                                    &c.to_string(),
+                                    0.0,
+                                    egui::TextFormat {
+                                        font_id: font.clone(),
+                                        color: if hit { highlight } else { normal },
+                                        underline: if hit {
+                                            egui::Stroke::new(1.0, highlight)
+                                        } else {
+                                            egui::Stroke::NONE
+                                        },
+                                        ..Default::default()
+                                    },
+                                );
+                            }
+                            let selected = index == self.quick_open_selected;
+                            let response = ui.selectable_label(selected, job);
+                            if selected && (up || down) {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                chosen = Some(path.clone());
+                            }
+                        }
+                    });
+            });
+
+        if let Some(path) = chosen {
+            self.show_quick_open = false;
+            self.request_action(ctx, DeferredAction::OpenPath(path));
+        } else if escape {
+            self.show_quick_open = false;
+        }
+    }
+
+    /// Shows the footnote text, or the start of the linked document, while
+    /// the pointer is over a footnote reference or a local link in the
+    /// preview. The viewer does not report what is under the pointer, so
+    /// the hovered block's text is laid out again to find it.
+    fn show_hover_preview(&mut self, ui: &egui::Ui, viewport: egui::Rect, offset: f32) {
+        // Large files have no glossary underlines to hover.
+        let terms = if self.is_large_file() {
+            BTreeMap::new()
+        } else {
+            self.glossary.with_document(&self.content)
+        };
+        let target = ui
+            .input(|i| i.pointer.hover_pos())
+            .filter(|pos| viewport.contains(*pos))
+            .and_then(|pointer| {
+                let y = pointer.y - viewport.top() + offset;
+                let block = self
+                    .source_map
+                    .preview
+                    .iter()
+                    .find(|block| block.top <= y && y < block.bottom)?;
+                let source = self
+                    .source_map
+                    .blocks
+                    .iter()
+                    .find(|source| source.start_line == block.start_line)?;
+                let (text, mut targets) = peek::targets(&source.markdown);
+                let found = glossary::find(&text, &terms);
+                targets.extend(
+                    found
+                        .into_iter()
+                        .map(|(range, term)| (range, peek::Target::Term(term))),
+                );
+                if targets.is_empty() {
+                    return None;
+                }
+                let font = egui::TextStyle::Body.resolve(ui.style());
+                let galley =
+                    ui.fonts(|fonts| fonts.layout(text, font, Color32::WHITE, viewport.width()));
+                let index = galley
+                    .cursor_from_pos(egui::vec2(pointer.x - viewport.left(), y - block.top))
+                    .ccursor
+                    .index;
+                targets
+                    .into_iter()
+                    .find(|(range, _)| range.start <= index && index <= range.end)
+                    .map(|(_, target)| target)
+            });
+        let Some(target) = target else {
+            self.hover_preview = None;
+            return;
+        };
+        let current = self
+            .hover_preview
+            .as_ref()
+            .is_some_and(|(document, shown, _)| *document == self.file_path && *shown == target);
+        if !current {
+            self.hover_preview = self
+                .peek_markdown(&target)
+                .map(|markdown| (self.file_path.clone(), target, markdown));
+        }
+        if let Some((_, _, markdown)) = &self.hover_preview {
+            egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("hover_preview"), |ui| {
+                ui.set_max_width(360.0);
+                CommonMarkViewer::new("hover_preview").show(ui, &mut self.cache, markdown);
+            });
+        }
+    }
+
+    /// Shows the start of the linked document while Ctrl is held with the
+    /// pointer over a local link in the editor.
+    fn show_editor_peek(&mut self, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput) {
+        let destination = ui
+            .input(|i| i.modifiers.ctrl.then(|| i.pointer.hover_pos()).flatten())
+            .filter(|pos| output.response.rect.contains(*pos))
+            .and_then(|pos| {
+                let cursor = output
+                    .galley
+                    .cursor_from_pos(pos - output.galley_pos)
+                    .pcursor;
+                let line = self.content.split('\n').nth(cursor.paragraph)?;
+                peek::link_at(line, graphemes::char_to_byte(line, cursor.offset))
+            });
+        let Some(destination) = destination else {
+            self.editor_peek = None;
+            return;
+        };
+        let current = self
+            .editor_peek
+            .as_ref()
+            .is_some_and(|(document, shown, _)| {
+                *document == self.file_path && *shown == destination
+            });
+        if !current {
+            self.editor_peek = self
+                .peek_markdown(&peek::Target::Link(destination.clone()))
+                .map(|markdown| (self.file_path.clone(), destination, markdown));
+        }
+        if let Some((_, _, markdown)) = &self.editor_peek {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+            egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("editor_peek"), |ui| {
+                ui.set_max_width(360.0);
+                CommonMarkViewer::new("editor_peek").show(ui, &mut self.cache, markdown);
+            });
+        }
+    }
+
+    /// Draws a dotted line under the glossary terms in the visible
+    /// paragraphs of the preview; hovering one shows its definition. Like
+    /// the hover previews, the paragraph is laid out again to find them,
+    /// so headings, lists and tables, whose layout differs, are skipped.
+    fn show_glossary_underlines(&self, ui: &egui::Ui, viewport: egui::Rect, offset: f32) {
+        let terms = self.glossary.with_document(&self.content);
+        if terms.is_empty() {
+            return;
+        }
+        let painter = ui.painter_at(viewport);
+        let color = ui.visuals().text_color().gamma_multiply(0.7);
+        let font = egui::TextStyle::Body.resolve(ui.style());
+        let visible = self
+            .source_map
+            .preview
+            .iter()
+            .filter(|block| block.bottom >= offset && block.top <= offset + viewport.height());
+        for block in visible {
+            let Some(source) = self
+                .source_map
+                .blocks
+                .iter()
+                .find(|source| source.start_line == block.start_line)
+            else {
+                continue;
+            };
+            let first = source.markdown.trim_start();
+            let paragraph = source.heading_level.is_none()
+                && source.diagram.is_none()
+                && !first.starts_with(['-', '*', '+', '>', '|', '`', '~', '<', '#'])
+                && !first.starts_with(|c: char| c.is_ascii_digit());
+            if !paragraph
+                || !terms
+                    .keys()
+                    .any(|term| source.markdown.contains(term.as_str()))
+            {
+                continue;
+            }
+            let (text, _) = peek::targets(&source.markdown);
+            let found = glossary::find(&text, &terms);
+            if found.is_empty() {
+                continue;
+            }
+            let galley = ui
+                .fonts(|fonts| fonts.layout(text, font.clone(), Color32::WHITE, viewport.width()));
+            let origin = egui::pos2(viewport.left(), viewport.top() + block.top - offset);
+            for (range, _) in found {
+                // One line per row the term is wrapped over.
+                let mut rows: Vec<(f32, f32, f32)> = Vec::new();
+                for index in range {
+                    let start = galley.pos_from_ccursor(egui::text::CCursor::new(index));
+                    let end = galley.pos_from_ccursor(egui::text::CCursor::new(index + 1));
+                    if start.bottom() != end.bottom() {
+                        continue;
+                    }
+                    match rows.last_mut() {
+                        Some((y, _, right)) if *y == start.bottom() => *right = end.left(),
+                        _ => rows.push((start.bottom(), start.left(), end.left())),
+                    }
+                }
+                for (y, left, right) in rows {
+                    painter.extend(egui::Shape::dotted_line(
+                        &[origin + egui::vec2(left, y), origin + egui::vec2(right, y)],
+                        color,
+                        3.0,
+                        0.75,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// The popup text for a footnote reference or local link.
+    fn peek_markdown(&self, target: &peek::Target) -> Option<String> {
+        let destination = match target {
+            peek::Target::Footnote(label) => return peek::footnote(&self.content, label),
+            peek::Target::Term(term) => {
+                let terms = self.glossary.with_document(&self.content);
+                return terms
+                    .get(term)
+                    .map(|meaning| format!("**{}**: {}", term, meaning));
+            }
+            peek::Target::Link(destination) => destination,
+        };
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let (path, heading) = peek::resolve(
+            destination,
+            document.as_deref(),
+            &self.working_dir,
+            &self.markdown_files(),
+        )?;
+        let excerpt = match path {
+            Some(path) if Some(&path) != document.as_ref() => {
+                peek::excerpt(&fs::read_to_string(path).ok()?, heading.as_deref())
+            }
+            _ => peek::excerpt(&self.content, heading.as_deref()),
+        };
+        (!excerpt.is_empty()).then_some(excerpt)
+    }
+
+    /// Opens the document a clicked link leads to, at the heading its
+    /// fragment names.
+    fn follow_local_link(&mut self, ctx: &Context, url: &str) {
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let resolved = peek::resolve(url, document.as_deref(), &self.working_dir, &[]);
+        let Some((Some(path), heading)) = resolved else {
+            return;
+        };
+        let line = heading.and_then(|heading| wikilinks::heading_line(&path, &heading));
+        let action = match line {
+            Some(line) => DeferredAction::OpenAtLine(path, line),
+            None => DeferredAction::OpenPath(path),
+        };
+        self.request_action(ctx, action);
+    }
+
+    /// Opens the target of a clicked wikilink at its heading, or offers to
+    /// create it.
+    fn follow_wikilink(&mut self, ctx: &Context, url: &str) {
+        let Some(link) = wikilinks::from_url(url) else {
+            return;
+        };
+        let Some(link) = wikilinks::WikiLink::parse(&link) else {
+            return;
+        };
+        let current = self.file_path.as_ref().map(PathBuf::from);
+        let path = if link.target.is_empty() {
+            current.clone()
+        } else {
+            wikilinks::resolve(&self.working_dir, &self.markdown_files(), link.target)
+        };
+        let Some(path) = path else {
+            // Joplin links to its attachments by ID as well.
+            let document_dir = current.as_deref().and_then(Path::parent);
+            let dirs: Vec<&Path> = document_dir
+                .into_iter()
+                .chain([self.working_dir.as_path()])
+                .collect();
+            match joplin::resource(&dirs, link.target) {
+                Some(resource) => {
+                    if let Err(err) = mdbook::open_in_browser(&resource.to_string_lossy()) {
+                        self.notifications
+                            .error("Opening an attachment", err.to_string());
+                    }
+                }
+                None => {
+                    self.wikilink_to_create =
+                        Some(wikilinks::new_note_path(&self.working_dir, link.target))
+                }
+            }
+            return;
+        };
+        let line = link
+            .heading
+            .and_then(|heading| wikilinks::heading_line(&path, heading));
+        let action = match line {
+            Some(line) => DeferredAction::OpenAtLine(path, line),
+            None => DeferredAction::OpenPath(path),
+        };
+        self.request_action(ctx, action);
+    }
+
+    fn show_create_wikilink_window(&mut self, ctx: &Context) {
+        let Some(path) = self.wikilink_to_create.clone() else {
+            return;
+        };
+        let shown = path.strip_prefix(&self.working_dir).unwrap_or(&path);
+        let mut create = false;
+        let mut cancel = false;
+        egui::Window::new("Create Note")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("{} does not exist yet.", shown.display()));
+                ui.horizontal(|ui| {
+                    create = ui.button("Create and Open").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+        if cancel {
+            self.wikilink_to_create = None;
+        }
+        if !create {
+            return;
+        }
+        self.wikilink_to_create = None;
+        let title = path
+            .file_stem()
+            .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        self.create_note(ctx, path, &title);
+    }
+
+    /// Asks for the title of a file numbered after the others in its folder.
+    fn show_numbered_file_window(&mut self, ctx: &Context) {
+        let Some(dir) = self.numbered_file_dir.clone() else {
+            return;
+        };
+        let mut open = true;
+        let mut create = false;
+        egui::Window::new("New Numbered File")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let shown = dir.strip_prefix(&self.working_dir).unwrap_or(&dir);
+                ui.horizontal(|ui| {
+                    ui.label(format!("Folder: {}/", shown.display()));
+                    if ui.button("Change...").clicked() {
+                        if let Some(folder) = FileDialog::new().set_directory(&dir).pick_folder() {
+                            self.numbered_file_dir = Some(folder);
+                        }
+                    }
+                });
+                let response =
+                    ui.add(TextEdit::singleline(&mut self.numbered_file_title).hint_text("Title"));
+                response.request_focus();
+                let title = self.numbered_file_title.trim();
+                let path = numbering::next_path(&dir, title);
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                ui.weak(format!("Creates {}", name));
+                let submitted =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                let clicked = ui
+                    .add_enabled(!title.is_empty(), egui::Button::new("Create"))
+                    .clicked();
+                create = !title.is_empty() && (clicked || submitted);
+            });
+        if create {
+            let dir = self.numbered_file_dir.take().unwrap_or(dir);
+            let title = std::mem::take(&mut self.numbered_file_title);
+            let title = title.trim();
+            self.create_note(ctx, numbering::next_path(&dir, title), title);
+        } else if !open {
+            self.numbered_file_dir = None;
+            self.numbered_file_title.clear();
+        }
+    }
+
+    /// Writes a new note at `path` from its folder's workspace template, or
+    /// with `title` as its heading, and opens it.
+    fn create_note(&mut self, ctx: &Context, path: PathBuf, title: &str) {
+        let dir = path.parent().unwrap_or(&self.working_dir);
+        let content = match templates::for_folder(&self.working_dir, dir) {
+            Some(template) => match templates::render_for_note(&template, &path, title) {
+                Ok(content) => content,
+                Err(err) => {
+                    self.notifications.warning("Note template", err);
+                    format!("# {}\n\n", title)
+                }
+            },
+            None => format!("# {}\n\n", title),
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| {
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+            })
+            .and_then(|mut file| file.write_all(content.as_bytes()));
+        match result {
+            Ok(()) => {
+                self.file_tree.refresh();
+                self.request_action(ctx, DeferredAction::OpenPath(path));
+            }
+            Err(err) => {
+                self.show_tool_message(format!("Failed to create {}: {}", path.display(), err))
+            }
+        }
+    }
+
+    fn export_template(&self) -> Option<PathBuf> {
+        self.config.export_template(&self.working_dir)
+    }
+
+    fn html_options(&self) -> Result<export::HtmlOptions, String> {
+        self.config.html_options(&self.working_dir)
+    }
+
+    fn show_template_menu(&mut self, ui: &mut egui::Ui) {
+        let current = self.export_template();
+        let label = current
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map_or("Built-in".to_string(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        ui.weak(format!("Current: {}", label));
+        if ui.button("Choose...").clicked() {
+            let mut dialog = FileDialog::new().add_filter("HTML", &["html", "htm"]);
+            if self.working_dir.is_dir() {
+                dialog = dialog.set_directory(&self.working_dir);
+            }
+            if let Some(path) = dialog.pick_file() {
+                // Templates inside the workspace are stored relative to it.
+                let stored = path
+                    .strip_prefix(&self.working_dir)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(path);
+                let key = self.working_dir.display().to_string();
+                self.config.export.templates.insert(key, stored);
+                self.save_config();
+            }
+            ui.close_menu();
+        }
+        if ui
+            .add_enabled(current.is_some(), egui::Button::new("Use Built-in"))
+            .clicked()
+        {
+            let key = self.working_dir.display().to_string();
+            self.config.export.templates.remove(&key);
+            self.save_config();
+            ui.close_menu();
+        }
+    }
+
+    /// Writes the document as HTML with the print typography to a temporary
+    /// file and opens it in the browser, which can justify and hyphenate.
+    fn open_print_preview(&mut self) {
+        let title = self
+            .file_path
+            .as_ref()
+            .and_then(|path| Path::new(path).file_stem())
+            .map_or("Untitled".to_string(), |stem| {
+                stem.to_string_lossy().into_owned()
+            });
+        let html = match self.html_options() {
+            Ok(html) => export::markdown_to_html(&self.content, &title, &html),
+            Err(err) => {
+                self.show_tool_message(err);
+                return;
+            }
+        };
+        let base = self
+            .file_path
+            .as_ref()
+            .and_then(|path| Path::new(path).parent().map(Path::to_path_buf))
+            .unwrap_or_else(|| self.working_dir.clone());
+        let html = export::with_base(&html, &base);
+        let path = env::temp_dir().join("md-echo-print-preview.html");
+        let result = fs::write(&path, html)
+            .and_then(|_| mdbook::open_in_browser(&path.display().to_string()));
+        if let Err(err) = result {
+            self.show_tool_message(format!("Could not open print preview: {}", err));
+        }
+    }
+
+    fn is_large_file(&self) -> bool {
+        self.content.len() > self.config.editor.large_file_kb * 1024
+    }
+
+    /// The status bar counts, recounted only when the history saw the text
+    /// change. In large-file mode that is at most once a second.
+    fn document_stats(&mut self) -> stats::DocumentStats {
+        let generation = self.history.generation();
+        match self.stats {
+            Some((counted, stats)) if counted == generation => stats,
+            _ => {
+                let stats = stats::DocumentStats::of(&self.content);
+                self.stats = Some((generation, stats));
+                stats
+            }
+        }
+    }
+
+    /// The merge conflicts in the text, found again only when the history
+    /// saw the text change.
+    fn document_conflicts(&mut self) -> &[conflicts::Conflict] {
+        let generation = self.history.generation();
+        if self.conflicts.0 != generation {
+            self.conflicts = (generation, conflicts::find_conflicts(&self.content));
+        }
+        &self.conflicts.1
+    }
+
+    /// Renders the preview blocks and records where each one landed.
+    fn show_preview_blocks(&mut self, ui: &mut egui::Ui, rtl: bool) {
+        let origin_y = ui.min_rect().top();
+        // The viewer already leaves about a line after each block.
+        let font_size = egui::TextStyle::Body.resolve(ui.style()).size;
+        let extra_spacing = (self.config.preview.paragraph_spacing - 1.0).max(0.0) * font_size;
+        let mut preview = Vec::with_capacity(self.source_map.blocks.len());
+        let keys = section_keys(&self.source_map.blocks);
+        let (light, dark) = (
+            &self.config.preview.code_theme_light,
+            &self.config.preview.code_theme_dark,
+        );
+        let viewer = |index: usize| {
+            CommonMarkViewer::new(("preview_block", index))
+                .syntax_theme_light(light)
+                .syntax_theme_dark(dark)
+        };
+        let now = ui.input(|i| i.time);
+        let mermaid_command = self.config.preview.mermaid_command.as_deref();
+        if let Some(command) = mermaid_command {
+            self.diagrams.poll(ui.ctx(), command, now);
+        }
+        let link_cards = self.link_cards_enabled();
+        if link_cards {
+            self.link_cards.poll(ui.ctx());
+        }
+        let mut toggled = None;
+        let mut ticked = None;
+        let mut media_action = None;
+        let mut heights = self.source_map.take_heights(ui.available_width());
+        let visible = ui.clip_rect().y_range();
+        // Large files guess the height of blocks not drawn yet from their
+        // line count, so only the blocks in view are ever drawn.
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        let large = self.is_large_file();
+        // Level of the collapsed heading whose section is being skipped.
+        let mut hidden_below: Option<usize> = None;
+        for (index, block) in self.source_map.blocks.iter().enumerate() {
+            if let Some(hidden) = hidden_below {
+                if block.heading_level.is_some_and(|level| level <= hidden) {
+                    hidden_below = None;
+                } else {
+                    continue;
+                }
+            }
+            let top = ui.cursor().top() - origin_y;
+            // Blocks drawn before that are out of view only take up their
+            // space. Headings are always drawn, as they fold sections.
+            let estimate = || (block.end_line + 2 - block.start_line) as f32 * row_height;
+            let height = heights[index].or_else(|| large.then(estimate));
+            let offscreen = height.filter(|height| {
+                block.heading_level.is_none()
+                    && (top + origin_y + height < visible.min || top + origin_y > visible.max)
+            });
+            if let Some(height) = offscreen {
+                ui.add_space(height);
+                preview.push(PreviewBlock {
+                    start_line: block.start_line,
+                    end_line: block.end_line,
+                    top,
+                    bottom: top + height,
+                });
+                ui.add_space(extra_spacing);
+                continue;
+            }
+            if block.page_break {
+                show_page_break(ui);
+            } else if let (Some(key), Some(level)) = (&keys[index], block.heading_level) {
+                let collapsed = self.collapsed_sections.contains(key);
+                if collapsed {
+                    hidden_below = Some(level);
+                }
+                let layout = if rtl {
+                    egui::Layout::right_to_left(egui::Align::Center)
+                } else {
+                    egui::Layout::left_to_right(egui::Align::Center)
+                };
+                ui.with_layout(layout, |ui| {
+                    let size = egui::Vec2::splat(ui.spacing().icon_width);
+                    let (_, response) = ui.allocate_exact_size(size, egui::Sense::click());
+                    let response = response.on_hover_text(if collapsed {
+                        "Expand section"
+                    } else {
+                        "Collapse section"
+                    });
+                    let openness = if collapsed { 0.0 } else { 1.0 };
+                    egui::collapsing_header::paint_default_icon(ui, openness, &response);
+                    if response.clicked() {
+                        toggled = Some(key.clone());
+                    }
+                    ui.vertical(|ui| {
+                        if block.has_changes {
+                            show_review_block(ui, &block.markdown, egui::TextStyle::Heading);
+                        } else {
+                            viewer(index).show(ui, &mut self.cache, &block.markdown);
+                        }
+                    });
+                });
+            } else if block.has_changes {
+                show_review_block(ui, &block.markdown, egui::TextStyle::Body);
+            } else if let (Some(source), Some(_)) = (&block.diagram, mermaid_command) {
+                match self.diagrams.get(source, now) {
+                    diagrams::Diagram::Ready(texture) => {
+                        ui.add(
+                            egui::Image::from_texture(texture)
+                                .fit_to_original_size(1.0)
+                                .max_width(ui.available_width()),
+                        );
+                    }
+                    // Show the code until the diagram renders, or instead of it.
+                    diagram => {
+                        viewer(index).show(ui, &mut self.cache, &block.markdown);
+                        match diagram {
+                            diagrams::Diagram::Failed(err) => {
+                                ui.weak("⚠ Diagram not rendered").on_hover_text(err);
+                            }
+                            _ => {
+                                ui.weak("Rendering diagram…");
+                            }
+                        }
+                    }
+                }
+            } else if let Some(embed) = &block.media {
+                let player = self.media_players.get_mut(&embed.src);
+                if let Some(action) = show_media_player(ui, embed, player) {
+                    media_action = Some((embed.clone(), action));
+                }
+            } else if let (Some(url), true) = (&block.bare_url, link_cards) {
+                match self.link_cards.get(url) {
+                    link_cards::CardState::Ready(card) => show_link_card(ui, url, &card),
+                    link_cards::CardState::Pending => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.hyperlink(url);
+                        });
+                    }
+                    link_cards::CardState::Failed => {
+                        viewer(index).show(ui, &mut self.cache, &block.markdown);
+                    }
+                }
+            } else if block.task_count > 0 {
+                // The viewer lets checkboxes be clicked when it may edit the
+                // text; find which one changed and tick it in the source.
+                let mut markdown = block.markdown.clone();
+                viewer(index).show_mut(ui, &mut self.cache, &mut markdown);
+                if markdown != block.markdown {
+                    ticked = tasks::toggled(&block.markdown, &markdown).map(|task| (index, task));
+                }
+            } else {
+                viewer(index).show(ui, &mut self.cache, &block.markdown);
+            }
+            let bottom = ui.cursor().top() - origin_y;
+            heights[index] = Some(bottom - top);
+            if block.has_changes {
+                // Mark blocks with pending suggestions in the margin.
+                let x = if rtl {
+                    ui.max_rect().right() - 1.0
+                } else {
+                    ui.max_rect().left() + 1.0
+                };
+                ui.painter().vline(
+                    x,
+                    (top + origin_y)..=(bottom + origin_y),
+                    egui::Stroke::new(3.0, critic::SUBSTITUTION_COLOR),
+                );
+            }
+            preview.push(PreviewBlock {
+                start_line: block.start_line,
+                end_line: block.end_line,
+                top,
+                bottom,
+            });
+            ui.add_space(extra_spacing);
+        }
+        self.source_map.preview = preview;
+        self.source_map.keep_heights(heights);
+        if let Some(key) = toggled {
+            if !self.collapsed_sections.remove(&key) {
+                self.collapsed_sections.insert(key);
+            }
+        }
+        if let Some((block, task)) = ticked {
+            self.toggle_task(block, task);
+        }
+        if let Some((embed, action)) = media_action {
+            self.apply_media_action(&embed, action);
+        }
+    }
+
+    fn apply_media_action(&mut self, embed: &media::Embed, action: MediaAction) {
+        let location = if embed.src.contains("://") {
+            embed.src.clone()
+        } else {
+            let dir = self
+                .file_path
+                .as_ref()
+                .and_then(|path| Path::new(path).parent().map(Path::to_path_buf))
+                .unwrap_or_else(|| self.working_dir.clone());
+            dir.join(&embed.src).display().to_string()
+        };
+        match action {
+            MediaAction::Play => {
+                let program = &self.config.preview.media_player;
+                match media::Player::start(program, &location, embed.kind) {
+                    Ok(player) => {
+                        self.media_players.insert(embed.src.clone(), player);
+                    }
+                    Err(err) => self.show_tool_message(err),
+                }
+            }
+            MediaAction::Pause(paused) => {
+                if let Some(player) = self.media_players.get_mut(&embed.src) {
+                    player.set_paused(paused);
+                }
+            }
+            MediaAction::Seek(seconds) => {
+                if let Some(player) = self.media_players.get_mut(&embed.src) {
+                    player.seek(seconds);
+                }
+            }
+            MediaAction::Stop => {
+                self.media_players.remove(&embed.src);
+            }
+            MediaAction::Open => {
+                if let Err(err) = mdbook::open_in_browser(&location) {
+                    self.show_tool_message(format!("Could not open {}: {}", location, err));
+                }
+            }
+        }
+    }
+
+    fn show_code_theme_menu(&mut self, ui: &mut egui::Ui) {
+        let dark_mode = ui.visuals().dark_mode;
+        ui.weak(if dark_mode {
+            "For dark themes"
+        } else {
+            "For light themes"
+        });
+        let preview = &mut self.config.preview;
+        let current = if dark_mode {
+            &mut preview.code_theme_dark
+        } else {
+            &mut preview.code_theme_light
+        };
+        let mut changed = false;
+        egui::ScrollArea::vertical()
+            .max_height(320.0)
+            .show(ui, |ui| {
+                for theme in preview::code_themes() {
+                    changed |= ui.radio_value(current, theme.clone(), theme).clicked();
+                }
+            });
+        if changed {
+            self.save_config();
+            ui.close_menu();
+        }
+    }
+
+    /// Ticks or unticks the `task`-th task list item of preview block
+    /// `block` in the source.
+    fn toggle_task(&mut self, block: usize, task: usize) {
+        let Some(block) = self.source_map.blocks.get(block) else {
+            return;
+        };
+        let start = graphemes::line_to_byte(&self.content, block.start_line);
+        let end = graphemes::line_to_byte(&self.content, block.end_line + 1);
+        let mut source = self.content[start..end].to_string();
+        // Hidden comments can hold tasks the preview does not show; leave
+        // such blocks alone rather than tick the wrong item.
+        if tasks::find(&source).len() != block.task_count || !tasks::toggle(&mut source, task) {
+            return;
+        }
+        self.content.replace_range(start..end, &source);
+        self.update_modified();
+    }
+
+    fn track_ime(&mut self, ctx: &Context) {
+        ctx.input(|i| {
+            for event in &i.events {
+                match event {
+                    egui::Event::CompositionStart | egui::Event::CompositionUpdate(_) => {
+                        self.ime_composing = true
+                    }
+                    egui::Event::CompositionEnd(_) => self.ime_composing = false,
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    /// egui moves the cursor one char at a time, which can land inside a
+    /// grapheme cluster (emoji sequences, combining accents). Push it to the
+    /// cluster boundary in the direction it moved. Left alone while an IME
+    /// composition owns the selection.
+    fn snap_editor_cursor(&mut self, ctx: &Context, mut state: egui::text_edit::TextEditState) {
+        if self.ime_composing {
+            self.editor_cursor = None;
+            return;
+        }
+        let Some(range) = state.cursor.char_range() else {
+            return;
+        };
+        let index = range.primary.index;
+        let previous = self.editor_cursor.replace(index);
+        if previous == Some(index) {
+            return;
+        }
+
+        let forward = previous.is_none_or(|previous| index > previous);
+        let primary = graphemes::snap_to_boundary(&self.content, index, forward);
+        let secondary = if range.secondary.index == index {
+            primary
+        } else {
+            let outward = range.secondary.index > index;
+            graphemes::snap_to_boundary(&self.content, range.secondary.index, outward)
+        };
+        if primary != index || secondary != range.secondary.index {
+            state
+                .cursor
+                .set_char_range(Some(egui::text::CCursorRange::two(
+                    egui::text::CCursor::new(secondary),
+                    egui::text::CCursor::new(primary),
+                )));
+            TextEdit::store_state(ctx, Self::editor_id(), state);
+            self.editor_cursor = Some(primary);
+        }
+    }
+
+    fn show_formatting_toolbar(&mut self, ui: &mut egui::Ui) {
+        let mut clicked = None;
+        ui.horizontal(|ui| {
+            for markup in Markup::ALL {
+                let label = match markup {
+                    Markup::Bold => egui::RichText::new("B").strong(),
+                    Markup::Italic => egui::RichText::new("I").italics(),
+                    Markup::Strikethrough => egui::RichText::new("S").strikethrough(),
+                    Markup::Code => egui::RichText::new("</>").monospace(),
+                    Markup::Link => egui::RichText::new("🔗"),
+                };
+                let hover = match self.keymap.label(Action::Markup(markup)) {
+                    chord if chord.is_empty() => markup.label().to_string(),
+                    chord => format!("{} ({})", markup.label(), chord),
+                };
+                if ui.button(label).on_hover_text(hover).clicked() {
+                    clicked = Some(markup);
+                }
+            }
+        });
+        ui.separator();
+        if let Some(markup) = clicked {
+            self.apply_markup(ui.ctx(), markup);
+        }
+    }
+
+    fn run_action(&mut self, ctx: &Context, action: Action) {
+        match action {
+            Action::New => self.pending_new = true,
+            Action::Open => self.pending_open = true,
+            Action::QuickOpen => self.open_quick_open(),
+            Action::Save => self.pending_save = true,
+            Action::SaveAs => self.pending_save_as = true,
+            Action::Quit => self.pending_exit = true,
+            Action::Lint => self.pending_lint = true,
+            Action::Format => self.pending_format = true,
+            Action::SearchInFiles => {
+                self.show_search = true;
+                self.search_focus = true;
+            }
+            Action::ToggleFileTree => self.toggle_file_tree(),
+            Action::ToggleTerminal => self.show_terminal = !self.show_terminal,
+            Action::PasteImage => self.pending_image_paste = true,
+            Action::PreviousChapter => self.pending_chapter = Some(false),
+            Action::NextChapter => self.pending_chapter = Some(true),
+            Action::Undo => self.step_history(ctx, false),
+            Action::Redo => self.step_history(ctx, true),
+            Action::Markup(markup) => self.apply_markup(ctx, markup),
+        }
+    }
+
+    /// Records the latest change to the document for undo. Large files are
+    /// only compared with the last step once a second, so changes within
+    /// that second undo together.
+    fn observe_history(&mut self, now: f64) {
+        let due = self.history.since_observed(now) >= LARGE_FILE_HISTORY_INTERVAL;
+        if due || !self.is_large_file() {
+            self.history
+                .observe(&self.content, now, self.config.editor.undo_depth);
+        }
+    }
+
+    /// Moves the document back (or forward, for `redo`) a step in its
+    /// history and puts the cursor after the part that changed.
+    fn step_history(&mut self, ctx: &Context, redo: bool) {
+        let now = ctx.input(|i| i.time);
+        self.history
+            .observe(&self.content, now, self.config.editor.undo_depth);
+        let restored = if redo {
+            self.history.redo(&self.content)
+        } else {
+            self.history.undo(&self.content)
+        };
+        let Some(restored) = restored else {
+            return;
+        };
+        let cursor = history::changed_end(&self.content, &restored);
+        self.content = restored;
+        self.update_modified();
+
+        let id = Self::editor_id();
+        let mut state = TextEdit::load_state(ctx, id).unwrap_or_default();
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(
+                egui::text::CCursor::new(cursor),
+            )));
+        TextEdit::store_state(ctx, id, state);
+        self.editor_cursor = Some(cursor);
+        self.vim_cursor = None;
+    }
+
+    /// Passes this frame's keys to the Vim emulation while the editor has
+    /// focus. Insert mode only takes Escape and leaves typing to the
+    /// editor; the other modes take every key without Ctrl.
+    fn handle_vim_keys(&mut self, ctx: &Context) {
+        let id = Self::editor_id();
+        if !ctx.memory(|memory| memory.has_focus(id)) {
+            return;
+        }
+        let insert = self.vim.mode == vim::Mode::Insert;
+        let keys = ctx.input_mut(|i| {
+            let mut keys = Vec::new();
+            i.events.retain(|event| match event {
+                egui::Event::Key {
+                    key,
+                    pressed,
+                    modifiers,
+                    ..
+                } if !modifiers.command && !modifiers.ctrl => {
+                    let key = match key {
+                        egui::Key::Escape => Some(vim::Key::Escape),
+                        _ if insert => return true,
+                        egui::Key::Enter => Some(vim::Key::Enter),
+                        egui::Key::Backspace => Some(vim::Key::Backspace),
+                        egui::Key::ArrowLeft => Some(vim::Key::Left),
+                        egui::Key::ArrowRight => Some(vim::Key::Right),
+                        egui::Key::ArrowUp => Some(vim::Key::Up),
+                        egui::Key::ArrowDown => Some(vim::Key::Down),
+                        _ => None,
+                    };
+                    keys.extend(key.filter(|_| *pressed));
+                    false
+                }
+                egui::Event::Text(text) if !insert => {
+                    keys.extend(text.chars().map(vim::Key::Char));
+                    false
+                }
+                egui::Event::Paste(_)
+                | egui::Event::Cut
+                | egui::Event::CompositionUpdate(_)
+                | egui::Event::CompositionEnd(_) => insert,
+                _ => true,
+            });
+            keys
+        });
+        if keys.is_empty() {
+            return;
+        }
+
+        let mut state = TextEdit::load_state(ctx, id).unwrap_or_default();
+        let range = state.cursor.char_range();
+        let mut cursor = match (range, &self.vim_cursor) {
+            (Some(range), Some((shown, cursor)))
+                if range.secondary.index == shown.start && range.primary.index == shown.end =>
+            {
+                *cursor
+            }
+            (Some(range), _) => range.primary.index,
+            (None, _) => 0,
+        };
+        let before = self.content.clone();
+        let mut undo = false;
+        for key in keys {
+            match self.vim.handle(&mut self.content, &mut cursor, key) {
+                Some(vim::Command::Save) => {
+                    self.save_file(false);
+                }
+                Some(vim::Command::Quit) => self.pending_exit = true,
+                Some(vim::Command::ForceQuit) => self.request_exit(ctx),
+                Some(vim::Command::SaveQuit) if self.save_file(false) => {
+                    self.after_save = Some(DeferredAction::Exit);
+                }
+                Some(vim::Command::SaveQuit) => {}
+                Some(vim::Command::Undo) => {
+                    undo = true;
+                    break;
+                }
+                None => {}
+            }
+        }
+
+        if self.content != before {
+            self.update_modified();
+        }
+        if undo {
+            self.step_history(ctx, false);
+            return;
+        }
+        let shown = self.vim.selection(&self.content, cursor);
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::two(
+                egui::text::CCursor::new(shown.start),
+                egui::text::CCursor::new(shown.end),
+            )));
+        TextEdit::store_state(ctx, id, state);
+        self.editor_cursor = Some(shown.end);
+        self.vim_cursor = Some((shown, cursor));
+    }
+
+    /// Wraps the editor selection in `markup` (or unwraps it) and selects
+    /// the formatted text or placeholder.
+    fn apply_markup(&mut self, ctx: &Context, markup: Markup) {
+        let id = Self::editor_id();
+        let mut state = TextEdit::load_state(ctx, id).unwrap_or_default();
+        let end = self.content.chars().count();
+        let selection = state.cursor.char_range().map_or(end..end, |range| {
+            let (a, b) = (range.primary.index, range.secondary.index);
+            a.min(b)..a.max(b)
+        });
+        let edit = inline_markup::apply(&self.content, selection, markup);
+        self.content = edit.text;
+        self.update_modified();
+
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::two(
+                egui::text::CCursor::new(edit.selection.start),
+                egui::text::CCursor::new(edit.selection.end),
+            )));
+        TextEdit::store_state(ctx, id, state);
+        self.editor_cursor = Some(edit.selection.end);
+        ctx.memory_mut(|memory| memory.request_focus(id));
+    }
+
+    /// Runs a Tools → Table command on the table at the editor cursor.
+    fn apply_table_action(&mut self, ctx: &Context, action: TableAction) {
+        let id = Self::editor_id();
+        let mut state = TextEdit::load_state(ctx, id).unwrap_or_default();
+        let cursor = state
+            .cursor
+            .char_range()
+            .map_or(self.content.chars().count(), |range| range.primary.index);
+        let edit = match table::apply(&self.content, cursor, action) {
+            Ok(edit) => edit,
+            Err(message) => {
+                self.show_tool_message(message);
+                return;
+            }
+        };
+        self.content = edit.text;
+        self.update_modified();
+
+        let cursor = egui::text::CCursor::new(edit.cursor);
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+        TextEdit::store_state(ctx, id, state);
+        self.editor_cursor = Some(edit.cursor);
+        ctx.memory_mut(|memory| memory.request_focus(id));
+    }
+
+    /// Inserts `text` at the editor cursor (replacing any selection), or at
+    /// the end if the editor has no cursor yet, and moves the cursor after it.
+    fn insert_at_cursor(&mut self, ctx: &Context, text: &str) {
+        let id = Self::editor_id();
+        let mut state = TextEdit::load_state(ctx, id).unwrap_or_default();
+        let end = self.content.chars().count();
+        let (start, stop) = state.cursor.char_range().map_or((end, end), |range| {
+            let (a, b) = (range.primary.index, range.secondary.index);
+            (a.min(b).min(end), a.max(b).min(end))
+        });
+        let byte_start = graphemes::char_to_byte(&self.content, start);
+        let byte_stop = graphemes::char_to_byte(&self.content, stop);
+        self.content.replace_range(byte_start..byte_stop, text);
+        self.update_modified();
+
+        let cursor = egui::text::CCursor::new(start + text.chars().count());
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+        TextEdit::store_state(ctx, id, state);
+        self.editor_cursor = Some(cursor.index);
+        ctx.memory_mut(|memory| memory.request_focus(id));
+    }
+
+    /// Saves the clipboard image under `[assets]` next to the document and
+    /// links it at the cursor.
+    fn paste_image(&mut self, ctx: &Context) {
+        let Some(document) = self.file_path.as_ref().map(PathBuf::from) else {
+            self.show_tool_message(
+                "Save the document first so pasted images have a folder to go in.",
+            );
+            return;
+        };
+        let target = assets::target_path(
+            &document,
+            &self.config.assets.folder,
+            &self.config.assets.paste_name,
+        );
+        match assets::save_clipboard_image(&target) {
+            Ok(true) => {
+                let link = assets::image_link(&document, &target);
+                self.insert_at_cursor(ctx, &link);
+            }
+            Ok(false) => self.show_tool_message("The clipboard holds no image."),
+            Err(err) => self.show_tool_message(format!("Could not paste image: {}", err)),
+        }
+    }
+
+    /// Opens a dropped markdown file (asking about unsaved changes first), or
+    /// queues dropped images for the editor to link where they were dropped.
+    fn handle_dropped_files(&mut self, ctx: &Context) {
+        let hovering = ctx.input(|i| !i.raw.hovered_files.is_empty());
+        if hovering {
+            let screen = ctx.screen_rect();
+            egui::Area::new(egui::Id::new("drop_overlay"))
+                .order(egui::Order::Foreground)
+                .fixed_pos(screen.min)
+                .interactable(false)
+                .show(ctx, |ui| {
+                    ui.painter()
+                        .rect_filled(screen, 0.0, Color32::from_black_alpha(96));
+                    ui.painter().text(
+                        screen.center(),
+                        egui::Align2::CENTER_CENTER,
+                        "Drop a markdown file to open it, or images to insert them",
+                        egui::FontId::proportional(18.0),
+                        Color32::WHITE,
+                    );
+                });
+        }
+
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        if dropped.is_empty() {
+            return;
+        }
+        if let Some(markdown) = dropped.iter().find(|path| export::is_markdown(path)) {
+            self.request_action(ctx, DeferredAction::OpenPath(markdown.clone()));
+            return;
+        }
+        let (images, others): (Vec<PathBuf>, Vec<PathBuf>) =
+            dropped.into_iter().partition(|path| assets::is_image(path));
+        if images.is_empty() {
+            let names: Vec<String> = others
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            self.show_tool_message(format!(
+                "Only markdown files and images can be dropped here:\n{}",
+                names.join("\n")
+            ));
+            return;
+        }
+        self.pending_image_drop = images;
+    }
+
+    /// Copies the dropped images into the asset folder and links them at
+    /// char `index`, or at the cursor if they were not dropped on the text.
+    fn insert_dropped_images(&mut self, ctx: &Context, index: Option<usize>) {
+        let images = std::mem::take(&mut self.pending_image_drop);
+        let Some(document) = self.file_path.as_ref().map(PathBuf::from) else {
+            self.show_tool_message(
+                "Save the document first so dropped images have a folder to go in.",
+            );
+            return;
+        };
+        let mut links = Vec::new();
+        let mut errors = Vec::new();
+        for image in &images {
+            match assets::import_file(&document, &self.config.assets.folder, image) {
+                Ok(target) => links.push(assets::image_link(&document, &target)),
+                Err(err) => errors.push(err),
+            }
+        }
+        if !links.is_empty() {
+            if let Some(index) = index {
+                let id = Self::editor_id();
+                let mut state = TextEdit::load_state(ctx, id).unwrap_or_default();
+                let cursor = egui::text::CCursor::new(index);
+                state
+                    .cursor
+                    .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+                TextEdit::store_state(ctx, id, state);
+            }
+            self.insert_at_cursor(ctx, &links.join("\n"));
+            ctx.request_repaint();
+        }
+        if !errors.is_empty() {
+            self.show_tool_message(format!("Could not copy images:\n{}", errors.join("\n")));
+        }
+    }
+
+    fn place_cursor_at_line(&self, ctx: &Context, line: usize) {
+        self.place_cursor(ctx, line, 0);
+    }
+
+    /// Puts the cursor at grapheme `column` of zero-based `line`.
+    fn place_cursor(&self, ctx: &Context, line: usize, column: usize) {
+        let line_start: usize = self
+            .content
+            .split('\n')
+            .take(line)
+            .map(|text| text.chars().count() + 1)
+            .sum();
+        let within = self
+            .content
+            .split('\n')
+            .nth(line)
+            .map_or(0, |text| graphemes::column_to_char(text, column));
+        let char_index = line_start + within;
+        let id = Self::editor_id();
+        let mut state = TextEdit::load_state(ctx, id).unwrap_or_default();
+        let cursor = egui::text::CCursor::new(char_index);
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+        TextEdit::store_state(ctx, id, state);
+        ctx.memory_mut(|memory| memory.request_focus(id));
+    }
+
+    /// Opens `file` (if it is not already the current document) and moves
+    /// the cursor to `line`.
+    fn go_to_location(&mut self, ctx: &Context, file: Option<&Path>, line: Option<usize>) {
+        let is_current = match (file, &self.file_path) {
+            (None, _) => true,
+            (Some(file), Some(current)) => {
+                export::normalize(file) == export::normalize(Path::new(current))
+            }
+            (Some(_), None) => false,
+        };
+        if is_current {
+            self.pending_goto_line = line;
+        } else if let Some(file) = file {
+            self.request_action(
+                ctx,
+                DeferredAction::OpenAtLine(file.to_path_buf(), line.unwrap_or(0)),
+            );
+        }
+    }
+
+    fn check_acronyms(&mut self) {
+        let terms = self.glossary.with_document(&self.content);
+        let file = self.file_path.as_deref().map(PathBuf::from);
+        let diagnostics =
+            acronyms::check(&self.content, file, &terms, &self.config.acronyms.ignore);
+        self.set_diagnostics(acronyms::SOURCE, diagnostics);
+        self.show_diagnostics = true;
+    }
+
+    fn check_alt_text(&mut self) {
+        let file = self.file_path.as_deref().map(PathBuf::from);
+        self.set_diagnostics(alt_text::SOURCE, alt_text::check(&self.content, file));
+        self.show_alt_text = true;
+    }
+
+    fn set_diagnostics(&mut self, source: &str, diagnostics: Vec<Diagnostic>) {
+        let diagnostics = self.without_suppressed(diagnostics);
+        self.diagnostics
+            .retain(|diagnostic| diagnostic.source != source);
+        if !diagnostics.is_empty() {
+            self.show_diagnostics = true;
+        }
+        self.diagnostics.extend(diagnostics);
+        self.diagnostics
+            .sort_by_key(|diagnostic| diagnostic.severity);
+    }
+
+    /// Drops diagnostics that fall inside `md-echo-disable` regions of the
+    /// document they point at.
+    fn without_suppressed(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let current = self
+            .file_path
+            .as_deref()
+            .map(|path| export::normalize(Path::new(path)));
+        let mut by_file: HashMap<Option<PathBuf>, Suppressions> = HashMap::new();
+        diagnostics
+            .into_iter()
+            .filter(|diagnostic| {
+                let Some(line) = diagnostic.line else {
+                    return true;
+                };
+                let file = diagnostic.file.as_deref().map(export::normalize);
+                let is_current = file.is_none() || file == current;
+                let suppressions = by_file.entry(file.clone()).or_insert_with(|| {
+                    if is_current {
+                        Suppressions::parse(&self.content)
+                    } else {
+                        file.as_deref()
+                            .and_then(|path| fs::read_to_string(path).ok())
+                            .map(|text| Suppressions::parse(&text))
+                            .unwrap_or_default()
+                    }
+                });
+                !suppressions.is_suppressed(line, &diagnostic.source)
+            })
+            .collect()
+    }
+
+    fn show_diagnostics_panel(&mut self, ui: &mut egui::Ui) {
+        let errors = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .count();
+        ui.horizontal(|ui| {
+            ui.strong("Diagnostics");
+            ui.label(format!(
+                "{} errors, {} other",
+                errors,
+                self.diagnostics.len() - errors
+            ));
+            if ui.small_button("Clear").clicked() {
+                self.diagnostics.clear();
+            }
+        });
+        ui.separator();
+
+        let mut target = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                if self.diagnostics.is_empty() {
+                    ui.weak("No problems reported.");
+                }
+                for diagnostic in &self.diagnostics {
+                    let location = match (&diagnostic.file, diagnostic.line) {
+                        (Some(file), Some(line)) => format!(
+                            "{}:{}",
+                            file.file_name().unwrap_or_default().to_string_lossy(),
+                            line + 1
+                        ),
+                        (Some(file), None) => file
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .to_string(),
+                        (None, Some(line)) => format!("line {}", line + 1),
+                        (None, None) => String::new(),
+                    };
+                    let text = format!(
+                        "{} [{}] {}  {}",
+                        diagnostic.severity.icon(),
+                        diagnostic.source,
+                        location,
+                        diagnostic.message
+                    );
+                    let response = ui.selectable_label(false, text);
+                    if response.clicked()
+                        && (diagnostic.file.is_some() || diagnostic.line.is_some())
+                    {
+                        target = Some((diagnostic.file.clone(), diagnostic.line));
+                    }
+                }
+            });
+
+        if let Some((file, line)) = target {
+            self.go_to_location(ui.ctx(), file.as_deref(), line);
+        }
+    }
+
+    fn show_git_gutter(&mut self, ui: &mut egui::Ui, editor_rect: egui::Rect, origin_y: f32) {
+        self.git_gutter
+            .update(self.file_path.as_deref().map(Path::new), &self.content);
+        let line_tops = &self.source_map.line_tops;
+        let line_y = |line: usize| {
+            line_tops
+                .get(line)
+                .map(|top| origin_y + top)
+                .unwrap_or(editor_rect.bottom())
+        };
+
+        let mut revert = None;
+        for (index, hunk) in self.git_gutter.hunks.iter().enumerate() {
+            let top = line_y(hunk.new_lines.start);
+            let (top, bottom) = if hunk.new_lines.is_empty() {
+                (top - 2.0, top + 2.0)
+            } else {
+                (top, line_y(hunk.new_lines.end))
+            };
+            let marker = egui::Rect::from_x_y_ranges(
+                editor_rect.left()..=editor_rect.left() + 3.0,
+                top..=bottom,
+            );
+            ui.painter().rect_filled(marker, 1.0, hunk.kind.color());
+
+            let old = self.git_gutter.head_text(hunk);
+            let hover = if old.is_empty() {
+                "Added since HEAD. Click to remove.".to_string()
+            } else {
+                format!("HEAD version (click to revert):\n\n{}", old.trim_end())
+            };
+            let response = ui
+                .interact(
+                    marker.expand2(egui::vec2(3.0, 0.0)),
+                    ui.id().with(("git_hunk", index)),
+                    egui::Sense::click(),
+                )
+                .on_hover_cursor(egui::CursorIcon::PointingHand)
+                .on_hover_text(hover);
+            if response.clicked() {
+                revert = Some(index);
+            }
+        }
+
+        if let Some(index) = revert {
+            self.content = self
+                .git_gutter
+                .revert(&self.content, &self.git_gutter.hunks[index]);
+            self.update_modified();
+        }
+    }
+
+    fn show_blame_column(&mut self, ui: &mut egui::Ui, column: egui::Rect, origin_y: f32) {
+        let Some(path) = self.file_path.as_deref().map(PathBuf::from) else {
+            return;
+        };
+        if self
+            .blame
+            .as_ref()
+            .map(|blame| blame.path != path)
+            .unwrap_or(true)
+        {
+            self.blame = Some(blame::Blame::start(&path, ui.ctx().clone()));
+        }
+        let Some(blame) = self.blame.as_mut() else {
+            return;
+        };
+        blame.update(&self.content);
+
+        let font = egui::FontId::proportional(11.0);
+        let color = ui.visuals().weak_text_color();
+        if blame.is_loading() || blame.error.is_some() {
+            let message = blame.error.as_deref().unwrap_or("Running git blame…");
+            ui.painter().text(
+                column.left_top(),
+                egui::Align2::LEFT_TOP,
+                message,
+                font,
+                color,
+            );
+            return;
+        }
+
+        let line_tops = &self.source_map.line_tops;
+        let clip = ui.clip_rect();
+        let mut previous_commit: Option<&str> = None;
+        for (line, top) in line_tops.iter().enumerate() {
+            let info = blame.line(line);
+            let commit = info.map(|info| info.commit.as_str());
+            let starts_run = commit != previous_commit;
+            previous_commit = commit;
+            let y = origin_y + top;
+            if !starts_run || y > clip.bottom() || y + 20.0 < clip.top() {
+                continue;
+            }
+            let Some(info) = info else {
+                continue;
+            };
+
+            let mut job =
+                egui::text::LayoutJob::simple_singleline(info.label(), font.clone(), color);
+            job.wrap = egui::text::TextWrapping {
+                max_width: column.width() - 8.0,
+                max_rows: 1,
+                break_anywhere: true,
+                overflow_character: Some('…'),
+            };
+            let galley = ui.fonts(|fonts| fonts.layout_job(job));
+            let rect = egui::Rect::from_min_size(egui::pos2(column.left(), y), galley.size());
+            ui.painter().galley(rect.min, galley, color);
+            if info.is_committed() {
+                ui.interact(rect, ui.id().with(("blame", line)), egui::Sense::hover())
+                    .on_hover_text(format!(
+                        "{}\n{} — {}",
+                        info.summary,
+                        &info.commit[..info.commit.len().min(8)],
+                        info.label()
+                    ));
+            }
+        }
+    }
+
+    fn show_conflict_bar(&mut self, ui: &mut egui::Ui, conflicts: &[conflicts::Conflict]) {
+        ui.horizontal(|ui| {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!("⚠ {} merge conflicts", conflicts.len()),
+            );
+            if ui.small_button("Take All Ours").clicked() {
+                self.resolve_conflicts(None, Resolution::Ours);
+            }
+            if ui.small_button("Take All Theirs").clicked() {
+                self.resolve_conflicts(None, Resolution::Theirs);
+            }
+        });
+
+        let mut target = None;
+        let mut resolve = None;
+        egui::ScrollArea::vertical()
+            .id_source("conflict_list")
+            .max_height(96.0)
+            .show(ui, |ui| {
+                for (index, conflict) in conflicts.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.link(format!("Line {}", conflict.line + 1)).clicked() {
+                            target = Some(conflict.line);
+                        }
+                        ui.weak(format!(
+                            "{} ↔ {}",
+                            if conflict.ours_label.is_empty() {
+                                "ours"
+                            } else {
+                                &conflict.ours_label
+                            },
+                            if conflict.theirs_label.is_empty() {
+                                "theirs"
+                            } else {
+                                &conflict.theirs_label
+                            }
+                        ));
+                        if ui.small_button("Take Ours").clicked() {
+                            resolve = Some((index, Resolution::Ours));
+                        }
+                        if ui.small_button("Take Theirs").clicked() {
+                            resolve = Some((index, Resolution::Theirs));
+                        }
+                        if ui.small_button("Take Both").clicked() {
+                            resolve = Some((index, Resolution::Both));
+                        }
+                    });
+                }
+            });
+
+        if let Some((index, resolution)) = resolve {
+            self.resolve_conflicts(Some(index), resolution);
+        }
+        if let Some(line) = target {
+            self.go_to_location(ui.ctx(), None, Some(line));
+        }
+    }
+
+    /// Rewrites the conflict at `index`, or every conflict when `index` is
+    /// `None`, keeping the chosen side.
+    fn resolve_conflicts(&mut self, index: Option<usize>, resolution: Resolution) {
+        let conflicts = conflicts::find_conflicts(&self.content);
+        // Replace back to front so earlier ranges stay valid.
+        for (position, conflict) in conflicts.iter().enumerate().rev() {
+            if index.is_none() || index == Some(position) {
+                let replacement = conflict.resolve(&self.content, resolution);
+                self.content
+                    .replace_range(conflict.range.clone(), &replacement);
+            }
+        }
+        self.update_modified();
+    }
+
+    fn show_search_panel(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Search");
+        let response = ui.add(
+            TextEdit::singleline(&mut self.search_query)
+                .hint_text("Search markdown files…")
+                .desired_width(f32::INFINITY),
+        );
+        if std::mem::take(&mut self.search_focus) {
+            response.request_focus();
+        }
+        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        let mut run = submitted;
+        ui.horizontal(|ui| {
+            run |= ui
+                .checkbox(&mut self.search_case_sensitive, "Match case")
+                .changed();
+            run |= ui.checkbox(&mut self.search_regex, "Regex").changed();
+            run |= ui.button("Search").clicked();
+        });
+        if run {
+            self.start_search(ui.ctx());
+        }
+
+        if let Some(err) = &self.search_error {
+            ui.colored_label(ui.visuals().error_fg_color, err);
+        }
+        let Some(job) = &self.search_job else {
+            return;
+        };
+        let (total, done, truncated) = job.status();
+        ui.label(match (done, truncated) {
+            (false, _) => format!("Searching… {} matches", total),
+            (true, true) => format!("{}+ matches (stopped early)", total),
+            (true, false) => format!("{} matches", total),
+        });
+        ui.separator();
+
+        let mut target = None;
+        egui::ScrollArea::vertical()
+            .id_source("search_results")
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                job.with_results(|files| {
+                    for file in files {
+                        let name = file
+                            .path
+                            .strip_prefix(&self.working_dir)
+                            .unwrap_or(&file.path)
+                            .display()
+                            .to_string();
+                        egui::CollapsingHeader::new(format!("{} ({})", name, file.hits.len()))
+                            .id_source(&file.path)
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                for hit in &file.hits {
+                                    if let Some(before) = &hit.before {
+                                        ui.weak(format!("{:>5}  {}", hit.line, before));
+                                    }
+                                    let response = ui.selectable_label(
+                                        false,
+                                        egui::RichText::new(format!(
+                                            "{:>5}  {}",
+                                            hit.line + 1,
+                                            hit.text
+                                        ))
+                                        .monospace(),
+                                    );
+                                    if response.clicked() {
+                                        target = Some((file.path.clone(), hit.line));
+                                    }
+                                    if let Some(after) = &hit.after {
+                                        ui.weak(format!("{:>5}  {}", hit.line + 2, after));
+                                    }
+                                    ui.add_space(4.0);
+                                }
+                            });
+                    }
+                });
+            });
+
+        if let Some((path, line)) = target {
+            self.go_to_location(ui.ctx(), Some(&path), Some(line));
+        }
+    }
+
+    /// Whether bare links are drawn as cards in the open document.
+    fn link_cards_enabled(&self) -> bool {
+        front_matter::get(&self.content, "link_cards")
+            .map_or(self.config.preview.link_cards, |value| value == "true")
+    }
+
+    /// Reads the workspace ignore patterns again when the working directory
+    /// or `[workspace] ignore` changed.
+    fn refresh_workspace_ignore(&mut self) {
+        let (root, configured, _) = &self.workspace_ignore;
+        if *root == self.working_dir && *configured == self.config.workspace.ignore {
+            return;
+        }
+        let configured = &self.config.workspace.ignore;
+        let (ignore, invalid) =
+            patterns::IgnorePatterns::for_workspace(&self.working_dir, configured);
+        for problem in invalid {
+            self.notifications
+                .warning("Workspace ignore patterns", problem);
+        }
+        self.workspace_ignore = (self.working_dir.clone(), configured.clone(), ignore);
+    }
+
+    /// What the workspace leaves out: the working directory's `.gitignore`
+    /// and `[workspace] ignore`.
+    fn workspace_ignore(&self) -> &patterns::IgnorePatterns {
+        &self.workspace_ignore.2
+    }
+
+    /// Starts indexing the working directory, again whenever it changes.
+    fn poll_workspace_index(&mut self, ctx: &Context) {
+        self.refresh_workspace_ignore();
+        let current = self.workspace_index.as_ref().is_some_and(|index| {
+            index.root() == self.working_dir
+                && index.ignore().patterns() == self.workspace_ignore().patterns()
+        });
+        if current {
+            if let Some(err) = self
+                .workspace_index
+                .as_ref()
+                .and_then(|i| i.take_cache_error())
+            {
+                self.notifications.warning("Workspace index cache", err);
+            }
+            return;
+        }
+        self.workspace_index = None;
+        if !self.working_dir.is_dir() {
+            return;
+        }
+        // One cache file per working directory.
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        std::hash::Hash::hash(&self.working_dir, &mut hasher);
+        let cache = ProjectDirs::from("com", "fibnas", "md-echo").map(|dirs| {
+            dirs.cache_dir()
+                .join("index")
+                .join(format!("{:016x}.json", std::hash::Hasher::finish(&hasher)))
+        });
+        let interval = Duration::from_secs_f64(self.config.index.refresh_secs.max(1.0));
+        self.workspace_index = Some(index::WorkspaceIndex::start(
+            &self.working_dir,
+            self.workspace_ignore().clone(),
+            cache,
+            interval,
+            ctx.clone(),
+        ));
+    }
+
+    /// The markdown files of the working directory that are not ignored,
+    /// from the index once it has been built.
+    fn markdown_files(&self) -> Vec<PathBuf> {
+        match &self.workspace_index {
+            Some(index) if index.is_ready() => index.files(),
+            Some(index) => export::markdown_files_except(&self.working_dir, index.ignore()),
+            None => export::markdown_files_except(&self.working_dir, self.workspace_ignore()),
+        }
+    }
+
+    /// Backlinks to the open document and the tags of the workspace, from
+    /// the workspace index.
+    fn show_links_panel(&mut self, ui: &mut egui::Ui) {
+        ui.strong("Links and Tags");
+        ui.separator();
+        let Some(index) = &self.workspace_index else {
+            ui.weak("The working directory is unavailable.");
+            return;
+        };
+        if !index.is_ready() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.weak("Indexing...");
+            });
+            return;
+        }
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let backlinks = document
+            .as_ref()
+            .map(|path| index.backlinks(path))
+            .unwrap_or_default();
+        let tags = index.tags();
+        let relative = |path: &Path| {
+            path.strip_prefix(&self.working_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+
+        let mut target = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                egui::CollapsingHeader::new(format!("Backlinks ({})", backlinks.len()))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        if document.is_none() {
+                            ui.weak("Save the document to see links to it.");
+                        } else if backlinks.is_empty() {
+                            ui.weak("No other file links here.");
+                        }
+                        for backlink in &backlinks {
+                            let label =
+                                format!("{}:{}", relative(&backlink.source), backlink.line + 1);
+                            if ui.selectable_label(false, label).clicked() {
+                                target = Some((backlink.source.clone(), backlink.line));
+                            }
+                        }
+                    });
+                egui::CollapsingHeader::new(format!("Tags ({})", tags.len()))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        if tags.is_empty() {
+                            ui.weak("No #tags or front matter tags yet.");
+                        }
+                        for (tag, places) in &tags {
+                            egui::CollapsingHeader::new(format!("#{} ({})", tag, places.len()))
+                                .id_source(("tag", tag))
+                                .show(ui, |ui| {
+                                    for (path, line) in places {
+                                        let label = format!("{}:{}", relative(path), line + 1);
+                                        if ui.selectable_label(false, label).clicked() {
+                                            target = Some((path.clone(), *line));
+                                        }
+                                    }
+                                });
+                        }
+                    });
+            });
+
+        if let Some((path, line)) = target {
+            self.go_to_location(ui.ctx(), Some(&path), Some(line));
+        }
+    }
+
+    fn start_search(&mut self, ctx: &Context) {
+        self.search_job = None;
+        self.search_error = None;
+        if self.search_query.is_empty() {
+            return;
+        }
+        match SearchJob::start(
+            self.markdown_files(),
+            &self.search_query,
+            self.search_case_sensitive,
+            self.search_regex,
+            ctx.clone(),
+        ) {
+            Ok(job) => self.search_job = Some(job),
+            Err(err) => self.search_error = Some(err),
+        }
+    }
+
+    fn show_comments_panel(&mut self, ui: &mut egui::Ui) {
+        let notes: Vec<_> = comments::find_comments(&self.content)
+            .into_iter()
+            .filter(|comment| !comment.is_directive())
+            .collect();
+        ui.horizontal(|ui| {
+            ui.strong("Comments");
+            ui.label(format!("{} notes", notes.len()));
+        });
+        ui.separator();
+
+        let mut target = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                if notes.is_empty() {
+                    ui.weak("No <!-- comments --> in this document.");
+                }
+                for comment in &notes {
+                    let first_line = comment.text.lines().next().unwrap_or_default();
+                    let response = ui
+                        .selectable_label(false, format!("{}  {}", comment.line + 1, first_line))
+                        .on_hover_text(&comment.text);
+                    if response.clicked() {
+                        target = Some(comment.line);
+                    }
+                }
+            });
+
+        if let Some(line) = target {
+            self.go_to_location(ui.ctx(), None, Some(line));
+        }
+    }
+
+    /// The front matter as editable fields, or as raw YAML.
+    fn show_front_matter_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.strong("Front Matter");
+            ui.checkbox(&mut self.front_matter_raw, "Raw");
+        });
+        ui.separator();
+
+        let Some(block) = front_matter::block(&self.content) else {
+            ui.weak("This document has no --- front matter.");
+            if ui.button("Add Front Matter").clicked() {
+                let title = self
+                    .file_path
+                    .as_deref()
+                    .and_then(|path| Path::new(path).file_stem())
+                    .map_or("Untitled".to_string(), |stem| {
+                        stem.to_string_lossy().into_owned()
+                    });
+                self.content = front_matter::set(&self.content, "title", &title);
+                self.update_modified();
+            }
+            return;
+        };
+
+        let mut updated = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                if self.front_matter_raw {
+                    let mut raw = self.content[block.clone()].to_string();
+                    let response = ui.add(
+                        TextEdit::multiline(&mut raw)
+                            .code_editor()
+                            .desired_width(f32::INFINITY),
+                    );
+                    if response.changed() {
+                        if !raw.is_empty() && !raw.ends_with('\n') {
+                            raw.push('\n');
+                        }
+                        let mut content = self.content.clone();
+                        content.replace_range(block.clone(), &raw);
+                        updated = Some(content);
+                    }
+                    return;
+                }
+                updated = self.show_front_matter_fields(ui);
+            });
+
+        if let Some(content) = updated {
+            self.content = content;
+            self.update_modified();
+        }
+    }
+
+    /// One row per front matter entry; returns the document with the edits
+    /// made this frame.
+    fn show_front_matter_fields(&mut self, ui: &mut egui::Ui) -> Option<String> {
+        let entries = front_matter::entries(&self.content);
+        let mut updated = None;
+        egui::Grid::new("front_matter_fields")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for entry in &entries {
+                    ui.label(&entry.key);
+                    if let Some(items) = entry.list() {
+                        // Lists are rewritten when the field loses focus, so
+                        // commas and spaces can be typed.
+                        let mut value = match &self.front_matter_list_edit {
+                            Some((key, text)) if *key == entry.key => text.clone(),
+                            _ => items.join(", "),
+                        };
+                        let response = ui
+                            .text_edit_singleline(&mut value)
+                            .on_hover_text("List, one item per comma");
+                        if response.changed() {
+                            self.front_matter_list_edit = Some((entry.key.clone(), value.clone()));
+                        }
+                        if response.lost_focus() && self.front_matter_list_edit.take().is_some() {
+                            let items: Vec<String> = value
+                                .split(',')
+                                .map(|item| item.trim().to_string())
+                                .filter(|item| !item.is_empty())
+                                .collect();
+                            let lines = front_matter::list_lines(&entry.key, &items);
+                            updated =
+                                Some(front_matter::replace_entry(&self.content, entry, &lines));
+                        }
+                    } else if entry.nested.trim().is_empty() {
+                        let mut value = entry.value.clone();
+                        if ui.text_edit_singleline(&mut value).changed() {
+                            let lines = if value.is_empty() {
+                                format!("{}:\n", entry.key)
+                            } else {
+                                format!("{}: {}\n", entry.key, value)
+                            };
+                            updated =
+                                Some(front_matter::replace_entry(&self.content, entry, &lines));
+                        }
+                    } else {
+                        ui.weak("(nested)")
+                            .on_hover_text(format!("Edit in the Raw view:\n{}", entry.nested));
+                    }
+                    if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                        updated = Some(front_matter::replace_entry(&self.content, entry, ""));
+                    }
+                    ui.end_row();
+                }
+            });
+        if entries.is_empty() {
+            ui.weak("No entries yet.");
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut self.front_matter_new_key)
+                    .hint_text("New key")
+                    .desired_width(120.0),
+            );
+            let key = self.front_matter_new_key.trim().to_string();
+            let valid = !key.is_empty()
+                && !key.contains([':', ' ', '#'])
+                && entries.iter().all(|entry| entry.key != key);
+            if ui.add_enabled(valid, egui::Button::new("Add")).clicked() {
+                updated = Some(front_matter::set(&self.content, &key, ""));
+                self.front_matter_new_key.clear();
+            }
+        });
+        updated
+    }
+
+    fn show_review_panel(&mut self, ui: &mut egui::Ui) {
+        let changes = critic::find_changes(&self.content);
+        ui.horizontal(|ui| {
+            ui.strong("Suggestions");
+            ui.label(format!("{} pending", changes.len()));
+        });
+        ui.horizontal(|ui| {
+            if ui.small_button("Accept All").clicked() {
+                self.resolve_review(None, true);
+            }
+            if ui.small_button("Reject All").clicked() {
+                self.resolve_review(None, false);
+            }
+        });
+        ui.separator();
+
+        let mut target = None;
+        let mut resolve = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                if changes.is_empty() {
+                    ui.weak(
+                        "No {++additions++}, {--deletions--}, or {>>comments<<} in this document.",
+                    );
+                }
+                for (index, change) in changes.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✔").on_hover_text("Accept").clicked() {
+                            resolve = Some((index, true));
+                        }
+                        if ui.small_button("✖").on_hover_text("Reject").clicked() {
+                            resolve = Some((index, false));
+                        }
+                        let response = ui.selectable_label(
+                            false,
+                            format!("{}  {}", change.line + 1, change.summary()),
+                        );
+                        if response.clicked() {
+                            target = Some(change.line);
+                        }
+                    });
+                }
+            });
+
+        if let Some((index, accept)) = resolve {
+            self.resolve_review(Some(index), accept);
+        }
+        if let Some(line) = target {
+            self.go_to_location(ui.ctx(), None, Some(line));
+        }
+    }
+
+    /// The `lang` front matter entry, or the configured default.
+    fn document_language(&self) -> Language {
+        Language::of_document(&self.content, &self.config.editor.language)
+    }
+
+    fn document_direction(&self) -> Direction {
+        Direction::of_document(&self.content, &self.document_language())
+    }
+
+    /// Toggles the `dir` front matter entry.
+    fn show_direction_toggle(&mut self, ui: &mut egui::Ui) {
+        let rtl = self.document_direction().is_rtl();
+        let label = if rtl { "⇐ RTL" } else { "⇒ LTR" };
+        if ui
+            .selectable_label(rtl, label)
+            .on_hover_text("Text direction, stored as `dir` in the front matter")
+            .clicked()
+        {
+            let dir = if rtl { "ltr" } else { "rtl" };
+            self.content = front_matter::set(&self.content, "dir", dir);
+            self.update_modified();
+        }
+    }
+
+    fn show_language_picker(&mut self, ui: &mut egui::Ui, current: &Language) {
+        let mut selected = None;
+        egui::ComboBox::from_id_source("document_language")
+            .selected_text(format!("🌐 {}", current.tag))
+            .show_ui(ui, |ui| {
+                for (tag, name) in language::CHOICES {
+                    let label = format!("{} ({})", name, tag);
+                    if ui.selectable_label(current.tag == tag, label).clicked() {
+                        selected = Some(tag);
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Document language, stored as `lang` in the front matter");
+        if let Some(tag) = selected.filter(|tag| *tag != current.tag) {
+            self.content = front_matter::set(&self.content, "lang", tag);
+            self.update_modified();
+        }
+    }
+
+    /// Whether the buffer or its encoding and line endings differ from
+    /// the file.
+    fn update_modified(&mut self) {
+        self.modified =
+            self.content != self.original_content || self.text_format != self.saved_format;
+    }
+
+    /// Converts the document to another encoding or line ending, written
+    /// on the next save.
+    fn show_text_format_menu(&mut self, ui: &mut egui::Ui) {
+        let mut format = self.text_format;
+        ui.label("Line Endings");
+        for ending in [text_format::LineEnding::Lf, text_format::LineEnding::Crlf] {
+            ui.radio_value(&mut format.line_ending, ending, ending.label());
+        }
+        ui.separator();
+        ui.label("Encoding");
+        for (label, name, bom) in text_format::ENCODINGS {
+            let encoding = text_format::encoding_for(name);
+            let current = format.encoding == encoding && format.bom == bom;
+            if ui.radio(current, label).clicked() {
+                format.encoding = encoding;
+                format.bom = bom;
+            }
+        }
+        if format != self.text_format {
+            self.text_format = format;
+            self.update_modified();
+            ui.close_menu();
+        }
+    }
+
+    fn apply_smart_quotes(&mut self) {
+        let converted = language::smart_quotes(&self.content, &self.document_language());
+        if converted != self.content {
+            self.content = converted;
+            self.update_modified();
+        }
+    }
+
+    /// Starts a check of the workspace's markdown files on a background
+    /// thread, using the editor's text for the open document.
+    fn start_workspace_job<T: Send + 'static>(
+        &self,
+        ctx: &Context,
+        description: &'static str,
+        work: impl FnOnce(&[(PathBuf, String)]) -> T + Send + 'static,
+    ) -> workspace_job::WorkspaceJob<T> {
+        let open = self
+            .file_path
+            .as_ref()
+            .map(|path| (PathBuf::from(path), self.content.clone()));
+        workspace_job::WorkspaceJob::start(
+            description,
+            self.markdown_files(),
+            open,
+            ctx.clone(),
+            work,
+        )
+    }
+
+    /// Checks the style rules across the workspace's markdown files.
+    /// `errors` are the files an earlier fix could not write, shown along
+    /// with the reports.
+    fn run_consistency_check(&mut self, ctx: &Context, errors: Vec<String>) {
+        let job = self.start_workspace_job(ctx, "Checking consistency…", move |documents| {
+            (consistency::check(documents), errors)
+        });
+        self.consistency_job = Some(job);
+    }
+
+    /// Looks for near-duplicate paragraphs across the workspace's markdown
+    /// files.
+    fn find_duplicates(&mut self, ctx: &Context) {
+        let config = self.config.duplicates.clone();
+        let job =
+            self.start_workspace_job(ctx, "Finding duplicate paragraphs…", move |documents| {
+                duplicates::find(documents, config.min_words, config.threshold)
+            });
+        self.duplicates_job = Some(job);
+    }
+
+    /// Shows the results of workspace checks that have finished.
+    fn poll_workspace_jobs(&mut self) {
+        if let Some(result) = self.consistency_job.as_ref().and_then(|job| job.take()) {
+            self.consistency = Some(result);
+            self.consistency_job = None;
+        }
+        if let Some(groups) = self.duplicates_job.as_ref().and_then(|job| job.take()) {
+            self.duplicates = Some(groups);
+            self.duplicates_job = None;
+        }
+    }
+
+    /// The groups of near-duplicate paragraphs, each place a link to it.
+    fn show_duplicates_window(&mut self, ctx: &Context) {
+        let Some(groups) = &self.duplicates else {
+            return;
+        };
+        let working_dir = &self.working_dir;
+        let relative = |path: &Path| {
+            path.strip_prefix(working_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        let mut open = true;
+        let mut location = None;
+        let mut recheck = false;
+        egui::Window::new("Duplicate Paragraphs")
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                if groups.is_empty() {
+                    ui.label("No paragraph appears more than once across the workspace.");
+                } else {
+                    ui.label(format!("{} group(s) of similar paragraphs.", groups.len()));
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(420.0)
+                    .show(ui, |ui| {
+                        for (index, group) in groups.iter().enumerate() {
+                            let first = &group.paragraphs[0];
+                            let preview: String = first.excerpt.chars().take(60).collect();
+                            egui::CollapsingHeader::new(format!(
+                                "{} copies, {:.0}% alike: {}",
+                                group.paragraphs.len(),
+                                group.similarity * 100.0,
+                                preview
+                            ))
+                            .id_source(("duplicates", index))
+                            .default_open(groups.len() <= 10)
+                            .show(ui, |ui| {
+                                for paragraph in &group.paragraphs {
+                                    let label = format!(
+                                        "{}:{}  {}",
+                                        relative(&paragraph.path),
+                                        paragraph.line + 1,
+                                        paragraph.excerpt
+                                    );
+                                    if ui.selectable_label(false, label).clicked() {
+                                        location = Some((paragraph.path.clone(), paragraph.line));
+                                    }
+                                }
+                            });
+                        }
+                    });
+                ui.separator();
+                recheck = ui.button("Check Again").clicked();
+            });
+
+        if !open {
+            self.duplicates = None;
+            return;
+        }
+        if let Some((path, line)) = location {
+            self.go_to_location(ctx, Some(&path), Some(line));
+        }
+        if recheck {
+            self.find_duplicates(ctx);
+        }
+    }
+
+    /// Lists each rule with more than one style in use, with the places
+    /// that differ from the chosen style and a button that rewrites them.
+    fn show_consistency_window(&mut self, ctx: &Context) {
+        let Some((reports, errors)) = &mut self.consistency else {
+            return;
+        };
+        let working_dir = &self.working_dir;
+        let relative = |path: &Path| {
+            path.strip_prefix(working_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        let mut open = true;
+        let mut location = None;
+        let mut fix = None;
+        let mut recheck = false;
+        egui::Window::new("Consistency Check")
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                for error in errors.iter() {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                if !reports.iter().any(|report| report.is_mixed()) {
+                    ui.label("Each rule is followed the same way across the workspace.");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(420.0)
+                    .show(ui, |ui| {
+                        for report in reports.iter_mut().filter(|report| report.is_mixed()) {
+                            let rule = report.rule;
+                            let styles = rule.styles();
+                            egui::CollapsingHeader::new(format!(
+                                "{} ({})",
+                                rule.label(),
+                                report.deviations().count()
+                            ))
+                            .id_source(("consistency", rule.label()))
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    for (style, name) in styles.iter().enumerate() {
+                                        let label = format!("{} ({})", name, report.counts[style]);
+                                        ui.radio_value(&mut report.target, style, label);
+                                    }
+                                });
+                                for finding in report.deviations() {
+                                    let label = format!(
+                                        "{}:{}  {}",
+                                        relative(&finding.path),
+                                        finding.line + 1,
+                                        finding.excerpt
+                                    );
+                                    if ui
+                                        .selectable_label(false, label)
+                                        .on_hover_text(styles[finding.style])
+                                        .clicked()
+                                    {
+                                        location = Some((finding.path.clone(), finding.line));
+                                    }
+                                }
+                                let mut paths: Vec<PathBuf> = report
+                                    .deviations()
+                                    .map(|finding| finding.path.clone())
+                                    .collect();
+                                paths.dedup();
+                                let button = format!(
+                                    "Use {} in {} file{}",
+                                    styles[report.target],
+                                    paths.len(),
+                                    if paths.len() == 1 { "" } else { "s" }
+                                );
+                                if ui.button(button).clicked() {
+                                    fix = Some((rule, report.target, paths));
+                                }
+                            });
+                        }
+                    });
+                ui.separator();
+                recheck = ui.button("Check Again").clicked();
+            });
+
+        if !open {
+            self.consistency = None;
+            return;
+        }
+        if let Some((path, line)) = location {
+            self.go_to_location(ctx, Some(&path), Some(line));
+        }
+        if let Some((rule, target, paths)) = fix {
+            let errors = self.fix_consistency(rule, target, &paths);
+            self.run_consistency_check(ctx, errors);
+        } else if recheck {
+            self.run_consistency_check(ctx, Vec::new());
+        }
+    }
+
+    /// Rewrites `paths` to use style `target` of `rule`. The open document
+    /// is changed in the editor, to be saved as usual; other files are
+    /// written directly. Returns the files that could not be written.
+    fn fix_consistency(
+        &mut self,
+        rule: consistency::Rule,
+        target: usize,
+        paths: &[PathBuf],
+    ) -> Vec<String> {
+        let current = self.file_path.as_ref().map(PathBuf::from);
+        let mut errors = Vec::new();
+        for path in paths {
+            if current.as_ref() == Some(path) {
+                self.content = consistency::fix(&self.content, rule, target);
+                self.update_modified();
+                continue;
+            }
+            let result = fs::read_to_string(path).and_then(|text| {
+                let fixed = consistency::fix(&text, rule, target);
+                fs::write(path, &fixed)?;
+                if let Some(index) = &self.workspace_index {
+                    index.update(path, &fixed);
+                }
+                Ok(())
+            });
+            if let Err(err) = result {
+                errors.push(format!("{}: {}", path.display(), err));
+            }
+        }
+        self.git_status.refresh();
+        errors
+    }
+
+    /// Accepts or rejects the CriticMarkup change at `index`, or every change
+    /// when `index` is `None`.
+    fn resolve_review(&mut self, index: Option<usize>, accept: bool) {
+        match index {
+            Some(index) => {
+                let Some(change) = critic::find_changes(&self.content).into_iter().nth(index)
+                else {
+                    return;
+                };
+                let replacement = change.resolve(accept).to_string();
+                self.content.replace_range(change.range, &replacement);
+            }
+            None => self.content = critic::resolve_all(&self.content, accept),
+        }
+        self.update_modified();
+    }
+
+    fn show_mdbook_menu(&mut self, ui: &mut egui::Ui) {
+        let serving = self
+            .mdbook_server
+            .as_mut()
+            .map(|server| server.is_running())
+            .unwrap_or(false);
+        if !serving {
+            if ui.button("Serve").clicked() {
+                self.start_mdbook_server(ui.ctx());
+                ui.close_menu();
+            }
+        } else if ui.button("Stop Server").clicked() {
+            self.stop_mdbook_server();
+            ui.close_menu();
+        }
+        if ui.button("Build").clicked() {
+            self.build_mdbook();
+            ui.close_menu();
+        }
+        ui.separator();
+        if ui.button("Open Chapter in Browser").clicked() {
+            self.open_chapter_in_browser();
+            ui.close_menu();
+        }
+        if serving && ui.button("Show Server Log").clicked() {
+            let log = self
+                .mdbook_server
+                .as_ref()
+                .map(MdBookServer::log)
+                .unwrap_or_default();
+            self.show_tool_message(log);
+            ui.close_menu();
+        }
+    }
+
+    fn start_mdbook_server(&mut self, ctx: &Context) {
+        let Some(book) = &self.book else {
+            return;
+        };
+        match MdBookServer::start(
+            &self.config.mdbook.command,
+            &book.root,
+            self.config.mdbook.port,
+            ctx.clone(),
+        ) {
+            Ok(server) => self.mdbook_server = Some(server),
+            Err(err) => self.show_tool_message(err),
+        }
+    }
+
+    fn stop_mdbook_server(&mut self) {
+        if let Some(server) = self.mdbook_server.take() {
+            let root = self
+                .book
+                .as_ref()
+                .map(|book| book.root.clone())
+                .unwrap_or_else(|| self.working_dir.clone());
+            let diagnostics = diagnostics::parse_mdbook_output(&server.log(), &root);
+            self.set_diagnostics("mdbook", diagnostics);
+        }
+    }
+
+    fn build_mdbook(&mut self) {
+        let Some(root) = self.book.as_ref().map(|book| book.root.clone()) else {
+            return;
+        };
+        match mdbook::build(&self.config.mdbook.command, &root) {
+            Ok(output) => {
+                let log = format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                let diagnostics = diagnostics::parse_mdbook_output(&log, &root);
+                let status = if output.status.success() {
+                    "mdbook build succeeded"
+                } else {
+                    "mdbook build failed"
+                };
+                self.set_diagnostics("mdbook", diagnostics);
+                self.show_tool_message(format!("{}\n\n{}", status, log.trim_end()));
+            }
+            Err(err) => self.show_tool_message(err),
+        }
+    }
+
+    fn open_chapter_in_browser(&mut self) {
+        let Some(book) = &self.book else {
+            return;
+        };
+        let page = self
+            .file_path
+            .as_deref()
+            .and_then(|path| book.rendered_page(Path::new(path)))
+            .unwrap_or_else(|| "index.html".to_string());
+
+        let serving = self
+            .mdbook_server
+            .as_mut()
+            .map(|server| server.is_running())
+            .unwrap_or(false);
+        let target = match &self.mdbook_server {
+            Some(server) if serving => server.url(&page),
+            _ => {
+                let built = book.build_dir.join(&page);
+                if !built.is_file() {
+                    self.show_tool_message(format!(
+                        "{} does not exist yet. Run mdBook → Build or Serve first.",
+                        built.display()
+                    ));
+                    return;
+                }
+                built.display().to_string()
+            }
+        };
+        if let Err(err) = mdbook::open_in_browser(&target) {
+            self.show_tool_message(format!("Failed to open browser for {}: {}", target, err));
+        }
+    }
+
+    fn show_discard_prompt(&mut self, ctx: &Context) {
+        let exiting = matches!(self.discard_prompt, Some(DeferredAction::Exit));
+        let mut choice = None;
+        egui::Window::new("Unsaved Changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(if exiting {
+                    "You have unsaved changes. Save before exiting?"
+                } else {
+                    "You have unsaved changes. Save before continuing?"
+                });
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(if exiting { "Save and Exit" } else { "Save" })
+                        .clicked()
+                    {
+                        choice = Some(true);
+                    }
+                    if ui
+                        .button(if exiting {
+                            "Discard and Exit"
+                        } else {
+                            "Discard"
+                        })
+                        .clicked()
+                    {
+                        choice = Some(false);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.discard_prompt = None;
+                    }
+                });
+            });
+
+        match choice {
+            // The action waits for the save; keep asking if the save was
+            // cancelled.
+            Some(true) if self.save_file(false) => {
+                self.after_save = self.discard_prompt.take();
+            }
+            Some(false) => {
+                if let Some(action) = self.discard_prompt.take() {
+                    self.perform_action(ctx, action);
+                }
+            }
+            Some(true) | None => {}
+        }
+    }
+
+    fn request_exit(&mut self, ctx: &Context) {
+        self.persist_on_exit();
+        self.allow_close = true;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+
+    fn persist_on_exit(&mut self) {
+        self.save_session();
+        self.save_config();
+        self.terminal = None;
+        self.mdbook_server = None;
+    }
+
+    /// Brings back the panels of the last session, and its document when
+    /// `with_file` (none was given on the command line).
+    fn restore_session(&mut self, with_file: bool) {
+        if !self.config.restore_session {
+            return;
+        }
+        let Some(session) = session::load() else {
+            return;
+        };
+        let panels = session.panels;
+        self.show_search = panels.search;
+        self.show_outline = panels.outline;
+        self.show_front_matter_panel = panels.front_matter;
+        self.show_comments_panel = panels.comments;
+        self.show_links_panel = panels.links;
+        self.show_diagnostics = panels.diagnostics;
+        self.show_terminal = panels.terminal;
+        self.show_blame = panels.blame;
+        if let (true, Some(file)) = (with_file, &session.file) {
+            if file.is_file() {
+                self.open_file_from_path(&file.clone(), None);
+                self.restoring = Some(session);
+            }
+        }
+    }
+
+    fn save_session(&mut self) {
+        if !self.config.restore_session || self.watching {
+            return;
+        }
+        let session = session::Session {
+            file: self.file_path.as_ref().map(PathBuf::from),
+            cursor: self.cursor_position,
+            editor_scroll: self.scroll_left,
+            preview_scroll: self.scroll_right,
+            panels: session::Panels {
+                search: self.show_search,
+                outline: self.show_outline,
+                front_matter: self.show_front_matter_panel,
+                comments: self.show_comments_panel,
+                links: self.show_links_panel,
+                diagnostics: self.show_diagnostics,
+                terminal: self.show_terminal,
+                blame: self.show_blame,
+            },
+        };
+        if let Err(err) = session::save(&session) {
+            self.notifications.error("Saving the session", err);
+        }
+    }
+
+    fn load_config(notifications: &mut Notifications) -> (AppConfig, Option<PathBuf>) {
+        let config_path = MarkdownApp::config_file_path();
+        let mut config = AppConfig::default();
+
+        if let Some(path) = &config_path {
+            if path.exists() {
+                match fs::read_to_string(path) {
+                    Ok(contents) => match toml::from_str::<AppConfig>(&contents) {
+                        Ok(parsed) => config = parsed,
+                        Err(err) => notifications.error(
+                            "Loading settings",
+                            format!(
+                                "{} is not valid, the defaults are used instead: {}",
+                                path.display(),
+                                err
+                            ),
+                        ),
+                    },
+                    Err(err) => notifications.error(
+                        "Loading settings",
+                        format!("Could not read {}: {}", path.display(), err),
+                    ),
+                }
+            }
+        }
+
+        if config.working_dir.is_none() {
+            if let Some(legacy_path) = MarkdownApp::legacy_settings_path() {
+                if let Ok(contents) = fs::read_to_string(&legacy_path) {
+                    let candidate = PathBuf::from(contents.trim());
+                    if candidate.is_dir() {
+                        config.working_dir = Some(candidate);
+                    }
+                }
+            }
+        }
+
+        (config, config_path)
+    }
+
+    fn initial_working_directory(
+        config: &mut AppConfig,
+        notifications: &mut Notifications,
+    ) -> PathBuf {
+        let default_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        if let Some(dir) = &config.working_dir {
+            if dir.is_dir() {
+                return dir.clone();
+            }
+            notifications.warning(
+                "Working directory",
+                format!(
+                    "{} is no longer a folder; using {} instead",
+                    dir.display(),
+                    default_dir.display()
+                ),
+            );
+        }
+
+        config.working_dir = Some(default_dir.clone());
+        default_dir
+    }
+
+    fn config_file_path() -> Option<PathBuf> {
+        ProjectDirs::from("com", "fibnas", "md-echo")
+            .map(|dirs| dirs.config_dir().join("config.toml"))
+    }
+
+    fn legacy_settings_path() -> Option<PathBuf> {
+        ProjectDirs::from("com", "fibnas", "md-echo")
+            .map(|dirs| dirs.config_dir().join("settings.txt"))
+    }
+
+    fn toggle_file_tree(&mut self) {
+        self.config.layout.tree_collapsed = !self.config.layout.tree_collapsed;
+        self.save_config();
+    }
+
+    fn save_config(&mut self) {
+        if let Some(config_path) = &self.config_path {
+            if let Some(parent) = config_path.parent() {
+                if let Err(err) = fs::create_dir_all(parent) {
+                    self.notifications.error(
+                        "Saving settings",
+                        format!("Could not create {}: {}", parent.display(), err),
+                    );
+                    return;
+                }
+            }
+
+            let result = toml::to_string_pretty(&self.config)
+                .map_err(|err| err.to_string())
+                .and_then(|serialized| {
+                    fs::write(config_path, serialized).map_err(|err| {
+                        format!("Could not write {}: {}", config_path.display(), err)
+                    })
+                });
+            if let Err(err) = result {
+                self.notifications.error("Saving settings", err);
+            }
+        }
+    }
+
+    fn ensure_theme(&mut self, ctx: &Context) {
+        if self.theme_applied {
+            return;
+        }
+        let visuals = self.config.theme.to_visuals();
+        ctx.set_visuals(visuals);
+        let missing = fonts::install(ctx, &self.config.fonts.fallbacks);
+        // The defaults are a best-effort list; only report fonts the user asked for.
+        if self.config.fonts.fallbacks != fonts::default_fallbacks() {
+            for font in missing {
+                self.notifications
+                    .warning("Fonts", format!("Fallback font '{}' not found", font));
+            }
+        }
+        self.theme_applied = true;
+    }
+
+    fn set_working_directory(&mut self, new_dir: PathBuf) {
+        if new_dir.is_dir() {
+            self.working_dir = new_dir.clone();
+            self.config.working_dir = Some(new_dir);
+            self.book = Book::detect(&self.working_dir);
+            self.obsidian = obsidian::applies(&self.config.flavor, &self.working_dir);
+            self.mdbook_server = None;
+            self.stop_watch_export();
+            self.save_config();
+        } else {
+            self.notifications.error(
+                "Changing the working directory",
+                format!("{} is not a folder", new_dir.display()),
+            );
+        }
+    }
+
+    fn show_file_tree(&mut self, ui: &mut egui::Ui) {
+        ui.label("Working Directory");
+        ui.monospace(self.working_dir.display().to_string());
+
+        ui.horizontal(|ui| {
+            if ui.button("Change...").clicked() {
+                let mut dialog = FileDialog::new();
+                if self.working_dir.is_dir() {
+                    dialog = dialog.set_directory(&self.working_dir);
+                }
+                if let Some(path) = dialog.pick_folder() {
+                    self.set_working_directory(path);
+                }
+            }
+            if ui.button("⟳").on_hover_text("Rescan folders").clicked() {
+                self.file_tree.refresh();
+                self.git_status.refresh();
+            }
+            ui.menu_button("Filter", |ui| self.show_tree_filter_menu(ui));
+        });
+        let now = ui.input(|i| i.time);
+        self.file_tree.poll(now, self.config.file_tree.refresh_secs);
+
+        ui.separator();
+
+        if !self.working_dir.is_dir() {
+            ui.label("Working directory is unavailable.");
+            return;
+        }
+
+        if self.tree_selection.len() > 1 {
+            self.show_batch_actions(ui);
+            ui.separator();
+        }
+
+        let root_dir = self.working_dir.clone();
+        self.file_tree.set_root(&root_dir);
+        let filter = &self.config.file_tree;
+        let mut ignore = filter.ignore.clone();
+        ignore.extend(self.workspace_ignore().patterns().iter().cloned());
+        if self.obsidian {
+            ignore.extend(obsidian::HIDDEN_FOLDERS.map(String::from));
+        }
+        let invalid = self
+            .file_tree
+            .set_filter(filter.markdown_only, filter.hide_hidden, &ignore);
+        for problem in invalid {
+            self.notifications
+                .warning("File tree ignore patterns", problem);
+        }
+        self.tree_visible_files.clear();
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                self.render_directory(ui, &root_dir, true);
+            });
+    }
+
+    fn show_tree_filter_menu(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let filter = &mut self.config.file_tree;
+        changed |= ui
+            .checkbox(&mut filter.markdown_only, "Markdown files only")
+            .changed();
+        changed |= ui
+            .checkbox(&mut filter.hide_hidden, "Hide dotfiles")
+            .changed();
+        ui.separator();
+        ui.label("Ignore patterns (one per line):");
+        let response = ui.add(
+            egui::TextEdit::multiline(&mut self.tree_ignore_text)
+                .hint_text("target/\n*.html")
+                .desired_rows(4)
+                .code_editor(),
+        );
+        if response.changed() {
+            filter.ignore = self
+                .tree_ignore_text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect();
+            changed = true;
+        }
+        if changed {
+            self.save_config();
+        }
+        ui.separator();
+        ui.label("Status:");
+        let filter = &mut self.tree_status_filter;
+        ui.radio_value(filter, None, "Any");
+        for state in &self.config.status.states {
+            ui.radio_value(filter, Some(state.clone()), state);
+        }
+        ui.radio_value(filter, Some(String::new()), "No status");
+    }
+
+    fn handle_tree_click(&mut self, ui: &mut egui::Ui, file: &Path) {
+        let modifiers = ui.input(|i| i.modifiers);
+        if modifiers.shift {
+            let anchor = self
+                .tree_anchor
+                .clone()
+                .unwrap_or_else(|| file.to_path_buf());
+            let start = self.tree_visible_files.iter().position(|p| p == &anchor);
+            let end = self.tree_visible_files.iter().position(|p| p == file);
+            if let (Some(start), Some(end)) = (start, end) {
+                let (low, high) = if start <= end {
+                    (start, end)
+                } else {
+                    (end, start)
+                };
+                self.tree_selection = self.tree_visible_files[low..=high].to_vec();
+            } else {
+                self.tree_selection = vec![file.to_path_buf()];
+            }
+            return;
+        }
+
+        if modifiers.command {
+            if let Some(index) = self.tree_selection.iter().position(|p| p == file) {
+                self.tree_selection.remove(index);
+            } else {
+                self.tree_selection.push(file.to_path_buf());
+            }
+            self.tree_anchor = Some(file.to_path_buf());
+            return;
+        }
+
+        self.tree_selection = vec![file.to_path_buf()];
+        self.tree_anchor = Some(file.to_path_buf());
+        // PDFs are previewed on double-click rather than opened as text.
+        if pdf_preview::is_pdf(file) {
+            return;
+        }
+        self.request_action(ui.ctx(), DeferredAction::OpenPath(file.to_path_buf()));
+    }
+
+    fn show_batch_actions(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!("{} selected", self.tree_selection.len()));
+            if ui.button("Open All").clicked() {
+                self.open_selection();
+            }
+            if ui.button("Move...").clicked() {
+                self.move_selection();
+            }
+            if ui.button("Delete").clicked() {
+                self.confirm_batch_delete = true;
+            }
+            if ui.button("Export...").clicked() {
+                self.open_batch_export();
+            }
+            if ui
+                .button("Promote Status")
+                .on_hover_text(format!(
+                    "Move each file's status on to the next of {}",
+                    self.config.status.states.join(", ")
+                ))
+                .clicked()
+            {
+                self.promote_status(self.tree_selection.clone());
+            }
+            ui.menu_button("Run Tool", |ui| {
+                if ui.button("Lint").clicked() {
+                    let command = self.config.tools.lint.clone();
+                    self.run_tool_on_selection(command, "lint");
+                    ui.close_menu();
+                }
+                if ui.button("Format").clicked() {
+                    let command = self.config.tools.format.clone();
+                    self.run_tool_on_selection(command, "format");
+                    ui.close_menu();
+                }
+                for tool in self.config.tools.custom.clone() {
+                    if ui.button(&tool.name).clicked() {
+                        self.run_tool_on_selection(Some(tool.command_line()), &tool.name);
+                        ui.close_menu();
+                    }
+                }
+            });
+            if ui.button("Clear").clicked() {
+                self.tree_selection.clear();
+            }
+        });
+    }
+
+    /// The open document's status, with a menu to move it to another state.
+    fn show_status_badge(&mut self, ui: &mut egui::Ui, status: &str) {
+        let color = self.config.status.color(status);
+        let badge = egui::RichText::new(format!("● {}", status)).color(color);
+        ui.menu_button(badge, |ui| {
+            for state in self.config.status.states.clone() {
+                let current = state.eq_ignore_ascii_case(status);
+                if ui.radio(current, &state).clicked() && !current {
+                    self.content = front_matter::set(&self.content, status::KEY, &state);
+                    self.update_modified();
+                    ui.close_menu();
+                }
+            }
+        })
+        .response
+        .on_hover_text("Front matter status");
+    }
+
+    /// Moves the status of each of `files` on to the next configured state.
+    /// The open document is changed in the editor, the others on disk.
+    fn promote_status(&mut self, files: Vec<PathBuf>) {
+        let states = self.config.status.states.clone();
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let files: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|file| export::is_markdown(file))
+            .collect();
+        let mut promoted = 0;
+        let mut report = Vec::new();
+        for file in &files {
+            let name = file.strip_prefix(&self.working_dir).unwrap_or(file);
+            let is_open = document.as_deref() == Some(file.as_path());
+            let text = if is_open {
+                Ok(self.content.clone())
+            } else {
+                fs::read_to_string(file).map_err(|err| err.to_string())
+            };
+            let result = text
+                .and_then(|text| status::promote(&text, &states))
+                .and_then(|(text, change)| {
+                    if is_open {
+                        self.content = text;
+                        self.update_modified();
+                    } else {
+                        fs::write(file, &text).map_err(|err| err.to_string())?;
+                        if let Some(index) = &self.workspace_index {
+                            index.update(file, &text);
+                        }
+                    }
+                    Ok(change)
+                });
+            match result {
+                Ok(change) => {
+                    promoted += 1;
+                    report.push(format!("{}: {}", name.display(), change));
+                }
+                Err(err) => report.push(format!("{}: {}", name.display(), err)),
+            }
+        }
+        self.show_tool_message(format!(
+            "Promoted {} of {} file(s).\n\n{}",
+            promoted,
+            files.len(),
+            report.join("\n")
+        ));
+    }
+
+    /// Opens the first selected file here and every other one in its own md-echo window.
+    fn open_selection(&mut self) {
+        let mut files = self.tree_selection.clone().into_iter();
+        let Some(first) = files.next() else {
+            return;
+        };
+
+        let mut errors = Vec::new();
+        match env::current_exe() {
+            Ok(exe) => {
+                for file in files {
+                    if let Err(err) = Command::new(&exe).arg(&file).spawn() {
+                        errors.push(format!("{}: {}", file.display(), err));
+                    }
+                }
+            }
+            Err(err) => errors.push(format!("Cannot locate md-echo executable: {}", err)),
+        }
+
+        if !self.modified {
+            self.open_file_from_path(&first, None);
+        } else {
+            errors.push(format!(
+                "{}: not opened here because the current document has unsaved changes",
+                first.display()
+            ));
+        }
+
+        if !errors.is_empty() {
+            self.show_tool_message(format!(
+                "Open All finished with errors:\n{}",
+                errors.join("\n")
+            ));
+        }
+    }
+
+    fn move_selection(&mut self) {
+        let mut dialog = FileDialog::new();
+        if self.working_dir.is_dir() {
+            dialog = dialog.set_directory(&self.working_dir);
+        }
+        let Some(target_dir) = dialog.pick_folder() else {
+            return;
+        };
+
+        let mut report = Vec::new();
+        let mut moved = Vec::new();
+        for file in self.tree_selection.clone() {
+            let Some(name) = file.file_name() else {
+                continue;
+            };
+            let destination = target_dir.join(name);
+            if destination.exists() {
+                report.push(format!(
+                    "skipped {}: {} already exists",
+                    file.display(),
+                    destination.display()
+                ));
+                continue;
+            }
+            match fs::rename(&file, &destination) {
+                Ok(()) => {
+                    if self.file_path.as_deref() == Some(file.display().to_string().as_str()) {
+                        self.file_path = Some(destination.display().to_string());
+                        self.file_mtime = Self::disk_mtime(&destination);
+                    }
+                    report.push(format!(
+                        "moved {} -> {}",
+                        file.display(),
+                        destination.display()
+                    ));
+                    moved.push(destination);
+                }
+                Err(err) => report.push(format!("failed {}: {}", file.display(), err)),
+            }
+        }
+
+        self.tree_selection = moved;
+        self.file_tree.refresh();
+        self.show_tool_message(report.join("\n"));
+    }
+
+    fn show_batch_delete_confirm(&mut self, ctx: &Context) {
+        egui::Window::new("Delete Files")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Permanently delete {} selected files?",
+                    self.tree_selection.len()
+                ));
+                for file in self.tree_selection.iter().take(10) {
+                    ui.monospace(file.display().to_string());
+                }
+                if self.tree_selection.len() > 10 {
+                    ui.label(format!("...and {} more", self.tree_selection.len() - 10));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Delete").clicked() {
+                        self.delete_selection();
+                        self.confirm_batch_delete = false;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_batch_delete = false;
+                    }
+                });
+            });
+    }
+
+    fn delete_selection(&mut self) {
+        let mut errors = Vec::new();
+        for file in std::mem::take(&mut self.tree_selection) {
+            match fs::remove_file(&file) {
+                Ok(()) => {
+                    if self.file_path.as_deref() == Some(file.display().to_string().as_str()) {
+                        // Keep the buffer so the content can still be saved elsewhere.
+                        self.file_path = None;
+                        self.file_mtime = None;
+                        self.original_content.clear();
+                        self.modified = !self.content.is_empty();
+                    }
+                }
+                Err(err) => errors.push(format!("{}: {}", file.display(), err)),
+            }
+        }
+        self.file_tree.refresh();
+        if !errors.is_empty() {
+            self.show_tool_message(format!(
+                "Some files could not be deleted:\n{}",
+                errors.join("\n")
+            ));
+        }
+    }
+
+    fn run_tool_on_selection(&mut self, command: Option<Vec<String>>, kind: &str) {
+        let Some(command) = command.filter(|command| !command.is_empty()) else {
+            self.show_tool_message(format!(
+                "No {kind} command configured. Add a [tools] {kind} entry to config.toml."
+            ));
+            return;
+        };
 
-                    // RIGHT: Preview
-                    strip.cell(|ui| {
-                        let line_height = ui.text_style_height(&egui::TextStyle::Body);
-                        let target_scroll_y = self.current_line as f32 * line_height;
-                        let scroll = egui::ScrollArea::vertical()
-                            .auto_shrink([false; 2])
-                            .scroll_offset(egui::vec2(0.0, target_scroll_y))
-                            .show(ui, |ui| {
-                                CommonMarkViewer::new("full_markdown").show(
-                                    ui,
-                                    &mut self.cache,
-                                    &self.content,
-                                );
-                            });
-                        self.scroll_right = scroll.state.offset.y;
+        let mut report = String::new();
+        let mut failures = 0;
+        for file in self.tree_selection.clone() {
+            let config = self.detect_tool_config(&command, file.parent());
+            let expanded = tool_config::expand_command(&command, config.as_deref());
+            let mut cmd = Command::new(&expanded[0]);
+            cmd.args(&expanded[1..]);
+            if self.working_dir.is_dir() {
+                cmd.current_dir(&self.working_dir);
+            }
+            cmd.arg(&file);
+            match cmd.output() {
+                Ok(output) => {
+                    if !output.status.success() {
+                        failures += 1;
+                    }
+                    report.push_str(&Self::describe_tool_output(
+                        &command,
+                        &expanded,
+                        config.as_deref(),
+                        &file,
+                        &output,
+                    ));
+                }
+                Err(err) => {
+                    failures += 1;
+                    report.push_str(&format!("Failed to run '{}': {}\n", command[0], err));
+                }
+            }
+            report.push('\n');
+        }
+
+        // The open document may have been rewritten on disk; the change watcher
+        // picks that up and offers a reload.
+        self.show_tool_message(format!(
+            "Ran {} on {} files ({} failed)\n\n{}",
+            kind,
+            self.tree_selection.len(),
+            failures,
+            report
+        ));
+    }
+
+    fn render_directory(&mut self, ui: &mut egui::Ui, path: &Path, is_root: bool) {
+        let name = if is_root {
+            path.display().to_string()
+        } else {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string())
+        };
+
+        let name = if self.git_status.dir_changed(path) {
+            egui::RichText::new(format!("{} •", name)).color(git::FileStatus::Modified.color())
+        } else {
+            egui::RichText::new(name)
+        };
+        let expanded = self.file_tree.is_expanded(path);
+        let response = egui::CollapsingHeader::new(name)
+            .id_source(path.display().to_string())
+            .open(Some(expanded))
+            .show(ui, |ui| {
+                let Some(listing) = self.file_tree.listing(path, ui.ctx()) else {
+                    ui.weak("Loading…");
+                    return;
+                };
+                if let Some(err) = &listing.error {
+                    ui.label(err);
+                    return;
+                }
+
+                for dir in &listing.dirs {
+                    if self.file_tree.shows(dir, true) {
+                        self.render_directory(ui, dir, false);
+                    }
+                }
+
+                for file in &listing.files {
+                    if !self.file_tree.shows(file, false) {
+                        continue;
+                    }
+                    let document_status = self
+                        .workspace_index
+                        .as_ref()
+                        .and_then(|index| index.status(file));
+                    if let Some(wanted) = &self.tree_status_filter {
+                        let matches = match &document_status {
+                            Some(status) => status.eq_ignore_ascii_case(wanted),
+                            None => wanted.is_empty() && export::is_markdown(file),
+                        };
+                        if !matches {
+                            continue;
+                        }
+                    }
+                    let file_name = file
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_else(|| file.display().to_string());
+
+                    let file_path_string = file.display().to_string();
+                    let is_selected = self.tree_selection.contains(file)
+                        || self
+                            .file_path
+                            .as_deref()
+                            .map(|current| current == file_path_string.as_str())
+                            .unwrap_or(false);
+
+                    let status = self.git_status.file(file);
+                    let response = ui
+                        .horizontal(|ui| {
+                            let response = ui.selectable_label(is_selected, file_name);
+                            if let Some(status) = status {
+                                ui.label(
+                                    egui::RichText::new(status.badge())
+                                        .color(status.color())
+                                        .small(),
+                                )
+                                .on_hover_text(status.description());
+                            }
+                            if let Some(document_status) = &document_status {
+                                let color = self.config.status.color(document_status);
+                                ui.label(egui::RichText::new(document_status).color(color).small());
+                            }
+                            response
+                        })
+                        .inner
+                        .on_hover_text(file_path_string);
+                    if response.clicked() {
+                        self.handle_tree_click(ui, file);
+                    }
+                    if response.double_clicked() && pdf_preview::is_pdf(file) {
+                        let command = self.config.preview.pdf_command.as_deref();
+                        self.pdf_preview =
+                            Some(pdf_preview::PdfPreview::start(file, command, ui.ctx()));
+                    }
+                    self.tree_visible_files.push(file.clone());
+                }
+            });
+        if response.header_response.clicked() {
+            self.file_tree.set_expanded(path, !expanded);
+        }
+        if !is_root {
+            response.header_response.context_menu(|ui| {
+                if ui
+                    .button("Move Folder...")
+                    .on_hover_text("Move the folder and update the links into and out of it")
+                    .clicked()
+                {
+                    self.plan_folder_move(path);
+                    ui.close_menu();
+                }
+            });
+        }
+    }
+
+    /// Asks where to move `folder` and shows the links that would change.
+    fn plan_folder_move(&mut self, folder: &Path) {
+        let mut dialog = FileDialog::new();
+        if let Some(parent) = folder.parent() {
+            dialog = dialog.set_directory(parent);
+        }
+        let Some(target_dir) = dialog.pick_folder() else {
+            return;
+        };
+        match self.folder_move_plan(folder, &target_dir) {
+            Ok(plan) => {
+                let report = plan.report(&self.working_dir);
+                self.folder_move = Some((plan, report));
+            }
+            Err(err) => self.notifications.error("Moving folder", err),
+        }
+    }
+
+    /// The links that change when `folder` moves into `target_dir`, from
+    /// the documents as they are now, the open one's unsaved text included.
+    fn folder_move_plan(
+        &self,
+        folder: &Path,
+        target_dir: &Path,
+    ) -> Result<folder_move::FolderMove, String> {
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let open = document
+            .as_deref()
+            .map(|path| (path, self.content.as_str()));
+        let ignore = self.workspace_ignore();
+        folder_move::FolderMove::plan(&self.working_dir, ignore, folder, target_dir, open)
+    }
+
+    fn show_folder_move_window(&mut self, ctx: &Context) {
+        let Some((plan, report)) = &self.folder_move else {
+            return;
+        };
+        let mut open = true;
+        let mut apply = false;
+        let mut cancel = false;
+        egui::Window::new("Move Folder")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} document(s) get updated links. Nothing has changed yet.",
+                    plan.edits.len()
+                ));
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| ui.monospace(report.as_str()));
+                ui.horizontal(|ui| {
+                    apply = ui.button("Move and Update Links").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+        if apply {
+            if let Some((plan, shown)) = self.folder_move.take() {
+                // Documents may have been edited while the window was open;
+                // only move if the links shown are still the ones to change.
+                let target_dir = plan.to.parent().unwrap_or(&plan.to);
+                match self.folder_move_plan(&plan.from, target_dir) {
+                    Ok(fresh) => {
+                        let report = fresh.report(&self.working_dir);
+                        if report == shown {
+                            self.apply_folder_move(fresh);
+                        } else {
+                            self.notifications.warning(
+                                "Moving folder",
+                                "The documents changed; check the updated list of links.",
+                            );
+                            self.folder_move = Some((fresh, report));
+                        }
+                    }
+                    Err(err) => self.notifications.error("Moving folder", err),
+                }
+            }
+        } else if !open || cancel {
+            self.folder_move = None;
+        }
+    }
+
+    fn apply_folder_move(&mut self, plan: folder_move::FolderMove) {
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let moved_document = document
+            .as_deref()
+            .map(export::normalize)
+            .filter(|path| path.starts_with(&plan.from))
+            .map(|path| plan.to.join(path.strip_prefix(&plan.from).unwrap_or(&path)));
+        let (from, to, edits) = (plan.from.clone(), plan.to.clone(), plan.edits.len());
+        match plan.apply(document.as_deref()) {
+            Ok((open_text, failures)) => {
+                if let Some(path) = moved_document {
+                    self.file_path = Some(path.display().to_string());
+                    self.file_mtime = Self::disk_mtime(&path);
+                }
+                if let Some(text) = open_text {
+                    self.content = text;
+                    self.update_modified();
+                }
+                self.tree_selection
+                    .retain(|path| !export::normalize(path).starts_with(&from));
+                self.file_tree.refresh();
+                let mut message = format!(
+                    "Moved {} -> {} and updated links in {} document(s).",
+                    from.display(),
+                    to.display(),
+                    edits
+                );
+                if !failures.is_empty() {
+                    message.push_str(&format!(
+                        "\n\nThese could not be written:\n{}",
+                        failures.join("\n")
+                    ));
+                }
+                self.show_tool_message(message);
+            }
+            Err(err) => self.notifications.error("Moving folder", err),
+        }
+    }
+
+    /// Files for combined and batch export: the markdown files selected in
+    /// the tree, or else the book's chapters or every markdown file.
+    fn export_candidates(&self) -> Vec<PathBuf> {
+        let files = if let (true, Some(book)) = (self.tree_selection.is_empty(), &self.book) {
+            book.chapter_paths()
+        } else if self.tree_selection.is_empty() {
+            export::markdown_files_except(&self.working_dir, self.workspace_ignore())
+        } else {
+            self.tree_selection
+                .iter()
+                .filter(|path| export::is_markdown(path))
+                .cloned()
+                .collect()
+        };
+        export::order_files(files, &self.working_dir)
+    }
+
+    fn open_combined_export(&mut self) {
+        self.combined_export_files = self.export_candidates();
+        self.show_combined_export = true;
+    }
+
+    fn open_batch_export(&mut self) {
+        if self.batch_export_job.is_none() {
+            self.batch_export_files = self.export_candidates();
+        }
+        self.show_batch_export = true;
+    }
+
+    fn show_watch_export_menu(&mut self, ui: &mut egui::Ui) {
+        if let Some((watcher, settings)) = &self.watch_export {
+            ui.weak(format!(
+                "Exporting {} files from {}",
+                settings.format.label(),
+                watcher.root().display()
+            ));
+            if ui.button("Stop").clicked() {
+                self.stop_watch_export();
+                ui.close_menu();
+            }
+            return;
+        }
+        ui.weak("Export every markdown file now and again whenever it changes");
+        for format in ExportFormat::ALL {
+            if ui.button(format!("As {}...", format.label())).clicked() {
+                self.start_watch_export(format);
+                ui.close_menu();
+            }
+        }
+    }
+
+    /// Starts watching the working directory. Exports go to `[export]
+    /// output_dir`, or mirror the folder structure into a folder picked now.
+    fn start_watch_export(&mut self, format: ExportFormat) {
+        let (destination, ignore) = match &self.config.export.output_dir {
+            Some(dir) => (
+                batch_export::Destination::Pattern {
+                    root: self.working_dir.clone(),
+                    dir: dir.clone(),
+                },
+                watch::pattern_root(&self.working_dir, dir),
+            ),
+            None => {
+                let mut dialog = FileDialog::new();
+                if self.working_dir.is_dir() {
+                    dialog = dialog.set_directory(&self.working_dir);
+                }
+                let Some(folder) = dialog.pick_folder() else {
+                    return;
+                };
+                let destination = batch_export::Destination::Mirror {
+                    source: self.working_dir.clone(),
+                    output: folder.clone(),
+                };
+                (destination, Some(folder))
+            }
+        };
+        let html = match self.html_options() {
+            Ok(html) => html,
+            Err(err) => {
+                self.show_tool_message(err);
+                return;
+            }
+        };
+        let settings = batch_export::Settings {
+            format,
+            filename_pattern: self.config.export.filename_pattern.clone(),
+            destination,
+            pdf_command: self.config.export.pdf_command.clone(),
+            html,
+        };
+        let patterns = self.workspace_ignore().clone();
+        let watcher = watch::Watcher::new(&self.working_dir, ignore, patterns);
+        self.watch_export = Some((watcher, settings));
+        self.watch_export_next = 0.0;
+        self.watch_export_status.clear();
+    }
+
+    fn stop_watch_export(&mut self) {
+        self.watch_export = None;
+        self.watch_export_job = None;
+        self.watch_export_status.clear();
+    }
+
+    /// The file Clipboard Capture appends to.
+    fn capture_file(&self) -> PathBuf {
+        self.working_dir.join(&self.config.capture.file)
+    }
+
+    /// Writes what was copied since the last frame to the capture file, or
+    /// into the editor if the capture file is open.
+    fn poll_clipboard_capture(&mut self) {
+        let Some(capture) = &mut self.clipboard_capture else {
+            return;
+        };
+        if let Some(err) = capture.error() {
+            self.clipboard_capture = None;
+            self.show_tool_message(err);
+            return;
+        }
+        let entries: Vec<String> = capture
+            .take()
+            .iter()
+            .map(|text| capture::entry(text))
+            .collect();
+        if entries.is_empty() {
+            return;
+        }
+        capture.count += entries.len();
+        let target = self.capture_file();
+        if let Err(err) = self.append_entries(&target, &entries) {
+            self.clipboard_capture = None;
+            self.show_tool_message(format!("Clipboard capture stopped: {}", err));
+        }
+    }
+
+    /// Appends `entries` to the document at `target`: in the editor if it
+    /// is open, else on disk.
+    fn append_entries(&mut self, target: &Path, entries: &[String]) -> Result<(), String> {
+        if self.file_path.as_deref().map(Path::new) == Some(target) {
+            for entry in entries {
+                capture::append_entry(&mut self.content, entry);
+            }
+            self.update_modified();
+            return Ok(());
+        }
+        capture::append_to_file(target, entries)
+    }
+
+    /// Starts or stops the web clipper to match the config and files the
+    /// clips it received into the inbox.
+    fn poll_web_clipper(&mut self, ctx: &Context) {
+        let config = &self.config.clipper;
+        if !config.enabled {
+            self.web_clipper = None;
+            return;
+        }
+        let running = self
+            .web_clipper
+            .as_ref()
+            .is_some_and(|clipper| clipper.port == config.port);
+        if !running {
+            self.web_clipper = None;
+            match clipper::Clipper::start(config.port, &config.token, ctx.clone()) {
+                Ok(clipper) => self.web_clipper = Some(clipper),
+                Err(err) => {
+                    // Not saved, so the next start tries again.
+                    self.config.clipper.enabled = false;
+                    self.show_tool_message(err);
+                    return;
+                }
+            }
+        }
+        let Some(clipper) = &mut self.web_clipper else {
+            return;
+        };
+        let entries: Vec<String> = clipper.take().iter().map(clipper::entry).collect();
+        if entries.is_empty() {
+            return;
+        }
+        clipper.count += entries.len();
+        let inbox = self.working_dir.join(&self.config.clipper.inbox);
+        if let Err(err) = self.append_entries(&inbox, &entries) {
+            self.show_tool_message(format!("Could not file web clip: {}", err));
+        }
+    }
+
+    /// Starts the preview server and opens it in the browser, or stops it.
+    fn toggle_preview_server(&mut self, serve: bool) {
+        if !serve {
+            self.preview_server = None;
+            return;
+        }
+        match preview_server::PreviewServer::start(self.config.preview.serve_port) {
+            Ok(server) => {
+                let url = server.url();
+                self.preview_server = Some(server);
+                self.update_served_preview();
+                if let Err(err) = mdbook::open_in_browser(&url) {
+                    self.show_tool_message(format!("Serving the preview at {} ({})", url, err));
+                }
+            }
+            Err(err) => self.show_tool_message(err),
+        }
+    }
+
+    /// Renders the document for the preview server when it has changed.
+    fn update_served_preview(&mut self) {
+        let Some(server) = &self.preview_server else {
+            return;
+        };
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let root = document
+            .as_ref()
+            .and_then(|path| path.parent())
+            .unwrap_or(&self.working_dir)
+            .to_path_buf();
+        if server.is_showing(&self.content, &root) {
+            return;
+        }
+        let title = document
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .map_or("Untitled".to_string(), |stem| {
+                stem.to_string_lossy().into_owned()
+            });
+        // A broken template falls back to the built-in page rather than
+        // stopping the preview.
+        let options = self.html_options().unwrap_or_else(|_| export::HtmlOptions {
+            typography: self.config.typography(),
+            ..Default::default()
+        });
+        let html = export::markdown_to_html(&self.content, &title, &options);
+        server.show(&self.content, &html, &root);
+    }
+
+    fn show_web_clipper_menu(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .checkbox(&mut self.config.clipper.enabled, "Listen for Clips")
+            .on_hover_text(format!(
+                "Accept pages from the bookmarklet on 127.0.0.1:{}",
+                self.config.clipper.port
+            ))
+            .clicked()
+        {
+            if self.config.clipper.token.is_empty() {
+                self.config.clipper.token = clipper::new_token();
+            }
+            self.save_config();
+        }
+        ui.weak(format!("Clips go to {}", self.config.clipper.inbox));
+        ui.separator();
+        let enabled = !self.config.clipper.token.is_empty();
+        if ui
+            .add_enabled(enabled, egui::Button::new("Copy Bookmarklet"))
+            .on_hover_text("Add it as a bookmark, then click it on a page to clip it")
+            .on_disabled_hover_text("Turn on Listen for Clips first")
+            .clicked()
+        {
+            let bookmarklet =
+                clipper::bookmarklet(self.config.clipper.port, &self.config.clipper.token);
+            ui.output_mut(|output| output.copied_text = bookmarklet);
+            ui.close_menu();
+        }
+    }
+
+    /// Reports the finished run and exports whatever changed since.
+    fn poll_watch_export(&mut self, ctx: &Context, now: f64) {
+        let Some((watcher, settings)) = &mut self.watch_export else {
+            return;
+        };
+        if let Some(job) = &self.watch_export_job {
+            if !job.is_done() {
+                return;
+            }
+            self.watch_export_status = job.with_progress(|outcomes, _| {
+                let failures: Vec<String> = outcomes
+                    .iter()
+                    .filter_map(|outcome| {
+                        let err = outcome.result.as_ref().err()?;
+                        Some(format!("{}: {}", outcome.source.display(), err))
+                    })
+                    .collect();
+                let mut status = format!(
+                    "Last run: {} exported, {} failed",
+                    outcomes.len() - failures.len(),
+                    failures.len()
+                );
+                for failure in failures {
+                    status.push('\n');
+                    status.push_str(&failure);
+                }
+                status
+            });
+            self.watch_export_job = None;
+        }
+
+        if now >= self.watch_export_next {
+            self.watch_export_next = now + watch::INTERVAL_SECS;
+            let changed = watcher.changes();
+            if !changed.is_empty() {
+                self.watch_export_job = Some(BatchExportJob::start(
+                    changed,
+                    settings.clone(),
+                    ctx.clone(),
+                ));
+            }
+        }
+        ctx.request_repaint_after(Duration::from_secs_f64(watch::INTERVAL_SECS));
+    }
+
+    fn show_batch_export_window(&mut self, ctx: &Context) {
+        let mut open = true;
+        egui::Window::new("Batch Export")
+            .open(&mut open)
+            .resizable(true)
+            .show(ctx, |ui| {
+                if self.batch_export_job.is_some() {
+                    self.show_batch_export_progress(ui);
+                } else {
+                    self.show_batch_export_setup(ui);
+                }
+            });
+        if !open {
+            self.show_batch_export = false;
+            self.batch_export_job = None;
+        }
+    }
+
+    fn show_batch_export_setup(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Format:");
+            for format in ExportFormat::ALL {
+                ui.radio_value(&mut self.batch_export_format, format, format.label());
+            }
+        });
+        match &self.config.export.output_dir {
+            Some(dir) => ui.label(format!("Output folder: {} ([export] output_dir)", dir)),
+            None => ui.label("Output folder: chosen when you export"),
+        };
+        ui.label(format!(
+            "File names: {} ([export] filename_pattern)",
+            self.config.export.filename_pattern
+        ));
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(format!("{} files:", self.batch_export_files.len()));
+            if ui
+                .button("Load Manifest...")
+                .on_hover_text("A SUMMARY.md, or a text file with one path per line")
+                .clicked()
+            {
+                self.load_batch_manifest();
+            }
+        });
+
+        let mut remove = None;
+        egui::ScrollArea::vertical()
+            .max_height(240.0)
+            .show(ui, |ui| {
+                for (index, file) in self.batch_export_files.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✖").clicked() {
+                            remove = Some(index);
+                        }
+                        let display = file
+                            .strip_prefix(&self.working_dir)
+                            .unwrap_or(file)
+                            .display()
+                            .to_string();
+                        ui.monospace(display);
                     });
+                }
+            });
+        if let Some(index) = remove {
+            self.batch_export_files.remove(index);
+        }
+
+        ui.separator();
+        let mut export_clicked = false;
+        ui.add_enabled_ui(!self.batch_export_files.is_empty(), |ui| {
+            if ui.button("Export").clicked() {
+                export_clicked = true;
+            }
+        });
+        if export_clicked {
+            self.start_batch_export(ui.ctx());
+        }
+    }
+
+    fn load_batch_manifest(&mut self) {
+        let mut dialog = FileDialog::new();
+        if self.working_dir.is_dir() {
+            dialog = dialog.set_directory(&self.working_dir);
+        }
+        let Some(manifest) = dialog.pick_file() else {
+            return;
+        };
+        match batch_export::manifest_files(&manifest) {
+            Ok(files) => self.batch_export_files = files,
+            Err(err) => self.show_tool_message(err),
+        }
+    }
+
+    fn start_batch_export(&mut self, ctx: &Context) {
+        let destination = match &self.config.export.output_dir {
+            Some(dir) => batch_export::Destination::Pattern {
+                root: self.working_dir.clone(),
+                dir: dir.clone(),
+            },
+            None => {
+                let mut dialog = FileDialog::new();
+                if self.working_dir.is_dir() {
+                    dialog = dialog.set_directory(&self.working_dir);
+                }
+                let Some(folder) = dialog.pick_folder() else {
+                    return;
+                };
+                batch_export::Destination::Folder(folder)
+            }
+        };
+        let html = match self.html_options() {
+            Ok(html) => html,
+            Err(err) => {
+                self.show_tool_message(err);
+                return;
+            }
+        };
+        let settings = batch_export::Settings {
+            format: self.batch_export_format,
+            filename_pattern: self.config.export.filename_pattern.clone(),
+            destination,
+            pdf_command: self.config.export.pdf_command.clone(),
+            html,
+        };
+        self.batch_export_job = Some(BatchExportJob::start(
+            self.batch_export_files.clone(),
+            settings,
+            ctx.clone(),
+        ));
+    }
+
+    fn show_batch_export_progress(&mut self, ui: &mut egui::Ui) {
+        let Some(job) = &self.batch_export_job else {
+            return;
+        };
+        let done = job.is_done();
+        let total = job.total;
+        let mut back = false;
+        job.with_progress(|outcomes, current| {
+            let finished = outcomes.len();
+            let failed = outcomes
+                .iter()
+                .filter(|outcome| outcome.result.is_err())
+                .count();
+            let relative = |path: &Path| {
+                path.strip_prefix(&self.working_dir)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string()
+            };
+
+            if done {
+                let skipped = total - finished;
+                let mut summary = format!("{} exported, {} failed", finished - failed, failed);
+                if skipped > 0 {
+                    summary.push_str(&format!(", {} cancelled", skipped));
+                }
+                ui.strong(summary);
+            } else {
+                ui.add(
+                    egui::ProgressBar::new(finished as f32 / total.max(1) as f32)
+                        .text(format!("{} / {}", finished, total)),
+                );
+                if let Some(current) = current {
+                    ui.label(format!("Exporting {}", relative(current)));
+                }
+            }
+
+            egui::ScrollArea::vertical()
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    for outcome in outcomes.iter().filter(|outcome| outcome.result.is_err()) {
+                        if let Err(err) = &outcome.result {
+                            ui.colored_label(
+                                Color32::from_rgb(220, 80, 80),
+                                format!("✖ {}: {}", relative(&outcome.source), err),
+                            );
+                        }
+                    }
+                    for outcome in outcomes {
+                        if let Ok(output) = &outcome.result {
+                            ui.label(format!(
+                                "✔ {} → {}",
+                                relative(&outcome.source),
+                                relative(output)
+                            ));
+                        }
+                    }
                 });
+
+            ui.separator();
+            if done {
+                back = ui.button("Back").clicked();
+            } else if ui.button("Cancel").clicked() {
+                job.cancel();
+            }
         });
+        if back {
+            self.batch_export_job = None;
+        }
+    }
 
-        // ==== STATUS BAR ====
-        TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                let name = self.file_path.as_deref().unwrap_or("Untitled");
+    fn show_combined_export_window(&mut self, ctx: &Context) {
+        let mut open = true;
+        let mut export_clicked = false;
+        egui::Window::new("Export Combined Document")
+            .open(&mut open)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Format:");
+                    for format in ExportFormat::ALL {
+                        ui.radio_value(&mut self.combined_export_format, format, format.label());
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Top heading level per file:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.config.export.combined_heading_level)
+                            .clamp_range(1..=6),
+                    );
+                });
+                ui.separator();
                 ui.label(format!(
-                    "{}{}",
-                    if self.modified { "🟡 " } else { "🟢 " },
-                    name
+                    "{} files, in order (book manifest or numeric prefixes):",
+                    self.combined_export_files.len()
                 ));
+
+                let mut move_up = None;
+                let mut remove = None;
+                egui::ScrollArea::vertical()
+                    .max_height(240.0)
+                    .show(ui, |ui| {
+                        for (index, file) in self.combined_export_files.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("⬆").clicked() && index > 0 {
+                                    move_up = Some(index);
+                                }
+                                if ui.small_button("✖").clicked() {
+                                    remove = Some(index);
+                                }
+                                let display = file
+                                    .strip_prefix(&self.working_dir)
+                                    .unwrap_or(file)
+                                    .display()
+                                    .to_string();
+                                ui.monospace(display);
+                            });
+                        }
+                    });
+                if let Some(index) = move_up {
+                    self.combined_export_files.swap(index, index - 1);
+                }
+                if let Some(index) = remove {
+                    self.combined_export_files.remove(index);
+                }
+
                 ui.separator();
-                ui.label(format!("✍️ {} chars", self.content.len()));
+                ui.add_enabled_ui(!self.combined_export_files.is_empty(), |ui| {
+                    if ui.button("Export...").clicked() {
+                        export_clicked = true;
+                    }
+                });
             });
-        });
 
-        if self.show_tool_output {
-            let mut open = true;
-            egui::Window::new("Tool Output")
-                .open(&mut open)
-                .resizable(true)
-                .vscroll(true)
-                .show(ctx, |ui| {
-                    if let Some(output) = &self.tool_output {
-                        ui.monospace(output);
-                    } else {
-                        ui.label("No output available.");
+        if export_clicked {
+            self.export_combined();
+        }
+        if !open {
+            self.show_combined_export = false;
+        }
+    }
+
+    /// Asks for the passphrase of a protected HTML export, twice.
+    fn show_protected_export_window(&mut self, ctx: &Context) {
+        let mut open = true;
+        let mut export = false;
+        egui::Window::new("Protected HTML Export")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("The exported page asks for this passphrase before showing the document.");
+                egui::Grid::new("protected_export")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Passphrase:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.protected_export_passphrase)
+                                .password(true),
+                        );
+                        ui.end_row();
+                        ui.label("Repeat:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.protected_export_confirm)
+                                .password(true),
+                        );
+                        ui.end_row();
+                    });
+                let passphrase = &self.protected_export_passphrase;
+                let problem = if passphrase.chars().count() < protect::MIN_PASSPHRASE_LEN {
+                    Some(format!(
+                        "At least {} characters.",
+                        protect::MIN_PASSPHRASE_LEN
+                    ))
+                } else if *passphrase != self.protected_export_confirm {
+                    Some("The passphrases differ.".to_string())
+                } else {
+                    None
+                };
+                match &problem {
+                    Some(problem) => ui.weak(problem),
+                    None => ui.weak("There is no way to recover a forgotten passphrase."),
+                };
+                export = ui
+                    .add_enabled(problem.is_none(), egui::Button::new("Export..."))
+                    .clicked();
+            });
+        if export {
+            let passphrase = self.protected_export_passphrase.clone();
+            self.export_document(ExportFormat::Html, Some(passphrase));
+        }
+        if export || !open {
+            self.show_protected_export = false;
+            self.protected_export_passphrase.clear();
+            self.protected_export_confirm.clear();
+        }
+    }
+
+    /// Packs the document and the local files it links to into a zip, with
+    /// the links rewritten to point inside it.
+    fn export_bundle(&mut self) {
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let document_name = document
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map_or("Untitled.md".to_string(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        let dir = document
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map_or_else(|| self.working_dir.clone(), Path::to_path_buf);
+        let stem = Path::new(&document_name)
+            .file_stem()
+            .map_or("Untitled".into(), |stem| stem.to_string_lossy());
+        let mut dialog = FileDialog::new()
+            .add_filter("Zip archive", &["zip"])
+            .set_file_name(&format!("{}.zip", stem));
+        if dir.is_dir() {
+            dialog = dialog.set_directory(&dir);
+        }
+        let Some(output) = dialog.save_file() else {
+            return;
+        };
+        let bundle = bundle::collect(&self.content, &dir);
+        match bundle::write(&bundle, &document_name, &output) {
+            Ok(message) => self.show_tool_message(message),
+            Err(err) => self.notifications.error("Exporting a bundle", err),
+        }
+    }
+
+    /// Exports the open document through pandoc to a file picked now.
+    fn export_with_pandoc(&mut self, format: pandoc::Format) {
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let stem = document
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .map_or("Untitled".to_string(), |stem| {
+                stem.to_string_lossy().into_owned()
+            });
+        let name =
+            export::expand_pattern(&self.config.export.filename_pattern, &self.content, &stem);
+        let name = if name.trim().is_empty() {
+            stem.clone()
+        } else {
+            name
+        };
+        let dir = document
+            .as_ref()
+            .and_then(|path| path.parent())
+            .unwrap_or(&self.working_dir)
+            .to_path_buf();
+        let mut dialog = FileDialog::new()
+            .add_filter(format.label(), &[format.extension()])
+            .set_file_name(&format!("{}.{}", name, format.extension()));
+        if dir.is_dir() {
+            dialog = dialog.set_directory(&dir);
+        }
+        let Some(output) = dialog.save_file() else {
+            return;
+        };
+        let config = &self.config.pandoc;
+        let result = pandoc::export(
+            &config.command,
+            &config.export_args,
+            &self.content,
+            &stem,
+            &dir,
+            format,
+            &output,
+        );
+        match result {
+            Ok(message) => self.show_tool_message(message),
+            Err(err) => self.show_tool_message(err),
+        }
+    }
+
+    /// Exports the working directory as a static site to a folder picked
+    /// now.
+    fn export_site(&mut self) {
+        let mut dialog = FileDialog::new();
+        if let Some(parent) = self.working_dir.parent() {
+            dialog = dialog.set_directory(parent);
+        }
+        let Some(output) = dialog.pick_folder() else {
+            return;
+        };
+        let html = match self.html_options() {
+            Ok(html) => html,
+            Err(err) => {
+                self.show_tool_message(err);
+                return;
+            }
+        };
+        let site = match site::export(&self.working_dir, self.workspace_ignore(), &output, &html) {
+            Ok(site) => site,
+            Err(err) => {
+                self.notifications.error("Exporting a static site", err);
+                return;
+            }
+        };
+        let mut message = format!(
+            "Wrote {} page(s) and copied {} other file(s) to {}.",
+            site.pages,
+            site.assets,
+            output.display()
+        );
+        if site.index {
+            message.push_str(" index.html lists every page.");
+        }
+        if !site.failures.is_empty() {
+            message.push_str(&format!(
+                "\n\n{} failed:\n{}",
+                site.failures.len(),
+                site.failures.join("\n")
+            ));
+        }
+        self.show_tool_message(message);
+    }
+
+    /// Copies the notes at `source` into the working directory and opens
+    /// the first document.
+    fn import_notes(&mut self, ctx: &Context, source: &Path, kind: NoteImport) {
+        let root = &self.working_dir;
+        let imported = match kind {
+            NoteImport::Bundle => {
+                bundle::read_source(source).and_then(|files| bundle::import(files, root))
+            }
+            NoteImport::Notion => notion::read_export(source)
+                .and_then(|files| bundle::import_renamed(files, root, notion::clean_path)),
+            NoteImport::Evernote => {
+                evernote::read_enex(source).and_then(|files| bundle::import(files, root))
+            }
+        };
+        let imported = match imported {
+            Ok(imported) => imported,
+            Err(err) => {
+                self.notifications.error("Importing notes", err);
+                return;
+            }
+        };
+        self.file_tree.refresh();
+        let mut message = format!(
+            "Imported {} document(s) and {} other file(s) from {}.",
+            imported.documents.len(),
+            imported.assets,
+            source.display()
+        );
+        if imported.reused > 0 {
+            message.push_str(&format!(
+                " {} file(s) were already in the workspace.",
+                imported.reused
+            ));
+        }
+        for (name, path) in &imported.renamed {
+            message.push_str(&format!("\n{} was renamed to {}", name, path.display()));
+        }
+        self.show_tool_message(message);
+        if let Some(first) = imported.documents.into_iter().next() {
+            self.request_action(ctx, DeferredAction::OpenPath(first));
+        }
+    }
+
+    /// Exports the open document. With `[export] output_dir` set the file is
+    /// written there under `filename_pattern` straight away; otherwise the
+    /// pattern only suggests the name in the save dialog. HTML is encrypted
+    /// behind `passphrase` if one is given.
+    fn export_document(&mut self, format: ExportFormat, passphrase: Option<String>) {
+        let document = self.file_path.as_ref().map(PathBuf::from);
+        let stem = document
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .map_or("Untitled".to_string(), |stem| {
+                stem.to_string_lossy().into_owned()
+            });
+        let file_name = export::output_name(
+            &self.config.export.filename_pattern,
+            &self.content,
+            &stem,
+            format,
+        );
+
+        let output = match &self.config.export.output_dir {
+            Some(dir) => {
+                let dir = export::expand_pattern(dir, &self.content, &stem);
+                let output = self.working_dir.join(dir).join(&file_name);
+                if let Some(parent) = output.parent() {
+                    if let Err(err) = fs::create_dir_all(parent) {
+                        self.show_tool_message(format!(
+                            "Failed to create {}: {}",
+                            parent.display(),
+                            err
+                        ));
+                        return;
                     }
-                });
-            if !open {
-                self.show_tool_output = false;
+                }
+                output
             }
+            None => {
+                let mut dialog = FileDialog::new()
+                    .add_filter(format.label(), &[format.extension()])
+                    .set_file_name(&file_name.display().to_string());
+                let dir = document
+                    .as_ref()
+                    .and_then(|path| path.parent())
+                    .unwrap_or(&self.working_dir);
+                if dir.is_dir() {
+                    dialog = dialog.set_directory(dir);
+                }
+                let Some(output) = dialog.save_file() else {
+                    return;
+                };
+                output
+            }
+        };
+
+        let base_dir = document
+            .as_ref()
+            .and_then(|path| path.parent())
+            .unwrap_or(&self.working_dir);
+        let output_dir = output.parent().unwrap_or(Path::new("."));
+        let markdown = export::rebase_links(&self.content, base_dir, output_dir);
+        let html = match self.html_options() {
+            Ok(html) => export::HtmlOptions { passphrase, ..html },
+            Err(err) => {
+                self.show_tool_message(err);
+                return;
+            }
+        };
+        let result = export::write_document(
+            &markdown,
+            &stem,
+            format,
+            &output,
+            self.config.export.pdf_command.as_deref(),
+            &html,
+        );
+        match result {
+            Ok(message) => self.show_tool_message(message),
+            Err(err) => self.show_tool_message(err),
         }
+    }
 
-        // ==== EXIT CONFIRMATION ====
-        if self.show_exit_confirm {
-            egui::Window::new("Unsaved Changes")
-                .collapsible(false)
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.label("You have unsaved changes. Save before exiting?");
-                    ui.horizontal(|ui| {
-                        if ui.button("Save and Exit").clicked() {
-                            self.save_file(false);
-                            std::process::exit(0);
-                        }
-                        if ui.button("Discard and Exit").clicked() {
-                            std::process::exit(0);
-                        }
-                        if ui.button("Cancel").clicked() {
-                            self.show_exit_confirm = false;
-                        }
+    fn export_combined(&mut self) {
+        let format = self.combined_export_format;
+        let mut dialog = FileDialog::new()
+            .add_filter(format.label(), &[format.extension()])
+            .set_file_name(&format!("combined.{}", format.extension()));
+        if self.working_dir.is_dir() {
+            dialog = dialog.set_directory(&self.working_dir);
+        }
+        let Some(output) = dialog.save_file() else {
+            return;
+        };
+
+        let output_dir = output
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| self.working_dir.clone());
+        let level = self.config.export.combined_heading_level.clamp(1, 6);
+        let combined = match export::combine(&self.combined_export_files, level, &output_dir) {
+            Ok(combined) => combined,
+            Err(err) => {
+                self.show_tool_message(err);
+                return;
+            }
+        };
+
+        let title = self
+            .working_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Combined".to_string());
+        let html = match self.html_options() {
+            Ok(html) => html,
+            Err(err) => {
+                self.show_tool_message(err);
+                return;
+            }
+        };
+        let result = export::write_document(
+            &combined,
+            &title,
+            format,
+            &output,
+            self.config.export.pdf_command.as_deref(),
+            &html,
+        );
+        match result {
+            Ok(message) => {
+                self.show_combined_export = false;
+                self.save_config();
+                self.show_tool_message(message);
+            }
+            Err(err) => self.show_tool_message(err),
+        }
+    }
+
+    fn show_feed_export_window(&mut self, ctx: &Context) {
+        let mut open = true;
+        let mut export_clicked = false;
+        egui::Window::new("Export Feed")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Format:");
+                    for format in feed::FeedFormat::ALL {
+                        ui.radio_value(&mut self.feed_export_format, format, format.label());
+                    }
+                });
+                let config = &mut self.config.feed;
+                egui::Grid::new("feed_export")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Title:");
+                        ui.text_edit_singleline(&mut config.title);
+                        ui.end_row();
+                        ui.label("Site URL:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut config.site_url)
+                                .hint_text("https://example.com/notes"),
+                        );
+                        ui.end_row();
+                        ui.label("Description:");
+                        ui.text_edit_singleline(&mut config.description);
+                        ui.end_row();
+                        ui.label("Author:");
+                        ui.text_edit_singleline(&mut config.author);
+                        ui.end_row();
+                        ui.label("Newest posts:");
+                        ui.add(egui::DragValue::new(&mut config.limit).clamp_range(1..=1000));
+                        ui.end_row();
                     });
+                ui.weak(format!(
+                    "Of these {} files, those with a `date` (YYYY-MM-DD) in their front matter \
+                     are posts; drafts are skipped.",
+                    self.feed_export_files.len()
+                ));
+                ui.add_enabled_ui(!config.site_url.trim().is_empty(), |ui| {
+                    export_clicked = ui.button("Export...").clicked();
                 });
+            });
+
+        if export_clicked {
+            self.export_feed();
+        }
+        if !open {
+            self.show_feed_export = false;
         }
     }
-}
 
-impl MarkdownApp {
-    fn load_config() -> (AppConfig, Option<PathBuf>) {
-        let config_path = MarkdownApp::config_file_path();
-        let mut config = AppConfig::default();
+    fn export_feed(&mut self) {
+        let format = self.feed_export_format;
+        let mut dialog = FileDialog::new()
+            .add_filter(format.label(), &["xml"])
+            .set_file_name(format.file_name());
+        if self.working_dir.is_dir() {
+            dialog = dialog.set_directory(&self.working_dir);
+        }
+        let Some(output) = dialog.save_file() else {
+            return;
+        };
 
-        if let Some(path) = &config_path {
-            if path.exists() {
-                match fs::read_to_string(path) {
-                    Ok(contents) => match toml::from_str::<AppConfig>(&contents) {
-                        Ok(parsed) => config = parsed,
-                        Err(err) => eprintln!("Config parse error ({}): {}", path.display(), err),
-                    },
-                    Err(err) => eprintln!("Config read error ({}): {}", path.display(), err),
-                }
+        let config = &self.config.feed;
+        let site_url = config.site_url.trim();
+        let items = feed::items(&self.feed_export_files, &self.working_dir, site_url);
+        let title = if config.title.trim().is_empty() {
+            self.working_dir
+                .file_name()
+                .map_or("Notes".to_string(), |name| {
+                    name.to_string_lossy().into_owned()
+                })
+        } else {
+            config.title.trim().to_string()
+        };
+        let channel = feed::Channel {
+            title: &title,
+            description: config.description.trim(),
+            author: config.author.trim(),
+            site_url,
+        };
+        let xml = feed::render(format, &channel, &items, config.limit.max(1));
+        match fs::write(&output, xml) {
+            Ok(()) => {
+                self.show_feed_export = false;
+                self.save_config();
+                self.show_tool_message(format!(
+                    "Wrote {} posts to {}",
+                    items.len().min(self.config.feed.limit.max(1)),
+                    output.display()
+                ));
+            }
+            Err(err) => {
+                self.show_tool_message(format!("Failed to write {}: {}", output.display(), err))
             }
         }
+    }
 
-        if config.working_dir.is_none() {
-            if let Some(legacy_path) = MarkdownApp::legacy_settings_path() {
-                if let Ok(contents) = fs::read_to_string(&legacy_path) {
-                    let candidate = PathBuf::from(contents.trim());
-                    if candidate.is_dir() {
-                        config.working_dir = Some(candidate);
+    fn show_diff_export_window(&mut self, ctx: &Context) {
+        let mut open = true;
+        let mut export_clicked = false;
+        egui::Window::new("Export Annotated Diff")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Leave a revision empty to compare the saved file with your current edits.",
+                );
+                egui::Grid::new("diff_export_grid").show(ui, |ui| {
+                    ui.label("Old version:");
+                    ui.add(
+                        TextEdit::singleline(&mut self.diff_base_revision)
+                            .hint_text("saved file (or a git revision, e.g. HEAD)"),
+                    );
+                    ui.end_row();
+                    ui.label("New version:");
+                    ui.add(
+                        TextEdit::singleline(&mut self.diff_target_revision)
+                            .hint_text("current buffer (or a git revision)"),
+                    );
+                    ui.end_row();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Format:");
+                    for format in ExportFormat::ALL {
+                        ui.radio_value(&mut self.diff_export_format, format, format.label());
                     }
+                });
+                ui.separator();
+                if ui.button("Export...").clicked() {
+                    export_clicked = true;
+                }
+            });
+
+        if export_clicked {
+            self.export_annotated_diff();
+        }
+        if !open {
+            self.show_diff_export = false;
+        }
+    }
+
+    fn show_format_review_window(&mut self, ctx: &Context) {
+        let Some(review) = &self.format_review else {
+            return;
+        };
+        let stale = review.original != self.content;
+        let mut open = true;
+        let mut accept = false;
+        let mut reject = false;
+        egui::Window::new(format!("{} Changes", review.tool))
+            .id(egui::Id::new("format_review"))
+            .open(&mut open)
+            .resizable(true)
+            .default_size([720.0, 480.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} changed blocks", review.diff.changes));
+                    ui.separator();
+                    ui.radio_value(&mut self.format_diff_side_by_side, false, "Unified");
+                    ui.radio_value(&mut self.format_diff_side_by_side, true, "Side by Side");
+                });
+                if stale {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "The document was edited after formatting; run the formatter again.",
+                    );
                 }
+                ui.horizontal(|ui| {
+                    accept = ui
+                        .add_enabled(!stale, egui::Button::new("Accept"))
+                        .clicked();
+                    reject = ui.button("Reject").clicked();
+                });
+                ui.separator();
+                review.diff.show(ui, self.format_diff_side_by_side);
+            });
+
+        if accept {
+            if let Some(review) = self.format_review.take() {
+                self.content = review.formatted;
+                self.update_modified();
             }
         }
+        if reject || !open {
+            self.format_review = None;
+        }
+    }
 
-        (config, config_path)
+    fn export_annotated_diff(&mut self) {
+        let Some(path) = self.file_path.as_deref().map(PathBuf::from) else {
+            return;
+        };
+        let version = |revision: &str, fallback: Result<String, String>| {
+            if revision.trim().is_empty() {
+                fallback
+            } else {
+                git::show(&path, revision)
+            }
+        };
+        let old = version(
+            &self.diff_base_revision,
+            fs::read_to_string(&path)
+                .map_err(|err| format!("Failed to read {}: {}", path.display(), err)),
+        );
+        let new = version(&self.diff_target_revision, Ok(self.content.clone()));
+        let (old, new) = match (old, new) {
+            (Ok(old), Ok(new)) => (old, new),
+            (Err(err), _) | (_, Err(err)) => {
+                self.show_tool_message(err);
+                return;
+            }
+        };
+
+        let format = self.diff_export_format;
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "document".to_string());
+        let mut dialog = FileDialog::new()
+            .add_filter(format.label(), &[format.extension()])
+            .set_file_name(&format!("{}-changes.{}", stem, format.extension()));
+        if let Some(parent) = path.parent() {
+            dialog = dialog.set_directory(parent);
+        }
+        let Some(output) = dialog.save_file() else {
+            return;
+        };
+
+        let annotated = annotate::annotate(&old, &new);
+        let markdown = match format {
+            ExportFormat::Markdown => annotated,
+            ExportFormat::Html | ExportFormat::Pdf => critic::to_html_markup(&annotated),
+        };
+        let html = match self.html_options() {
+            Ok(html) => html,
+            Err(err) => {
+                self.show_tool_message(err);
+                return;
+            }
+        };
+        let result = export::write_document(
+            &markdown,
+            &format!("Changes to {}", stem),
+            format,
+            &output,
+            self.config.export.pdf_command.as_deref(),
+            &html,
+        );
+        match result {
+            Ok(message) => {
+                self.show_diff_export = false;
+                self.show_tool_message(message);
+            }
+            Err(err) => self.show_tool_message(err),
+        }
     }
 
-    fn initial_working_directory(config: &mut AppConfig) -> PathBuf {
-        if let Some(dir) = &config.working_dir {
-            if dir.is_dir() {
-                return dir.clone();
+    fn show_pdf_preview_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(preview) = &mut self.pdf_preview else {
+            return;
+        };
+        preview.poll(ui.ctx());
+        let mut close = false;
+        let mut open_externally = false;
+        ui.horizontal(|ui| {
+            ui.heading("PDF");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                close = ui.small_button("✖").on_hover_text("Close").clicked();
+            });
+        });
+        let name = preview
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        ui.label(egui::RichText::new(name).strong())
+            .on_hover_text(preview.path.display().to_string());
+        if let Some(size) = preview.size {
+            ui.weak(pdf_preview::format_size(size));
+        }
+        if ui.button("Open Externally").clicked() {
+            open_externally = true;
+        }
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .id_source("pdf_preview_scroll")
+            .auto_shrink([false; 2])
+            .show(ui, |ui| match &preview.page {
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.weak("Rendering the first page…");
+                    });
+                }
+                Some(Ok(texture)) => {
+                    ui.add(
+                        egui::Image::from_texture(texture)
+                            .fit_to_original_size(1.0)
+                            .max_width(ui.available_width()),
+                    );
+                }
+                Some(Err(err)) => {
+                    ui.weak(err);
+                }
+            });
+
+        let path = preview.path.display().to_string();
+        if open_externally {
+            if let Err(err) = mdbook::open_in_browser(&path) {
+                self.notifications
+                    .error("Opening a PDF", format!("Could not open {}: {}", path, err));
             }
         }
+        if close {
+            self.pdf_preview = None;
+        }
+    }
+
+    fn show_outline_panel(&mut self, ui: &mut egui::Ui) {
+        self.outline.update(&self.content);
+        let current_line = self.source_map.editor_offset_to_line(self.scroll_left);
+        let current_section = self.outline.section_at(current_line);
+        let follow = current_section != self.outline_section;
+        self.outline_section = current_section;
+
+        ui.heading("Outline");
+        let mut target = None;
+        egui::ScrollArea::vertical()
+            .id_source("outline_scroll")
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                if self.outline.headings.is_empty() {
+                    ui.weak("No headings in this document.");
+                }
+                let base_level = self
+                    .outline
+                    .headings
+                    .iter()
+                    .map(|heading| heading.level)
+                    .min()
+                    .unwrap_or(1);
+                for (index, heading) in self.outline.headings.iter().enumerate() {
+                    let selected = current_section == Some(index);
+                    ui.horizontal(|ui| {
+                        ui.add_space((heading.level - base_level) as f32 * 12.0);
+                        let response = ui
+                            .selectable_label(selected, &heading.title)
+                            .on_hover_text(format!("Line {}", heading.line + 1));
+                        if selected && follow {
+                            response.scroll_to_me(None);
+                        }
+                        if response.clicked() {
+                            target = Some(heading.line);
+                        }
+                    });
+                }
 
-        let default_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-        config.working_dir = Some(default_dir.clone());
-        default_dir
-    }
+                if self.book.is_some() {
+                    ui.add_space(8.0);
+                    egui::CollapsingHeader::new("Chapters")
+                        .default_open(true)
+                        .show(ui, |ui| self.show_chapter_list(ui));
+                }
+            });
 
-    fn config_file_path() -> Option<PathBuf> {
-        ProjectDirs::from("com", "fibnas", "md-echo")
-            .map(|dirs| dirs.config_dir().join("config.toml"))
+        if let Some(line) = target {
+            self.go_to_location(ui.ctx(), None, Some(line));
+        }
     }
 
-    fn legacy_settings_path() -> Option<PathBuf> {
-        ProjectDirs::from("com", "fibnas", "md-echo")
-            .map(|dirs| dirs.config_dir().join("settings.txt"))
-    }
+    fn show_chapter_list(&mut self, ui: &mut egui::Ui) {
+        let Some(book) = &self.book else {
+            return;
+        };
 
-    fn save_config(&self) {
-        if let Some(config_path) = &self.config_path {
-            if let Some(parent) = config_path.parent() {
-                if let Err(err) = fs::create_dir_all(parent) {
-                    eprintln!("Config directory error: {}", err);
-                    return;
-                }
+        ui.strong(book.title.as_deref().unwrap_or("Book"))
+            .on_hover_text(book.src_dir.display().to_string());
+        let current = self.file_path.as_deref().map(PathBuf::from);
+        let current_index = current.as_deref().and_then(|path| book.chapter_index(path));
+        let mut open_target = None;
+        let mut rescan = false;
+        ui.horizontal(|ui| {
+            if ui.small_button("◀ Prev").clicked() {
+                self.pending_chapter = Some(false);
             }
+            if ui.small_button("Next ▶").clicked() {
+                self.pending_chapter = Some(true);
+            }
+            if ui.small_button("Rescan").clicked() {
+                rescan = true;
+            }
+        });
 
-            match toml::to_string_pretty(&self.config) {
-                Ok(serialized) => {
-                    if let Err(err) = fs::write(config_path, serialized) {
-                        eprintln!("Config write error: {}", err);
+        for (index, chapter) in book.chapters.iter().enumerate() {
+            let indent = chapter.depth.saturating_sub(1) as f32 * 12.0;
+            ui.horizontal(|ui| {
+                ui.add_space(indent);
+                match &chapter.path {
+                    Some(path) => {
+                        let response = ui
+                            .selectable_label(current_index == Some(index), &chapter.title)
+                            .on_hover_text(path.display().to_string());
+                        if response.clicked() {
+                            open_target = Some(path.clone());
+                        }
+                    }
+                    None if chapter.depth == 0 => {
+                        ui.strong(&chapter.title);
+                    }
+                    None => {
+                        ui.weak(format!("{} (draft)", chapter.title));
                     }
                 }
-                Err(err) => eprintln!("Config serialize error: {}", err),
-            }
+            });
+        }
+
+        if let Some(path) = open_target {
+            self.request_action(ui.ctx(), DeferredAction::OpenPath(path));
+        }
+        if rescan {
+            self.book = Book::detect(&self.working_dir);
         }
     }
 
-    fn ensure_theme(&mut self, ctx: &Context) {
-        if self.theme_applied {
+    fn go_to_chapter(&mut self, ui: &mut egui::Ui, forward: bool) {
+        let (Some(book), Some(current)) = (&self.book, &self.file_path) else {
             return;
-        }
-        let visuals = self.config.theme.to_visuals();
-        ctx.set_visuals(visuals);
-        self.theme_applied = true;
+        };
+        let target = match book.neighbour(Path::new(current), forward) {
+            Some(target) => target,
+            None if book.chapter_index(Path::new(current)).is_none() => {
+                match book.chapter_paths().into_iter().next() {
+                    Some(first) => first,
+                    None => return,
+                }
+            }
+            None => return,
+        };
+        self.request_action(ui.ctx(), DeferredAction::OpenPath(target));
     }
 
-    fn set_working_directory(&mut self, new_dir: PathBuf) {
-        if new_dir.is_dir() {
-            self.working_dir = new_dir.clone();
-            self.config.working_dir = Some(new_dir);
-            self.save_config();
-        } else {
-            eprintln!("Invalid working directory: {}", new_dir.display());
+    /// Lists the markdown files of a folder in the config directory
+    /// (`templates` or `snippets`) and returns the one clicked.
+    fn show_template_files(ui: &mut egui::Ui, folder: &str) -> Option<PathBuf> {
+        let dir = ProjectDirs::from("com", "fibnas", "md-echo")
+            .map(|dirs| dirs.config_dir().join(folder))?;
+        let files = templates::list(&dir);
+        if files.is_empty() {
+            ui.label(format!("No {} yet", folder));
+        }
+        let mut selected = None;
+        for path in files {
+            let label = path
+                .file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+            if ui.button(label).clicked() {
+                selected = Some(path);
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+        if ui
+            .button("Open Folder")
+            .on_hover_text(dir.display().to_string())
+            .clicked()
+        {
+            let _ = fs::create_dir_all(&dir);
+            let _ = mdbook::open_in_browser(&dir.display().to_string());
+            ui.close_menu();
         }
+        selected
     }
 
-    fn show_file_tree(&mut self, ui: &mut egui::Ui) {
-        ui.label("Working Directory");
-        ui.monospace(self.working_dir.display().to_string());
+    fn show_recent_files_menu(&mut self, ui: &mut egui::Ui) {
+        if self.config.recent_files.is_empty() {
+            ui.label("No recent files");
+            return;
+        }
 
-        if ui.button("Change...").clicked() {
-            let mut dialog = FileDialog::new();
-            if self.working_dir.is_dir() {
-                dialog = dialog.set_directory(&self.working_dir);
-            }
-            if let Some(path) = dialog.pick_folder() {
-                self.set_working_directory(path);
+        let mut selected = None;
+        for path in &self.config.recent_files {
+            let label = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string());
+            if ui
+                .button(label)
+                .on_hover_text(path.display().to_string())
+                .clicked()
+            {
+                selected = Some(path.clone());
             }
         }
 
         ui.separator();
-
-        if !self.working_dir.is_dir() {
-            ui.label("Working directory is unavailable.");
-            return;
+        if ui.button("Clear Recent").clicked() {
+            self.config.recent_files.clear();
+            self.save_config();
+            ui.close_menu();
         }
 
-        let root_dir = self.working_dir.clone();
-        egui::ScrollArea::vertical()
-            .auto_shrink([false; 2])
-            .show(ui, |ui| {
-                self.render_directory(ui, &root_dir, true);
-            });
+        if let Some(path) = selected {
+            self.request_action(ui.ctx(), DeferredAction::OpenPath(path));
+            ui.close_menu();
+        }
     }
 
-    fn render_directory(&mut self, ui: &mut egui::Ui, path: &Path, is_root: bool) {
-        let name = if is_root {
-            path.display().to_string()
-        } else {
-            path.file_name()
-                .map(|name| name.to_string_lossy().to_string())
-                .unwrap_or_else(|| path.display().to_string())
-        };
-
-        let header = egui::CollapsingHeader::new(name)
-            .id_source(path.display().to_string())
-            .default_open(is_root);
-
-        header.show(ui, |ui| match fs::read_dir(path) {
-            Ok(entries) => {
-                let mut directories = Vec::new();
-                let mut files = Vec::new();
+    fn remember_recent_file(&mut self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.config
+            .recent_files
+            .retain(|existing| existing != &path);
+        self.config.recent_files.insert(0, path);
+        self.config
+            .recent_files
+            .truncate(self.config.recent_files_limit);
+        self.save_config();
+    }
 
-                for entry in entries.flatten() {
-                    let entry_path = entry.path();
-                    if entry_path.is_dir() {
-                        directories.push(entry_path);
-                    } else {
-                        files.push(entry_path);
-                    }
+    fn show_terminal_panel(&mut self, ui: &mut egui::Ui) {
+        if self.terminal.is_none() {
+            let shell = self.config.terminal.shell.clone();
+            match TerminalSession::spawn(shell.as_deref(), &self.working_dir, ui.ctx().clone()) {
+                Ok(session) => self.terminal = Some(session),
+                Err(err) => {
+                    self.show_tool_message(err);
+                    self.show_terminal = false;
+                    return;
                 }
+            }
+        }
 
-                directories.sort();
-                files.sort();
-
-                for dir in directories {
-                    self.render_directory(ui, &dir, false);
-                }
+        let mut restart = false;
+        let mut close = false;
+        if let Some(session) = self.terminal.as_mut() {
+            let running = session.is_running();
+            ui.horizontal(|ui| {
+                ui.label(if running {
+                    "Terminal"
+                } else {
+                    "Terminal (exited)"
+                });
+                ui.monospace(self.working_dir.display().to_string());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("Close").clicked() {
+                        close = true;
+                    }
+                    if ui.button("Restart").clicked() {
+                        restart = true;
+                    }
+                    if ui.button("Clear").clicked() {
+                        session.clear();
+                    }
+                    if ui.button("Ctrl+C").clicked() {
+                        let _ = session.send_interrupt();
+                    }
+                });
+            });
 
-                for file in files {
-                    let file_name = file
-                        .file_name()
-                        .map(|name| name.to_string_lossy().to_string())
-                        .unwrap_or_else(|| file.display().to_string());
+            let char_width = ui.fonts(|fonts| {
+                fonts.glyph_width(&egui::TextStyle::Monospace.resolve(ui.style()), 'M')
+            });
+            if char_width > 0.0 {
+                let cols = (ui.available_width() / char_width).clamp(20.0, 500.0) as u16;
+                session.resize(24, cols);
+            }
 
-                    let file_path_string = file.display().to_string();
-                    let is_selected = self
-                        .file_path
-                        .as_deref()
-                        .map(|current| current == file_path_string.as_str())
-                        .unwrap_or(false);
+            let input_height = ui.spacing().interact_size.y + ui.spacing().item_spacing.y * 2.0;
+            egui::ScrollArea::vertical()
+                .auto_shrink([false; 2])
+                .stick_to_bottom(true)
+                .max_height((ui.available_height() - input_height).max(0.0))
+                .show(ui, |ui| {
+                    ui.monospace(session.output());
+                });
 
-                    let response = ui
-                        .selectable_label(is_selected, file_name)
-                        .on_hover_text(file_path_string);
-                    if response.clicked() {
-                        if !self.modified || self.confirm_discard(ui) {
-                            self.open_file_from_path(&file);
-                        }
-                    }
+            let response = ui.add(
+                TextEdit::singleline(&mut self.terminal_input)
+                    .code_editor()
+                    .desired_width(f32::INFINITY)
+                    .hint_text("Type a command and press Enter"),
+            );
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                let line = std::mem::take(&mut self.terminal_input);
+                if let Err(err) = session.send_line(&line) {
+                    self.notifications.error("Terminal", err.to_string());
                 }
+                response.request_focus();
             }
-            Err(err) => {
-                ui.label(format!("Cannot read {}: {}", path.display(), err));
-            }
-        });
+        }
+
+        if restart {
+            self.terminal = None;
+        }
+        if close {
+            self.terminal = None;
+            self.show_terminal = false;
+        }
     }
 
     fn run_lint_tool(&mut self) {
         match self.config.tools.lint.clone() {
             Some(command) => {
-                self.run_external_tool(&command, false, self.config.tools.lint_use_open_file)
+                let use_open_file = self.config.tools.lint_use_open_file;
+                self.run_external_tool("lint", &command, false, use_open_file)
             }
             None => self.show_tool_message(
                 "No lint command configured. Add a [tools] lint entry to config.toml.",
@@ -679,7 +9444,8 @@ impl MarkdownApp {
     fn run_format_tool(&mut self) {
         match self.config.tools.format.clone() {
             Some(command) => {
-                self.run_external_tool(&command, true, self.config.tools.format_use_open_file)
+                let use_open_file = self.config.tools.format_use_open_file;
+                self.run_external_tool("Formatter", &command, true, use_open_file)
             }
             None => self.show_tool_message(
                 "No format command configured. Add a [tools] format entry to config.toml.",
@@ -687,8 +9453,27 @@ impl MarkdownApp {
         }
     }
 
+    fn run_custom_tool(&mut self, index: usize) {
+        let Some(tool) = self.config.tools.custom.get(index).cloned() else {
+            return;
+        };
+        if tool.command.trim().is_empty() {
+            self.show_tool_message(format!("Tool '{}' has no command configured.", tool.name));
+            return;
+        }
+        self.run_external_tool(
+            &tool.name,
+            &tool.command_line(),
+            tool.modifies_content,
+            tool.use_open_file,
+        );
+    }
+
+    /// Runs `command` on the document. `name` labels the diagnostics it
+    /// reports, or the review of the changes it makes.
     fn run_external_tool(
         &mut self,
+        name: &str,
         command: &[String],
         modifies_content: bool,
         use_current_file: bool,
@@ -739,8 +9524,14 @@ impl MarkdownApp {
             path
         };
 
-        let mut cmd = Command::new(&command[0]);
-        for arg in &command[1..] {
+        let document_dir = self
+            .file_path
+            .as_deref()
+            .and_then(|path| Path::new(path).parent().map(Path::to_path_buf));
+        let config = self.detect_tool_config(command, document_dir.as_deref());
+        let expanded = tool_config::expand_command(command, config.as_deref());
+        let mut cmd = Command::new(&expanded[0]);
+        for arg in &expanded[1..] {
             cmd.arg(arg);
         }
         if self.working_dir.is_dir() {
@@ -758,15 +9549,102 @@ impl MarkdownApp {
 
         if let Some(file) = temp_file.as_mut() {
             if let Err(err) = file.flush() {
-                eprintln!("Temp file flush error: {}", err);
+                self.notifications.warning(
+                    format!("Running {}", command[0]),
+                    format!(
+                        "Could not write the document to the temporary file: {}",
+                        err
+                    ),
+                );
+            }
+        }
+
+        let mut message = Self::describe_tool_output(
+            command,
+            &expanded,
+            config.as_deref(),
+            &target_path,
+            &output,
+        );
+
+        if !modifies_content {
+            let combined = format!(
+                "{}\n{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let actual = self.file_path.as_deref().map(Path::new);
+            let diagnostics = diagnostics::parse_tool_output(
+                name,
+                &combined,
+                &self.working_dir,
+                &target_path,
+                actual,
+            );
+            self.set_diagnostics(name, diagnostics);
+        }
+
+        if modifies_content && output.status.success() {
+            let fallback = text_format::encoding_for(&self.config.editor.fallback_encoding);
+            match text_format::read(&target_path, fallback) {
+                Ok((new_content, _)) => {
+                    if use_current_file && new_content != self.content {
+                        // Keep the file as it was until the changes are
+                        // accepted; accepting puts them in the buffer.
+                        let restored = text_format::encode(&self.content, &self.saved_format)
+                            .and_then(|bytes| {
+                                fs::write(&target_path, bytes).map_err(|err| err.to_string())
+                            });
+                        if let Err(err) = restored {
+                            message.push_str(&format!(
+                                "\nFormat note: failed to restore {}: {}\n",
+                                target_path.display(),
+                                err
+                            ));
+                        }
+                    }
+                    if new_content != self.content {
+                        self.format_review = Some(FormatReview {
+                            tool: name.to_string(),
+                            diff: diff_view::DiffView::new(&self.content, &new_content),
+                            original: self.content.clone(),
+                            formatted: new_content,
+                        });
+                    } else {
+                        message.push_str(&format!("\n{} made no changes.\n", name));
+                    }
+                }
+                Err(err) => {
+                    message.push_str(&format!(
+                        "\nFormat note: failed to read formatter output ({}): {}\n",
+                        target_path.display(),
+                        err
+                    ));
+                }
             }
         }
 
+        self.show_tool_message(message);
+    }
+
+    fn describe_tool_output(
+        command: &[String],
+        expanded: &[String],
+        config: Option<&Path>,
+        target_path: &Path,
+        output: &std::process::Output,
+    ) -> String {
         let mut message = String::new();
         message.push_str(&format!(
             "$ {}\n",
-            Self::format_command_for_display(command, &target_path)
+            Self::format_command_for_display(expanded, target_path)
         ));
+        if tool_config::uses_config(command) {
+            match config {
+                Some(config) => message.push_str(&format!("Config: {}\n", config.display())),
+                None => message.push_str("Config: none found (tool defaults)\n"),
+            }
+        }
         message.push_str(&format!("Status: {:?}\n", output.status));
 
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -783,25 +9661,19 @@ impl MarkdownApp {
             message.push('\n');
         }
 
-        if modifies_content && output.status.success() {
-            match fs::read_to_string(&target_path) {
-                Ok(new_content) => {
-                    if new_content != self.content {
-                        self.content = new_content;
-                        self.modified = self.content != self.original_content;
-                    }
-                }
-                Err(err) => {
-                    message.push_str(&format!(
-                        "\nFormat note: failed to read formatter output ({}): {}\n",
-                        target_path.display(),
-                        err
-                    ));
-                }
-            }
-        }
+        message
+    }
 
-        self.show_tool_message(message);
+    /// Finds the lint/format config for a document in `start_dir`, if the
+    /// command asks for one.
+    fn detect_tool_config(&self, command: &[String], start_dir: Option<&Path>) -> Option<PathBuf> {
+        if !tool_config::uses_config(command) {
+            return None;
+        }
+        let start = start_dir
+            .filter(|dir| dir.starts_with(&self.working_dir))
+            .unwrap_or(&self.working_dir);
+        tool_config::find_config(start, &self.working_dir, &self.config.tools.config_files)
     }
 
     fn format_command_for_display(command: &[String], path: &Path) -> String {
@@ -822,61 +9694,302 @@ impl MarkdownApp {
         self.show_tool_output = true;
     }
 
-    fn open_file_from_path(&mut self, path: &Path) {
-        match fs::read_to_string(path) {
-            Ok(data) => {
+    /// Opens the paths given on the command line. A folder becomes the
+    /// working directory and the first file is opened; until there are tabs
+    /// to put them in, the other files go to the top of Open Recent. A file
+    /// that does not exist yet opens blank, to be saved there.
+    fn open_command_line(&mut self, args: &[String]) {
+        let current_dir = env::current_dir().unwrap_or_default();
+        let mut files = Vec::new();
+        for arg in args {
+            let path = current_dir.join(arg);
+            if path.is_dir() {
+                self.set_working_directory(fs::canonicalize(&path).unwrap_or(path));
+            } else if path.is_file() || path.parent().is_some_and(Path::is_dir) {
+                files.push(path);
+            } else {
+                self.notifications.error(
+                    "Opening from the command line",
+                    format!("{}: no such file or folder", arg),
+                );
+            }
+        }
+        let mut files = files.into_iter();
+        let Some(first) = files.next() else {
+            return;
+        };
+        let (others, missing): (Vec<PathBuf>, Vec<PathBuf>) =
+            files.partition(|path| path.is_file());
+        for path in missing {
+            self.notifications.error(
+                "Opening from the command line",
+                format!("{}: no such file", path.display()),
+            );
+        }
+        for path in others.iter().rev() {
+            self.remember_recent_file(path);
+        }
+        if first.is_file() {
+            self.open_file_from_path(&first, None);
+        } else {
+            self.file_path = Some(first.display().to_string());
+        }
+        if !others.is_empty() {
+            self.show_tool_message(format!(
+                "{} more file(s) from the command line are in File → Open Recent.",
+                others.len()
+            ));
+        }
+    }
+
+    /// Starts reading `path` in the background. The document is replaced,
+    /// and the cursor put on `line`, once it has been read.
+    fn open_file_from_path(&mut self, path: &Path, line: Option<usize>) {
+        if let Some(job) = &self.file_job {
+            let message = format!("Still busy: {}", job.description);
+            self.show_tool_message(message);
+            return;
+        }
+        let fallback = text_format::encoding_for(&self.config.editor.fallback_encoding);
+        self.file_job = Some(file_io::FileJob::open(path, line, fallback));
+    }
+
+    /// Applies the finished file job: the opened document, or the state
+    /// after a save. Runs the action waiting for a successful save.
+    fn poll_file_job(&mut self, ctx: &Context) {
+        let Some(job) = &self.file_job else {
+            return;
+        };
+        let Some(outcome) = job.poll() else {
+            ctx.request_repaint_after(Duration::from_millis(50));
+            return;
+        };
+        self.file_job = None;
+        match outcome {
+            file_io::Outcome::Opened {
+                path,
+                line,
+                result: Ok((data, format)),
+                mtime,
+            } => {
+                let path_text = path.display().to_string();
+                if self.file_path.as_ref() != Some(&path_text) {
+                    self.history.clear();
+                }
                 self.content = data.clone();
                 self.original_content = data;
-                self.file_path = Some(path.display().to_string());
+                self.file_path = Some(path_text);
                 self.modified = false;
+                self.text_format = format;
+                self.saved_format = format;
+                self.external_change = false;
+                self.file_mtime = mtime;
+                self.collapsed_sections.clear();
+                self.media_players.clear();
+                self.remember_recent_file(&path);
+                self.pending_goto_line = line;
+                let restoring = self.restoring.as_ref().filter(|session| {
+                    session.file.as_deref() == Some(path.as_path()) && line.is_none()
+                });
+                match restoring {
+                    Some(session) => {
+                        if let Some((line, column)) = session.cursor {
+                            self.place_cursor(ctx, line, column);
+                        }
+                        self.sync_editor_to = Some(session.editor_scroll);
+                    }
+                    None => self.restoring = None,
+                }
             }
-            Err(err) => {
-                eprintln!("Error reading file '{}': {}", path.display(), err);
+            file_io::Outcome::Saved {
+                path,
+                submitted,
+                text,
+                warning,
+                format,
+                result: Ok(()),
+                mtime,
+            } => {
+                // Take the formatter's changes unless the buffer moved on.
+                if self.content == submitted {
+                    self.content = text.clone();
+                }
+                if let Some(warning) = warning {
+                    self.notifications.warning("Formatting on save", warning);
+                }
+                self.file_path = Some(path.display().to_string());
+                self.original_content = text;
+                self.saved_format = format;
+                self.update_modified();
+                self.external_change = false;
+                self.file_mtime = mtime;
+                self.remember_recent_file(&path);
+                self.refresh_after_save();
+                if let Some(action) = self.after_save.take() {
+                    if !self.modified {
+                        self.perform_action(ctx, action);
+                    }
+                }
+            }
+            file_io::Outcome::Opened {
+                result: Err(err), ..
+            } => {
+                self.restoring = None;
+                self.notifications.error("Opening", err);
+            }
+            file_io::Outcome::Saved {
+                result: Err(err), ..
+            } => {
+                self.after_save = None;
+                self.notifications.error("Saving", err);
             }
         }
     }
 
-    fn save_file(&mut self, save_as: bool) {
-        if save_as || self.file_path.is_none() {
-            if let Some(path) = FileDialog::new().save_file() {
-                if let Err(err) = fs::write(&path, &self.content) {
-                    eprintln!("Save error: {}", err);
-                } else {
-                    self.file_path = Some(path.display().to_string());
-                    self.original_content = self.content.clone();
-                    self.modified = false;
+    fn refresh_after_save(&mut self) {
+        if let (Some(index), Some(path)) = (&self.workspace_index, &self.file_path) {
+            index.update(Path::new(path), &self.content);
+        }
+        self.git_gutter.reload();
+        self.file_tree.refresh();
+        self.git_status.refresh();
+        self.blame = None;
+        if self.book.is_some() {
+            self.book = Book::detect(&self.working_dir);
+        }
+    }
+
+    fn disk_mtime(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
+    fn check_external_changes(&mut self, ctx: &Context) {
+        let Some(path) = self.file_path.clone() else {
+            return;
+        };
+        ctx.request_repaint_after(Duration::from_secs(1));
+
+        let now = ctx.input(|i| i.time);
+        // A save in progress changes the file under us.
+        if self.external_change || self.file_job.is_some() || now - self.last_disk_check < 1.0 {
+            return;
+        }
+        self.last_disk_check = now;
+
+        let mtime = Self::disk_mtime(Path::new(&path));
+        if mtime.is_some() && mtime != self.file_mtime {
+            let fallback = text_format::encoding_for(&self.config.editor.fallback_encoding);
+            match text_format::read(Path::new(&path), fallback) {
+                Ok((disk, _)) if disk == self.original_content || disk == self.content => {
+                    self.original_content = disk;
+                    self.update_modified();
+                    self.file_mtime = mtime;
                 }
-            }
-        } else if let Some(path) = &self.file_path {
-            if let Err(err) = fs::write(path, &self.content) {
-                eprintln!("Save error: {}", err);
-            } else {
-                self.original_content = self.content.clone();
-                self.modified = false;
+                Ok((disk, _)) if self.preview_only && !self.modified => {
+                    self.content = disk.clone();
+                    self.original_content = disk;
+                    self.update_modified();
+                    self.file_mtime = mtime;
+                }
+                _ => self.external_change = true,
             }
         }
     }
 
-    fn confirm_discard(&self, ui: &mut egui::Ui) -> bool {
-        if self.modified {
-            let mut confirmed = false;
-            egui::Window::new("Unsaved Changes")
-                .collapsible(false)
-                .resizable(false)
-                .show(ui.ctx(), |ui| {
-                    ui.label("Discard unsaved changes?");
-                    ui.horizontal(|ui| {
-                        if ui.button("Yes").clicked() {
-                            confirmed = true;
-                        }
-                        if ui.button("No").clicked() {
-                            confirmed = false;
+    fn show_external_change_prompt(&mut self, ctx: &Context) {
+        let name = self.file_path.clone().unwrap_or_default();
+        egui::Window::new("File Changed on Disk")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-16.0, -40.0))
+            .show(ctx, |ui| {
+                ui.label(format!("'{}' was modified by another program.", name));
+                if self.modified {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "You also have unsaved edits. Reloading discards them; keeping yours \
+                         will overwrite the external changes on the next save.",
+                    );
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Reload from Disk").clicked() {
+                        self.open_file_from_path(Path::new(&name), None);
+                    }
+                    if ui.button("Keep My Version").clicked() {
+                        let path = Path::new(&name);
+                        let fallback =
+                            text_format::encoding_for(&self.config.editor.fallback_encoding);
+                        if let Ok((disk, _)) = text_format::read(path, fallback) {
+                            self.original_content = disk;
                         }
-                    });
+                        self.update_modified();
+                        self.file_mtime = Self::disk_mtime(path);
+                        self.external_change = false;
+                    }
                 });
-            confirmed
-        } else {
-            true
+            });
+    }
+
+    /// Starts writing the document in the background, asking for a path
+    /// first if it has none or for `save_as`. Returns whether a save
+    /// started.
+    fn save_file(&mut self, save_as: bool) -> bool {
+        if let Some(job) = &self.file_job {
+            let message = format!("Still busy: {}", job.description);
+            self.show_tool_message(message);
+            return false;
+        }
+        let path = match &self.file_path {
+            Some(path) if !save_as => PathBuf::from(path),
+            _ => match FileDialog::new().save_file() {
+                Some(path) => path,
+                None => return false,
+            },
+        };
+        // A new, empty document saved into a folder with a workspace template
+        // starts from that template.
+        let template = path
+            .parent()
+            .filter(|_| self.file_path.is_none() && self.content.trim().is_empty())
+            .and_then(|dir| templates::for_folder(&self.working_dir, dir));
+        if let Some(template) = template {
+            let title = path
+                .file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+            match templates::render_for_note(&template, &path, &title) {
+                Ok(content) => self.content = content,
+                Err(err) => self.notifications.warning("Note template", err),
+            }
+        }
+        let save = &self.config.save;
+        // Joplin reads its notes back, so they are written as they are.
+        let joplin = joplin::metadata(&self.content).is_some();
+        if save.trim_trailing_whitespace && !joplin {
+            self.content = on_save::trim_trailing_whitespace(&self.content);
         }
+        if save.final_newline && !joplin {
+            self.content = on_save::ensure_final_newline(&self.content);
+        }
+        let formatter = self
+            .config
+            .tools
+            .format
+            .clone()
+            .filter(|command| save.format && !joplin && !command.is_empty())
+            .map(|command| {
+                let dir = path.parent();
+                let config = self.detect_tool_config(&command, dir);
+                file_io::Formatter {
+                    command: tool_config::expand_command(&command, config.as_deref()),
+                    dir: self.working_dir.clone(),
+                }
+            });
+        self.file_job = Some(file_io::FileJob::save(
+            &path,
+            self.content.clone(),
+            self.text_format,
+            formatter,
+        ));
+        true
     }
 }
@@ -0,0 +1,96 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A running `mdbook serve` process whose combined output is collected on
+/// background threads.
+pub struct MdBookServer {
+    child: Child,
+    log: Arc<Mutex<String>>,
+    pub port: u16,
+}
+
+impl MdBookServer {
+    pub fn start(
+        command: &str,
+        root: &Path,
+        port: u16,
+        ctx: egui::Context,
+    ) -> Result<Self, String> {
+        let mut child = Command::new(command)
+            .arg("serve")
+            .arg("--port")
+            .arg(port.to_string())
+            .current_dir(root)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Failed to run '{} serve': {}", command, err))?;
+
+        let log = Arc::new(Mutex::new(String::new()));
+        if let Some(stdout) = child.stdout.take() {
+            collect_lines(stdout, Arc::clone(&log), ctx.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            collect_lines(stderr, Arc::clone(&log), ctx);
+        }
+
+        Ok(Self { child, log, port })
+    }
+
+    pub fn log(&self) -> String {
+        self.log.lock().map(|log| log.clone()).unwrap_or_default()
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    pub fn url(&self, page: &str) -> String {
+        format!("http://localhost:{}/{}", self.port, page)
+    }
+}
+
+impl Drop for MdBookServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn collect_lines<R: Read + Send + 'static>(reader: R, log: Arc<Mutex<String>>, ctx: egui::Context) {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            if let Ok(mut log) = log.lock() {
+                log.push_str(&line);
+                log.push('\n');
+            }
+            ctx.request_repaint();
+        }
+    });
+}
+
+pub fn build(command: &str, root: &Path) -> Result<Output, String> {
+    Command::new(command)
+        .arg("build")
+        .current_dir(root)
+        .output()
+        .map_err(|err| format!("Failed to run '{} build': {}", command, err))
+}
+
+pub fn open_in_browser(target: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut cmd = Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", ""]);
+        cmd
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut cmd = Command::new("xdg-open");
+
+    cmd.arg(target).spawn().map(|_| ())
+}
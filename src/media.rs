@@ -0,0 +1,312 @@
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const AUDIO_EXTENSIONS: [&str; 7] = ["mp3", "ogg", "oga", "wav", "flac", "m4a", "opus"];
+const VIDEO_EXTENSIONS: [&str; 6] = ["mp4", "webm", "mkv", "mov", "ogv", "m4v"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Audio,
+    Video,
+}
+
+impl Kind {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Kind::Audio => "🎵",
+            Kind::Video => "🎬",
+        }
+    }
+}
+
+/// Audio or video the preview draws as a player.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Embed {
+    pub kind: Kind,
+    pub src: String,
+    pub title: String,
+}
+
+fn media_tag_regex() -> &'static Regex {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    TAG.get_or_init(|| Regex::new(r"(?is)^\s*<(audio|video)\b(.*?)>").unwrap())
+}
+
+fn src_regex() -> &'static Regex {
+    static SRC: OnceLock<Regex> = OnceLock::new();
+    SRC.get_or_init(|| Regex::new(r#"(?i)\bsrc\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap())
+}
+
+fn kind_of(src: &str) -> Option<Kind> {
+    let path = src.split(['?', '#']).next().unwrap_or(src);
+    let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+    if AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+        Some(Kind::Audio)
+    } else if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        Some(Kind::Video)
+    } else {
+        None
+    }
+}
+
+fn is_local(src: &str) -> bool {
+    !src.contains("://") && !src.starts_with("data:")
+}
+
+/// The media a block consists of: an `<audio>` or `<video>` tag (with
+/// `src` on the tag or on its first `<source>`), or a link or image that
+/// stands alone and points at a local audio or video file.
+pub fn embed(markdown: &str) -> Option<Embed> {
+    if let Some(caps) = media_tag_regex().captures(markdown) {
+        let kind = if caps[1].eq_ignore_ascii_case("audio") {
+            Kind::Audio
+        } else {
+            Kind::Video
+        };
+        let src = src_regex()
+            .captures(&caps[2])
+            .or_else(|| {
+                let rest = &markdown[caps.get(0)?.end()..];
+                let source = rest.find("<source").map(|at| &rest[at..])?;
+                src_regex().captures(source)
+            })
+            .and_then(|src| src.iter().skip(1).flatten().next())?
+            .as_str()
+            .to_string();
+        let title = src.rsplit('/').next().unwrap_or(&src).to_string();
+        return Some(Embed { kind, src, title });
+    }
+
+    // A paragraph holding one link or image and nothing else.
+    let mut found: Option<(String, String)> = None;
+    let mut depth = 0;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Paragraph) | Event::End(TagEnd::Paragraph) => {}
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. })
+                if depth == 0 && found.is_none() =>
+            {
+                found = Some((dest_url.to_string(), String::new()));
+                depth += 1;
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) if depth == 1 => depth = 0,
+            Event::Text(text) | Event::Code(text) if depth == 1 => {
+                if let Some((_, title)) = &mut found {
+                    title.push_str(&text);
+                }
+            }
+            _ => return None,
+        }
+    }
+    let (src, title) = found?;
+    let kind = kind_of(&src).filter(|_| is_local(&src))?;
+    let title = if title.trim().is_empty() {
+        src.rsplit('/').next().unwrap_or(&src).to_string()
+    } else {
+        title
+    };
+    Some(Embed { kind, src, title })
+}
+
+/// `seconds` as `m:ss`, or `h:mm:ss` from an hour on.
+pub fn format_time(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Playback state as last reported by the player.
+#[derive(Clone, Copy, Default)]
+pub struct Status {
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub paused: bool,
+    /// The player has exited.
+    pub finished: bool,
+}
+
+#[cfg(unix)]
+pub use self::mpv::Player;
+
+/// Playback through mpv, which plays audio itself and opens its own window
+/// for video, controlled over its JSON IPC socket.
+#[cfg(unix)]
+mod mpv {
+    use super::{Kind, Status};
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+    use std::process::{Child, Command, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// How often the position is asked for while the preview is drawn.
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    pub struct Player {
+        child: Child,
+        socket: PathBuf,
+        stream: Option<BufReader<UnixStream>>,
+        next_request: u64,
+        status: Status,
+        polled: Option<Instant>,
+    }
+
+    impl Player {
+        /// Starts `program` (mpv or a compatible player) playing `location`.
+        pub fn start(program: &str, location: &str, kind: Kind) -> Result<Self, String> {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let socket = std::env::temp_dir().join(format!(
+                "md-echo-mpv-{}-{}.sock",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let mut command = Command::new(program);
+            command
+                .arg("--no-terminal")
+                .arg("--keep-open=yes")
+                .arg(format!("--input-ipc-server={}", socket.display()));
+            if kind == Kind::Video {
+                command.arg("--force-window=yes");
+            } else {
+                command.arg("--no-video");
+            }
+            let child = command
+                .arg("--")
+                .arg(location)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|err| format!("Failed to run '{}': {}", program, err))?;
+            Ok(Self {
+                child,
+                socket,
+                stream: None,
+                next_request: 0,
+                status: Status::default(),
+                polled: None,
+            })
+        }
+
+        /// The playback state, refreshed from the player every so often.
+        pub fn status(&mut self) -> Status {
+            if self.status.finished {
+                return self.status;
+            }
+            if let Ok(Some(_)) = self.child.try_wait() {
+                self.status.finished = true;
+                return self.status;
+            }
+            if self.polled.is_some_and(|at| at.elapsed() < POLL_INTERVAL) {
+                return self.status;
+            }
+            self.polled = Some(Instant::now());
+            if let Some(position) = self.property("time-pos").and_then(|v| v.as_f64()) {
+                self.status.position = position;
+            }
+            if let Some(duration) = self.property("duration").and_then(|v| v.as_f64()) {
+                self.status.duration = Some(duration);
+            }
+            if let Some(paused) = self.property("pause").and_then(|v| v.as_bool()) {
+                self.status.paused = paused;
+            }
+            self.status
+        }
+
+        pub fn set_paused(&mut self, paused: bool) {
+            if self
+                .request(json!(["set_property", "pause", paused]))
+                .is_some()
+            {
+                self.status.paused = paused;
+            }
+        }
+
+        /// Jumps to `seconds` from the start.
+        pub fn seek(&mut self, seconds: f64) {
+            if self.request(json!(["seek", seconds, "absolute"])).is_some() {
+                self.status.position = seconds;
+            }
+        }
+
+        fn property(&mut self, name: &str) -> Option<Value> {
+            self.request(json!(["get_property", name]))
+        }
+
+        /// Sends `command` and waits briefly for its answer, skipping the
+        /// events mpv sends in between.
+        fn request(&mut self, command: Value) -> Option<Value> {
+            if self.stream.is_none() {
+                let stream = UnixStream::connect(&self.socket).ok()?;
+                stream
+                    .set_read_timeout(Some(Duration::from_millis(200)))
+                    .ok()?;
+                self.stream = Some(BufReader::new(stream));
+            }
+            let stream = self.stream.as_mut()?;
+            self.next_request += 1;
+            let id = self.next_request;
+            let message = json!({ "command": command, "request_id": id });
+            if writeln!(stream.get_mut(), "{}", message).is_err() {
+                self.stream = None;
+                return None;
+            }
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match stream.read_line(&mut line) {
+                    Ok(0) | Err(_) => return None,
+                    Ok(_) => {}
+                }
+                let Ok(reply) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                if reply["request_id"].as_u64() == Some(id) {
+                    return (reply["error"] == "success").then(|| reply["data"].clone());
+                }
+            }
+        }
+    }
+
+    impl Drop for Player {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            let _ = std::fs::remove_file(&self.socket);
+        }
+    }
+}
+
+/// Stands in for the mpv player where there are no Unix sockets.
+#[cfg(not(unix))]
+pub struct Player;
+
+#[cfg(not(unix))]
+impl Player {
+    pub fn start(_program: &str, _location: &str, _kind: Kind) -> Result<Self, String> {
+        Err(
+            "Playing media in the preview needs mpv's IPC, which is only available on Unix. \
+             Open the file in your media player instead."
+                .to_string(),
+        )
+    }
+
+    pub fn status(&mut self) -> Status {
+        Status {
+            finished: true,
+            ..Status::default()
+        }
+    }
+
+    pub fn set_paused(&mut self, _paused: bool) {}
+
+    pub fn seek(&mut self, _seconds: f64) {}
+}
@@ -0,0 +1,218 @@
+use crate::dates;
+use eframe::egui;
+use egui::{Color32, Context, RichText};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a toast stays up, the last part of it fading out.
+const TOAST_DURATION: Duration = Duration::from_secs(6);
+const FADE: Duration = Duration::from_millis(800);
+/// Toasts drawn at once; older ones stay in the log only.
+const MAX_TOASTS: usize = 4;
+/// Entries kept in the log.
+const LOG_LIMIT: usize = 200;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Level::Warning => "⚠",
+            Level::Error => "⛔",
+        }
+    }
+
+    pub fn color(self) -> Color32 {
+        match self {
+            Level::Warning => Color32::from_rgb(230, 170, 40),
+            Level::Error => Color32::from_rgb(220, 70, 60),
+        }
+    }
+}
+
+/// A failure, with what was being done when it happened.
+pub struct Entry {
+    pub level: Level,
+    /// What was being done, e.g. `Saving notes.md`.
+    pub context: String,
+    pub message: String,
+    pub time: SystemTime,
+    shown: Instant,
+    dismissed: bool,
+}
+
+/// Failures shown as toasts in the corner and kept in the error log.
+#[derive(Default)]
+pub struct Notifications {
+    entries: VecDeque<Entry>,
+    /// Entries added since the log was last opened.
+    pub unread: usize,
+}
+
+impl Notifications {
+    pub fn push(&mut self, level: Level, context: impl Into<String>, message: impl Into<String>) {
+        if self.entries.len() == LOG_LIMIT {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            level,
+            context: context.into(),
+            message: message.into(),
+            time: SystemTime::now(),
+            shown: Instant::now(),
+            dismissed: false,
+        });
+        self.unread += 1;
+    }
+
+    pub fn error(&mut self, context: impl Into<String>, message: impl Into<String>) {
+        self.push(Level::Error, context, message);
+    }
+
+    pub fn warning(&mut self, context: impl Into<String>, message: impl Into<String>) {
+        self.push(Level::Warning, context, message);
+    }
+
+    /// The log, oldest first.
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.unread = 0;
+    }
+
+    /// Draws the recent entries as toasts above the status bar. Returns
+    /// true when one was clicked, to open the log.
+    pub fn show_toasts(&mut self, ctx: &Context) -> bool {
+        let mut open_log = false;
+        let live: Vec<usize> = self
+            .entries
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, entry)| !entry.dismissed && entry.shown.elapsed() < TOAST_DURATION)
+            .map(|(index, _)| index)
+            .take(MAX_TOASTS)
+            .collect();
+        if live.is_empty() {
+            return false;
+        }
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -36.0))
+            .order(egui::Order::Foreground)
+            .interactable(true)
+            .show(ctx, |ui| {
+                ui.set_max_width(360.0);
+                for &index in &live {
+                    let entry = &mut self.entries[index];
+                    let left = TOAST_DURATION.saturating_sub(entry.shown.elapsed());
+                    ui.set_opacity((left.as_secs_f32() / FADE.as_secs_f32()).min(1.0));
+                    let response = egui::Frame::popup(ui.style())
+                        .stroke(egui::Stroke::new(1.0, entry.level.color()))
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.colored_label(entry.level.color(), entry.level.icon());
+                                ui.vertical(|ui| {
+                                    ui.label(RichText::new(&entry.context).strong());
+                                    ui.label(&entry.message);
+                                });
+                                if ui.small_button("✖").on_hover_text("Dismiss").clicked() {
+                                    entry.dismissed = true;
+                                }
+                            });
+                        })
+                        .response
+                        .interact(egui::Sense::click())
+                        .on_hover_text("Click to open the error log");
+                    if response.clicked() {
+                        entry.dismissed = true;
+                        open_log = true;
+                    }
+                    if response.hovered() {
+                        // Keep it up while the pointer is on it.
+                        entry.shown = Instant::now();
+                    }
+                    ui.add_space(4.0);
+                }
+            });
+        ctx.request_repaint_after(Duration::from_millis(50));
+        open_log
+    }
+
+    /// The log as a window, newest first.
+    pub fn show_log(&mut self, ctx: &Context, open: &mut bool) {
+        self.unread = 0;
+        let mut clear = false;
+        let mut copy = None;
+        egui::Window::new("Error Log")
+            .open(open)
+            .resizable(true)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} entries", self.entries.len()));
+                    if ui
+                        .add_enabled(!self.is_empty(), egui::Button::new("Copy All"))
+                        .clicked()
+                    {
+                        copy = Some(self.to_text());
+                    }
+                    if ui
+                        .add_enabled(!self.is_empty(), egui::Button::new("Clear"))
+                        .clicked()
+                    {
+                        clear = true;
+                    }
+                });
+                ui.separator();
+                if self.is_empty() {
+                    ui.weak("Nothing has gone wrong.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for entry in self.entries.iter().rev() {
+                            ui.horizontal_wrapped(|ui| {
+                                ui.colored_label(entry.level.color(), entry.level.icon());
+                                ui.monospace(dates::clock_time(entry.time));
+                                ui.label(RichText::new(&entry.context).strong());
+                            });
+                            ui.label(&entry.message);
+                            ui.separator();
+                        }
+                    });
+            });
+        if let Some(text) = copy {
+            ctx.output_mut(|output| output.copied_text = text);
+        }
+        if clear {
+            self.clear();
+        }
+    }
+
+    fn to_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{} UTC  {}: {}",
+                    dates::clock_time(entry.time),
+                    entry.context,
+                    entry.message
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
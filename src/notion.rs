@@ -0,0 +1,34 @@
+use crate::{bundle, zip};
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// The ID Notion puts after the title in the name of every exported page
+/// and folder.
+fn id_regex() -> &'static Regex {
+    static ID: OnceLock<Regex> = OnceLock::new();
+    ID.get_or_init(|| Regex::new(r" [0-9a-f]{32}(\.[^./]+)?$").unwrap())
+}
+
+/// The files of a Notion "Markdown & CSV" export zip. Large exports come
+/// as a zip of zips, which are unpacked as well.
+pub fn read_export(source: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files = Vec::new();
+    for (name, data) in bundle::read_source(source)? {
+        if name.to_ascii_lowercase().ends_with(".zip") {
+            files.extend(zip::read_archive(&data).map_err(|err| format!("{}: {}", name, err))?);
+        } else {
+            files.push((name, data));
+        }
+    }
+    Ok(files)
+}
+
+/// `path` with the page IDs taken out of its file and folder names:
+/// `Notes 0123…cdef/Idea 4567…89ab.md` becomes `Notes/Idea.md`.
+pub fn clean_path(path: &str) -> String {
+    path.split('/')
+        .map(|part| id_regex().replace(part, "$1"))
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,39 @@
+use crate::export;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Digits of the first number in a folder without numbered files: `0001-`.
+const DEFAULT_WIDTH: usize = 4;
+
+fn prefix_regex() -> &'static Regex {
+    static PREFIX: OnceLock<Regex> = OnceLock::new();
+    PREFIX.get_or_init(|| Regex::new(r"^(\d+)-").unwrap())
+}
+
+/// The next number in `dir`, one past the highest `NNNN-` prefix of the
+/// names in it, formatted as wide as that prefix.
+pub fn next_number(dir: &Path) -> String {
+    let (highest, width) = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let digits = prefix_regex().captures(&name)?.get(1)?.as_str().len();
+            let number: u64 = name[..digits].parse().ok()?;
+            Some((number, digits))
+        })
+        .max()
+        .unwrap_or((0, DEFAULT_WIDTH));
+    format!("{:0width$}", highest.saturating_add(1), width = width)
+}
+
+/// Where the next numbered file in `dir` titled `title` goes:
+/// `0007-use-postgres.md` after `0006-…`.
+pub fn next_path(dir: &Path, title: &str) -> PathBuf {
+    let slug = export::slugify(title);
+    let slug = if slug.is_empty() { "untitled" } else { &slug };
+    dir.join(format!("{}-{}.md", next_number(dir), slug))
+}
@@ -0,0 +1,99 @@
+use crate::{assets, export, wikilinks};
+use std::path::Path;
+
+/// Folders a vault keeps Obsidian's settings and deleted notes in, as file
+/// tree ignore patterns.
+pub const HIDDEN_FOLDERS: [&str; 2] = [".obsidian/", ".trash/"];
+
+/// Whether Obsidian's conventions apply in `root` under the `flavor`
+/// setting: always for `obsidian`, and for `auto` when `root` is a vault.
+pub fn applies(flavor: &str, root: &Path) -> bool {
+    match flavor.to_ascii_lowercase().as_str() {
+        "obsidian" => true,
+        "auto" => root.join(".obsidian").is_dir(),
+        _ => false,
+    }
+}
+
+/// `markdown` with the text of `%%comments%%` outside code taken out. The
+/// line breaks inside a comment stay, so every line keeps its number; a
+/// comment that is never closed runs to the end, as in Obsidian.
+pub fn hide_comments(markdown: &str) -> String {
+    if !markdown.contains("%%") {
+        return markdown.to_string();
+    }
+    let mut shown = String::with_capacity(markdown.len());
+    let mut in_fence = false;
+    let mut in_comment = false;
+    for line in markdown.split_inclusive('\n') {
+        if !in_comment && export::is_fence(line) {
+            in_fence = !in_fence;
+        }
+        if in_fence {
+            shown.push_str(line);
+            continue;
+        }
+        let mut in_code = false;
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            if !in_code && rest.starts_with("%%") {
+                in_comment = !in_comment;
+                rest = &rest[2..];
+                continue;
+            }
+            if in_comment {
+                if c == '\n' {
+                    shown.push(c);
+                }
+            } else {
+                in_code ^= c == '`';
+                shown.push(c);
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    shown
+}
+
+/// Rewrites the `![[embeds]]` of `markdown` outside code: images become
+/// markdown images, and anything else a link to what it embeds.
+pub fn render_embeds(markdown: &str) -> String {
+    if !markdown.contains("![[") {
+        return markdown.to_string();
+    }
+    export::map_outside_code(markdown, render_part)
+}
+
+fn render_part(text: &str, rendered: &mut String) {
+    let mut rest = text;
+    while let Some(start) = rest.find("![[") {
+        let Some(end) = rest[start + 3..].find("]]") else {
+            break;
+        };
+        let inner = &rest[start + 3..start + 3 + end];
+        rendered.push_str(&rest[..start]);
+        match wikilinks::WikiLink::parse(inner) {
+            Some(link) if assets::is_image(Path::new(link.target)) => {
+                // `![[photo.png|300]]` gives a width, not a caption.
+                let sized = link
+                    .label
+                    .split('x')
+                    .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()));
+                let alt = if sized { link.target } else { link.label };
+                rendered.push_str(&format!(
+                    "![{}]({})",
+                    alt.replace(['[', ']'], ""),
+                    wikilinks::encode(link.target)
+                ));
+            }
+            Some(link) => rendered.push_str(&format!(
+                "↪ [{}]({})",
+                link.label.replace(['[', ']'], ""),
+                wikilinks::to_url(inner)
+            )),
+            None => rendered.push_str(&rest[start..start + end + 5]),
+        }
+        rest = &rest[start + end + 5..];
+    }
+    rendered.push_str(rest);
+}
@@ -0,0 +1,40 @@
+use crate::export;
+
+/// `markdown` without spaces and tabs at the ends of lines. Two or more
+/// trailing spaces before another line of text are a hard line break and
+/// are kept as two, except in headings and fenced code.
+pub fn trim_trailing_whitespace(markdown: &str) -> String {
+    let lines: Vec<&str> = markdown.split('\n').collect();
+    let mut in_code = false;
+    let mut trimmed = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        if export::is_fence(line) {
+            in_code = !in_code;
+        }
+        let text = line.trim_end_matches([' ', '\t']);
+        let continues = lines
+            .get(index + 1)
+            .is_some_and(|next| !next.trim().is_empty());
+        let hard_break = !in_code
+            && !text.trim().is_empty()
+            && export::heading_level(text).is_none()
+            && line[text.len()..].starts_with("  ")
+            && continues;
+        if hard_break {
+            trimmed.push(format!("{}  ", text));
+        } else {
+            trimmed.push(text.to_string());
+        }
+    }
+    trimmed.join("\n")
+}
+
+/// `markdown` ending in exactly one newline; an empty document stays empty.
+pub fn ensure_final_newline(markdown: &str) -> String {
+    let text = markdown.trim_end_matches('\n');
+    if text.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", text)
+    }
+}
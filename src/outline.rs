@@ -0,0 +1,77 @@
+use crate::export;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// A heading in the current document.
+pub struct Heading {
+    pub level: usize,
+    pub title: String,
+    /// Zero-based source line the heading starts on.
+    pub line: usize,
+}
+
+/// Headings of the open document, re-parsed only when the text changes.
+#[derive(Default)]
+pub struct Outline {
+    content_hash: u64,
+    pub headings: Vec<Heading>,
+}
+
+impl Outline {
+    pub fn update(&mut self, text: &str) {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+        if hash != self.content_hash {
+            self.content_hash = hash;
+            self.headings = headings(text);
+        }
+    }
+
+    /// Index of the heading whose section contains `line`.
+    pub fn section_at(&self, line: f32) -> Option<usize> {
+        self.headings
+            .iter()
+            .rposition(|heading| heading.line as f32 <= line + 0.5)
+    }
+}
+
+/// Collects ATX and setext headings with their source lines.
+pub fn headings(text: &str) -> Vec<Heading> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+
+    let mut headings = Vec::new();
+    let mut current: Option<Heading> = None;
+    for (event, range) in Parser::new_ext(text, export::markdown_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current = Some(Heading {
+                    level: level as usize,
+                    title: String::new(),
+                    line: line_starts.partition_point(|start| *start <= range.start) - 1,
+                });
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(mut heading) = current.take() {
+                    heading.title = heading.title.trim().to_string();
+                    headings.push(heading);
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = &mut current {
+                    heading.title.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(heading) = &mut current {
+                    heading.title.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
@@ -0,0 +1,167 @@
+use crate::front_matter;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+/// Formats exported through pandoc, beyond the built-in HTML and PDF.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Docx,
+    Odt,
+    Epub,
+    Latex,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Docx, Format::Odt, Format::Epub, Format::Latex];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Format::Docx => "Word (DOCX)",
+            Format::Odt => "OpenDocument (ODT)",
+            Format::Epub => "EPUB",
+            Format::Latex => "LaTeX",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Docx => "docx",
+            Format::Odt => "odt",
+            Format::Epub => "epub",
+            Format::Latex => "tex",
+        }
+    }
+
+    /// Pandoc's name for the format.
+    fn writer(self) -> &'static str {
+        match self {
+            Format::Docx => "docx",
+            Format::Odt => "odt",
+            Format::Epub => "epub3",
+            Format::Latex => "latex",
+        }
+    }
+}
+
+/// Extensions of the files Import → With Pandoc offers; pandoc picks the
+/// reader by extension.
+pub const IMPORT_EXTENSIONS: [&str; 7] = ["docx", "odt", "html", "htm", "epub", "rtf", "tex"];
+
+/// Writes `markdown`, a document in `dir` titled `title`, to `output` as
+/// `format`. Relative images are looked up in `dir`. Returns pandoc's
+/// report either way, for the tool output window.
+pub fn export(
+    command: &str,
+    extra_args: &[String],
+    markdown: &str,
+    title: &str,
+    dir: &Path,
+    format: Format,
+    output: &Path,
+) -> Result<String, String> {
+    let mut args = vec![
+        "--from".to_string(),
+        "markdown".to_string(),
+        "--to".to_string(),
+        format.writer().to_string(),
+        "--standalone".to_string(),
+        "--output".to_string(),
+        output.display().to_string(),
+    ];
+    // An EPUB needs a title, which the front matter may not give.
+    if format == Format::Epub && front_matter::get(markdown, "title").is_none() {
+        args.push("--metadata".to_string());
+        args.push(format!("title={}", title));
+    }
+    args.extend(extra_args.iter().cloned());
+    let (result, report) = run(command, &args, dir, Some(markdown))?;
+    if result.status.success() {
+        Ok(format!("{}\nWrote {}", report, output.display()))
+    } else {
+        Err(report)
+    }
+}
+
+/// Converts `source`, a file in any format pandoc reads, to markdown in
+/// pandoc's `flavor`. Images inside the file are written to a folder named
+/// after it in `media_dir`, and linked relative to `media_dir`. Pandoc's
+/// report comes along when it warned about something.
+pub fn import(
+    command: &str,
+    flavor: &str,
+    source: &Path,
+    media_dir: &Path,
+) -> Result<(String, Option<String>), String> {
+    let stem = source.file_stem().map_or("imported".to_string(), |stem| {
+        stem.to_string_lossy().into_owned()
+    });
+    let args = vec![
+        "--to".to_string(),
+        flavor.to_string(),
+        "--wrap=none".to_string(),
+        format!("--extract-media={}-media", stem),
+        source.display().to_string(),
+    ];
+    let (result, report) = run(command, &args, media_dir, None)?;
+    if !result.status.success() {
+        return Err(report);
+    }
+    let warnings = (!result.stderr.is_empty()).then_some(report);
+    Ok((
+        String::from_utf8_lossy(&result.stdout).into_owned(),
+        warnings,
+    ))
+}
+
+/// Runs pandoc in `dir`, feeding it `input`, and describes the run the way
+/// [`crate::export::run_converter`] does.
+fn run(
+    command: &str,
+    args: &[String],
+    dir: &Path,
+    input: Option<&str>,
+) -> Result<(Output, String), String> {
+    let mut child = Command::new(command)
+        .args(args)
+        .current_dir(dir)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            format!(
+                "Failed to run '{}': {}. Install pandoc or set [pandoc] command.",
+                command, err
+            )
+        })?;
+    // Written from another thread so a full stdout pipe cannot stall both.
+    let writer = child.stdin.take().zip(input).map(|(mut stdin, input)| {
+        let input = input.to_string();
+        thread::spawn(move || stdin.write_all(input.as_bytes()))
+    });
+    let result = child
+        .wait_with_output()
+        .map_err(|err| format!("Failed to run '{}': {}", command, err))?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    let mut report = format!(
+        "$ {} {}\nStatus: {:?}\n",
+        command,
+        args.join(" "),
+        result.status
+    );
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if !stderr.trim().is_empty() {
+        report.push_str("\nstderr:\n");
+        report.push_str(stderr.trim_end());
+        report.push('\n');
+    }
+    Ok((result, report))
+}
@@ -0,0 +1,100 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::Path;
+
+/// `.gitignore`-style patterns: `*.html` matches names anywhere, `build/`
+/// matches folders only, and patterns containing `/` match paths relative
+/// to the root they are checked against.
+#[derive(Clone, Default)]
+pub struct IgnorePatterns {
+    patterns: Vec<String>,
+    /// Patterns without a `/`, matched against entry names.
+    names: GlobSet,
+    /// Patterns with a `/`, matched against paths relative to the root.
+    paths: GlobSet,
+    /// Patterns ending in `/` only apply to directories.
+    dir_names: GlobSet,
+}
+
+impl IgnorePatterns {
+    /// The patterns, and a message for each one that is not a valid glob
+    /// and was left out. Blank lines and `#` comments are skipped.
+    pub fn new(patterns: &[String]) -> (Self, Vec<String>) {
+        let mut invalid = Vec::new();
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        let mut dir_names = GlobSetBuilder::new();
+        for pattern in patterns {
+            let pattern = pattern.trim();
+            if pattern.is_empty() || pattern.starts_with('#') {
+                continue;
+            }
+            let (pattern, dir_only) = match pattern.strip_suffix('/') {
+                Some(dir) => (dir, true),
+                None => (pattern, false),
+            };
+            let glob = match Glob::new(pattern.trim_start_matches('/')) {
+                Ok(glob) => glob,
+                Err(err) => {
+                    invalid.push(format!("Ignoring invalid pattern '{}': {}", pattern, err));
+                    continue;
+                }
+            };
+            if pattern.contains('/') {
+                paths.add(glob);
+            } else if dir_only {
+                dir_names.add(glob);
+            } else {
+                names.add(glob);
+            }
+        }
+        let build = |builder: GlobSetBuilder| builder.build().unwrap_or_else(|_| GlobSet::empty());
+        let ignore = Self {
+            patterns: patterns.to_vec(),
+            names: build(names),
+            paths: build(paths),
+            dir_names: build(dir_names),
+        };
+        (ignore, invalid)
+    }
+
+    /// The patterns of the `.gitignore` at `root`, followed by `configured`.
+    /// Negated (`!`) `.gitignore` lines are not supported and left out.
+    pub fn for_workspace(root: &Path, configured: &[String]) -> (Self, Vec<String>) {
+        let gitignore = fs::read_to_string(root.join(".gitignore")).unwrap_or_default();
+        let mut patterns: Vec<String> = gitignore
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('!'))
+            .map(str::to_string)
+            .collect();
+        patterns.extend(configured.iter().cloned());
+        Self::new(&patterns)
+    }
+
+    /// The patterns as they were given.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// Whether `path`, below `root`, is ignored.
+    pub fn matches(&self, path: &Path, root: &Path, is_dir: bool) -> bool {
+        let name = path.file_name().unwrap_or_default();
+        if self.names.is_match(name) || (is_dir && self.dir_names.is_match(name)) {
+            return true;
+        }
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        self.paths.is_match(relative)
+    }
+
+    /// Whether the file at `path`, or a folder it is in below `root`, is
+    /// ignored.
+    pub fn covers(&self, path: &Path, root: &Path) -> bool {
+        self.matches(path, root, false)
+            || path
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(root) && *dir != root)
+                .any(|dir| self.matches(dir, root, true))
+    }
+}
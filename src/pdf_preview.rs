@@ -0,0 +1,91 @@
+use crate::{diagrams, export};
+use egui::{ColorImage, TextureHandle, TextureOptions};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"))
+}
+
+/// `bytes` as e.g. `340 KB` or `2.4 MB`.
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} bytes", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{} KB", bytes / 1024)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// A PDF from the file tree, with its first page rendered to PNG by an
+/// external command on a background thread.
+pub struct PdfPreview {
+    pub path: PathBuf,
+    pub size: Option<u64>,
+    pending: Arc<Mutex<Option<Result<ColorImage, String>>>>,
+    pub page: Option<Result<TextureHandle, String>>,
+}
+
+impl PdfPreview {
+    /// Renders the first page of `path` with `command` (`{input}` is the
+    /// PDF and `{output}` the PNG to write), if there is one.
+    pub fn start(path: &Path, command: Option<&[String]>, ctx: &egui::Context) -> Self {
+        let size = fs::metadata(path).ok().map(|meta| meta.len());
+        let pending = Arc::new(Mutex::new(None));
+        let page = match command {
+            Some(command) if !command.is_empty() => {
+                let pending = Arc::clone(&pending);
+                let (path, command, ctx) = (path.to_path_buf(), command.to_vec(), ctx.clone());
+                thread::spawn(move || {
+                    let result = render(&path, &command);
+                    if let Ok(mut slot) = pending.lock() {
+                        *slot = Some(result);
+                    }
+                    ctx.request_repaint();
+                });
+                None
+            }
+            _ => Some(Err(
+                "Set [preview] pdf_command to see the first page here.".to_string()
+            )),
+        };
+        Self {
+            path: path.to_path_buf(),
+            size,
+            pending,
+            page,
+        }
+    }
+
+    /// Picks up the rendered page once the command has finished.
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        if self.page.is_some() {
+            return;
+        }
+        let result = self.pending.lock().ok().and_then(|mut slot| slot.take());
+        self.page = result.map(|result| {
+            result.map(|image| {
+                let name = format!("pdf-{}", self.path.display());
+                ctx.load_texture(name, image, TextureOptions::LINEAR)
+            })
+        });
+    }
+}
+
+fn render(path: &Path, command: &[String]) -> Result<ColorImage, String> {
+    let dir = tempfile::tempdir().map_err(|err| format!("Failed to prepare temp dir: {}", err))?;
+    // pdftoppm and pdftocairo add `.png` to the name they are given.
+    let output = dir.path().join("page");
+    export::run_converter(command, path, &output)?;
+    let written = [output.with_extension("png"), output]
+        .into_iter()
+        .find(|path| path.is_file())
+        .ok_or("The command wrote no image.")?;
+    let data = fs::read(&written).map_err(|err| format!("No image written: {}", err))?;
+    diagrams::decode_png(data.as_slice())
+}
@@ -0,0 +1,199 @@
+use crate::{export, front_matter, preview, wikilinks};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Excerpts stop after this many characters.
+const MAX_EXCERPT: usize = 800;
+
+/// Something in the preview that shows a popup when hovered.
+#[derive(Clone, PartialEq)]
+pub enum Target {
+    /// A footnote reference, by label.
+    Footnote(String),
+    /// A link to a markdown file, a heading, or a wikilink, by destination.
+    Link(String),
+    /// A glossary term.
+    Term(String),
+}
+
+/// The text of `markdown` roughly as the viewer lays it out, and the char
+/// ranges in it of footnote references and links to local documents.
+pub fn targets(markdown: &str) -> (String, Vec<(Range<usize>, Target)>) {
+    let mut text = String::new();
+    let mut chars = 0;
+    let mut targets = Vec::new();
+    let mut link: Option<(usize, String)> = None;
+    let push = |text: &mut String, chars: &mut usize, piece: &str| {
+        text.push_str(piece);
+        *chars += piece.chars().count();
+    };
+    for event in Parser::new_ext(markdown, export::markdown_options()) {
+        match event {
+            Event::Text(piece) | Event::Code(piece) => push(&mut text, &mut chars, &piece),
+            Event::SoftBreak => push(&mut text, &mut chars, " "),
+            Event::HardBreak => push(&mut text, &mut chars, "\n"),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item) => {
+                push(&mut text, &mut chars, "\n")
+            }
+            Event::FootnoteReference(label) => {
+                let start = chars;
+                push(&mut text, &mut chars, &label);
+                targets.push((start..chars, Target::Footnote(label.to_string())));
+            }
+            Event::Start(Tag::Link { dest_url, .. }) if is_local(&dest_url) => {
+                link = Some((chars, dest_url.to_string()));
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some((start, destination)) = link.take() {
+                    targets.push((start..chars, Target::Link(destination)));
+                }
+            }
+            _ => {}
+        }
+    }
+    (text, targets)
+}
+
+/// The destination of the local link in source `line` that spans byte
+/// `column`, if any: an inline link or a wikilink, as its URL.
+pub fn link_at(line: &str, column: usize) -> Option<String> {
+    let inline = export::link_regex()
+        .captures_iter(line)
+        .find(|captures| {
+            let whole = captures.get(0).unwrap();
+            !captures[1].starts_with('!') && whole.start() <= column && column < whole.end()
+        })
+        .map(|captures| captures[2].to_string());
+    let destination = inline.or_else(|| {
+        let mut searched = 0;
+        while let Some(start) = line[searched..].find("[[").map(|start| searched + start) {
+            let end = start + 2 + line[start + 2..].find("]]")?;
+            if start <= column && column < end + 2 {
+                let inner = &line[start + 2..end];
+                return wikilinks::WikiLink::parse(inner).map(|_| wikilinks::to_url(inner));
+            }
+            searched = end + 2;
+        }
+        None
+    })?;
+    is_local(&destination).then_some(destination)
+}
+
+/// Whether a link destination leads to a markdown document or a heading.
+pub fn is_local(destination: &str) -> bool {
+    if destination.starts_with(wikilinks::SCHEME) || destination.starts_with('#') {
+        return true;
+    }
+    // The preview makes relative links absolute.
+    if let Some(path) = preview::file_url_path(destination) {
+        return export::is_markdown(&path);
+    }
+    let path = destination.split('#').next().unwrap_or_default();
+    !export::is_external(destination) && export::is_markdown(Path::new(path))
+}
+
+/// The document and heading a local link leads to. `None` as the path
+/// means the document the link is in.
+pub fn resolve(
+    destination: &str,
+    document: Option<&Path>,
+    root: &Path,
+    files: &[PathBuf],
+) -> Option<(Option<PathBuf>, Option<String>)> {
+    if let Some(link) = wikilinks::from_url(destination) {
+        let link = wikilinks::WikiLink::parse(&link)?;
+        let heading = link.heading.map(str::to_string);
+        if link.target.is_empty() {
+            return Some((None, heading));
+        }
+        return Some((Some(wikilinks::resolve(root, files, link.target)?), heading));
+    }
+    let (path, heading) = match destination.split_once('#') {
+        Some((path, heading)) => (path, Some(heading.to_string())),
+        None => (destination, None),
+    };
+    if path.is_empty() {
+        return Some((None, heading));
+    }
+    if let Some(path) = preview::file_url_path(path) {
+        return Some((Some(path), heading));
+    }
+    let dir = document.and_then(Path::parent).unwrap_or(root);
+    let path = export::normalize(&dir.join(path.replace("%20", " ")));
+    Some((Some(path), heading))
+}
+
+/// The text of footnote `label` in `document`, with its continuation
+/// lines.
+pub fn footnote(document: &str, label: &str) -> Option<String> {
+    let marker = format!("[^{}]:", label);
+    let mut lines = document.lines();
+    let first = lines
+        .by_ref()
+        .find_map(|line| line.trim_start().strip_prefix(&marker))?;
+    let mut text = first.trim().to_string();
+    let mut blank = false;
+    for line in lines {
+        if line.trim().is_empty() {
+            blank = true;
+            continue;
+        }
+        let indented = line.starts_with("    ") || line.starts_with('\t');
+        if (blank && !indented) || line.trim_start().starts_with("[^") {
+            break;
+        }
+        text.push_str(if blank { "\n\n" } else { "\n" });
+        text.push_str(line.trim());
+        blank = false;
+    }
+    Some(text)
+}
+
+/// The beginning of `text` for a preview: the title and first paragraph,
+/// or the section under `heading` (matched by text or anchor) when given.
+pub fn excerpt(text: &str, heading: Option<&str>) -> String {
+    let body = &text[front_matter::body_start(text)..];
+    let mut lines = body.lines();
+    let mut excerpt = Vec::new();
+    if let Some(heading) = heading {
+        let wanted = heading.trim().to_lowercase();
+        let found = lines.by_ref().find(|line| {
+            export::heading_level(line).is_some_and(|level| {
+                let title = line.trim_start()[level..]
+                    .trim()
+                    .trim_end_matches('#')
+                    .trim();
+                title.to_lowercase() == wanted || export::slugify(title) == wanted
+            })
+        });
+        excerpt.extend(found);
+    }
+    // Up to the end of the first paragraph that is not a heading.
+    let mut in_paragraph = false;
+    for line in lines {
+        if line.trim().is_empty() {
+            if in_paragraph {
+                break;
+            }
+            if !excerpt.is_empty() {
+                excerpt.push("");
+            }
+            continue;
+        }
+        if export::heading_level(line).is_some() && !in_paragraph {
+            if excerpt.iter().any(|line| !line.is_empty()) && heading.is_some() {
+                break;
+            }
+        } else {
+            in_paragraph = true;
+        }
+        excerpt.push(line);
+    }
+    let mut excerpt = excerpt.join("\n").trim().to_string();
+    if excerpt.chars().count() > MAX_EXCERPT {
+        excerpt = excerpt.chars().take(MAX_EXCERPT).collect();
+        excerpt.push('…');
+    }
+    excerpt
+}
@@ -0,0 +1,199 @@
+use crate::{bundle, comments, critic, export, glossary, joplin, obsidian, wikilinks};
+use pulldown_cmark::{Event, LinkType, Parser, Tag};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use syntect::highlighting::ThemeSet;
+
+/// Settings that change how source markdown is turned into preview markdown.
+#[derive(Clone, Default, Hash, PartialEq)]
+pub struct PreviewOptions {
+    pub show_comments: bool,
+    /// Render Obsidian's `![[embeds]]` and hide its `%%comments%%`.
+    pub obsidian: bool,
+    /// Folder relative links and images are resolved against: the open
+    /// file's, or the working directory for a new document.
+    pub base_dir: PathBuf,
+}
+
+/// The document text the preview shows. It follows the editor once typing
+/// has paused, so long documents are not re-split on every keystroke.
+#[derive(Default)]
+pub struct PreviewText {
+    text: String,
+    /// Hash of the editor text when it last changed, and when that was.
+    latest: u64,
+    changed_at: f64,
+}
+
+impl PreviewText {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Takes `current` once it has gone `delay` seconds unchanged. Returns
+    /// how long to wait before checking again while an update is pending.
+    pub fn update(&mut self, current: &str, now: f64, delay: f64) -> Option<f64> {
+        if current == self.text {
+            return None;
+        }
+        let mut hasher = DefaultHasher::new();
+        current.hash(&mut hasher);
+        let hash = hasher.finish();
+        if hash != self.latest {
+            self.latest = hash;
+            self.changed_at = now;
+        }
+        let waited = now - self.changed_at;
+        if self.text.is_empty() || waited >= delay {
+            self.text = current.to_string();
+            None
+        } else {
+            Some(delay - waited)
+        }
+    }
+}
+
+/// Rewrites one block of source markdown before it is handed to the viewer.
+pub fn prepare(markdown: &str, options: &PreviewOptions) -> String {
+    let markdown = comments::render_comments(markdown, options.show_comments);
+    let mut markdown = joplin::render_links(&glossary::hide_definitions(&markdown));
+    if options.obsidian {
+        markdown = obsidian::render_embeds(&markdown);
+    }
+    let markdown = wikilinks::render(&markdown);
+    resolve_links(&markdown, &options.base_dir)
+}
+
+/// `markdown` with the relative targets of its links, images and reference
+/// definitions made absolute `file://` URLs in `dir`. The viewer would
+/// otherwise load them relative to the directory md-echo was started in.
+/// Code spans and blocks are left as written.
+pub fn resolve_links(markdown: &str, dir: &Path) -> String {
+    let linked = markdown.contains("](") || markdown.contains("]:");
+    if dir.as_os_str().is_empty() || !linked {
+        return markdown.to_string();
+    }
+    let parser = Parser::new_ext(markdown, export::markdown_options());
+    let mut targets: Vec<Range<usize>> = parser
+        .reference_definitions()
+        .iter()
+        .filter_map(|(_, def)| {
+            let colon = markdown[def.span.clone()].find("]:")?;
+            destination(markdown, def.span.start + colon + 2, &def.dest)
+        })
+        .collect();
+    for (event, range) in parser.into_offset_iter() {
+        let dest_url = match event {
+            Event::Start(Tag::Link {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            })
+            | Event::Start(Tag::Image {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            }) => dest_url,
+            _ => continue,
+        };
+        // The first `](` followed by the destination; an image in the link
+        // text comes before it.
+        let found = markdown[range.clone()]
+            .match_indices("](")
+            .find_map(|(at, _)| destination(markdown, range.start + at + 2, &dest_url));
+        targets.extend(found);
+    }
+    targets.sort_by_key(|target| target.start);
+    targets.dedup();
+
+    let mut resolved = String::with_capacity(markdown.len());
+    let mut copied = 0;
+    for target in targets {
+        let Some(url) = file_url(&markdown[target.clone()], dir) else {
+            continue;
+        };
+        resolved.push_str(&markdown[copied..target.start]);
+        if markdown[..target.start].ends_with('<') {
+            resolved.push_str(&url);
+        } else {
+            resolved.push_str(&format!("<{}>", url));
+        }
+        copied = target.end;
+    }
+    resolved.push_str(&markdown[copied..]);
+    resolved
+}
+
+/// Range of the link destination written at `start`, after any blank
+/// space, if it is `dest` (angle brackets excluded).
+fn destination(markdown: &str, start: usize, dest: &str) -> Option<Range<usize>> {
+    let rest = &markdown[start..];
+    let start = start + (rest.len() - rest.trim_start().len());
+    let rest = &markdown[start..];
+    let range = if let Some(angled) = rest.strip_prefix('<') {
+        let end = angled.find(['>', '\n'])?;
+        start + 1..start + 1 + end
+    } else {
+        let mut depth = 0usize;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| match c {
+                '(' => {
+                    depth += 1;
+                    false
+                }
+                ')' if depth == 0 => true,
+                ')' => {
+                    depth -= 1;
+                    false
+                }
+                c => c.is_whitespace(),
+            })
+            .map_or(rest.len(), |(end, _)| end);
+        start..start + end
+    };
+    (!range.is_empty() && markdown[range.clone()] == *dest).then_some(range)
+}
+
+/// `target`, relative to `dir`, as a `file://` URL. The image loader takes
+/// the path as it is, so it is not percent-encoded. Targets with a scheme,
+/// or starting with `/` or `#`, are left alone.
+fn file_url(target: &str, dir: &Path) -> Option<String> {
+    let (path_part, suffix) = match target.find(['?', '#']) {
+        Some(at) => target.split_at(at),
+        None => (target, ""),
+    };
+    if path_part.is_empty() || export::is_external(target) || path_part.contains(':') {
+        return None;
+    }
+    let path = export::normalize(&dir.join(bundle::percent_decode(path_part)));
+    let path = path.to_string_lossy().replace('\\', "/");
+    let slash = if path.starts_with('/') { "" } else { "/" };
+    Some(format!("file://{}{}{}", slash, path, suffix))
+}
+
+/// The path of a `file://` URL as [`file_url`] writes them, without its
+/// query or fragment.
+pub fn file_url_path(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file://")?;
+    let path = &path[..path.find(['?', '#']).unwrap_or(path.len())];
+    if cfg!(windows) {
+        Some(PathBuf::from(path.trim_start_matches('/')))
+    } else {
+        Some(PathBuf::from(path))
+    }
+}
+
+/// Whether a block carries CriticMarkup suggestions that are still pending.
+pub fn has_review_marks(markdown: &str) -> bool {
+    !critic::find_changes(markdown).is_empty()
+}
+
+/// Names of the syntect color schemes available for code blocks.
+pub fn code_themes() -> &'static [String] {
+    static THEMES: OnceLock<Vec<String>> = OnceLock::new();
+    THEMES.get_or_init(|| ThemeSet::load_defaults().themes.into_keys().collect())
+}
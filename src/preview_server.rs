@@ -0,0 +1,240 @@
+use crate::bundle;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Request, Response, Server};
+
+/// The event stream that tells open pages the document changed. Anything
+/// else besides `/` is a file next to the document.
+const EVENTS_PATH: &str = "/__md-echo/events";
+
+/// How often an idle event stream sends a comment, so closed tabs are
+/// noticed and their threads end.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Swaps in the new body on every change instead of reloading, so the page
+/// keeps its scroll position.
+const RELOAD_SCRIPT: &str = r#"<script>
+new EventSource("/__md-echo/events").onmessage = () => fetch(location.pathname)
+  .then((response) => response.text())
+  .then((html) => {
+    const page = new DOMParser().parseFromString(html, "text/html");
+    document.title = page.title;
+    document.body.innerHTML = page.body.innerHTML;
+  });
+</script>
+"#;
+
+struct Page {
+    /// The markdown `html` was rendered from.
+    source: String,
+    html: String,
+    /// Where the document's relative links and images are looked up.
+    root: PathBuf,
+    version: u64,
+    stopped: bool,
+}
+
+type Shared = Arc<(Mutex<Page>, Condvar)>;
+
+/// A local HTTP server, on 127.0.0.1 only, showing the current document as
+/// exported HTML. Open pages update themselves over server-sent events as
+/// the document changes. Dropping the server stops it.
+pub struct PreviewServer {
+    server: Arc<Server>,
+    shared: Shared,
+    pub port: u16,
+}
+
+impl PreviewServer {
+    pub fn start(port: u16) -> Result<Self, String> {
+        let server = Server::http(("127.0.0.1", port))
+            .map_err(|err| format!("Preview server could not listen on port {}: {}", port, err))?;
+        let server = Arc::new(server);
+        let shared: Shared = Arc::new((
+            Mutex::new(Page {
+                source: String::new(),
+                html: String::new(),
+                root: PathBuf::new(),
+                version: 0,
+                stopped: false,
+            }),
+            Condvar::new(),
+        ));
+        {
+            let server = Arc::clone(&server);
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let url = request.url();
+                    let path = url[..url.find(['?', '#']).unwrap_or(url.len())].to_string();
+                    if path == EVENTS_PATH {
+                        let shared = Arc::clone(&shared);
+                        thread::spawn(move || stream_events(request, &shared));
+                        continue;
+                    }
+                    let response = respond(&path, &shared);
+                    let _ = request.respond(response);
+                }
+            });
+        }
+        Ok(Self {
+            server,
+            shared,
+            port,
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+
+    /// Whether the page already shows `markdown` with links under `root`.
+    pub fn is_showing(&self, markdown: &str, root: &Path) -> bool {
+        self.shared
+            .0
+            .lock()
+            .is_ok_and(|page| page.source == markdown && page.root == root)
+    }
+
+    /// Replaces the page with `html`, rendered from `markdown`, and tells
+    /// open pages to fetch it.
+    pub fn show(&self, markdown: &str, html: &str, root: &Path) {
+        let (page, changed) = &*self.shared;
+        let Ok(mut page) = page.lock() else {
+            return;
+        };
+        page.source = markdown.to_string();
+        page.html = match html.rfind("</body>") {
+            Some(at) => format!("{}{}{}", &html[..at], RELOAD_SCRIPT, &html[at..]),
+            None => format!("{}{}", html, RELOAD_SCRIPT),
+        };
+        page.root = root.to_path_buf();
+        page.version += 1;
+        changed.notify_all();
+    }
+}
+
+impl Drop for PreviewServer {
+    fn drop(&mut self) {
+        let (page, changed) = &*self.shared;
+        if let Ok(mut page) = page.lock() {
+            page.stopped = true;
+        }
+        changed.notify_all();
+        self.server.unblock();
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+/// The page at `/`, or the file at `path` below the document's folder.
+fn respond(path: &str, shared: &Shared) -> Response<std::io::Cursor<Vec<u8>>> {
+    let Ok(page) = shared.0.lock() else {
+        return Response::from_string("Preview unavailable").with_status_code(500);
+    };
+    if path == "/" {
+        return Response::from_string(page.html.clone())
+            .with_header(header("Content-Type", "text/html; charset=utf-8"))
+            .with_header(header("Cache-Control", "no-store"));
+    }
+    let relative = PathBuf::from(bundle::percent_decode(path.trim_start_matches('/')));
+    let root = page.root.clone();
+    drop(page);
+    let not_found = || Response::from_string("Not found").with_status_code(404);
+    // Only files inside the document's folder are served, checked before
+    // anything is read, and after symlinks are followed.
+    let inside = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !inside {
+        return not_found();
+    }
+    let (Ok(root), Ok(file)) = (root.canonicalize(), root.join(&relative).canonicalize()) else {
+        return not_found();
+    };
+    if !file.starts_with(&root) {
+        return not_found();
+    }
+    match fs::read(&file) {
+        Ok(data) => Response::from_data(data)
+            .with_header(header("Content-Type", content_type(&file)))
+            .with_header(header("Cache-Control", "no-store")),
+        Err(_) => not_found(),
+    }
+}
+
+/// Holds the connection open and sends an event whenever the page changes.
+fn stream_events(request: Request, shared: &Shared) {
+    let (page, changed) = &**shared;
+    let Ok(mut seen) = page.lock().map(|page| page.version) else {
+        return;
+    };
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                Cache-Control: no-store\r\nConnection: keep-alive\r\n\r\n";
+    if writer
+        .write_all(head.as_bytes())
+        .and_then(|_| writer.flush())
+        .is_err()
+    {
+        return;
+    }
+    loop {
+        let message = {
+            let Ok(guard) = page.lock() else {
+                return;
+            };
+            let waited = changed.wait_timeout_while(guard, KEEP_ALIVE, |page| {
+                !page.stopped && page.version == seen
+            });
+            let Ok((current, _)) = waited else {
+                return;
+            };
+            if current.stopped {
+                return;
+            }
+            if current.version == seen {
+                ": keep-alive\n\n".to_string()
+            } else {
+                seen = current.version;
+                format!("data: {}\n\n", seen)
+            }
+        };
+        if writer
+            .write_all(message.as_bytes())
+            .and_then(|_| writer.flush())
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("md" | "markdown" | "txt") => "text/plain; charset=utf-8",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("pdf") => "application/pdf",
+        Some("mp3") => "audio/mpeg",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Frames the overlay averages over and graphs.
+const HISTORY: usize = 120;
+
+/// The bar graph's full height: two frames at 60 Hz.
+const GRAPH_SCALE: f32 = 1000.0 / 30.0;
+
+struct Frame {
+    /// Time spent in `update`.
+    total: Duration,
+    /// The whole previous frame as eframe measured it, drawing included.
+    cpu: Option<f32>,
+    scopes: Vec<(&'static str, Duration)>,
+}
+
+/// Frame timings broken down by subsystem, for the debug overlay. Timings
+/// are only taken while it is enabled.
+#[derive(Default)]
+pub struct Profiler {
+    enabled: bool,
+    started: Option<Instant>,
+    scopes: Vec<(&'static str, Duration)>,
+    frames: VecDeque<Frame>,
+}
+
+/// Average and worst time of one scope over the recorded frames, in ms.
+struct Summary {
+    name: &'static str,
+    average: f32,
+    max: f32,
+}
+
+fn ms(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+impl Profiler {
+    pub fn begin_frame(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.frames.clear();
+            return;
+        }
+        self.started = Some(Instant::now());
+        self.scopes.clear();
+    }
+
+    /// Adds the time since `since` to the scope `name` of this frame.
+    pub fn record(&mut self, name: &'static str, since: Instant) {
+        if !self.enabled {
+            return;
+        }
+        let elapsed = since.elapsed();
+        match self.scopes.iter_mut().find(|(scope, _)| *scope == name) {
+            Some((_, total)) => *total += elapsed,
+            None => self.scopes.push((name, elapsed)),
+        }
+    }
+
+    /// Ends the frame; `cpu` is eframe's measure of the previous one.
+    pub fn end_frame(&mut self, cpu: Option<f32>) {
+        let Some(started) = self.started.take().filter(|_| self.enabled) else {
+            return;
+        };
+        if self.frames.len() == HISTORY {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(Frame {
+            total: started.elapsed(),
+            cpu,
+            scopes: std::mem::take(&mut self.scopes),
+        });
+    }
+
+    fn summaries(&self) -> Vec<Summary> {
+        let count = self.frames.len().max(1) as f32;
+        let mut names: Vec<&'static str> = Vec::new();
+        for frame in &self.frames {
+            for (name, _) in &frame.scopes {
+                if !names.contains(name) {
+                    names.push(name);
+                }
+            }
+        }
+        let summary = |name: &'static str, time: &dyn Fn(&Frame) -> f32| Summary {
+            name,
+            average: self.frames.iter().map(time).sum::<f32>() / count,
+            max: self.frames.iter().map(time).fold(0.0, f32::max),
+        };
+        let mut summaries = vec![
+            summary("Frame (update)", &|frame| ms(frame.total)),
+            summary("Frame (with drawing)", &|frame| {
+                frame.cpu.map_or(0.0, |cpu| cpu * 1000.0)
+            }),
+        ];
+        for name in names {
+            summaries.push(summary(name, &|frame| {
+                frame
+                    .scopes
+                    .iter()
+                    .find(|(scope, _)| *scope == name)
+                    .map_or(0.0, |(_, time)| ms(*time))
+            }));
+        }
+        summaries
+    }
+
+    /// The timings as text, for pasting into a bug report along with what
+    /// `context` says about the document.
+    pub fn report(&self, context: &str) -> String {
+        let mut report = format!(
+            "md-echo {} profile over {} frames\n{}\n\n{:<24}{:>10}{:>10}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.frames.len(),
+            context,
+            "",
+            "avg ms",
+            "max ms"
+        );
+        for summary in self.summaries() {
+            report.push_str(&format!(
+                "{:<24}{:>10.2}{:>10.2}\n",
+                summary.name, summary.average, summary.max
+            ));
+        }
+        report
+    }
+
+    /// Draws the overlay in the top right corner of the window. `context`
+    /// describes the document, for the copied report.
+    pub fn show(&self, ctx: &egui::Context, context: &str) {
+        if !self.enabled {
+            return;
+        }
+        egui::Area::new(egui::Id::new("profiler_overlay"))
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 32.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.strong("Profiler");
+                    ui.weak(context);
+                    self.show_graph(ui);
+                    egui::Grid::new("profiler_scopes")
+                        .num_columns(3)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.weak("");
+                            ui.weak("avg ms");
+                            ui.weak("max ms");
+                            ui.end_row();
+                            for summary in self.summaries() {
+                                ui.label(summary.name);
+                                ui.monospace(format!("{:>7.2}", summary.average));
+                                ui.monospace(format!("{:>7.2}", summary.max));
+                                ui.end_row();
+                            }
+                        });
+                    if ui
+                        .small_button("Copy Report")
+                        .on_hover_text("Copy these timings to attach to a bug report")
+                        .clicked()
+                    {
+                        ui.output_mut(|output| output.copied_text = self.report(context));
+                    }
+                });
+            });
+    }
+
+    /// A bar per recent frame, its height the time spent in `update`, with
+    /// a line at 60 Hz.
+    fn show_graph(&self, ui: &mut egui::Ui) {
+        let size = egui::vec2(HISTORY as f32 * 2.0, 40.0);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        let height = |ms: f32| (ms / GRAPH_SCALE).min(1.0) * rect.height();
+        for (index, frame) in self.frames.iter().enumerate() {
+            let x = rect.left() + index as f32 * 2.0;
+            let time = ms(frame.total);
+            let color = if time > GRAPH_SCALE / 2.0 {
+                ui.visuals().error_fg_color
+            } else {
+                ui.visuals().selection.bg_fill
+            };
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(x, rect.bottom() - height(time)),
+                    egui::pos2(x + 1.5, rect.bottom()),
+                ),
+                0.0,
+                color,
+            );
+        }
+        let budget = rect.bottom() - height(GRAPH_SCALE / 2.0);
+        painter.hline(
+            rect.x_range(),
+            budget,
+            egui::Stroke::new(1.0, ui.visuals().weak_text_color()),
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use crate::export;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::Aes256Gcm;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use pbkdf2::pbkdf2_hmac;
+use sha2::Sha256;
+
+/// PBKDF2 rounds for turning the passphrase into a key; each guess at the
+/// passphrase costs an attacker this many hashes.
+const ITERATIONS: u32 = 600_000;
+
+/// Shorter passphrases are refused.
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Wraps an HTML page in a page that only shows a passphrase prompt. The
+/// original page is encrypted with AES-256-GCM under a key derived from
+/// the passphrase and decrypted in the browser with the Web Crypto API,
+/// so it can be hosted anywhere without a server-side login.
+pub fn protect_html(html: &str, title: &str, passphrase: &str) -> Result<String, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "The passphrase must be at least {} characters long.",
+            MIN_PASSPHRASE_LEN
+        ));
+    }
+    let mut salt = [0u8; 16];
+    OsRng
+        .try_fill_bytes(&mut salt)
+        .map_err(|err| format!("No random source: {}", err))?;
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), &salt, ITERATIONS, &mut key);
+    let cipher = Aes256Gcm::new(&key.into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, html.as_bytes())
+        .map_err(|_| "Failed to encrypt the document.".to_string())?;
+
+    // The title goes in last so a title that looks like a placeholder is
+    // left alone.
+    Ok(PAGE
+        .replace("{{iterations}}", &ITERATIONS.to_string())
+        .replace("{{salt}}", &STANDARD.encode(salt))
+        .replace("{{iv}}", &STANDARD.encode(nonce))
+        .replace("{{data}}", &STANDARD.encode(ciphertext))
+        .replace("{{title}}", &export::escape_html(title)))
+}
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>{{title}}</title>
+<style>
+body { font-family: sans-serif; background: #f4f4f4; margin: 0; }
+form { max-width: 22em; margin: 15vh auto; padding: 2em; background: #fff; border-radius: 6px; box-shadow: 0 1px 4px rgba(0, 0, 0, 0.15); }
+h1 { font-size: 1.2em; margin-top: 0; }
+input, button { box-sizing: border-box; width: 100%; padding: 0.6em; margin-top: 0.6em; font-size: 1em; }
+#error { color: #c0392b; min-height: 1.2em; margin: 0.6em 0 0; }
+</style>
+</head>
+<body>
+<form id="unlock">
+<h1>{{title}}</h1>
+<p>This document is protected. Enter the passphrase to read it.</p>
+<input id="passphrase" type="password" autocomplete="current-password" autofocus>
+<button type="submit">Unlock</button>
+<p id="error"></p>
+</form>
+<script>
+const payload = { iterations: {{iterations}}, salt: "{{salt}}", iv: "{{iv}}", data: "{{data}}" };
+const bytes = (text) => Uint8Array.from(atob(text), (c) => c.charCodeAt(0));
+document.getElementById("unlock").addEventListener("submit", async (event) => {
+  event.preventDefault();
+  const error = document.getElementById("error");
+  if (!window.crypto || !crypto.subtle) {
+    error.textContent = "This page must be opened over HTTPS or from a local file.";
+    return;
+  }
+  error.textContent = "Unlocking…";
+  try {
+    const passphrase = new TextEncoder().encode(document.getElementById("passphrase").value);
+    const material = await crypto.subtle.importKey("raw", passphrase, "PBKDF2", false, ["deriveKey"]);
+    const key = await crypto.subtle.deriveKey(
+      { name: "PBKDF2", salt: bytes(payload.salt), iterations: payload.iterations, hash: "SHA-256" },
+      material, { name: "AES-GCM", length: 256 }, false, ["decrypt"]);
+    const plain = await crypto.subtle.decrypt({ name: "AES-GCM", iv: bytes(payload.iv) }, key, bytes(payload.data));
+    document.open();
+    document.write(new TextDecoder().decode(plain));
+    document.close();
+  } catch (_) {
+    error.textContent = "Wrong passphrase.";
+  }
+});
+</script>
+</body>
+</html>
+"#;
@@ -0,0 +1,252 @@
+use crate::diagrams;
+use crate::export;
+use crate::joplin;
+use crate::link_cards;
+use crate::media;
+use crate::obsidian;
+use crate::preview::{self, PreviewOptions};
+use crate::tasks;
+use pulldown_cmark::{Event, Parser, Tag};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// A top-level markdown block: the source lines it spans and the markdown
+/// rendered for it in the preview.
+pub struct SourceBlock {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub markdown: String,
+    /// The source contains CriticMarkup suggestions.
+    pub has_changes: bool,
+    /// 1 to 6 for headings.
+    pub heading_level: Option<usize>,
+    /// The block is a page-break marker, drawn as a divider.
+    pub page_break: bool,
+    /// Task list items in the rendered markdown, which can be ticked off in
+    /// the preview.
+    pub task_count: usize,
+    /// Source of a mermaid diagram, drawn as an image when it renders.
+    pub diagram: Option<String>,
+    /// The URL the block consists of alone, drawn as a preview card when
+    /// link cards are on.
+    pub bare_url: Option<String>,
+    /// Audio or video the block consists of, drawn as a player.
+    pub media: Option<media::Embed>,
+}
+
+/// Where a rendered block ended up inside the preview scroll area.
+#[derive(Clone, Copy)]
+pub struct PreviewBlock {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// Maps source lines to editor and preview scroll offsets so both panes can
+/// follow each other.
+#[derive(Default)]
+pub struct SourceMap {
+    content_hash: u64,
+    pub blocks: Vec<SourceBlock>,
+    /// Height of each block as last drawn at `heights_width`, so blocks
+    /// outside the viewport can be skipped. Cleared when the blocks change.
+    heights: Vec<Option<f32>>,
+    heights_width: f32,
+    pub line_tops: Vec<f32>,
+    pub preview: Vec<PreviewBlock>,
+}
+
+impl SourceMap {
+    pub fn update_blocks(&mut self, text: &str, options: &PreviewOptions) {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        options.hash(&mut hasher);
+        let hash = hasher.finish();
+        if hash == self.content_hash && !self.blocks.is_empty() {
+            return;
+        }
+        self.content_hash = hash;
+        // These can span blocks, so they go before the text is split.
+        let text = joplin::hide_metadata(text);
+        self.blocks = if options.obsidian {
+            split_blocks(&obsidian::hide_comments(&text))
+        } else {
+            split_blocks(&text)
+        };
+        for block in &mut self.blocks {
+            block.has_changes = preview::has_review_marks(&block.markdown);
+            block.markdown = preview::prepare(&block.markdown, options);
+            block.task_count = tasks::find(&block.markdown).len();
+            block.diagram = diagrams::mermaid_source(&block.markdown);
+        }
+        self.blocks
+            .retain(|block| !block.markdown.trim().is_empty());
+        self.heights.clear();
+    }
+
+    /// Takes the block heights drawn at `width`, or none if the width
+    /// changed; hand them back with `keep_heights`.
+    pub fn take_heights(&mut self, width: f32) -> Vec<Option<f32>> {
+        let mut heights = std::mem::take(&mut self.heights);
+        if heights.len() != self.blocks.len() || (width - self.heights_width).abs() > 0.5 {
+            heights = vec![None; self.blocks.len()];
+        }
+        self.heights_width = width;
+        heights
+    }
+
+    pub fn keep_heights(&mut self, heights: Vec<Option<f32>>) {
+        self.heights = heights;
+    }
+
+    /// Records the top of every logical line in the editor galley, relative
+    /// to the scroll content origin.
+    pub fn record_editor_rows(&mut self, galley: &egui::Galley, offset_y: f32) {
+        self.line_tops.clear();
+        let mut line_start = true;
+        for row in &galley.rows {
+            if line_start {
+                self.line_tops.push(row.rect.min.y + offset_y);
+            }
+            line_start = row.ends_with_newline;
+        }
+    }
+
+    pub fn editor_offset_to_line(&self, y: f32) -> f32 {
+        interpolate_index(&self.line_tops, y)
+    }
+
+    pub fn line_to_editor_offset(&self, line: f32) -> f32 {
+        if self.line_tops.is_empty() {
+            return 0.0;
+        }
+        let index = (line.floor() as usize).min(self.line_tops.len() - 1);
+        let top = self.line_tops[index];
+        let next = self
+            .line_tops
+            .get(index + 1)
+            .copied()
+            .unwrap_or(top + line_height(&self.line_tops));
+        top + (next - top) * line.fract()
+    }
+
+    pub fn line_to_preview_offset(&self, line: f32) -> Option<f32> {
+        let block = self
+            .preview
+            .iter()
+            .rev()
+            .find(|block| block.start_line as f32 <= line)
+            .or_else(|| self.preview.first())?;
+        let span = (block.end_line + 1 - block.start_line) as f32;
+        let fraction = ((line - block.start_line as f32) / span).clamp(0.0, 1.0);
+        Some(block.top + (block.bottom - block.top) * fraction)
+    }
+
+    pub fn preview_offset_to_line(&self, y: f32) -> Option<f32> {
+        let block = self
+            .preview
+            .iter()
+            .rev()
+            .find(|block| block.top <= y)
+            .or_else(|| self.preview.first())?;
+        let height = (block.bottom - block.top).max(1.0);
+        let fraction = ((y - block.top) / height).clamp(0.0, 1.0);
+        let span = (block.end_line + 1 - block.start_line) as f32;
+        Some(block.start_line as f32 + span * fraction)
+    }
+}
+
+fn line_height(line_tops: &[f32]) -> f32 {
+    match line_tops {
+        [.., a, b] => b - a,
+        _ => 16.0,
+    }
+}
+
+fn interpolate_index(tops: &[f32], y: f32) -> f32 {
+    if tops.is_empty() {
+        return 0.0;
+    }
+    let index = tops.partition_point(|top| *top <= y).saturating_sub(1);
+    let top = tops[index];
+    let next = tops
+        .get(index + 1)
+        .copied()
+        .unwrap_or(top + line_height(tops));
+    index as f32 + ((y - top) / (next - top).max(1.0)).clamp(0.0, 1.0)
+}
+
+/// Splits `text` into its top-level blocks. Link reference definitions are
+/// appended to every block so reference-style links still resolve when the
+/// blocks are rendered separately.
+pub fn split_blocks(text: &str) -> Vec<SourceBlock> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|start| *start <= offset) - 1;
+
+    let definitions: String = text
+        .lines()
+        .filter(|line| is_reference_definition(line))
+        .map(|line| format!("\n{}", line))
+        .collect();
+
+    let mut blocks = Vec::new();
+    let mut depth = 0usize;
+    for (event, range) in Parser::new_ext(text, export::markdown_options()).into_offset_iter() {
+        match event {
+            Event::Start(tag) => {
+                // Front matter is not part of the rendered document.
+                if depth == 0 && !matches!(tag, Tag::MetadataBlock(_)) {
+                    let level = match tag {
+                        Tag::Heading { level, .. } => Some(level as usize),
+                        _ => None,
+                    };
+                    blocks.push((range.clone(), level));
+                }
+                depth += 1;
+            }
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ if depth == 0 => blocks.push((range.clone(), None)),
+            _ => {}
+        }
+    }
+
+    blocks
+        .into_iter()
+        .filter(|(range, _)| range.start < range.end)
+        .map(|(range, heading_level)| {
+            let page_break = export::is_page_break(&text[range.clone()]);
+            let bare_url = link_cards::bare_url(&text[range.clone()]);
+            let media = media::embed(&text[range.clone()]);
+            let mut markdown = text[range.clone()].to_string();
+            if !definitions.is_empty() {
+                markdown.push('\n');
+                markdown.push_str(&definitions);
+            }
+            SourceBlock {
+                start_line: line_of(range.start),
+                end_line: line_of(range.end.saturating_sub(1)),
+                markdown,
+                has_changes: false,
+                heading_level,
+                page_break,
+                task_count: 0,
+                diagram: None,
+                bare_url,
+                media,
+            }
+        })
+        .collect()
+}
+
+fn is_reference_definition(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with('[')
+        && !trimmed.starts_with("[^")
+        && trimmed
+            .find("]:")
+            .map(|end| end > 1 && !trimmed[1..end].contains(']'))
+            .unwrap_or(false)
+}
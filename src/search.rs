@@ -0,0 +1,133 @@
+use regex::{Regex, RegexBuilder};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Stop collecting once this many matches were found.
+const MAX_HITS: usize = 2000;
+
+pub struct SearchHit {
+    /// Zero-based line of the match.
+    pub line: usize,
+    pub text: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+pub struct FileHits {
+    pub path: PathBuf,
+    pub hits: Vec<SearchHit>,
+}
+
+#[derive(Default)]
+struct Progress {
+    files: Vec<FileHits>,
+    total: usize,
+    done: bool,
+}
+
+/// A search over a list of markdown files, running on a background
+/// thread. Dropping the job cancels it.
+pub struct SearchJob {
+    progress: Arc<Mutex<Progress>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl SearchJob {
+    pub fn start(
+        files: Vec<PathBuf>,
+        query: &str,
+        case_sensitive: bool,
+        use_regex: bool,
+        ctx: egui::Context,
+    ) -> Result<Self, String> {
+        let pattern = if use_regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!case_sensitive)
+            .build()
+            .map_err(|err| format!("Invalid pattern: {}", err))?;
+
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let progress = Arc::clone(&progress);
+            let cancel = Arc::clone(&cancel);
+            thread::spawn(move || {
+                for path in files {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let Ok(text) = fs::read_to_string(&path) else {
+                        continue;
+                    };
+                    let hits = search_text(&text, &regex);
+                    if hits.is_empty() {
+                        continue;
+                    }
+                    let Ok(mut progress) = progress.lock() else {
+                        break;
+                    };
+                    progress.total += hits.len();
+                    progress.files.push(FileHits { path, hits });
+                    ctx.request_repaint();
+                    if progress.total >= MAX_HITS {
+                        break;
+                    }
+                }
+                if let Ok(mut progress) = progress.lock() {
+                    progress.done = true;
+                }
+                ctx.request_repaint();
+            });
+        }
+
+        Ok(Self { progress, cancel })
+    }
+
+    /// Runs `f` on the results collected so far.
+    pub fn with_results<R>(&self, f: impl FnOnce(&[FileHits]) -> R) -> R {
+        match self.progress.lock() {
+            Ok(progress) => f(&progress.files),
+            Err(_) => f(&[]),
+        }
+    }
+
+    /// `(matches, done, truncated)`
+    pub fn status(&self) -> (usize, bool, bool) {
+        self.progress
+            .lock()
+            .map(|progress| (progress.total, progress.done, progress.total >= MAX_HITS))
+            .unwrap_or((0, true, false))
+    }
+}
+
+impl Drop for SearchJob {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn search_text(text: &str, regex: &Regex) -> Vec<SearchHit> {
+    let lines: Vec<&str> = text.lines().collect();
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| regex.is_match(line))
+        .map(|(index, line)| SearchHit {
+            line: index,
+            text: line.trim_end().to_string(),
+            before: index
+                .checked_sub(1)
+                .map(|before| lines[before].trim_end().to_string()),
+            after: lines
+                .get(index + 1)
+                .map(|after| after.trim_end().to_string()),
+        })
+        .collect()
+}
@@ -0,0 +1,53 @@
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// What was open when md-echo last closed, restored on the next start.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub file: Option<PathBuf>,
+    /// Zero-based line and column of the cursor.
+    pub cursor: Option<(usize, usize)>,
+    pub editor_scroll: f32,
+    pub preview_scroll: f32,
+    pub panels: Panels,
+}
+
+/// The side panels and views that were showing.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Panels {
+    pub search: bool,
+    pub outline: bool,
+    pub front_matter: bool,
+    pub comments: bool,
+    pub links: bool,
+    pub diagnostics: bool,
+    pub terminal: bool,
+    pub blame: bool,
+}
+
+fn session_path() -> Option<PathBuf> {
+    ProjectDirs::from("com", "fibnas", "md-echo")
+        .map(|dirs| dirs.data_local_dir().join("session.json"))
+}
+
+/// The last session, if one was saved and can be read.
+pub fn load() -> Option<Session> {
+    let text = fs::read_to_string(session_path()?).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+pub fn save(session: &Session) -> Result<(), String> {
+    let Some(path) = session_path() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("Could not create {}: {}", dir.display(), err))?;
+    }
+    let json = serde_json::to_string_pretty(session).map_err(|err| err.to_string())?;
+    fs::write(&path, json).map_err(|err| format!("Could not write {}: {}", path.display(), err))
+}
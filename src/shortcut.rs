@@ -0,0 +1,38 @@
+use egui::{Key, KeyboardShortcut, Modifiers};
+
+/// Parses a hotkey such as `Ctrl+Shift+K` or `Alt+F5`. Modifier names are
+/// case-insensitive; `Cmd` is the command key on macOS and Ctrl elsewhere.
+pub fn parse(text: &str) -> Option<KeyboardShortcut> {
+    let mut modifiers = Modifiers::NONE;
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let key = parts.pop().filter(|key| !key.is_empty())?;
+    for part in parts {
+        match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => modifiers.ctrl = true,
+            "shift" => modifiers.shift = true,
+            "alt" | "option" => modifiers.alt = true,
+            "cmd" | "command" => modifiers = modifiers.plus(Modifiers::COMMAND),
+            _ => return None,
+        }
+    }
+    let key = Key::from_name(key).or_else(|| Key::from_name(&key.to_ascii_uppercase()))?;
+    Some(KeyboardShortcut::new(modifiers, key))
+}
+
+/// Writes a hotkey the way [`parse`] reads it, e.g. `Ctrl+Shift+K`. Cmd is
+/// written as Ctrl.
+pub fn format(shortcut: &KeyboardShortcut) -> String {
+    let modifiers = shortcut.modifiers;
+    let mut parts = Vec::new();
+    if modifiers.ctrl || modifiers.command || modifiers.mac_cmd {
+        parts.push("Ctrl");
+    }
+    if modifiers.alt {
+        parts.push("Alt");
+    }
+    if modifiers.shift {
+        parts.push("Shift");
+    }
+    parts.push(shortcut.logical_key.name());
+    parts.join("+")
+}
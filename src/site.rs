@@ -0,0 +1,218 @@
+use crate::export::{self, HtmlOptions};
+use crate::patterns::IgnorePatterns;
+use crate::{bundle, front_matter, wikilinks};
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The page listing every other one, written unless the folder has an
+/// `index.md` of its own.
+const INDEX: &str = "index.html";
+
+/// What [`export`] wrote.
+pub struct Site {
+    pub pages: usize,
+    pub assets: usize,
+    /// Files that could not be read, written or copied, and why.
+    pub failures: Vec<String>,
+    /// Whether an index page was generated.
+    pub index: bool,
+}
+
+fn unresolved_regex() -> &'static Regex {
+    static UNRESOLVED: OnceLock<Regex> = OnceLock::new();
+    UNRESOLVED.get_or_init(|| {
+        Regex::new(&format!(
+            r"\[([^\]]*)\]\({}[^)]*\)",
+            regex::escape(wikilinks::SCHEME)
+        ))
+        .unwrap()
+    })
+}
+
+/// Exports the markdown files under `root` that `ignore` leaves in to
+/// `output` as a static site. Each file becomes an HTML page at the same
+/// place below `output`, links and wikilinks between documents point at
+/// their pages, and the local images and attachments the pages link to
+/// are copied alongside.
+pub fn export(
+    root: &Path,
+    ignore: &IgnorePatterns,
+    output: &Path,
+    html: &HtmlOptions,
+) -> Result<Site, String> {
+    let root = export::normalize(root);
+    let output = export::normalize(output);
+    if root.starts_with(&output) {
+        return Err("The site cannot be written to a folder the documents are in.".to_string());
+    }
+    fs::create_dir_all(&output)
+        .map_err(|err| format!("Failed to create {}: {}", output.display(), err))?;
+    // A site written inside the working directory is not part of itself.
+    let files: Vec<PathBuf> = export::markdown_files_except(&root, ignore)
+        .into_iter()
+        .map(|file| export::normalize(&file))
+        .filter(|file| !file.starts_with(&output))
+        .collect();
+    if files.is_empty() {
+        return Err(format!("{} has no markdown files.", root.display()));
+    }
+    let mut site = Site {
+        pages: 0,
+        assets: 0,
+        failures: Vec::new(),
+        index: false,
+    };
+    let mut assets = BTreeSet::new();
+    for file in &files {
+        let relative = file.strip_prefix(&root).unwrap_or(file);
+        let page = output.join(relative).with_extension("html");
+        let page_dir = page.parent().unwrap_or(&output);
+        let markdown = match fs::read_to_string(file) {
+            Ok(markdown) => markdown,
+            Err(err) => {
+                site.failures
+                    .push(format!("{}: {}", relative.display(), err));
+                continue;
+            }
+        };
+        let dir = file.parent().unwrap_or(&root);
+        let (markdown, linked) = link_pages(&markdown, dir, page_dir, &root, &files);
+        assets.extend(linked);
+        let stem = file.file_stem().map_or("Untitled".to_string(), |stem| {
+            stem.to_string_lossy().into_owned()
+        });
+        let result = fs::create_dir_all(page_dir)
+            .and_then(|_| fs::write(&page, export::markdown_to_html(&markdown, &stem, html)));
+        match result {
+            Ok(()) => site.pages += 1,
+            Err(err) => site.failures.push(format!("{}: {}", page.display(), err)),
+        }
+    }
+
+    for asset in assets {
+        let Ok(relative) = asset.strip_prefix(&root) else {
+            continue;
+        };
+        let copy = output.join(relative);
+        let result = copy
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::copy(&asset, &copy));
+        match result {
+            Ok(_) => site.assets += 1,
+            Err(err) => site
+                .failures
+                .push(format!("{}: {}", relative.display(), err)),
+        }
+    }
+
+    if !root.join("index.md").is_file() {
+        let title = root.file_name().map_or("Index".to_string(), |name| {
+            name.to_string_lossy().into_owned()
+        });
+        let index = output.join(INDEX);
+        let markdown = index_markdown(&title, &export::order_files(files, &root), &root);
+        match fs::write(&index, export::markdown_to_html(&markdown, &title, html)) {
+            Ok(()) => site.index = true,
+            Err(err) => site.failures.push(format!("{}: {}", index.display(), err)),
+        }
+    }
+    Ok(site)
+}
+
+/// Rewrites the links of `markdown`, a document in `dir` whose page goes
+/// in `page_dir`: links to other documents point at their pages,
+/// wikilinks become links to the pages they resolve to (or plain text),
+/// and files outside `root` are linked where they are. Also returns the
+/// local files inside `root` it links to, which are copied into the site.
+fn link_pages(
+    markdown: &str,
+    dir: &Path,
+    page_dir: &Path,
+    root: &Path,
+    files: &[PathBuf],
+) -> (String, Vec<PathBuf>) {
+    let mut assets = Vec::new();
+    let text = bundle::rewrite_targets(&wikilinks::render(markdown), |target| {
+        if let Some(link) = wikilinks::from_url(target) {
+            let target = link.split('#').next().unwrap_or_default();
+            let page = wikilinks::resolve(root, files, target)?;
+            // The site mirrors the folders, so the way from the document to
+            // the other one is the way between their pages.
+            let relative = export::relative_path(dir, &page.with_extension("html"));
+            return Some(wikilinks::encode(
+                &relative.to_string_lossy().replace('\\', "/"),
+            ));
+        }
+        if export::is_external(target) || target.starts_with("data:") {
+            return None;
+        }
+        let (path_part, suffix) = match target.find(['?', '#']) {
+            Some(at) => target.split_at(at),
+            None => (target, ""),
+        };
+        let path = export::normalize(&dir.join(bundle::percent_decode(path_part)));
+        if files.contains(&path) {
+            let (stem, _) = path_part.rsplit_once('.')?;
+            return Some(format!("{}.html{}", stem, suffix));
+        }
+        if !path.is_file() {
+            return None;
+        }
+        if path.starts_with(root) {
+            assets.push(path);
+            return None;
+        }
+        let relative = export::relative_path(page_dir, &path);
+        Some(format!(
+            "{}{}",
+            wikilinks::encode(&relative.to_string_lossy().replace('\\', "/")),
+            suffix
+        ))
+    });
+    let text = unresolved_regex().replace_all(&text, "$1").into_owned();
+    (text, assets)
+}
+
+/// A list of every page under a `title` heading, nested by folder.
+fn index_markdown(title: &str, files: &[PathBuf], root: &Path) -> String {
+    let mut markdown = format!("# {}\n\n", title);
+    let mut open: Vec<String> = Vec::new();
+    for file in files {
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        let folders: Vec<String> = relative
+            .parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .map(|part| part.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let shared = open
+            .iter()
+            .zip(&folders)
+            .take_while(|(open, folder)| open == folder)
+            .count();
+        open.truncate(shared);
+        for folder in &folders[shared..] {
+            markdown.push_str(&format!("{}- {}/\n", "  ".repeat(open.len()), folder));
+            open.push(folder.clone());
+        }
+        let text = fs::read_to_string(file).unwrap_or_default();
+        let label = front_matter::get(&text, "title")
+            .or_else(|| export::atx_headings(&text).next().map(|(_, title)| title))
+            .unwrap_or_else(|| {
+                file.file_stem()
+                    .map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+            });
+        let link = relative.with_extension("html");
+        markdown.push_str(&format!(
+            "{}- [{}]({})\n",
+            "  ".repeat(open.len()),
+            label.replace(['[', ']'], ""),
+            wikilinks::encode(&link.to_string_lossy().replace('\\', "/"))
+        ));
+    }
+    markdown
+}
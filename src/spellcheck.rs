@@ -0,0 +1,382 @@
+use crate::export;
+use crate::front_matter;
+use crate::hunspell::{self, Dictionary};
+use crate::language::Language;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// At most this many corrections are offered.
+const MAX_SUGGESTIONS: usize = 8;
+
+/// A word that is neither in the dictionary nor in the word list, with its
+/// char range in the document.
+#[derive(Clone)]
+pub struct Misspelling {
+    pub range: Range<usize>,
+    pub word: String,
+}
+
+enum Loading {
+    Pending(Arc<Mutex<Option<Result<Dictionary, String>>>>),
+    Ready(Arc<Dictionary>),
+    Failed(String),
+}
+
+/// Spell checking of the open document: dictionaries by language, loaded
+/// on a background thread the first time they are needed, plus the
+/// project word list and words ignored for this session.
+#[derive(Default)]
+pub struct SpellChecker {
+    dictionaries: HashMap<String, Loading>,
+    word_list: Option<PathBuf>,
+    custom: HashSet<String>,
+    ignored: HashSet<String>,
+    checked_hash: u64,
+    misspellings: Vec<Misspelling>,
+}
+
+impl SpellChecker {
+    /// Reads the project word list (one word per line) when `path`
+    /// changes.
+    pub fn set_word_list(&mut self, path: PathBuf) {
+        if self.word_list.as_ref() == Some(&path) {
+            return;
+        }
+        self.custom = fs::read_to_string(&path)
+            .map(|text| {
+                text.lines()
+                    .map(str::trim)
+                    .filter(|word| !word.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.word_list = Some(path);
+        self.checked_hash = 0;
+    }
+
+    /// Misspelled words of `text` in `language`, using the dictionary
+    /// configured for it or an installed one. Empty while the dictionary
+    /// loads.
+    pub fn check(
+        &mut self,
+        text: &str,
+        language: &Language,
+        configured: &BTreeMap<String, PathBuf>,
+        ctx: &egui::Context,
+    ) -> &[Misspelling] {
+        let Some(dictionary) = self.dictionary(language, configured, ctx) else {
+            self.misspellings.clear();
+            self.checked_hash = 0;
+            return &self.misspellings;
+        };
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        language.tag.hash(&mut hasher);
+        self.custom.len().hash(&mut hasher);
+        self.ignored.len().hash(&mut hasher);
+        let hash = hasher.finish();
+        if hash != self.checked_hash {
+            self.checked_hash = hash;
+            self.misspellings = words(text)
+                .into_iter()
+                .filter(|(_, word)| !self.is_known(&dictionary, word))
+                .map(|(range, word)| Misspelling { range, word })
+                .collect();
+        }
+        &self.misspellings
+    }
+
+    /// The misspellings found by the last check.
+    pub fn misspellings(&self) -> &[Misspelling] {
+        &self.misspellings
+    }
+
+    /// Why `language` is not being checked, if it is not.
+    pub fn problem(&self, language: &Language) -> Option<String> {
+        match self.dictionaries.get(&language.tag)? {
+            Loading::Pending(_) => Some("Loading dictionary…".to_string()),
+            Loading::Ready(_) => None,
+            Loading::Failed(err) => Some(err.clone()),
+        }
+    }
+
+    fn dictionary(
+        &mut self,
+        language: &Language,
+        configured: &BTreeMap<String, PathBuf>,
+        ctx: &egui::Context,
+    ) -> Option<Arc<Dictionary>> {
+        let entry = self
+            .dictionaries
+            .entry(language.tag.clone())
+            .or_insert_with(|| {
+                let path = configured
+                    .get(&language.tag)
+                    .or_else(|| configured.get(&language.primary()))
+                    .cloned()
+                    .or_else(|| hunspell::find(&language.tag));
+                let Some(path) = path else {
+                    return Loading::Failed(format!(
+                        "No dictionary for '{}'. Install a Hunspell dictionary or add one under [spellcheck.dictionaries].",
+                        language.tag
+                    ));
+                };
+                let slot = Arc::new(Mutex::new(None));
+                {
+                    let slot = Arc::clone(&slot);
+                    let ctx = ctx.clone();
+                    thread::spawn(move || {
+                        let dictionary = Dictionary::load(&path);
+                        if let Ok(mut slot) = slot.lock() {
+                            *slot = Some(dictionary);
+                        }
+                        ctx.request_repaint();
+                    });
+                }
+                Loading::Pending(slot)
+            });
+        if let Loading::Pending(slot) = entry {
+            match slot.lock().ok().and_then(|mut slot| slot.take()) {
+                Some(Ok(dictionary)) => *entry = Loading::Ready(Arc::new(dictionary)),
+                Some(Err(err)) => *entry = Loading::Failed(err),
+                None => {}
+            }
+        }
+        match entry {
+            Loading::Ready(dictionary) => Some(Arc::clone(dictionary)),
+            _ => None,
+        }
+    }
+
+    fn is_known(&self, dictionary: &Dictionary, word: &str) -> bool {
+        let word = word.replace('’', "'");
+        let lower = word.to_lowercase();
+        let mut chars = word.chars();
+        let capitalized =
+            chars.next().is_some_and(char::is_uppercase) && chars.all(|c| !c.is_uppercase());
+        dictionary.contains(&word)
+            || (capitalized && dictionary.contains(&lower))
+            || self.custom.contains(&word)
+            || self.custom.contains(&lower)
+            || self.ignored.contains(&word)
+    }
+
+    /// Dictionary words one or two edits away from `word`, closest first,
+    /// capitalized like `word`.
+    pub fn suggest(&self, word: &str, language: &Language) -> Vec<String> {
+        let Some(Loading::Ready(dictionary)) = self.dictionaries.get(&language.tag) else {
+            return Vec::new();
+        };
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        let lower = word.to_lowercase().replace('’', "'");
+        let known = |candidate: &String| dictionary.contains(candidate);
+
+        let mut seen = HashSet::new();
+        let mut suggestions: Vec<String> = Vec::new();
+        let first = edits(&lower, &dictionary.try_chars);
+        for candidate in first.iter().filter(|candidate| known(candidate)) {
+            if seen.insert(candidate.clone()) {
+                suggestions.push(candidate.clone());
+            }
+        }
+        // Two words run together.
+        let chars: Vec<char> = lower.chars().collect();
+        for split in 2..chars.len().saturating_sub(1) {
+            let (left, right): (String, String) = (
+                chars[..split].iter().collect(),
+                chars[split..].iter().collect(),
+            );
+            if known(&left) && known(&right) {
+                suggestions.push(format!("{} {}", left, right));
+            }
+        }
+        if suggestions.len() < MAX_SUGGESTIONS && chars.len() <= 12 {
+            // Case was already folded, which keeps the second round small.
+            let letters: Vec<char> = dictionary
+                .try_chars
+                .iter()
+                .copied()
+                .filter(|c| !c.is_uppercase())
+                .collect();
+            'second: for edit in &first {
+                for candidate in edits(edit, &letters) {
+                    if known(&candidate) && seen.insert(candidate.clone()) {
+                        suggestions.push(candidate);
+                        if suggestions.len() >= MAX_SUGGESTIONS {
+                            break 'second;
+                        }
+                    }
+                }
+            }
+        }
+        suggestions.truncate(MAX_SUGGESTIONS);
+        if capitalized {
+            for suggestion in &mut suggestions {
+                let mut chars = suggestion.chars();
+                if let Some(first) = chars.next() {
+                    *suggestion = first.to_uppercase().chain(chars).collect();
+                }
+            }
+        }
+        suggestions
+    }
+
+    /// Accepts `word` for the rest of the session.
+    pub fn ignore(&mut self, word: &str) {
+        self.ignored.insert(word.to_string());
+    }
+
+    /// Adds `word` to the project word list file.
+    pub fn add_word(&mut self, word: &str) -> Result<(), String> {
+        let path = self.word_list.clone().ok_or("No word list configured.")?;
+        // Start on a new line if the file does not end with one.
+        let separator = match fs::read(&path) {
+            Ok(bytes) if bytes.last().is_some_and(|&byte| byte != b'\n') => "\n",
+            _ => "",
+        };
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| writeln!(file, "{}{}", separator, word))
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        self.custom.insert(word.to_string());
+        Ok(())
+    }
+}
+
+/// Words one deletion, transposition, replacement or insertion away.
+fn edits(word: &str, letters: &[char]) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut edits = Vec::new();
+    for index in 0..=chars.len() {
+        let (left, right) = chars.split_at(index);
+        if !right.is_empty() {
+            edits.push(left.iter().chain(&right[1..]).collect());
+        }
+        if right.len() > 1 {
+            edits.push(
+                left.iter()
+                    .chain([&right[1], &right[0]])
+                    .chain(&right[2..])
+                    .collect(),
+            );
+        }
+        for letter in letters {
+            if !right.is_empty() && right[0] != *letter {
+                edits.push(left.iter().chain([letter]).chain(&right[1..]).collect());
+            }
+            edits.push(left.iter().chain([letter]).chain(right).collect());
+        }
+    }
+    edits
+}
+
+/// The prose words of a markdown document with their char ranges. Front
+/// matter, code, HTML tags, link destinations, URLs, e-mail addresses,
+/// acronyms, mixed-case and single-letter words and words with digits or
+/// underscores are left out.
+pub fn words(text: &str) -> Vec<(Range<usize>, String)> {
+    let body = front_matter::body_start(text);
+    let mut offset = text[..body].chars().count();
+    let mut words = Vec::new();
+    let mut in_fence = false;
+    for line in text[body..].split_inclusive('\n') {
+        if export::is_fence(line) {
+            in_fence = !in_fence;
+        } else if !in_fence && !line.starts_with("    ") && !line.starts_with('\t') {
+            line_words(line, offset, &mut words);
+        }
+        offset += line.chars().count();
+    }
+    words
+}
+
+fn line_words(line: &str, offset: usize, words: &mut Vec<(Range<usize>, String)>) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let prev = index.checked_sub(1).map(|i| chars[i]);
+        if c == '`' {
+            // Skip the code span up to the closing run of the same length.
+            let run = chars[index..].iter().take_while(|&&c| c == '`').count();
+            let mut end = index + run;
+            while end < chars.len() {
+                let close = chars[end..].iter().take_while(|&&c| c == '`').count();
+                if close == run {
+                    break;
+                }
+                end += close.max(1);
+            }
+            index = (end + run).min(chars.len());
+        } else if c == '<'
+            && chars
+                .get(index + 1)
+                .is_some_and(|n| n.is_ascii_alphabetic() || *n == '/' || *n == '!')
+        {
+            index += chars[index..].iter().take_while(|&&c| c != '>').count();
+        } else if c == '(' && prev == Some(']') {
+            index += chars[index..].iter().take_while(|&&c| c != ')').count();
+        } else if c.is_alphanumeric()
+            && prev.is_none_or(char::is_whitespace)
+            && is_address(&chars[index..])
+        {
+            index += chars[index..]
+                .iter()
+                .take_while(|c| !c.is_whitespace())
+                .count();
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = index;
+            while index < chars.len() {
+                let c = chars[index];
+                let inner_apostrophe = (c == '\'' || c == '’')
+                    && chars.get(index + 1).is_some_and(|n| n.is_alphabetic());
+                if !(c.is_alphanumeric() || c == '_' || inner_apostrophe) {
+                    break;
+                }
+                index += 1;
+            }
+            let word: String = chars[start..index].iter().collect();
+            if is_checked(&word) {
+                words.push((offset + start..offset + index, word));
+            }
+        } else {
+            index += 1;
+        }
+    }
+}
+
+/// A URL, `www.` host or e-mail address starting at the beginning of
+/// `chars`.
+fn is_address(chars: &[char]) -> bool {
+    let token: String = chars.iter().take_while(|c| !c.is_whitespace()).collect();
+    token.contains("://") || token.starts_with("www.") || token.contains('@')
+}
+
+fn is_checked(word: &str) -> bool {
+    let mut chars = word.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    let rest: Vec<char> = chars.collect();
+    !rest.is_empty()
+        && first.is_alphabetic()
+        && !word.chars().any(|c| c.is_numeric() || c == '_')
+        && !rest.iter().any(|c| c.is_uppercase())
+}
+
+/// The misspelling at char `index`, including a cursor right after it.
+pub fn at(misspellings: &[Misspelling], index: usize) -> Option<&Misspelling> {
+    misspellings
+        .iter()
+        .find(|misspelling| misspelling.range.start <= index && index <= misspelling.range.end)
+}
@@ -0,0 +1,22 @@
+use egui::{CursorIcon, Id, Sense, Stroke};
+
+/// Width reserved for each draggable boundary between panes.
+pub const HANDLE_WIDTH: f32 = 6.0;
+
+/// Draws a vertical drag handle filling the current cell and returns the
+/// response so the caller can apply `drag_delta` to its ratios.
+pub fn vertical_handle(ui: &mut egui::Ui, id: Id) -> egui::Response {
+    let rect = ui.max_rect();
+    let response = ui
+        .interact(rect, id, Sense::drag())
+        .on_hover_cursor(CursorIcon::ResizeHorizontal);
+
+    let visuals = ui.visuals();
+    let stroke = if response.dragged() || response.hovered() {
+        visuals.widgets.active.fg_stroke
+    } else {
+        Stroke::new(1.0, visuals.widgets.noninteractive.bg_stroke.color)
+    };
+    ui.painter().vline(rect.center().x, rect.y_range(), stroke);
+    response
+}
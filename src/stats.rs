@@ -0,0 +1,52 @@
+use crate::graphemes;
+use crate::language::Language;
+
+/// Counts shown in the status bar.
+#[derive(Clone, Copy)]
+pub struct DocumentStats {
+    /// Grapheme clusters, not bytes.
+    pub chars: usize,
+    pub words: usize,
+    pub lines: usize,
+    /// Letters and ideographs, for languages written without spaces.
+    pub letters: usize,
+}
+
+impl DocumentStats {
+    pub fn of(text: &str) -> Self {
+        Self {
+            chars: graphemes::count(text),
+            words: text.split_whitespace().map(count_words).sum(),
+            lines: text.lines().count().max(1),
+            letters: text.chars().filter(|c| c.is_alphanumeric()).count(),
+        }
+    }
+
+    pub fn reading_minutes(&self, language: &Language) -> usize {
+        let units = if language.counts_characters() {
+            self.letters
+        } else {
+            self.words
+        };
+        units.div_ceil(language.reading_speed())
+    }
+}
+
+/// Words in a whitespace-separated token. Chinese and Japanese characters
+/// count as one word each, like common word processors do, and any other
+/// text between them counts once. Bare markup such as `#`, `-` or `|` is
+/// skipped so only prose counts.
+fn count_words(token: &str) -> usize {
+    let mut words = 0;
+    let mut in_word = false;
+    for c in token.chars() {
+        if graphemes::is_cjk(c) {
+            words += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() && !in_word {
+            words += 1;
+            in_word = true;
+        }
+    }
+    words
+}
@@ -0,0 +1,43 @@
+use crate::front_matter;
+
+/// The front matter entry a document's lifecycle state is kept in.
+pub const KEY: &str = "status";
+
+/// The `status` of `markdown`, if it has one.
+pub fn get(markdown: &str) -> Option<String> {
+    front_matter::get(markdown, KEY)
+        .map(|status| status.trim().to_string())
+        .filter(|status| !status.is_empty())
+}
+
+/// The state after `current` in `states`: the first one for a document
+/// without a status, and none after the last or for a status that is not
+/// one of `states`.
+pub fn next<'a>(states: &'a [String], current: Option<&str>) -> Option<&'a str> {
+    let at = match current {
+        None => return states.first().map(String::as_str),
+        Some(current) => states
+            .iter()
+            .position(|state| state.eq_ignore_ascii_case(current))?,
+    };
+    states.get(at + 1).map(String::as_str)
+}
+
+/// `markdown` moved on to the state after its current one, with the
+/// change as `from → to`, or why it cannot move on.
+pub fn promote(markdown: &str, states: &[String]) -> Result<(String, String), String> {
+    let current = get(markdown);
+    match next(states, current.as_deref()) {
+        Some(next) => {
+            let change = format!("{} → {}", current.as_deref().unwrap_or("none"), next);
+            Ok((front_matter::set(markdown, KEY, next), change))
+        }
+        None => match current {
+            Some(current) if states.iter().any(|s| s.eq_ignore_ascii_case(&current)) => {
+                Err(format!("already {}", current))
+            }
+            Some(current) => Err(format!("'{}' is not a configured status", current)),
+            None => Err("no statuses are configured".to_string()),
+        },
+    }
+}
@@ -0,0 +1,237 @@
+use crate::assistant::Backend;
+use crate::{export, front_matter};
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The heading the summary goes under.
+pub const HEADING: &str = "## Summary";
+
+const PROMPT: &str = "Summarize the Markdown document below as three to seven short bullet \
+                      points starting with \"- \", in the document's language. Reply with the \
+                      bullet points only.";
+
+/// Common words that say nothing about what a sentence is about.
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "also", "because", "been", "before", "being", "both", "but", "can", "could",
+    "does", "each", "from", "have", "here", "into", "just", "like", "more", "most", "much", "must",
+    "only", "other", "over", "same", "should", "some", "such", "than", "that", "their", "them",
+    "then", "there", "these", "they", "this", "those", "through", "very", "want", "were", "what",
+    "when", "where", "which", "while", "will", "with", "would", "your",
+];
+
+/// A bullet summary of the document, written by the assistant backend on
+/// a background thread or picked from the document's own sentences.
+pub struct Summary {
+    /// How the summary was made, for the window.
+    pub method: &'static str,
+    pending: Arc<Mutex<Option<Result<String, String>>>>,
+    pub result: Option<Result<String, String>>,
+}
+
+impl Summary {
+    /// Summarizes `markdown` with `backend`, or extractively without one.
+    pub fn start(backend: Option<Backend>, markdown: &str, ctx: egui::Context) -> Self {
+        let Some(backend) = backend else {
+            return Self {
+                method: "Key sentences from the document",
+                pending: Arc::default(),
+                result: Some(extractive(markdown)),
+            };
+        };
+        let pending = Arc::new(Mutex::new(None));
+        {
+            let pending = Arc::clone(&pending);
+            let text = markdown[front_matter::body_start(markdown)..].to_string();
+            thread::spawn(move || {
+                let result = backend.complete(PROMPT, &text);
+                if let Ok(mut slot) = pending.lock() {
+                    *slot = Some(result);
+                }
+                ctx.request_repaint();
+            });
+        }
+        Self {
+            method: "Written by the assistant",
+            pending,
+            result: None,
+        }
+    }
+
+    /// Picks up the summary once the backend has answered.
+    pub fn poll(&mut self) {
+        if self.result.is_none() {
+            self.result = self.pending.lock().ok().and_then(|mut slot| slot.take());
+        }
+    }
+}
+
+/// The sentences of the document's paragraphs and list items, in order.
+fn sentences(markdown: &str) -> Vec<String> {
+    let body = &markdown[front_matter::body_start(markdown)..];
+    let mut sentences = Vec::new();
+    let mut block = String::new();
+    let mut skip = 0;
+    for event in Parser::new_ext(body, export::markdown_options()) {
+        match event {
+            Event::Start(Tag::Heading { .. } | Tag::CodeBlock(_) | Tag::Table(_)) => skip += 1,
+            Event::End(TagEnd::Heading(_) | TagEnd::CodeBlock | TagEnd::Table) => skip -= 1,
+            Event::Text(text) | Event::Code(text) if skip == 0 => block.push_str(&text),
+            Event::SoftBreak | Event::HardBreak if skip == 0 => block.push(' '),
+            Event::End(TagEnd::Paragraph | TagEnd::Item) => {
+                sentences.extend(split_sentences(&block));
+                block.clear();
+            }
+            _ => {}
+        }
+    }
+    sentences
+}
+
+/// Splits `text` after `.`, `!` or `?` followed by a space.
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let ends = matches!(c, '.' | '!' | '?')
+            && chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+        if ends {
+            let end = index + c.len_utf8();
+            let sentence = text[start..end].trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence.to_string());
+            }
+            start = end;
+        }
+    }
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest.to_string());
+    }
+    sentences
+}
+
+fn content_words(sentence: &str) -> impl Iterator<Item = String> + '_ {
+    sentence
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 3)
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+}
+
+/// The document's most representative sentences as bullets: those whose
+/// words occur most often across the document, in their original order.
+pub fn extractive(markdown: &str) -> Result<String, String> {
+    let sentences: Vec<String> = sentences(markdown)
+        .into_iter()
+        .filter(|sentence| sentence.split_whitespace().count() >= 5)
+        .collect();
+    if sentences.is_empty() {
+        return Err("The document has no sentences to summarize.".to_string());
+    }
+    let mut frequency: HashMap<String, usize> = HashMap::new();
+    for sentence in &sentences {
+        for word in content_words(sentence) {
+            *frequency.entry(word).or_default() += 1;
+        }
+    }
+    let mut scored: Vec<(usize, f64)> = sentences
+        .iter()
+        .enumerate()
+        .map(|(index, sentence)| {
+            let words: Vec<String> = content_words(sentence).collect();
+            let total: usize = words.iter().map(|word| frequency[word]).sum();
+            (index, total as f64 / (words.len().max(1) as f64).sqrt())
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let count = (sentences.len() / 5).clamp(3, 7);
+    let mut picked: Vec<usize> = scored.iter().take(count).map(|(index, _)| *index).collect();
+    picked.sort_unstable();
+    Ok(picked
+        .into_iter()
+        .map(|index| format!("- {}", sentences[index]))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// `markdown` with `summary` under the summary heading: replacing an
+/// earlier summary (the heading and the list right after it, or the heading
+/// alone), or else after the title (or at the top). A summary heading with
+/// anything else under it was written by hand and is kept.
+pub fn insert(markdown: &str, summary: &str) -> String {
+    let section = format!("{}\n\n{}\n", HEADING, summary.trim());
+    let body_start = front_matter::body_start(markdown);
+    let replace = |start: usize, end: usize| {
+        let rest = markdown[end..].trim_start_matches('\n');
+        let gap = if rest.is_empty() { "" } else { "\n" };
+        format!("{}{}{}{}", &markdown[..start], section, gap, rest)
+    };
+    // Start of the summary heading being read.
+    let mut heading = None;
+    // Start of an earlier summary heading, and whether its list has begun.
+    let mut existing: Option<(usize, bool)> = None;
+    let mut title_end = None;
+    let mut lists = 0;
+    let parser = Parser::new_ext(&markdown[body_start..], export::markdown_options());
+    for (event, range) in parser.into_offset_iter() {
+        let range = body_start + range.start..body_start + range.end;
+        match event {
+            Event::Start(Tag::List(_)) => {
+                if let Some((start, false)) = existing {
+                    existing = Some((start, true));
+                }
+                lists += 1;
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists -= 1;
+                if let (Some((start, true)), 0) = (existing, lists) {
+                    return replace(start, range.end);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(start) = heading.take() {
+                    existing = Some((start, false));
+                }
+            }
+            Event::Start(Tag::Heading { level, .. }) => {
+                if let Some((start, false)) = existing {
+                    return replace(start, range.start);
+                } else if level == HeadingLevel::H2 && markdown[range.clone()].trim() == HEADING {
+                    heading = Some(range.start);
+                } else if level == HeadingLevel::H1 && title_end.is_none() {
+                    // The end of the heading's last line.
+                    let last = range.end - 1;
+                    title_end = Some(
+                        markdown[last..]
+                            .find('\n')
+                            .map_or(markdown.len(), |at| last + at + 1),
+                    );
+                }
+            }
+            // Anything but a list under the heading: a hand-written section.
+            _ if heading.is_none() && matches!(existing, Some((_, false))) => existing = None,
+            _ => {}
+        }
+    }
+    if let Some((start, false)) = existing {
+        return replace(start, markdown.len());
+    }
+    match title_end {
+        Some(end) => {
+            let before = markdown[..end].trim_end_matches('\n');
+            format!(
+                "{}\n\n{}\n{}",
+                before,
+                section,
+                markdown[end..].trim_start_matches('\n')
+            )
+        }
+        None => {
+            let rest = markdown[body_start..].trim_start_matches('\n');
+            format!("{}{}\n{}", &markdown[..body_start], section, rest)
+        }
+    }
+}
@@ -0,0 +1,111 @@
+/// Lines where diagnostics are silenced by `md-echo-disable` comments.
+///
+/// Supported forms, each optionally followed by the diagnostic sources they
+/// apply to (for example `<!-- md-echo-disable spell -->`):
+///
+/// - `<!-- md-echo-disable -->` ... `<!-- md-echo-enable -->`
+/// - `<!-- md-echo-disable-line -->` at the end of a line
+/// - `<!-- md-echo-disable-next-line -->` on the line before
+#[derive(Default)]
+pub struct Suppressions {
+    /// Per line: `None` when nothing is suppressed, `Some(empty)` when every
+    /// source is suppressed, otherwise the suppressed source names.
+    lines: Vec<Option<Vec<String>>>,
+}
+
+enum Directive {
+    Disable(Vec<String>),
+    Enable(Vec<String>),
+    DisableLine(Vec<String>),
+    DisableNextLine(Vec<String>),
+}
+
+impl Suppressions {
+    pub fn parse(text: &str) -> Self {
+        let line_count = text.lines().count();
+        let mut lines: Vec<Option<Vec<String>>> = vec![None; line_count];
+        let mut active: Option<Vec<String>> = None;
+        let mut next_line: Option<Vec<String>> = None;
+
+        for (index, line) in text.lines().enumerate() {
+            if let Some(sources) = next_line.take() {
+                merge(&mut lines[index], &sources);
+            }
+            if let Some(sources) = &active {
+                merge(&mut lines[index], sources);
+            }
+
+            for directive in directives(line) {
+                match directive {
+                    Directive::Disable(sources) => {
+                        merge(&mut lines[index], &sources);
+                        active = Some(sources);
+                    }
+                    Directive::Enable(sources) => {
+                        active = match (active.take(), sources.is_empty()) {
+                            (_, true) | (None, _) => None,
+                            (Some(current), false) => {
+                                let remaining: Vec<String> = current
+                                    .into_iter()
+                                    .filter(|source| !sources.contains(source))
+                                    .collect();
+                                (!remaining.is_empty()).then_some(remaining)
+                            }
+                        };
+                    }
+                    Directive::DisableLine(sources) => merge(&mut lines[index], &sources),
+                    Directive::DisableNextLine(sources) => next_line = Some(sources),
+                }
+            }
+        }
+
+        Self { lines }
+    }
+
+    pub fn is_suppressed(&self, line: usize, source: &str) -> bool {
+        match self.lines.get(line) {
+            Some(Some(sources)) => sources.is_empty() || sources.iter().any(|s| s == source),
+            _ => false,
+        }
+    }
+}
+
+fn merge(slot: &mut Option<Vec<String>>, sources: &[String]) {
+    match slot {
+        Some(existing) if existing.is_empty() => {}
+        Some(_) if sources.is_empty() => *slot = Some(Vec::new()),
+        Some(existing) => existing.extend(sources.iter().cloned()),
+        None => *slot = Some(sources.to_vec()),
+    }
+}
+
+fn directives(line: &str) -> Vec<Directive> {
+    let mut found = Vec::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("<!--") {
+        let after = &rest[start + 4..];
+        let Some(end) = after.find("-->") else {
+            break;
+        };
+        let body = after[..end].trim();
+        rest = &after[end + 3..];
+
+        let mut words = body.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let sources: Vec<String> = words
+            .flat_map(|word| word.split(','))
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect();
+        match keyword {
+            "md-echo-disable" => found.push(Directive::Disable(sources)),
+            "md-echo-enable" => found.push(Directive::Enable(sources)),
+            "md-echo-disable-line" => found.push(Directive::DisableLine(sources)),
+            "md-echo-disable-next-line" => found.push(Directive::DisableNextLine(sources)),
+            _ => {}
+        }
+    }
+    found
+}
@@ -0,0 +1,370 @@
+use crate::graphemes;
+use std::ops::Range;
+
+/// Commands of the Tools → Table menu.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TableAction {
+    Insert,
+    Format,
+    AddRowBelow,
+    DeleteRow,
+    AddColumnRight,
+    DeleteColumn,
+}
+
+impl TableAction {
+    pub const ALL: [TableAction; 6] = [
+        TableAction::Insert,
+        TableAction::Format,
+        TableAction::AddRowBelow,
+        TableAction::DeleteRow,
+        TableAction::AddColumnRight,
+        TableAction::DeleteColumn,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TableAction::Insert => "Insert Table",
+            TableAction::Format => "Format Table",
+            TableAction::AddRowBelow => "Add Row Below",
+            TableAction::DeleteRow => "Delete Row",
+            TableAction::AddColumnRight => "Add Column Right",
+            TableAction::DeleteColumn => "Delete Column",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// A pipe table: header row, body rows and column alignments, with the
+/// source lines it was read from.
+struct Table {
+    lines: Range<usize>,
+    indent: String,
+    rows: Vec<Vec<String>>,
+    alignments: Vec<Alignment>,
+}
+
+/// The text after a table command, with the char index for the cursor.
+pub struct Edit {
+    pub text: String,
+    pub cursor: usize,
+}
+
+/// Applies `action` at char `cursor` of `text`.
+pub fn apply(text: &str, cursor: usize, action: TableAction) -> Result<Edit, &'static str> {
+    let byte = graphemes::char_to_byte(text, cursor);
+    let line = text[..byte].matches('\n').count();
+    let line_start = text[..byte].rfind('\n').map_or(0, |newline| newline + 1);
+    let column = text[line_start..byte].chars().count();
+    let lines: Vec<&str> = text.split('\n').collect();
+
+    if action == TableAction::Insert {
+        let line_end = text[byte..]
+            .find('\n')
+            .map_or(text.len(), |newline| byte + newline);
+        return Ok(insert_skeleton(text, line_end, line_start));
+    }
+
+    let mut table = Table::at_line(&lines, line).ok_or("The cursor is not in a table.")?;
+    // Row 0 is the header, the delimiter row is not stored.
+    let row = match line - table.lines.start {
+        0 | 1 => 0,
+        row => row - 1,
+    };
+    let cell = cell_at(lines[line], column).min(table.columns() - 1);
+    let (row, cell) = match action {
+        TableAction::Insert | TableAction::Format => (row, cell),
+        TableAction::AddRowBelow => {
+            table
+                .rows
+                .insert(row + 1, vec![String::new(); table.columns()]);
+            (row + 1, cell)
+        }
+        TableAction::DeleteRow => {
+            if row == 0 {
+                return Err("The header row cannot be deleted.");
+            }
+            table.rows.remove(row);
+            (row.min(table.rows.len() - 1), cell)
+        }
+        TableAction::AddColumnRight => {
+            for cells in &mut table.rows {
+                cells.insert(cell + 1, String::new());
+            }
+            table.alignments.insert(cell + 1, Alignment::None);
+            (row, cell + 1)
+        }
+        TableAction::DeleteColumn => {
+            if table.columns() < 2 {
+                return Err("A table needs at least one column.");
+            }
+            for cells in &mut table.rows {
+                cells.remove(cell);
+            }
+            table.alignments.remove(cell);
+            (row, cell.min(table.columns() - 1))
+        }
+    };
+
+    let (mut rendered, cell_starts) = table.render();
+    if lines[table.lines.start].ends_with('\r') {
+        for line in &mut rendered {
+            line.push('\r');
+        }
+    }
+    let mut out: Vec<String> = lines[..table.lines.start]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+    let first_line = out.len();
+    out.extend(rendered);
+    out.extend(lines[table.lines.end..].iter().map(|line| line.to_string()));
+
+    // The delimiter row sits between the header and the body.
+    let cursor_line = first_line + if row == 0 { 0 } else { row + 1 };
+    let cursor = out[..cursor_line]
+        .iter()
+        .map(|line| line.chars().count() + 1)
+        .sum::<usize>()
+        + cell_starts[cell];
+    Ok(Edit {
+        text: out.join("\n"),
+        cursor,
+    })
+}
+
+/// A three-column table on its own lines after the line ending at `byte`,
+/// with the cursor in the first header cell.
+fn insert_skeleton(text: &str, byte: usize, line_start: usize) -> Edit {
+    let table = Table {
+        lines: 0..0,
+        indent: String::new(),
+        rows: vec![
+            vec!["Column 1".into(), "Column 2".into(), "Column 3".into()],
+            vec![String::new(); 3],
+        ],
+        alignments: vec![Alignment::None; 3],
+    };
+    let (rendered, cell_starts) = table.render();
+    let mut before = text[..byte].to_string();
+    if byte > line_start {
+        before.push('\n');
+    }
+    // Tables need a blank line before them unless they start the document.
+    if !before.is_empty() && !before.ends_with("\n\n") {
+        before.push('\n');
+    }
+    let after = &text[byte..];
+    let separator = if after.is_empty() || after.starts_with("\n\n") {
+        ""
+    } else if after.starts_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    let cursor = before.chars().count() + cell_starts[0];
+    Edit {
+        text: format!("{}{}{}{}", before, rendered.join("\n"), separator, after),
+        cursor,
+    }
+}
+
+impl Table {
+    /// The table whose lines include `line`: a header row, a delimiter row
+    /// and any body rows, without blank lines in between.
+    fn at_line(lines: &[&str], line: usize) -> Option<Self> {
+        let is_row = |index: usize| {
+            lines
+                .get(index)
+                .is_some_and(|line| !line.trim().is_empty() && line.contains('|'))
+        };
+        if !is_row(line) {
+            return None;
+        }
+        let mut start = line;
+        while start > 0 && is_row(start - 1) {
+            start -= 1;
+        }
+        let mut end = line + 1;
+        while is_row(end) {
+            end += 1;
+        }
+        // Rows above the header belong to a paragraph, not the table.
+        let header = (start..end.saturating_sub(1))
+            .filter(|&index| index <= line)
+            .rfind(|&index| parse_delimiter(lines[index + 1]).is_some())?;
+
+        let alignments = parse_delimiter(lines[header + 1])?;
+        let indent: String = lines[header]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .collect();
+        let columns = alignments.len();
+        let rows: Vec<Vec<String>> = std::iter::once(header)
+            .chain(header + 2..end)
+            .map(|index| {
+                let mut cells = split_cells(lines[index]);
+                cells.resize(columns.max(cells.len()), String::new());
+                cells
+            })
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(columns);
+        let mut table = Table {
+            lines: header..end,
+            indent,
+            rows,
+            alignments,
+        };
+        table.alignments.resize(columns, Alignment::None);
+        for cells in &mut table.rows {
+            cells.resize(columns, String::new());
+        }
+        Some(table)
+    }
+
+    fn columns(&self) -> usize {
+        self.alignments.len()
+    }
+
+    /// The aligned table lines, and the char offset of each column's
+    /// content within a row.
+    fn render(&self) -> (Vec<String>, Vec<usize>) {
+        let widths: Vec<usize> = (0..self.columns())
+            .map(|column| {
+                self.rows
+                    .iter()
+                    .map(|cells| graphemes::count(&cells[column]))
+                    .max()
+                    .unwrap_or(0)
+                    .max(3)
+            })
+            .collect();
+
+        let indent = self.indent.chars().count();
+        let cell_starts: Vec<usize> = widths
+            .iter()
+            .scan(indent + 2, |start, width| {
+                let cell = *start;
+                *start += width + 3;
+                Some(cell)
+            })
+            .collect();
+
+        let row = |cells: Vec<String>| format!("{}| {} |", self.indent, cells.join(" | "));
+        let mut lines = Vec::with_capacity(self.rows.len() + 1);
+        for (index, cells) in self.rows.iter().enumerate() {
+            let padded = cells
+                .iter()
+                .zip(&widths)
+                .zip(&self.alignments)
+                .map(|((cell, &width), &alignment)| pad(cell, width, alignment))
+                .collect();
+            lines.push(row(padded));
+            if index == 0 {
+                let delimiters = widths
+                    .iter()
+                    .zip(&self.alignments)
+                    .map(|(&width, &alignment)| delimiter(width, alignment))
+                    .collect();
+                lines.push(row(delimiters));
+            }
+        }
+        (lines, cell_starts)
+    }
+}
+
+fn pad(cell: &str, width: usize, alignment: Alignment) -> String {
+    let space = width - graphemes::count(cell);
+    let (left, right) = match alignment {
+        Alignment::Right => (space, 0),
+        Alignment::Center => (space / 2, space - space / 2),
+        Alignment::None | Alignment::Left => (0, space),
+    };
+    format!("{}{}{}", " ".repeat(left), cell, " ".repeat(right))
+}
+
+fn delimiter(width: usize, alignment: Alignment) -> String {
+    match alignment {
+        Alignment::None => "-".repeat(width),
+        Alignment::Left => format!(":{}", "-".repeat(width - 1)),
+        Alignment::Right => format!("{}:", "-".repeat(width - 1)),
+        Alignment::Center => format!(":{}:", "-".repeat(width - 2)),
+    }
+}
+
+/// Column alignments of a delimiter row such as `| :-- | --: |`.
+fn parse_delimiter(line: &str) -> Option<Vec<Alignment>> {
+    let cells = split_cells(line);
+    let alignments: Option<Vec<Alignment>> = cells
+        .iter()
+        .map(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Alignment::Center,
+                (true, false) => Alignment::Left,
+                (false, true) => Alignment::Right,
+                (false, false) => Alignment::None,
+            })
+        })
+        .collect();
+    alignments.filter(|alignments| !alignments.is_empty())
+}
+
+/// Trimmed cells of a table row. Pipes escaped with `\` or inside code
+/// spans do not separate cells.
+fn split_cells(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut code_ticks = 0;
+    let mut chars = line.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                cell.push(c);
+                if let Some(next) = chars.next() {
+                    cell.push(next);
+                }
+            }
+            '`' => {
+                let mut run = 1;
+                while chars.next_if_eq(&'`').is_some() {
+                    run += 1;
+                }
+                if code_ticks == 0 {
+                    code_ticks = run;
+                } else if run == code_ticks {
+                    code_ticks = 0;
+                }
+                cell.extend(std::iter::repeat_n('`', run));
+            }
+            '|' if code_ticks == 0 => cells.push(std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    let trimmed = line.trim();
+    if trimmed.starts_with('|') {
+        cells.remove(0);
+    }
+    if trimmed.ends_with('|') && !trimmed.ends_with("\\|") && cells.len() > 1 {
+        cells.pop();
+    }
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// Index of the cell containing char `column` of a table row.
+fn cell_at(line: &str, column: usize) -> usize {
+    let before: String = line.chars().take(column).collect();
+    let pipes = split_cells(&format!("{}x", before)).len();
+    pipes.saturating_sub(1)
+}
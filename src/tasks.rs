@@ -0,0 +1,54 @@
+use crate::export;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// A `- [ ]` / `- [x]` task list item.
+pub struct Task {
+    /// Byte offset of the char between the brackets.
+    pub state: usize,
+    pub done: bool,
+}
+
+/// Task list items in `text`, outside fenced code blocks, in order.
+pub fn find(text: &str) -> Vec<Task> {
+    static TASK: OnceLock<Regex> = OnceLock::new();
+    let task = TASK.get_or_init(|| {
+        Regex::new(r"^(?:[ \t]*>)*[ \t]*(?:[-*+]|\d{1,9}[.)])[ \t]+\[([ xX])\]").unwrap()
+    });
+    let mut tasks = Vec::new();
+    let mut in_fence = false;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if export::is_fence(line) {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            if let Some(state) = task.captures(line).and_then(|caps| caps.get(1)) {
+                tasks.push(Task {
+                    state: offset + state.start(),
+                    done: state.as_str() != " ",
+                });
+            }
+        }
+        offset += line.len();
+    }
+    tasks
+}
+
+/// Index of the first task whose state differs between `before` and
+/// `after`, which hold the same tasks.
+pub fn toggled(before: &str, after: &str) -> Option<usize> {
+    find(before)
+        .iter()
+        .zip(find(after))
+        .position(|(old, new)| old.done != new.done)
+}
+
+/// Flips the `index`-th task in `text`. Returns whether it was found.
+pub fn toggle(text: &mut String, index: usize) -> bool {
+    let Some(task) = find(text).into_iter().nth(index) else {
+        return false;
+    };
+    let mark = if task.done { " " } else { "x" };
+    text.replace_range(task.state..task.state + 1, mark);
+    true
+}
@@ -0,0 +1,144 @@
+use crate::dates;
+use crate::export;
+use crate::git;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use walkdir::WalkDir;
+
+/// Values for `{{name}}` placeholders in document templates, snippets and
+/// export templates, looked up for a folder: `{{date}}`, `{{time}}` (UTC
+/// `HH:MM`), `{{year}}`, `{{user}}` (login name), `{{hostname}}`,
+/// `{{git.user}}`, `{{git.email}}`, `{{git.branch}}` and `{{git.remote}}`
+/// (the web address of `origin`). Values that cannot be found are left
+/// out, so their placeholders stay in the text.
+pub fn variables(dir: &Path) -> BTreeMap<String, String> {
+    let mut variables = BTreeMap::new();
+    let today = dates::today();
+    variables.insert("year".to_string(), today.chars().take(4).collect());
+    variables.insert("date".to_string(), today);
+    variables.insert("time".to_string(), dates::time_of_day());
+
+    let login = env::var("USER").or_else(|_| env::var("USERNAME"));
+    variables.extend(login.ok().map(|user| ("user".to_string(), user)));
+    variables.extend(hostname().map(|host| ("hostname".to_string(), host)));
+
+    let git_values = [
+        ("git.user", git::config(dir, "user.name")),
+        ("git.email", git::config(dir, "user.email")),
+        ("git.branch", git::branch(dir)),
+        ("git.remote", git::remote_url(dir)),
+    ];
+    for (name, value) in git_values {
+        variables.extend(value.map(|value| (name.to_string(), value)));
+    }
+    variables
+}
+
+fn hostname() -> Option<String> {
+    let from_env = env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok();
+    let name = from_env
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| {
+            let output = Command::new("hostname").output().ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+        })?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Replaces the `{{name}}` placeholders of `template` found in
+/// `variables`, and `{{env.NAME}}` with the environment variable `NAME`.
+/// Anything else, such as an export template's `{{content}}`, is kept.
+pub fn expand(template: &str, variables: &BTreeMap<String, String>) -> String {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    let placeholder =
+        PLACEHOLDER.get_or_init(|| Regex::new(r"\{\{\s*([A-Za-z0-9_.-]+)\s*\}\}").unwrap());
+    placeholder
+        .replace_all(template, |caps: &regex::Captures| {
+            let name = &caps[1];
+            let value = match name.strip_prefix("env.") {
+                Some(var) => env::var(var).ok(),
+                None => variables.get(name).cloned(),
+            };
+            value.unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// The markdown files in a template or snippet folder, sorted by name.
+pub fn list(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && export::is_markdown(path))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Reads a template or snippet and fills in its placeholders for `dir`.
+pub fn render(path: &Path, dir: &Path) -> Result<String, String> {
+    let template =
+        fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(expand(&template, &variables(dir)))
+}
+
+/// Where a workspace keeps the templates for its folders: `adr.md` in it
+/// is the template for new notes in `adr/`, `docs/api.md` for `docs/api/`.
+pub const WORKSPACE_FOLDER: &str = ".md-echo/templates";
+
+/// The workspace templates under `root`, each with the folder it is for.
+pub fn workspace_templates(root: &Path) -> Vec<(String, PathBuf)> {
+    let dir = root.join(WORKSPACE_FOLDER);
+    let mut templates: Vec<(String, PathBuf)> = WalkDir::new(&dir)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file() && export::is_markdown(entry.path()))
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(&dir).ok()?.with_extension("");
+            let folder = relative.to_string_lossy().replace('\\', "/");
+            Some((folder, entry.into_path()))
+        })
+        .collect();
+    templates.sort();
+    templates
+}
+
+/// The workspace template for a new note in `dir`: the one for the
+/// deepest folder between `dir` and `root` that has one.
+pub fn for_folder(root: &Path, dir: &Path) -> Option<PathBuf> {
+    let relative = dir.strip_prefix(root).ok()?;
+    relative
+        .ancestors()
+        .filter(|folder| !folder.as_os_str().is_empty())
+        .map(|folder| {
+            let mut template = root.join(WORKSPACE_FOLDER).join(folder).into_os_string();
+            template.push(".md");
+            PathBuf::from(template)
+        })
+        .find(|template| template.is_file())
+}
+
+/// Renders the workspace template for the new note at `note` titled
+/// `title`.
+pub fn render_for_note(template: &Path, note: &Path, title: &str) -> Result<String, String> {
+    let text =
+        fs::read_to_string(template).map_err(|err| format!("{}: {}", template.display(), err))?;
+    let dir = note.parent().unwrap_or(Path::new("."));
+    let mut variables = variables(dir);
+    variables.insert("title".to_string(), title.to_string());
+    Ok(expand(&text, &variables))
+}
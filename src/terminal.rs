@@ -0,0 +1,220 @@
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const MAX_SCROLLBACK: usize = 200_000;
+
+pub struct TerminalSession {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    output: Arc<Mutex<String>>,
+}
+
+impl TerminalSession {
+    pub fn spawn(shell: Option<&str>, cwd: &Path, ctx: egui::Context) -> Result<Self, String> {
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 24,
+                cols: 120,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|err| format!("Failed to open pty: {}", err))?;
+
+        let mut cmd = match shell {
+            Some(shell) if !shell.trim().is_empty() => CommandBuilder::new(shell),
+            _ => CommandBuilder::new_default_prog(),
+        };
+        if cwd.is_dir() {
+            cmd.cwd(cwd);
+        }
+        cmd.env("TERM", "dumb");
+
+        let child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|err| format!("Failed to spawn shell: {}", err))?;
+        drop(pair.slave);
+
+        let mut reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|err| format!("Failed to read from pty: {}", err))?;
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|err| format!("Failed to write to pty: {}", err))?;
+
+        let output = Arc::new(Mutex::new(String::new()));
+        let sink = Arc::clone(&output);
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            let mut filter = AnsiFilter::default();
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if let Ok(mut out) = sink.lock() {
+                            filter.push(&buf[..n], &mut out);
+                            if out.len() > MAX_SCROLLBACK {
+                                let mut cut = out.len() - MAX_SCROLLBACK;
+                                while !out.is_char_boundary(cut) {
+                                    cut += 1;
+                                }
+                                out.drain(..cut);
+                            }
+                        }
+                        ctx.request_repaint();
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            master: pair.master,
+            writer,
+            child,
+            output,
+        })
+    }
+
+    pub fn output(&self) -> String {
+        self.output
+            .lock()
+            .map(|out| out.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut out) = self.output.lock() {
+            out.clear();
+        }
+    }
+
+    pub fn send_line(&mut self, line: &str) -> std::io::Result<()> {
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\r")?;
+        self.writer.flush()
+    }
+
+    pub fn send_interrupt(&mut self) -> std::io::Result<()> {
+        self.writer.write_all(&[0x03])?;
+        self.writer.flush()
+    }
+
+    pub fn resize(&self, rows: u16, cols: u16) {
+        let _ = self.master.resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        });
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for TerminalSession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+/// Strips ANSI escape sequences from pty output and applies carriage returns
+/// and backspaces to the line being written, keeping state across reads so
+/// sequences and lines split between chunks still come out right.
+#[derive(Default)]
+struct AnsiFilter {
+    state: AnsiState,
+    pending: Vec<u8>,
+    /// The unfinished last line and the column output goes to next.
+    line: Vec<char>,
+    column: usize,
+    /// Bytes of the unfinished line at the end of the output, replaced on
+    /// the next read.
+    shown: usize,
+}
+
+#[derive(Default, PartialEq)]
+enum AnsiState {
+    #[default]
+    Text,
+    Escape,
+    Csi,
+    Osc,
+}
+
+impl AnsiFilter {
+    /// Adds `bytes` to `out`, rewriting the unfinished line written last time.
+    fn push(&mut self, bytes: &[u8], out: &mut String) {
+        let mut plain = std::mem::take(&mut self.pending);
+        for &byte in bytes {
+            match self.state {
+                AnsiState::Text => match byte {
+                    0x1b => self.state = AnsiState::Escape,
+                    0x07 => {}
+                    _ => plain.push(byte),
+                },
+                AnsiState::Escape => {
+                    self.state = match byte {
+                        b'[' => AnsiState::Csi,
+                        b']' => AnsiState::Osc,
+                        _ => AnsiState::Text,
+                    }
+                }
+                AnsiState::Csi => {
+                    if (0x40..=0x7e).contains(&byte) {
+                        self.state = AnsiState::Text;
+                    }
+                }
+                AnsiState::Osc => {
+                    if byte == 0x07 {
+                        self.state = AnsiState::Text;
+                    } else if byte == 0x1b {
+                        self.state = AnsiState::Escape;
+                    }
+                }
+            }
+        }
+
+        // Hold back an incomplete UTF-8 sequence until the next read.
+        let valid_up_to = match std::str::from_utf8(&plain) {
+            Ok(_) => plain.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(_) => plain.len(),
+        };
+        self.pending = plain.split_off(valid_up_to);
+
+        let keep = out.len().saturating_sub(self.shown);
+        if out.is_char_boundary(keep) {
+            out.truncate(keep);
+        }
+        for c in String::from_utf8_lossy(&plain).chars() {
+            match c {
+                '\n' => {
+                    out.extend(self.line.drain(..));
+                    out.push('\n');
+                    self.column = 0;
+                }
+                '\r' => self.column = 0,
+                '\u{8}' => self.column = self.column.saturating_sub(1),
+                _ if self.column < self.line.len() => {
+                    self.line[self.column] = c;
+                    self.column += 1;
+                }
+                _ => {
+                    self.line.push(c);
+                    self.column += 1;
+                }
+            }
+        }
+        let start = out.len();
+        out.extend(self.line.iter());
+        self.shown = out.len() - start;
+    }
+}
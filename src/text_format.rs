@@ -0,0 +1,151 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::fs;
+use std::path::Path;
+
+/// Encodings offered for conversion: menu label, encoding label and
+/// whether to write a byte order mark. UTF-16 always gets one, as it could
+/// not be recognized without.
+pub const ENCODINGS: [(&str, &str, bool); 7] = [
+    ("UTF-8", "UTF-8", false),
+    ("UTF-8 with BOM", "UTF-8", true),
+    ("UTF-16 LE", "UTF-16LE", true),
+    ("UTF-16 BE", "UTF-16BE", true),
+    ("Western (Windows-1252)", "windows-1252", false),
+    ("Western (ISO-8859-15)", "ISO-8859-15", false),
+    ("Japanese (Shift_JIS)", "Shift_JIS", false),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn label(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+}
+
+/// How a document is stored on disk. The editor always holds it as UTF-8
+/// with `\n` line endings.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct TextFormat {
+    pub encoding: &'static Encoding,
+    /// Starts with a byte order mark.
+    pub bom: bool,
+    pub line_ending: LineEnding,
+}
+
+impl Default for TextFormat {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+            line_ending: LineEnding::Lf,
+        }
+    }
+}
+
+impl TextFormat {
+    /// For the status bar, e.g. `UTF-8 · CRLF`.
+    pub fn label(&self) -> String {
+        let bom = if self.bom { " BOM" } else { "" };
+        format!(
+            "{}{} · {}",
+            self.encoding.name(),
+            bom,
+            self.line_ending.label()
+        )
+    }
+}
+
+/// Decodes a file's bytes: by its byte order mark, as UTF-8, or else in the
+/// `fallback` encoding. Line endings are normalized to `\n` and the most
+/// common one is remembered.
+pub fn decode(bytes: &[u8], fallback: &'static Encoding) -> (String, TextFormat) {
+    let (encoding, bom, text) = match Encoding::for_bom(bytes) {
+        Some((encoding, length)) => {
+            let (text, _) = encoding.decode_without_bom_handling(&bytes[length..]);
+            (encoding, true, text.into_owned())
+        }
+        None => match std::str::from_utf8(bytes) {
+            Ok(text) => (UTF_8, false, text.to_string()),
+            Err(_) => {
+                let (text, _) = fallback.decode_without_bom_handling(bytes);
+                (fallback, false, text.into_owned())
+            }
+        },
+    };
+    let crlf = text.matches("\r\n").count();
+    let line_ending = if crlf > 0 && crlf * 2 >= text.matches('\n').count() {
+        LineEnding::Crlf
+    } else {
+        LineEnding::Lf
+    };
+    let format = TextFormat {
+        encoding,
+        bom,
+        line_ending,
+    };
+    (text.replace("\r\n", "\n"), format)
+}
+
+/// `text` as bytes in `format`, or an error naming a character the
+/// encoding cannot hold.
+pub fn encode(text: &str, format: &TextFormat) -> Result<Vec<u8>, String> {
+    let text = match format.line_ending {
+        LineEnding::Lf => text.to_string(),
+        LineEnding::Crlf => text.replace("\r\n", "\n").replace('\n', "\r\n"),
+    };
+    let encoding = format.encoding;
+    let mut bytes = Vec::new();
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        // encoding_rs only decodes UTF-16, so encode it here.
+        let little = encoding == UTF_16LE;
+        let units = format
+            .bom
+            .then_some(0xFEFF)
+            .into_iter()
+            .chain(text.encode_utf16());
+        for unit in units {
+            let pair = if little {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            };
+            bytes.extend_from_slice(&pair);
+        }
+        return Ok(bytes);
+    }
+    if format.bom && encoding == UTF_8 {
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+    let (encoded, _, unmappable) = encoding.encode(&text);
+    if unmappable {
+        let c = text
+            .chars()
+            .find(|c| encoding.encode(&c.to_string()).2)
+            .unwrap_or('?');
+        return Err(format!(
+            "'{}' cannot be saved as {}; convert the document to UTF-8 first",
+            c,
+            encoding.name()
+        ));
+    }
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+}
+
+/// Reads and decodes the file at `path`.
+pub fn read(path: &Path, fallback: &'static Encoding) -> std::io::Result<(String, TextFormat)> {
+    fs::read(path).map(|bytes| decode(&bytes, fallback))
+}
+
+/// The encoding with `label`, or Windows-1252 for unknown labels.
+pub fn encoding_for(label: &str) -> &'static Encoding {
+    Encoding::for_label(label.as_bytes()).unwrap_or(encoding_rs::WINDOWS_1252)
+}
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+
+/// Argument placeholder replaced with the detected lint/format config file.
+pub const CONFIG_PLACEHOLDER: &str = "{config}";
+
+pub fn default_config_files() -> Vec<String> {
+    [
+        ".rumdl.toml",
+        "rumdl.toml",
+        ".markdownlint.json",
+        ".markdownlint.jsonc",
+        ".markdownlint.yaml",
+        ".markdownlint.yml",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect()
+}
+
+/// Looks for one of `names` in `start` and its parents, stopping at `root`
+/// (inclusive). The first name found in the nearest directory wins.
+pub fn find_config(start: &Path, root: &Path, names: &[String]) -> Option<PathBuf> {
+    let mut dir = Some(start);
+    while let Some(current) = dir {
+        if let Some(found) = names
+            .iter()
+            .map(|name| current.join(name))
+            .find(|path| path.is_file())
+        {
+            return Some(found);
+        }
+        if current == root {
+            break;
+        }
+        dir = current.parent().filter(|parent| parent.starts_with(root));
+    }
+    None
+}
+
+/// Substitutes `{config}` in `command`. Without a config file, a bare
+/// `{config}` argument is dropped together with the flag before it (e.g.
+/// `--config {config}`), and arguments like `--config={config}` are dropped.
+pub fn expand_command(command: &[String], config: Option<&Path>) -> Vec<String> {
+    let mut expanded: Vec<String> = Vec::with_capacity(command.len());
+    for arg in command {
+        if !arg.contains(CONFIG_PLACEHOLDER) {
+            expanded.push(arg.clone());
+            continue;
+        }
+        match config {
+            Some(config) => {
+                expanded.push(arg.replace(CONFIG_PLACEHOLDER, &config.display().to_string()))
+            }
+            None if arg == CONFIG_PLACEHOLDER
+                && expanded.len() > 1
+                && expanded.last().is_some_and(|flag| flag.starts_with('-')) =>
+            {
+                expanded.pop();
+            }
+            None => {}
+        }
+    }
+    expanded
+}
+
+pub fn uses_config(command: &[String]) -> bool {
+    command.iter().any(|arg| arg.contains(CONFIG_PLACEHOLDER))
+}
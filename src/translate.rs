@@ -0,0 +1,103 @@
+use std::io::Write;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Stands for the target language in the translation command.
+pub const TARGET_PLACEHOLDER: &str = "{target}";
+/// Stands for the text in the translation command; without it the text is
+/// written to the command's standard input.
+pub const TEXT_PLACEHOLDER: &str = "{text}";
+
+/// A selection being translated by the configured command on a background
+/// thread.
+pub struct Translation {
+    /// Char range of the selection in the document.
+    pub range: Range<usize>,
+    pub source: String,
+    pending: Arc<Mutex<Option<Result<String, String>>>>,
+    pub result: Option<Result<String, String>>,
+}
+
+impl Translation {
+    pub fn start(
+        command: &[String],
+        target: &str,
+        range: Range<usize>,
+        source: String,
+        ctx: egui::Context,
+    ) -> Self {
+        let pending = Arc::new(Mutex::new(None));
+        {
+            let pending = Arc::clone(&pending);
+            let command: Vec<String> = command
+                .iter()
+                .map(|arg| arg.replace(TARGET_PLACEHOLDER, target))
+                .collect();
+            let text = source.clone();
+            thread::spawn(move || {
+                let result = run(&command, &text);
+                if let Ok(mut slot) = pending.lock() {
+                    *slot = Some(result);
+                }
+                ctx.request_repaint();
+            });
+        }
+        Self {
+            range,
+            source,
+            pending,
+            result: None,
+        }
+    }
+
+    /// Picks up the translation once the command has finished.
+    pub fn poll(&mut self) {
+        if self.result.is_none() {
+            self.result = self.pending.lock().ok().and_then(|mut slot| slot.take());
+        }
+    }
+}
+
+fn run(command: &[String], text: &str) -> Result<String, String> {
+    let Some((program, args)) = command.split_first() else {
+        return Err("The translation command is empty.".to_string());
+    };
+    let inline = args.iter().any(|arg| arg.contains(TEXT_PLACEHOLDER));
+    let mut child = Command::new(program)
+        .args(args.iter().map(|arg| arg.replace(TEXT_PLACEHOLDER, text)))
+        .stdin(if inline {
+            Stdio::null()
+        } else {
+            Stdio::piped()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Failed to run '{}': {}", program, err))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|err| format!("Failed to write to '{}': {}", program, err))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Failed to run '{}': {}", program, err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "'{}' failed ({}): {}",
+            program,
+            output.status,
+            stderr.trim()
+        ));
+    }
+    let translated = String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string();
+    if translated.is_empty() {
+        return Err(format!("'{}' returned no text.", program));
+    }
+    Ok(translated)
+}
@@ -0,0 +1,687 @@
+use crate::graphemes::char_to_byte;
+use std::ops::Range;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+    /// Typing a `:` command.
+    Command,
+}
+
+/// A key the emulation handles. Text comes in as `Char`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Escape,
+    Enter,
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// What the editor has to do for a key beyond editing the text.
+pub enum Command {
+    Save,
+    Quit,
+    /// `:q!`, quitting without asking about unsaved changes.
+    ForceQuit,
+    SaveQuit,
+    Undo,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+}
+
+impl Motion {
+    fn from_char(c: char) -> Option<Motion> {
+        Some(match c {
+            'h' => Motion::Left,
+            'l' | ' ' => Motion::Right,
+            'k' => Motion::Up,
+            'j' => Motion::Down,
+            'w' => Motion::WordForward,
+            'b' => Motion::WordBackward,
+            'e' => Motion::WordEnd,
+            '0' => Motion::LineStart,
+            '^' => Motion::FirstNonBlank,
+            '$' => Motion::LineEnd,
+            'G' => Motion::LastLine,
+            _ => return None,
+        })
+    }
+
+    fn from_key(key: Key) -> Option<Motion> {
+        match key {
+            Key::Char(c) => Motion::from_char(c),
+            Key::Left | Key::Backspace => Some(Motion::Left),
+            Key::Right => Some(Motion::Right),
+            Key::Up => Some(Motion::Up),
+            Key::Down | Key::Enter => Some(Motion::Down),
+            Key::Escape => None,
+        }
+    }
+
+    /// Operators on these motions work on whole lines.
+    fn linewise(self) -> bool {
+        matches!(
+            self,
+            Motion::Up | Motion::Down | Motion::FirstLine | Motion::LastLine
+        )
+    }
+
+    /// Operators on these motions include the character moved to.
+    fn inclusive(self) -> bool {
+        matches!(self, Motion::WordEnd | Motion::LineEnd)
+    }
+}
+
+/// The largest count a command takes, so a long run of digits cannot make
+/// one repeat for ever.
+const MAX_COUNT: usize = 10_000;
+
+/// A modal editing layer in the style of Vim. Keys are fed in one at a
+/// time with the editor's text and cursor (a char index), which it edits
+/// in place.
+#[derive(Default)]
+pub struct Vim {
+    pub mode: Mode,
+    /// The `:` command being typed.
+    command_line: String,
+    count: Option<usize>,
+    /// `d`, `c` or `y` waiting for a motion, with the count typed before it.
+    operator: Option<(char, Option<usize>)>,
+    /// `g` or `r` waiting for the next key.
+    prefix: Option<char>,
+    register: String,
+    /// The register holds whole lines.
+    linewise: bool,
+    /// Where a visual selection started.
+    anchor: usize,
+    /// An error from the last `:` command, shown until the next key.
+    message: Option<String>,
+}
+
+impl Vim {
+    /// The mode and pending keys, for the status bar.
+    pub fn status(&self) -> String {
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        let pending: String = self
+            .operator
+            .iter()
+            .flat_map(|(operator, count)| {
+                count
+                    .map(|count| count.to_string())
+                    .into_iter()
+                    .chain([operator.to_string()])
+            })
+            .chain(self.count.map(|count| count.to_string()))
+            .chain(self.prefix.map(String::from))
+            .collect();
+        match self.mode {
+            Mode::Normal if pending.is_empty() => "NORMAL".to_string(),
+            Mode::Normal => format!("NORMAL {}", pending),
+            Mode::Insert => "-- INSERT --".to_string(),
+            Mode::Visual => "-- VISUAL --".to_string(),
+            Mode::VisualLine => "-- VISUAL LINE --".to_string(),
+            Mode::Command => format!(":{}", self.command_line),
+        }
+    }
+
+    /// The char range to show selected for `cursor`: the character under
+    /// it in normal mode, which reads as a block cursor, or the visual
+    /// selection.
+    pub fn selection(&self, text: &str, cursor: usize) -> Range<usize> {
+        let chars: Vec<char> = text.chars().collect();
+        let (low, high) = (self.anchor.min(cursor), self.anchor.max(cursor));
+        match self.mode {
+            Mode::Normal if chars.get(cursor).is_some_and(|&c| c != '\n') => cursor..cursor + 1,
+            Mode::Visual => low..(high + 1).min(chars.len()),
+            Mode::VisualLine => line_start(&chars, low)..line_end(&chars, high),
+            _ => cursor..cursor,
+        }
+    }
+
+    pub fn handle(&mut self, text: &mut String, cursor: &mut usize, key: Key) -> Option<Command> {
+        self.message = None;
+        match self.mode {
+            Mode::Insert => {
+                if key == Key::Escape {
+                    let chars: Vec<char> = text.chars().collect();
+                    self.mode = Mode::Normal;
+                    *cursor = cursor.saturating_sub(1).max(line_start(&chars, *cursor));
+                }
+                None
+            }
+            Mode::Command => self.command_key(text, cursor, key),
+            Mode::Normal => self.normal_key(text, cursor, key),
+            Mode::Visual | Mode::VisualLine => self.visual_key(text, cursor, key),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.count = None;
+        self.operator = None;
+        self.prefix = None;
+    }
+
+    fn command_key(&mut self, text: &str, cursor: &mut usize, key: Key) -> Option<Command> {
+        match key {
+            Key::Char(c) => self.command_line.push(c),
+            Key::Backspace if self.command_line.is_empty() => self.mode = Mode::Normal,
+            Key::Backspace => {
+                self.command_line.pop();
+            }
+            Key::Escape => {
+                self.command_line.clear();
+                self.mode = Mode::Normal;
+            }
+            Key::Enter => {
+                self.mode = Mode::Normal;
+                let command = std::mem::take(&mut self.command_line);
+                match command.trim() {
+                    "" => {}
+                    "w" => return Some(Command::Save),
+                    "q" => return Some(Command::Quit),
+                    "q!" => return Some(Command::ForceQuit),
+                    "wq" | "x" => return Some(Command::SaveQuit),
+                    line => match line.parse::<usize>() {
+                        Ok(line) => {
+                            let chars: Vec<char> = text.chars().collect();
+                            *cursor = motion_target(&chars, *cursor, Motion::FirstLine, Some(line));
+                        }
+                        Err(_) => {
+                            self.message = Some(format!("Not an editor command: {}", command))
+                        }
+                    },
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn normal_key(&mut self, text: &mut String, cursor: &mut usize, key: Key) -> Option<Command> {
+        if key == Key::Escape {
+            self.reset();
+            return None;
+        }
+        let chars: Vec<char> = text.chars().collect();
+        if let Some(prefix) = self.prefix.take() {
+            let count = self.count.take();
+            match (prefix, key) {
+                ('g', Key::Char('g')) => self.motion(text, cursor, Motion::FirstLine, count),
+                ('r', Key::Char(c)) => {
+                    let count = count.unwrap_or(1);
+                    let end = *cursor + count;
+                    if end <= line_end(&chars, *cursor) {
+                        let with = c.to_string().repeat(count);
+                        replace(text, *cursor..end, &with);
+                        *cursor = end - 1;
+                    }
+                }
+                _ => self.reset(),
+            }
+            return None;
+        }
+        let Key::Char(c) = key else {
+            let count = self.count.take();
+            if let Some(motion) = Motion::from_key(key) {
+                self.motion(text, cursor, motion, count);
+            }
+            return None;
+        };
+        if c.is_ascii_digit() && (c != '0' || self.count.is_some()) {
+            self.count = Some(push_digit(self.count, c));
+            return None;
+        }
+        if let Some(motion) = Motion::from_char(c) {
+            let count = self.count.take();
+            self.motion(text, cursor, motion, count);
+            return None;
+        }
+        if matches!(c, 'g' | 'r') {
+            self.prefix = Some(c);
+            return None;
+        }
+        let count = self.count.take();
+        if let Some((operator, operator_count)) = self.operator.take() {
+            // `dd`, `cc` and `yy` act on the count's lines.
+            if c == operator {
+                let lines = (count.unwrap_or(1) * operator_count.unwrap_or(1)).min(MAX_COUNT);
+                let start = line_start(&chars, *cursor);
+                let mut end = start;
+                for _ in 0..lines {
+                    end = (line_end(&chars, end) + 1).min(chars.len());
+                }
+                self.operate(text, cursor, operator, start..end, true);
+            }
+            return None;
+        }
+
+        let repeat = count.unwrap_or(1);
+        let start = line_start(&chars, *cursor);
+        let end = line_end(&chars, *cursor);
+        match c {
+            'd' | 'c' | 'y' => self.operator = Some((c, count)),
+            'i' => self.mode = Mode::Insert,
+            'a' => {
+                self.mode = Mode::Insert;
+                *cursor = (*cursor + 1).min(end);
+            }
+            'I' => {
+                self.mode = Mode::Insert;
+                *cursor = first_non_blank(&chars, *cursor);
+            }
+            'A' => {
+                self.mode = Mode::Insert;
+                *cursor = end;
+            }
+            'o' => {
+                replace(text, end..end, "\n");
+                *cursor = end + 1;
+                self.mode = Mode::Insert;
+            }
+            'O' => {
+                replace(text, start..start, "\n");
+                *cursor = start;
+                self.mode = Mode::Insert;
+            }
+            'x' => {
+                let stop = (*cursor + repeat).min(end);
+                if stop > *cursor {
+                    self.operate(text, cursor, 'd', *cursor..stop, false);
+                }
+            }
+            'X' => {
+                let from = cursor.saturating_sub(repeat).max(start);
+                if from < *cursor {
+                    self.operate(text, cursor, 'd', from..*cursor, false);
+                }
+            }
+            'D' | 'C' => {
+                let operator = if c == 'D' { 'd' } else { 'c' };
+                self.operate(text, cursor, operator, *cursor..end, false);
+            }
+            'J' => {
+                for _ in 0..repeat {
+                    let chars: Vec<char> = text.chars().collect();
+                    let newline = line_end(&chars, *cursor);
+                    if newline >= chars.len() {
+                        break;
+                    }
+                    let next = first_non_blank(&chars, newline + 1);
+                    replace(text, newline..next, " ");
+                    *cursor = newline;
+                }
+            }
+            'p' | 'P' => self.paste(text, cursor, c == 'p', repeat),
+            'u' => return Some(Command::Undo),
+            'v' => {
+                self.mode = Mode::Visual;
+                self.anchor = *cursor;
+            }
+            'V' => {
+                self.mode = Mode::VisualLine;
+                self.anchor = *cursor;
+            }
+            ':' => self.mode = Mode::Command,
+            _ => {}
+        }
+        None
+    }
+
+    fn visual_key(&mut self, text: &mut String, cursor: &mut usize, key: Key) -> Option<Command> {
+        let chars: Vec<char> = text.chars().collect();
+        if let Key::Char(c) = key {
+            if c.is_ascii_digit() && (c != '0' || self.count.is_some()) {
+                self.count = Some(push_digit(self.count, c));
+                return None;
+            }
+            if self.prefix.take() == Some('g') && c == 'g' {
+                let count = self.count.take();
+                *cursor = motion_target(&chars, *cursor, Motion::FirstLine, count);
+                return None;
+            }
+        }
+        let count = self.count.take();
+        if let Some(motion) = Motion::from_key(key) {
+            *cursor = motion_target(&chars, *cursor, motion, count);
+            return None;
+        }
+        let linewise = self.mode == Mode::VisualLine;
+        let range = self.selection(text, *cursor);
+        let range = if linewise {
+            range.start..(range.end + 1).min(chars.len())
+        } else {
+            range
+        };
+        match key {
+            Key::Escape => self.mode = Mode::Normal,
+            Key::Char('g') => self.prefix = Some('g'),
+            Key::Char('o') => std::mem::swap(&mut self.anchor, cursor),
+            Key::Char('v') if !linewise => self.mode = Mode::Normal,
+            Key::Char('V') if linewise => self.mode = Mode::Normal,
+            Key::Char('v') => self.mode = Mode::Visual,
+            Key::Char('V') => self.mode = Mode::VisualLine,
+            Key::Char(operator @ ('d' | 'x' | 'y' | 'c')) => {
+                self.mode = Mode::Normal;
+                let operator = if operator == 'x' { 'd' } else { operator };
+                self.operate(text, cursor, operator, range, linewise);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Moves the cursor, or applies the waiting operator over the motion.
+    fn motion(
+        &mut self,
+        text: &mut String,
+        cursor: &mut usize,
+        motion: Motion,
+        count: Option<usize>,
+    ) {
+        let chars: Vec<char> = text.chars().collect();
+        let Some((operator, operator_count)) = self.operator.take() else {
+            *cursor = clamp_normal(&chars, motion_target(&chars, *cursor, motion, count));
+            return;
+        };
+        let count = match (count, operator_count) {
+            (None, None) => None,
+            (count, operator_count) => {
+                Some((count.unwrap_or(1) * operator_count.unwrap_or(1)).min(MAX_COUNT))
+            }
+        };
+        // `cw` changes to the end of the word, like `ce`.
+        let motion = if operator == 'c' && motion == Motion::WordForward {
+            Motion::WordEnd
+        } else {
+            motion
+        };
+        let target = motion_target(&chars, *cursor, motion, count);
+        let (low, high) = ((*cursor).min(target), (*cursor).max(target));
+        let range = if motion.linewise() {
+            line_start(&chars, low)..(line_end(&chars, high) + 1).min(chars.len())
+        } else if motion.inclusive() {
+            low..(high + 1).min(chars.len())
+        } else if motion == Motion::WordForward {
+            // A word motion stops at the end of the line it started on.
+            low..high.min(line_end(&chars, low).max(low + 1))
+        } else {
+            low..high
+        };
+        if !range.is_empty() {
+            self.operate(text, cursor, operator, range, motion.linewise());
+        }
+    }
+
+    /// Yanks `range` (chars), and deletes it for `d` or `c`.
+    fn operate(
+        &mut self,
+        text: &mut String,
+        cursor: &mut usize,
+        operator: char,
+        range: Range<usize>,
+        linewise: bool,
+    ) {
+        let chars: Vec<char> = text.chars().collect();
+        self.register = chars[range.clone()].iter().collect();
+        self.linewise = linewise;
+        if linewise && !self.register.ends_with('\n') {
+            self.register.push('\n');
+        }
+        match operator {
+            'y' => *cursor = range.start,
+            'c' if linewise => {
+                // The lines are emptied, not removed.
+                let end = if chars.get(range.end.wrapping_sub(1)) == Some(&'\n') {
+                    range.end - 1
+                } else {
+                    range.end
+                };
+                replace(text, range.start..end, "");
+                *cursor = range.start;
+                self.mode = Mode::Insert;
+            }
+            'c' => {
+                replace(text, range.clone(), "");
+                *cursor = range.start;
+                self.mode = Mode::Insert;
+            }
+            _ => {
+                // Deleting the last lines, when the text has no final
+                // newline, also takes the newline before them.
+                let unterminated = chars.get(range.end.wrapping_sub(1)) != Some(&'\n');
+                let range = if linewise && unterminated && range.start > 0 {
+                    range.start - 1..range.end
+                } else {
+                    range
+                };
+                replace(text, range.clone(), "");
+                let chars: Vec<char> = text.chars().collect();
+                let at = range.start.min(chars.len());
+                *cursor = if linewise {
+                    first_non_blank(&chars, at.min(chars.len()))
+                } else {
+                    clamp_normal(&chars, at)
+                };
+            }
+        }
+    }
+
+    fn paste(&mut self, text: &mut String, cursor: &mut usize, after: bool, count: usize) {
+        if self.register.is_empty() {
+            return;
+        }
+        let chars: Vec<char> = text.chars().collect();
+        let pasted = self.register.repeat(count);
+        if self.linewise {
+            let at = if after {
+                line_end(&chars, *cursor) + 1
+            } else {
+                line_start(&chars, *cursor)
+            };
+            if at > chars.len() {
+                // After the last line, which has no newline.
+                let lines = pasted.trim_end_matches('\n');
+                replace(text, chars.len()..chars.len(), &format!("\n{}", lines));
+            } else {
+                replace(text, at..at, &pasted);
+            }
+            let chars: Vec<char> = text.chars().collect();
+            *cursor = first_non_blank(&chars, at.min(chars.len()));
+        } else {
+            let on_char = chars.get(*cursor).is_some_and(|&c| c != '\n');
+            let at = if after && on_char {
+                *cursor + 1
+            } else {
+                *cursor
+            };
+            replace(text, at..at, &pasted);
+            *cursor = at + pasted.chars().count() - 1;
+        }
+    }
+}
+
+/// `count` with the digit `c` typed after it.
+fn push_digit(count: Option<usize>, c: char) -> usize {
+    let digit = c.to_digit(10).unwrap_or_default() as usize;
+    (count.unwrap_or(0) * 10 + digit).min(MAX_COUNT)
+}
+
+fn replace(text: &mut String, range: Range<usize>, with: &str) {
+    let start = char_to_byte(text, range.start);
+    let end = char_to_byte(text, range.end);
+    text.replace_range(start..end, with);
+}
+
+fn line_start(chars: &[char], index: usize) -> usize {
+    let index = index.min(chars.len());
+    chars[..index]
+        .iter()
+        .rposition(|&c| c == '\n')
+        .map_or(0, |newline| newline + 1)
+}
+
+/// The index of the newline ending the line, or the length of the text.
+fn line_end(chars: &[char], index: usize) -> usize {
+    let index = index.min(chars.len());
+    chars[index..]
+        .iter()
+        .position(|&c| c == '\n')
+        .map_or(chars.len(), |offset| index + offset)
+}
+
+fn first_non_blank(chars: &[char], index: usize) -> usize {
+    let start = line_start(chars, index);
+    let end = line_end(chars, index);
+    (start..end)
+        .find(|&index| !chars[index].is_whitespace())
+        .unwrap_or(start)
+}
+
+/// In normal mode the cursor sits on a character, not after the last
+/// one of a line.
+fn clamp_normal(chars: &[char], index: usize) -> usize {
+    let index = index.min(chars.len());
+    let start = line_start(chars, index);
+    if index > start && (index == chars.len() || chars[index] == '\n') {
+        index - 1
+    } else {
+        index
+    }
+}
+
+#[derive(PartialEq)]
+enum Class {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn class(c: char) -> Class {
+    if c.is_whitespace() {
+        Class::Space
+    } else if c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+fn motion_target(chars: &[char], cursor: usize, motion: Motion, count: Option<usize>) -> usize {
+    let repeat = count.unwrap_or(1);
+    let len = chars.len();
+    let mut index = cursor.min(len);
+    match motion {
+        Motion::LineStart => return line_start(chars, index),
+        Motion::FirstNonBlank => return first_non_blank(chars, index),
+        Motion::LineEnd => {
+            let mut end = line_end(chars, index);
+            for _ in 1..repeat {
+                if end < len {
+                    end = line_end(chars, end + 1);
+                }
+            }
+            return end.saturating_sub(1).max(line_start(chars, end));
+        }
+        Motion::FirstLine | Motion::LastLine => {
+            // A final newline ends the last line rather than starting one.
+            let newlines = chars.iter().filter(|&&c| c == '\n').count();
+            let lines = newlines + 1 - usize::from(chars.last() == Some(&'\n'));
+            let line = match (motion, count) {
+                (_, Some(line)) => line.clamp(1, lines) - 1,
+                (Motion::FirstLine, None) => 0,
+                _ => lines - 1,
+            };
+            let start = (0..line).fold(0, |start, _| line_end(chars, start) + 1);
+            return first_non_blank(chars, start);
+        }
+        _ => {}
+    }
+    for _ in 0..repeat {
+        index = match motion {
+            Motion::Left => index.saturating_sub(1).max(line_start(chars, index)),
+            Motion::Right => (index + 1).min(line_end(chars, index)),
+            Motion::Up | Motion::Down => {
+                let start = line_start(chars, index);
+                let column = index - start;
+                let target_start = if motion == Motion::Up {
+                    if start == 0 {
+                        return index;
+                    }
+                    line_start(chars, start - 1)
+                } else {
+                    let end = line_end(chars, index);
+                    if end + 1 >= len {
+                        return index;
+                    }
+                    end + 1
+                };
+                (target_start + column).min(line_end(chars, target_start))
+            }
+            Motion::WordForward => {
+                let mut next = index;
+                if next < len {
+                    let start_class = class(chars[next]);
+                    while next < len && class(chars[next]) == start_class {
+                        next += 1;
+                    }
+                }
+                while next < len && class(chars[next]) == Class::Space {
+                    next += 1;
+                }
+                next
+            }
+            Motion::WordBackward => {
+                let mut previous = index;
+                while previous > 0 && class(chars[previous - 1]) == Class::Space {
+                    previous -= 1;
+                }
+                if previous > 0 {
+                    let word_class = class(chars[previous - 1]);
+                    while previous > 0 && class(chars[previous - 1]) == word_class {
+                        previous -= 1;
+                    }
+                }
+                previous
+            }
+            Motion::WordEnd => {
+                let mut next = (index + 1).min(len);
+                while next < len && class(chars[next]) == Class::Space {
+                    next += 1;
+                }
+                if next < len {
+                    let word_class = class(chars[next]);
+                    while next + 1 < len && class(chars[next + 1]) == word_class {
+                        next += 1;
+                    }
+                }
+                next.min(len.saturating_sub(1))
+            }
+            _ => index,
+        };
+    }
+    index
+}
@@ -0,0 +1,89 @@
+use crate::batch_export::{self, Settings};
+use crate::export;
+use crate::patterns::IgnorePatterns;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Seconds between scans of the watched folder.
+pub const INTERVAL_SECS: f64 = 1.0;
+
+/// Finds the markdown files under a folder that were added or modified
+/// since the last scan, by modification time.
+pub struct Watcher {
+    root: PathBuf,
+    /// Files below this folder, usually the export output, are not watched.
+    ignore: Option<PathBuf>,
+    /// The workspace ignore patterns.
+    patterns: IgnorePatterns,
+    seen: HashMap<PathBuf, SystemTime>,
+}
+
+impl Watcher {
+    pub fn new(root: &Path, ignore: Option<PathBuf>, patterns: IgnorePatterns) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            ignore,
+            patterns,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Files added or modified since the last call. The first call returns
+    /// every file.
+    pub fn changes(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        let mut seen = HashMap::with_capacity(self.seen.len());
+        for path in export::markdown_files_except(&self.root, &self.patterns) {
+            if self
+                .ignore
+                .as_ref()
+                .is_some_and(|ignore| path.starts_with(ignore))
+            {
+                continue;
+            }
+            let Ok(modified) = fs::metadata(&path).and_then(|meta| meta.modified()) else {
+                continue;
+            };
+            if self.seen.get(&path) != Some(&modified) {
+                changed.push(path.clone());
+            }
+            seen.insert(path, modified);
+        }
+        self.seen = seen;
+        changed
+    }
+}
+
+/// The fixed folder at the start of an `[export] output_dir` pattern,
+/// below `root`; exports land somewhere inside it.
+pub fn pattern_root(root: &Path, pattern: &str) -> Option<PathBuf> {
+    let fixed = match pattern.split_once('{') {
+        // Only whole folders before the first placeholder are fixed.
+        Some((before, _)) => before.rsplit_once(['/', '\\']).map_or("", |(dir, _)| dir),
+        None => pattern,
+    };
+    (!fixed.trim().is_empty()).then(|| root.join(fixed))
+}
+
+/// Exports every file under the watched folder, then each file again
+/// whenever it changes, printing what was written. Runs until the process
+/// is stopped.
+pub fn run(mut watcher: Watcher, settings: &Settings) -> ! {
+    println!("Watching {} (Ctrl+C to stop)", watcher.root().display());
+    loop {
+        for source in watcher.changes() {
+            match batch_export::export_file(&source, settings) {
+                Ok(output) => println!("{} -> {}", source.display(), output.display()),
+                Err(err) => eprintln!("{}: {}", source.display(), err),
+            }
+        }
+        thread::sleep(Duration::from_secs_f64(INTERVAL_SECS));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::export;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Wikilinks are handed to the viewer as links with this scheme, so a
+/// click on one can be told apart from a web link.
+pub const SCHEME: &str = "md-echo-wikilink:";
+
+/// A `[[Target#Heading|Label]]` link.
+pub struct WikiLink<'a> {
+    pub target: &'a str,
+    pub heading: Option<&'a str>,
+    pub label: &'a str,
+}
+
+impl<'a> WikiLink<'a> {
+    /// Parses what is between the brackets.
+    pub fn parse(inner: &'a str) -> Option<Self> {
+        let (link, label) = match inner.split_once('|') {
+            Some((link, label)) => (link.trim(), Some(label.trim())),
+            None => (inner.trim(), None),
+        };
+        let (target, heading) = match link.split_once('#') {
+            Some((target, heading)) => (target.trim(), Some(heading.trim())),
+            None => (link, None),
+        };
+        if link.is_empty() || link.contains(['[', ']']) {
+            return None;
+        }
+        Some(Self {
+            target,
+            heading,
+            label: label.filter(|label| !label.is_empty()).unwrap_or(link),
+        })
+    }
+}
+
+/// Rewrites the `[[wikilinks]]` of `markdown` outside code into markdown
+/// links with [`SCHEME`] URLs.
+pub fn render(markdown: &str) -> String {
+    if !markdown.contains("[[") {
+        return markdown.to_string();
+    }
+    export::map_outside_code(markdown, render_links)
+}
+
+fn render_links(text: &str, rendered: &mut String) {
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let Some(end) = rest[start + 2..].find("]]") else {
+            break;
+        };
+        let inner = &rest[start + 2..start + 2 + end];
+        rendered.push_str(&rest[..start]);
+        match WikiLink::parse(inner) {
+            Some(link) => rendered.push_str(&format!(
+                "[{}]({})",
+                link.label.replace(['[', ']'], ""),
+                to_url(inner)
+            )),
+            None => rendered.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &rest[start + end + 4..];
+    }
+    rendered.push_str(rest);
+}
+
+/// The URL a wikilink with the text `inner` (between the brackets) is
+/// rendered with.
+pub fn to_url(inner: &str) -> String {
+    let target = inner.split('|').next().unwrap_or_default().trim();
+    format!("{}{}", SCHEME, encode(target))
+}
+
+/// The `Target#Heading` part of a clicked wikilink URL.
+pub fn from_url(url: &str) -> Option<String> {
+    url.strip_prefix(SCHEME).map(decode)
+}
+
+/// The markdown file under `root` a wikilink target names: a path
+/// relative to `root` (with or without `.md`), or else any of `files`
+/// with that name, the one nearest to `root` first. A target of eight or
+/// more digits is also a Zettlr ID, matching a file name containing it.
+pub fn resolve(root: &Path, files: &[PathBuf], target: &str) -> Option<PathBuf> {
+    let target = target.trim_start_matches('/');
+    let direct = root.join(target);
+    if direct.is_file() && export::is_markdown(&direct) {
+        return Some(direct);
+    }
+    let with_extension = root.join(format!("{}.md", target));
+    if with_extension.is_file() {
+        return Some(with_extension);
+    }
+    let name = Path::new(target)
+        .file_name()?
+        .to_string_lossy()
+        .to_lowercase();
+    let name = name.strip_suffix(".md").unwrap_or(&name).to_string();
+    let nearest = |matches: &dyn Fn(&str) -> bool| {
+        files
+            .iter()
+            .filter(|path| {
+                path.file_stem()
+                    .is_some_and(|stem| matches(&stem.to_string_lossy().to_lowercase()))
+            })
+            .min_by_key(|path| path.components().count())
+            .cloned()
+    };
+    let zettel_id = name.len() >= 8 && name.bytes().all(|b| b.is_ascii_digit());
+    nearest(&|stem| stem == name).or_else(|| {
+        zettel_id
+            .then(|| nearest(&|stem| stem.contains(name.as_str())))
+            .flatten()
+    })
+}
+
+/// Where a missing wikilink target is created: below `root`, with `.md`
+/// added.
+pub fn new_note_path(root: &Path, target: &str) -> PathBuf {
+    let target = target.trim_start_matches('/');
+    if target.to_lowercase().ends_with(".md") {
+        root.join(target)
+    } else {
+        root.join(format!("{}.md", target))
+    }
+}
+
+/// The line of the heading with this text, or this anchor slug, in the
+/// file at `path`.
+pub fn heading_line(path: &Path, heading: &str) -> Option<usize> {
+    let text = fs::read_to_string(path).ok()?;
+    text.lines().position(|line| {
+        export::heading_level(line).is_some_and(|level| {
+            let title = line.trim_start()[level..]
+                .trim()
+                .trim_end_matches('#')
+                .trim();
+            title.eq_ignore_ascii_case(heading) || export::slugify(title) == heading
+        })
+    })
+}
+
+/// Percent-encodes what would end a markdown link destination.
+pub fn encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '%' | ' ' | '(' | ')' | '<' | '>' | '\\' => {
+                encoded.push_str(&format!("%{:02X}", c as u32));
+            }
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = text.get(index + 1..index + 3);
+        match (
+            bytes[index],
+            hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+        ) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
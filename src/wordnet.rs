@@ -0,0 +1,177 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+/// WordNet's database files by part of speech.
+const PARTS: [(&str, &str); 4] = [
+    ("noun", "noun"),
+    ("verb", "verb"),
+    ("adj", "adjective"),
+    ("adv", "adverb"),
+];
+
+/// Endings WordNet's morphology strips to find the base form of inflected
+/// words, by part of speech.
+const DETACHMENTS: [(&str, &[(&str, &str)]); 3] = [
+    (
+        "noun",
+        &[
+            ("s", ""),
+            ("ses", "s"),
+            ("xes", "x"),
+            ("zes", "z"),
+            ("ches", "ch"),
+            ("shes", "sh"),
+            ("men", "man"),
+            ("ies", "y"),
+        ],
+    ),
+    (
+        "verb",
+        &[
+            ("s", ""),
+            ("ies", "y"),
+            ("es", "e"),
+            ("es", ""),
+            ("ed", "e"),
+            ("ed", ""),
+            ("ing", "e"),
+            ("ing", ""),
+        ],
+    ),
+    ("adj", &[("er", ""), ("est", ""), ("er", "e"), ("est", "e")]),
+];
+
+/// One meaning of a word: a WordNet synset.
+pub struct Sense {
+    pub part_of_speech: &'static str,
+    pub gloss: String,
+    /// The other words with this meaning.
+    pub synonyms: Vec<String>,
+}
+
+/// The meanings of `word` in the WordNet database in `dir`, trying its
+/// base form when the word itself is not listed.
+pub fn lookup(dir: &Path, word: &str) -> Result<Vec<Sense>, String> {
+    if !dir.join("index.noun").is_file() {
+        return Err(format!("No WordNet database in {}", dir.display()));
+    }
+    let word = word.trim().to_lowercase();
+    let word = word
+        .strip_suffix("'s")
+        .or_else(|| word.strip_suffix("’s"))
+        .unwrap_or(&word);
+    let lemma = word.replace([' ', '-'], "_");
+    let mut senses = Vec::new();
+    for (file, part_of_speech) in PARTS {
+        let index = dir.join(format!("index.{}", file));
+        for form in forms(file, &lemma) {
+            let offsets = index_offsets(&index, &form)?;
+            for &offset in &offsets {
+                let line = data_line(&dir.join(format!("data.{}", file)), offset)?;
+                senses.extend(parse_synset(&line, part_of_speech, &form));
+            }
+            if !offsets.is_empty() {
+                break;
+            }
+        }
+    }
+    Ok(senses)
+}
+
+/// `lemma` and the base forms its ending suggests for part of speech
+/// `file`.
+fn forms(file: &str, lemma: &str) -> Vec<String> {
+    let mut forms = vec![lemma.to_string()];
+    let rules = DETACHMENTS
+        .iter()
+        .find(|(part, _)| *part == file)
+        .map_or(&[][..], |(_, rules)| *rules);
+    for (suffix, replacement) in rules {
+        if let Some(stem) = lemma.strip_suffix(suffix).filter(|stem| !stem.is_empty()) {
+            forms.push(format!("{}{}", stem, replacement));
+        }
+    }
+    forms
+}
+
+/// The synset offsets of `lemma` in an index file. Index lines are
+/// `lemma pos synset_cnt p_cnt [ptr_symbol...] sense_cnt tagsense_cnt
+/// synset_offset...`.
+fn index_offsets(path: &Path, lemma: &str) -> Result<Vec<u64>, String> {
+    let file = File::open(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let prefix = format!("{} ", lemma);
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|err| format!("{}: {}", path.display(), err))?;
+        if let Some(rest) = line.strip_prefix(&prefix) {
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            let count: usize = fields.get(1).and_then(|n| n.parse().ok()).unwrap_or(0);
+            let start = fields.len().saturating_sub(count);
+            return Ok(fields[start..]
+                .iter()
+                .filter_map(|offset| offset.parse().ok())
+                .collect());
+        }
+    }
+    Ok(Vec::new())
+}
+
+fn data_line(path: &Path, offset: u64) -> Result<String, String> {
+    let error = |err: std::io::Error| format!("{}: {}", path.display(), err);
+    let mut file = File::open(path).map_err(error)?;
+    file.seek(SeekFrom::Start(offset)).map_err(error)?;
+    let mut line = String::new();
+    BufReader::new(file.take(64 * 1024))
+        .read_line(&mut line)
+        .map_err(error)?;
+    Ok(line)
+}
+
+/// Reads a data line: `offset lex_filenum ss_type w_cnt word lex_id
+/// [word lex_id...] p_cnt ... | gloss`, with `w_cnt` in hex.
+fn parse_synset(line: &str, part_of_speech: &'static str, lemma: &str) -> Option<Sense> {
+    let (fields, gloss) = line.split_once(" | ").unwrap_or((line, ""));
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    let count = usize::from_str_radix(fields.get(3)?, 16).ok()?;
+    let synonyms = (0..count)
+        .filter_map(|index| fields.get(4 + index * 2))
+        .map(|word| {
+            // Adjectives can carry a position marker such as `(a)`.
+            let word = word.split('(').next().unwrap_or(word);
+            word.replace('_', " ")
+        })
+        .filter(|word| word.to_lowercase() != lemma.replace('_', " "))
+        .collect();
+    Some(Sense {
+        part_of_speech,
+        gloss: gloss.trim().to_string(),
+        synonyms,
+    })
+}
+
+/// The word around char `index` of `text`, with its char range: letters
+/// with inner apostrophes and hyphens.
+pub fn word_at(text: &str, index: usize) -> Option<(Range<usize>, String)> {
+    let chars: Vec<char> = text.chars().collect();
+    let inner = |at: usize| {
+        matches!(chars.get(at), Some('\'' | '’' | '-'))
+            && chars.get(at + 1).is_some_and(|c| c.is_alphabetic())
+            && at
+                .checked_sub(1)
+                .is_some_and(|before| chars[before].is_alphabetic())
+    };
+    let is_part = |at: usize| chars.get(at).is_some_and(|c| c.is_alphabetic()) || inner(at);
+    let mut start = index.min(chars.len());
+    if !is_part(start) {
+        start = start.checked_sub(1).filter(|&before| is_part(before))?;
+    }
+    while start > 0 && is_part(start - 1) {
+        start -= 1;
+    }
+    let mut end = start;
+    while is_part(end) {
+        end += 1;
+    }
+    Some((start..end, chars[start..end].iter().collect()))
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A check across the workspace's markdown documents. The documents are
+/// read and checked on a background thread, so large workspaces do not
+/// freeze the window.
+pub struct WorkspaceJob<T> {
+    /// What the status bar says while the job runs.
+    pub description: &'static str,
+    pending: Arc<Mutex<Option<T>>>,
+}
+
+impl<T: Send + 'static> WorkspaceJob<T> {
+    /// Reads `files` and hands them to `work`. `open` is the document being
+    /// edited and its unsaved text, used instead of what is on disk.
+    pub fn start(
+        description: &'static str,
+        files: Vec<PathBuf>,
+        open: Option<(PathBuf, String)>,
+        ctx: egui::Context,
+        work: impl FnOnce(&[(PathBuf, String)]) -> T + Send + 'static,
+    ) -> Self {
+        let pending = Arc::new(Mutex::new(None));
+        {
+            let pending = Arc::clone(&pending);
+            thread::spawn(move || {
+                let result = work(&read_documents(files, open));
+                if let Ok(mut slot) = pending.lock() {
+                    *slot = Some(result);
+                }
+                ctx.request_repaint();
+            });
+        }
+        Self {
+            description,
+            pending,
+        }
+    }
+
+    /// The result, once the job has finished.
+    pub fn take(&self) -> Option<T> {
+        self.pending.lock().ok().and_then(|mut slot| slot.take())
+    }
+}
+
+/// The text of each of `files` that can be read, `open`'s unsaved text for
+/// the open document.
+fn read_documents(files: Vec<PathBuf>, open: Option<(PathBuf, String)>) -> Vec<(PathBuf, String)> {
+    files
+        .into_iter()
+        .filter_map(|path| {
+            let text = match &open {
+                Some((open_path, text)) if *open_path == path => text.clone(),
+                _ => fs::read_to_string(&path).ok()?,
+            };
+            Some((path, text))
+        })
+        .collect()
+}
@@ -0,0 +1,204 @@
+use crate::dates;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::{self, Read, Write};
+use std::time::SystemTime;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+/// Names are UTF-8.
+const FLAG_UTF8: u16 = 0x0800;
+const METHOD_STORE: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+/// Version 2.0: deflate and folders.
+const VERSION: u16 = 20;
+
+struct CentralEntry {
+    name: String,
+    crc: u32,
+    compressed: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Writes a zip archive of deflated files. There is no Zip64 support, so
+/// the archive and each file must stay under 4 GB.
+pub struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    entries: Vec<CentralEntry>,
+    /// Modification time and date of every entry, in MS-DOS format.
+    time: u16,
+    date: u16,
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "zip archives are limited to 4 GB",
+    )
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        let ((year, month, day), seconds) = dates::utc(SystemTime::now());
+        let time = (seconds / 3600) << 11 | (seconds / 60 % 60) << 5 | ((seconds % 60) / 2);
+        let date = ((year - 1980).clamp(0, 127) as u32) << 9 | month << 5 | day;
+        Self {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+            time: time as u16,
+            date: date as u16,
+        }
+    }
+
+    /// Adds a file at `name`, a `/`-separated path inside the archive.
+    pub fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        let entry = CentralEntry {
+            name: name.to_string(),
+            crc: crc32fast::hash(data),
+            compressed: compressed.len().try_into().map_err(|_| too_large())?,
+            size: data.len().try_into().map_err(|_| too_large())?,
+            offset: self.offset.try_into().map_err(|_| too_large())?,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+        header.extend_from_slice(&METHOD_DEFLATE.to_le_bytes());
+        header.extend_from_slice(&self.time.to_le_bytes());
+        header.extend_from_slice(&self.date.to_le_bytes());
+        header.extend_from_slice(&entry.crc.to_le_bytes());
+        header.extend_from_slice(&entry.compressed.to_le_bytes());
+        header.extend_from_slice(&entry.size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        self.out.write_all(&header)?;
+        self.out.write_all(&compressed)?;
+        self.offset += (header.len() + compressed.len()) as u64;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Writes the central directory and returns the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        let start: u32 = self.offset.try_into().map_err(|_| too_large())?;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+            directory.extend_from_slice(&VERSION.to_le_bytes());
+            directory.extend_from_slice(&VERSION.to_le_bytes());
+            directory.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+            directory.extend_from_slice(&METHOD_DEFLATE.to_le_bytes());
+            directory.extend_from_slice(&self.time.to_le_bytes());
+            directory.extend_from_slice(&self.date.to_le_bytes());
+            directory.extend_from_slice(&entry.crc.to_le_bytes());
+            directory.extend_from_slice(&entry.compressed.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            // Extra field, comment, disk number, internal and external
+            // attributes.
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let count = self.entries.len() as u16;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        end.extend_from_slice(&start.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.out.write_all(&directory)?;
+        self.out.write_all(&end)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The files in the zip archive `data`, by `/`-separated path, in archive
+/// order. Folder entries are skipped; files must be stored or deflated.
+pub fn read_archive(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let damaged = || "The archive is damaged or not a zip file.".to_string();
+    // The end record is last, followed only by a comment of up to 64 KB.
+    let search_from = data.len().saturating_sub(22 + 0xFFFF);
+    let end = (search_from..data.len().saturating_sub(21))
+        .rev()
+        .find(|&at| u32_at(data, at) == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(damaged)?;
+    let count = u16_at(data, end + 10).ok_or_else(damaged)?;
+    let mut at = u32_at(data, end + 16).ok_or_else(damaged)? as usize;
+    if count == 0xFFFF || at == 0xFFFF_FFFF {
+        return Err("Zip64 archives are not supported.".to_string());
+    }
+
+    let mut files = Vec::new();
+    for _ in 0..count {
+        if u32_at(data, at) != Some(CENTRAL_HEADER) {
+            return Err(damaged());
+        }
+        let field = |offset: usize| u16_at(data, at + offset).ok_or_else(damaged);
+        let method = field(10)?;
+        let name_length = field(28)? as usize;
+        let skip = name_length + field(30)? as usize + field(32)? as usize;
+        let crc = u32_at(data, at + 16).ok_or_else(damaged)?;
+        let compressed = u32_at(data, at + 20).ok_or_else(damaged)? as usize;
+        let size = u32_at(data, at + 24).ok_or_else(damaged)? as usize;
+        let local = u32_at(data, at + 42).ok_or_else(damaged)? as usize;
+        let name = data
+            .get(at + 46..at + 46 + name_length)
+            .ok_or_else(damaged)?;
+        let name = String::from_utf8_lossy(name).replace('\\', "/");
+        at += 46 + skip;
+        if name.ends_with('/') {
+            continue;
+        }
+        if compressed == 0xFFFF_FFFF || size == 0xFFFF_FFFF {
+            return Err("Zip64 archives are not supported.".to_string());
+        }
+
+        if u32_at(data, local) != Some(LOCAL_HEADER) {
+            return Err(damaged());
+        }
+        let start = local
+            + 30
+            + u16_at(data, local + 26).ok_or_else(damaged)? as usize
+            + u16_at(data, local + 28).ok_or_else(damaged)? as usize;
+        let stored = data.get(start..start + compressed).ok_or_else(damaged)?;
+        let contents = match method {
+            METHOD_STORE => stored.to_vec(),
+            METHOD_DEFLATE => {
+                let mut contents = Vec::with_capacity(size);
+                DeflateDecoder::new(stored)
+                    .take(size as u64)
+                    .read_to_end(&mut contents)
+                    .map_err(|err| format!("Could not unpack {}: {}", name, err))?;
+                contents
+            }
+            _ => return Err(format!("{} uses an unsupported compression method.", name)),
+        };
+        if contents.len() != size || crc32fast::hash(&contents) != crc {
+            return Err(format!("{} is damaged.", name));
+        }
+        files.push((name, contents));
+    }
+    Ok(files)
+}